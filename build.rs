@@ -77,6 +77,66 @@ fn main() -> Result<(), BuildError> {
             entry: "encode_r32ui",
             name_overwrite: Some("stage_e32ui"),
         },
+        SimpleSource {
+            path: "src/shaders/stage.frag",
+            kind: ShaderKind::Fragment,
+            entry: "decode_rgba16ui",
+            name_overwrite: Some("stage_drgba16ui"),
+        },
+        SimpleSource {
+            path: "src/shaders/stage.frag",
+            kind: ShaderKind::Fragment,
+            entry: "encode_rgba16ui",
+            name_overwrite: Some("stage_ergba16ui"),
+        },
+        SimpleSource {
+            path: "src/shaders/stage.frag",
+            kind: ShaderKind::Fragment,
+            entry: "decode_rgba32ui",
+            name_overwrite: Some("stage_drgba32ui"),
+        },
+        SimpleSource {
+            path: "src/shaders/stage.frag",
+            kind: ShaderKind::Fragment,
+            entry: "encode_rgba32ui",
+            name_overwrite: Some("stage_ergba32ui"),
+        },
+        SimpleSource {
+            path: "src/shaders/stage.frag",
+            kind: ShaderKind::Fragment,
+            entry: "decode_rgb8ui",
+            name_overwrite: Some("stage_drgb8ui"),
+        },
+        SimpleSource {
+            path: "src/shaders/stage.frag",
+            kind: ShaderKind::Fragment,
+            entry: "encode_rgb8ui",
+            name_overwrite: Some("stage_ergb8ui"),
+        },
+        SimpleSource {
+            path: "src/shaders/stage.frag",
+            kind: ShaderKind::Fragment,
+            entry: "decode_rgb16ui",
+            name_overwrite: Some("stage_drgb16ui"),
+        },
+        SimpleSource {
+            path: "src/shaders/stage.frag",
+            kind: ShaderKind::Fragment,
+            entry: "encode_rgb16ui",
+            name_overwrite: Some("stage_ergb16ui"),
+        },
+        SimpleSource {
+            path: "src/shaders/stage.frag",
+            kind: ShaderKind::Fragment,
+            entry: "decode_planar8ui",
+            name_overwrite: Some("stage_dplanar8ui"),
+        },
+        SimpleSource {
+            path: "src/shaders/stage.frag",
+            kind: ShaderKind::Fragment,
+            entry: "encode_planar8ui",
+            name_overwrite: Some("stage_eplanar8ui"),
+        },
         SimpleSource {
             path: "src/shaders/fill.frag",
             kind: ShaderKind::Fragment,
@@ -107,6 +167,18 @@ fn main() -> Result<(), BuildError> {
             entry: "main",
             name_overwrite: None,
         },
+        SimpleSource {
+            path: "src/shaders/channels.frag",
+            kind: ShaderKind::Fragment,
+            entry: "channels_swizzle",
+            name_overwrite: Some("channels_swizzle"),
+        },
+        SimpleSource {
+            path: "src/shaders/channels.frag",
+            kind: ShaderKind::Fragment,
+            entry: "channels_merge",
+            name_overwrite: Some("channels_merge"),
+        },
         SimpleSource {
             path: "src/shaders/oklab.frag",
             kind: ShaderKind::Fragment,
@@ -131,12 +203,138 @@ fn main() -> Result<(), BuildError> {
             entry: "srlab2_decode",
             name_overwrite: Some("srlab2_decode"),
         },
+        SimpleSource {
+            path: "src/shaders/cielab.frag",
+            kind: ShaderKind::Fragment,
+            entry: "cielab_encode",
+            name_overwrite: Some("cielab_encode"),
+        },
+        SimpleSource {
+            path: "src/shaders/cielab.frag",
+            kind: ShaderKind::Fragment,
+            entry: "cielab_decode",
+            name_overwrite: Some("cielab_decode"),
+        },
+        SimpleSource {
+            path: "src/shaders/hsl.frag",
+            kind: ShaderKind::Fragment,
+            entry: "main",
+            name_overwrite: None,
+        },
+        SimpleSource {
+            path: "src/shaders/indexed.frag",
+            kind: ShaderKind::Fragment,
+            entry: "indexed_decode",
+            name_overwrite: Some("indexed_decode"),
+        },
+        SimpleSource {
+            path: "src/shaders/indexed.frag",
+            kind: ShaderKind::Fragment,
+            entry: "indexed_encode",
+            name_overwrite: Some("indexed_encode"),
+        },
+        SimpleSource {
+            path: "src/shaders/kmeans.frag",
+            kind: ShaderKind::Fragment,
+            entry: "kmeans_init",
+            name_overwrite: Some("kmeans_init"),
+        },
+        SimpleSource {
+            path: "src/shaders/kmeans.frag",
+            kind: ShaderKind::Fragment,
+            entry: "kmeans_assign",
+            name_overwrite: Some("kmeans_assign"),
+        },
+        SimpleSource {
+            path: "src/shaders/kmeans.frag",
+            kind: ShaderKind::Fragment,
+            entry: "kmeans_update",
+            name_overwrite: Some("kmeans_update"),
+        },
         SimpleSource {
             path: "src/shaders/box3.frag",
             kind: ShaderKind::Fragment,
             entry: "main",
             name_overwrite: None,
         },
+        SimpleSource {
+            path: "src/shaders/dither.frag",
+            kind: ShaderKind::Fragment,
+            entry: "dither_levels",
+            name_overwrite: Some("dither_levels"),
+        },
+        SimpleSource {
+            path: "src/shaders/dither.frag",
+            kind: ShaderKind::Fragment,
+            entry: "dither_palette",
+            name_overwrite: Some("dither_palette"),
+        },
+        SimpleSource {
+            path: "src/shaders/orient.frag",
+            kind: ShaderKind::Fragment,
+            entry: "main",
+            name_overwrite: None,
+        },
+        SimpleSource {
+            path: "src/shaders/pad.frag",
+            kind: ShaderKind::Fragment,
+            entry: "main",
+            name_overwrite: None,
+        },
+        SimpleSource {
+            path: "src/shaders/perspective.frag",
+            kind: ShaderKind::Fragment,
+            entry: "main",
+            name_overwrite: None,
+        },
+        SimpleSource {
+            path: "src/shaders/warp.frag",
+            kind: ShaderKind::Fragment,
+            entry: "warp_undistort",
+            name_overwrite: Some("warp_undistort"),
+        },
+        SimpleSource {
+            path: "src/shaders/warp.frag",
+            kind: ShaderKind::Fragment,
+            entry: "warp_displace",
+            name_overwrite: Some("warp_displace"),
+        },
+        SimpleSource {
+            path: "src/shaders/channel_affine.frag",
+            kind: ShaderKind::Fragment,
+            entry: "main",
+            name_overwrite: None,
+        },
+        SimpleSource {
+            path: "src/shaders/convolve.frag",
+            kind: ShaderKind::Fragment,
+            entry: "main",
+            name_overwrite: None,
+        },
+        SimpleSource {
+            path: "src/shaders/morphology.frag",
+            kind: ShaderKind::Fragment,
+            entry: "morphology_element",
+            name_overwrite: Some("morphology_element"),
+        },
+        SimpleSource {
+            path: "src/shaders/morphology.frag",
+            kind: ShaderKind::Fragment,
+            entry: "morphology_scan",
+            name_overwrite: Some("morphology_scan"),
+        },
+        SimpleSource {
+            path: "src/shaders/morphology.frag",
+            kind: ShaderKind::Fragment,
+            entry: "morphology_merge",
+            name_overwrite: Some("morphology_merge"),
+        },
+        SimpleSource {
+            path: "src/shaders/morphology.frag",
+            kind: ShaderKind::Fragment,
+            entry: "morphology_difference",
+            name_overwrite: Some("morphology_difference"),
+        },
         SimpleSource {
             path: "src/shaders/mandelbrot.frag",
             kind: ShaderKind::Fragment,
@@ -149,6 +347,30 @@ fn main() -> Result<(), BuildError> {
             entry: "main",
             name_overwrite: None,
         },
+        SimpleSource {
+            path: "src/shaders/tone_map.frag",
+            kind: ShaderKind::Fragment,
+            entry: "main",
+            name_overwrite: None,
+        },
+        SimpleSource {
+            path: "src/shaders/lut1d_bake.frag",
+            kind: ShaderKind::Fragment,
+            entry: "main",
+            name_overwrite: None,
+        },
+        SimpleSource {
+            path: "src/shaders/lut1d_apply.frag",
+            kind: ShaderKind::Fragment,
+            entry: "main",
+            name_overwrite: None,
+        },
+        SimpleSource {
+            path: "src/shaders/lut3d.frag",
+            kind: ShaderKind::Fragment,
+            entry: "main",
+            name_overwrite: None,
+        },
     ];
 
     let mut compiler = Compiler::new().unwrap();
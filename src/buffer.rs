@@ -6,6 +6,8 @@ pub use image_canvas::{
     },
     Canvas,
 };
+use std::borrow::Cow;
+use std::convert::TryFrom;
 
 #[derive(Clone)]
 pub struct ImageBuffer {
@@ -18,6 +20,8 @@ pub struct ByteLayout {
     pub height: u32,
     pub row_stride: u64,
     pub texel_stride: u16,
+    /// The planes in which texels are stored, or `None` if they are stored next to each other.
+    pub planes: Option<Planes>,
 }
 
 /// Describes an image semantically.
@@ -29,6 +33,119 @@ pub struct Descriptor {
     pub color: Color,
     /// Describe how each single texel is interpreted.
     pub texel: Texel,
+    /// Whether color channels are stored multiplied by alpha.
+    pub alpha: AlphaMode,
+    /// The CIE 1976 color space of the values, for images described as linear scalars.
+    pub cie: Option<CieColor>,
+}
+
+/// The relation of the color channels of stored texels to their alpha channel.
+///
+/// This only concerns how texels are stored. When they are loaded all colors are converted to
+/// straight alpha, and premultiplied again when they are stored.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum AlphaMode {
+    /// Color channels are independent of alpha.
+    #[default]
+    Straight,
+    /// Color channels are multiplied by alpha, after the transfer function is applied.
+    Premultiplied,
+}
+
+/// A CIE 1976 color space, relative to a reference white.
+///
+/// The `Color` of a descriptor can not express these spaces. Images in them are described as
+/// linear scalars instead, with the space in [`Descriptor::cie`]. They are converted with
+/// [`CommandBuffer::cie_encode`] and [`CommandBuffer::cie_decode`].
+///
+/// [`CommandBuffer::cie_encode`]: crate::command::CommandBuffer::cie_encode
+/// [`CommandBuffer::cie_decode`]: crate::command::CommandBuffer::cie_decode
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CieColor {
+    /// The model of the space.
+    pub model: CieModel,
+    /// The reference white, to which the colors of the image are relative.
+    pub whitepoint: Whitepoint,
+}
+
+/// The models of the CIE 1976 color spaces.
+///
+/// Each can be stored in rectangular (`SampleParts::Lab`, `SampleParts::LabA`) or in polar
+/// coordinates (`SampleParts::Lch`, `SampleParts::LchA`). Texels with floating point channels
+/// hold the values as they are. Texels with integer channels hold them normalized: lightness is
+/// divided by `100` and the hue in degrees by `360`. The other values depend on the model, see
+/// its variants.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum CieModel {
+    /// CIE L*a*b*, or LCh(ab) in polar coordinates.
+    ///
+    /// Normalized a* and b* are offset by `128` and divided by `255` as in ICC, the chroma is
+    /// divided by `181.02`.
+    Lab,
+    /// CIE L*u*v*, or LCh(uv) in polar coordinates.
+    ///
+    /// The u* of saturated colors exceeds the range of a*. Normalized u* and v* are offset by
+    /// `200` and divided by `400`, the chroma is divided by `282.84`.
+    Luv,
+}
+
+/// The planes of a frame with subsampled chroma, such as a frame of video.
+///
+/// The stored bytes are a luma plane followed by the chroma planes, each sample an 8-bit code, with
+/// rows of luma as wide as the image and no padding between rows or planes. The image itself has
+/// texels of `SampleParts::Yuv` at the resolution of luma, see [`Self::descriptor`]. Chroma is
+/// upsampled when the image is staged and downsampled when it is stored.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Planes {
+    /// The arrangement and subsampling of the planes.
+    pub layout: PlanarLayout,
+    /// The position of chroma samples relative to luma samples.
+    pub siting: ChromaSiting,
+    /// The interpolation of chroma when it is upsampled.
+    pub upsampling: ChromaFilter,
+}
+
+/// The arrangement of planes in a frame with subsampled chroma.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum PlanarLayout {
+    /// 4:2:0, a luma plane followed by a Cb and a Cr plane.
+    #[default]
+    I420,
+    /// 4:2:0, a luma plane followed by one plane of interleaved Cb and Cr.
+    Nv12,
+    /// 4:2:2, a luma plane followed by a Cb and a Cr plane.
+    I422,
+    /// 4:2:2, a luma plane followed by one plane of interleaved Cb and Cr.
+    Nv16,
+}
+
+/// The position of chroma samples relative to the luma samples they cover.
+///
+/// This also chooses the filter when chroma is downsampled. A position centered between two luma
+/// samples averages both, a position co-sited with a luma sample weights it and its neighbors by
+/// `1:2:1`. Vertical positions apply only to 4:2:0 layouts.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ChromaSiting {
+    /// Co-sited with the left luma sample and vertically centered, as in MPEG-2 and H.264.
+    #[default]
+    Left,
+    /// Centered in both directions, as in JPEG and MPEG-1.
+    Center,
+    /// Co-sited with the top-left luma sample, as in BT.2020 and BT.2100.
+    TopLeft,
+}
+
+/// The interpolation of chroma samples when upsampling them to the luma resolution.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ChromaFilter {
+    /// Take the closest chroma sample.
+    Nearest,
+    /// Interpolate linearly between the four closest chroma samples.
+    #[default]
+    Bilinear,
 }
 
 /// Denotes the 'position' of a channel in the sample parts.
@@ -88,9 +205,12 @@ impl Descriptor {
                 height: 0,
                 row_stride: 0,
                 texel_stride: 0,
+                planes: None,
             },
             color: Color::SRGB,
             texel: Texel::new_u8(SampleParts::RgbA),
+            alpha: AlphaMode::Straight,
+            cie: None,
         }
     }
 
@@ -100,6 +220,7 @@ impl Descriptor {
             height,
             row_stride: u64::from(texel.bits.bytes()) * u64::from(width),
             texel_stride: texel.bits.bytes(),
+            planes: None,
         };
 
         let color = Color::Scalars {
@@ -110,6 +231,8 @@ impl Descriptor {
             color,
             layout,
             texel,
+            alpha: AlphaMode::Straight,
+            cie: None,
         };
 
         let _ = this.try_to_canvas()?;
@@ -117,28 +240,84 @@ impl Descriptor {
     }
 
     pub(crate) fn to_aligned(&self) -> Option<ByteLayout> {
-        let bytes_per_row = (self.layout.texel_stride as u32).checked_mul(self.layout.width)?;
+        let stored = self.to_stored()?;
+        let bytes_per_row = (stored.layout.texel_stride as u32).checked_mul(stored.layout.width)?;
         let bytes_per_row =
             (bytes_per_row / 256 + u32::from(bytes_per_row % 256 != 0)).checked_mul(256)?;
         Some(ByteLayout {
-            texel_stride: self.texel.bits.bytes(),
-            width: self.layout.width,
-            height: self.layout.height,
+            texel_stride: stored.texel.bits.bytes(),
+            width: stored.layout.width,
+            height: stored.layout.height,
             row_stride: bytes_per_row.into(),
+            planes: None,
         })
     }
 
+    /// Store the texels in planes, or next to each other with `None`.
+    ///
+    /// Returns `None` if the texels or the size do not fit the planes.
+    pub(crate) fn with_planes(self, planes: Option<Planes>) -> Option<Self> {
+        let Some(planes) = planes else {
+            return Some(self);
+        };
+
+        if !planes.fits(&self.texel, self.size()) {
+            return None;
+        }
+
+        let layout = ByteLayout {
+            row_stride: u64::from(self.layout.width),
+            planes: Some(planes),
+            ..self.layout
+        };
+
+        let this = Descriptor { layout, ..self };
+        let _ = this.try_to_canvas()?;
+        Some(this)
+    }
+
+    /// The descriptor of the stored bytes, with texels next to each other.
+    ///
+    /// This is the descriptor itself, unless its texels are stored in planes. The bytes of those
+    /// are an image of single bytes as wide as the luma plane, the rows of the chroma planes
+    /// following those of luma.
+    pub(crate) fn to_stored(&self) -> Option<Cow<'_, Descriptor>> {
+        let Some(planes) = self.layout.planes else {
+            return Some(Cow::Borrowed(self));
+        };
+
+        let (width, height) = self.size();
+        let rows = height.checked_add(planes.chroma_rows(height))?;
+
+        Some(Cow::Owned(Descriptor {
+            layout: ByteLayout {
+                width,
+                height: rows,
+                row_stride: u64::from(width),
+                texel_stride: 1,
+                planes: None,
+            },
+            color: Color::Scalars {
+                transfer: Transfer::Linear,
+            },
+            texel: Texel::new_u8(SampleParts::Luma),
+            alpha: AlphaMode::Straight,
+            cie: None,
+        }))
+    }
+
     pub(crate) fn to_canvas(&self) -> BufferLayout {
         self.try_to_canvas()
             .expect("To be validated on construction")
     }
 
     pub(crate) fn try_to_canvas(&self) -> Option<BufferLayout> {
+        let stored = self.to_stored()?;
         let descriptor = RowLayoutDescription {
-            width: self.layout.width,
-            height: self.layout.height,
-            row_stride: self.layout.row_stride,
-            texel: self.texel.clone(),
+            width: stored.layout.width,
+            height: stored.layout.height,
+            row_stride: stored.layout.row_stride,
+            texel: stored.texel.clone(),
         };
 
         BufferLayout::with_row_layout(&descriptor).ok()
@@ -152,7 +331,13 @@ impl Descriptor {
     /// texel descriptor has the same number of bytes as the layout, etc.
     pub fn is_consistent(&self) -> bool {
         // FIXME: other checks.
-        self.texel.bits.bytes() == <_>::from(self.layout.texel_stride)
+        let planes = self.layout.planes.map_or(true, |planes| {
+            planes.fits(&self.texel, self.size())
+                && self.layout.row_stride == u64::from(self.layout.width)
+                && matches!(self.color, Color::Rgb { .. })
+        });
+
+        self.texel.bits.bytes() == <_>::from(self.layout.texel_stride) && planes
     }
 
     /// Calculate the total number of pixels in width of this layout.
@@ -180,9 +365,26 @@ impl Descriptor {
             color: Color::SRGB,
             layout: ByteLayout::from(image),
             texel: Self::texel(image),
+            alpha: AlphaMode::Straight,
+            cie: None,
         }
     }
 
+    /// Convert the bytes of an image, with rows `row_stride` bytes apart, to an `image` buffer.
+    pub(crate) fn to_image(
+        texel: &Texel,
+        (width, height): (u32, u32),
+        row_stride: u64,
+        source: &[u8],
+    ) -> Option<image::DynamicImage> {
+        let allocator = Self::as_image_allocator(texel)?;
+        let row_stride = usize::try_from(row_stride).ok()?;
+        let texel_bytes = usize::from(texel.bits.bytes());
+        let source = tight_rows(source, (width, height), texel_bytes, row_stride)?;
+        allocator(width, height, &source)
+    }
+
+    /// Allocate an image from contiguous rows of texels.
     pub(crate) fn as_image_allocator(that: &Texel) -> Option<ImageAllocator> {
         use SampleBits as B;
         use SampleParts as P;
@@ -273,6 +475,102 @@ impl Descriptor {
                 })?;
                 Some(image::DynamicImage::ImageRgba16(buffer))
             },
+            // Packed texels are widened to the channels of the next larger image type.
+            Texel {
+                block: Block::Pixel,
+                parts: P::Rgb,
+                bits: B::UInt565,
+            } => |width, height, source| {
+                let data = widen_packed(source, (width, height), &[5, 6, 5], &[0, 1, 2], 8)?;
+                let buffer = image::ImageBuffer::from_vec(width, height, narrow_u8(data))?;
+                Some(image::DynamicImage::ImageRgb8(buffer))
+            },
+            Texel {
+                block: Block::Pixel,
+                parts: P::Bgr,
+                bits: B::UInt565,
+            } => |width, height, source| {
+                let data = widen_packed(source, (width, height), &[5, 6, 5], &[2, 1, 0], 8)?;
+                let buffer = image::ImageBuffer::from_vec(width, height, narrow_u8(data))?;
+                Some(image::DynamicImage::ImageRgb8(buffer))
+            },
+            Texel {
+                block: Block::Pixel,
+                parts: P::Rgb,
+                bits: B::UInt332,
+            } => |width, height, source| {
+                let data = widen_packed(source, (width, height), &[3, 3, 2], &[0, 1, 2], 8)?;
+                let buffer = image::ImageBuffer::from_vec(width, height, narrow_u8(data))?;
+                Some(image::DynamicImage::ImageRgb8(buffer))
+            },
+            Texel {
+                block: Block::Pixel,
+                parts: P::Bgr,
+                bits: B::UInt233,
+            } => |width, height, source| {
+                let data = widen_packed(source, (width, height), &[2, 3, 3], &[2, 1, 0], 8)?;
+                let buffer = image::ImageBuffer::from_vec(width, height, narrow_u8(data))?;
+                Some(image::DynamicImage::ImageRgb8(buffer))
+            },
+            Texel {
+                block: Block::Pixel,
+                parts: P::RgbA,
+                bits: B::UInt4x4,
+            } => |width, height, source| {
+                let data = widen_packed(source, (width, height), &[4, 4, 4, 4], &[0, 1, 2, 3], 8)?;
+                let buffer = image::ImageBuffer::from_vec(width, height, narrow_u8(data))?;
+                Some(image::DynamicImage::ImageRgba8(buffer))
+            },
+            Texel {
+                block: Block::Pixel,
+                parts: P::BgrA,
+                bits: B::UInt4x4,
+            } => |width, height, source| {
+                let data = widen_packed(source, (width, height), &[4, 4, 4, 4], &[2, 1, 0, 3], 8)?;
+                let buffer = image::ImageBuffer::from_vec(width, height, narrow_u8(data))?;
+                Some(image::DynamicImage::ImageRgba8(buffer))
+            },
+            Texel {
+                block: Block::Pixel,
+                parts: P::RgbA,
+                bits: B::UInt1010102,
+            } => |width, height, source| {
+                let data =
+                    widen_packed(source, (width, height), &[10, 10, 10, 2], &[0, 1, 2, 3], 16)?;
+                let buffer = image::ImageBuffer::from_vec(width, height, data)?;
+                Some(image::DynamicImage::ImageRgba16(buffer))
+            },
+            Texel {
+                block: Block::Pixel,
+                parts: P::BgrA,
+                bits: B::UInt1010102,
+            } => |width, height, source| {
+                let data =
+                    widen_packed(source, (width, height), &[10, 10, 10, 2], &[2, 1, 0, 3], 16)?;
+                let buffer = image::ImageBuffer::from_vec(width, height, data)?;
+                Some(image::DynamicImage::ImageRgba16(buffer))
+            },
+            // The alpha channel is in the least significant bits, as the stage shader decodes it.
+            Texel {
+                block: Block::Pixel,
+                parts: P::ARgb,
+                bits: B::UInt2101010,
+            } => |width, height, source| {
+                let data =
+                    widen_packed(source, (width, height), &[2, 10, 10, 10], &[1, 2, 3, 0], 16)?;
+                let buffer = image::ImageBuffer::from_vec(width, height, data)?;
+                Some(image::DynamicImage::ImageRgba16(buffer))
+            },
+            Texel {
+                block: Block::Pixel,
+                parts: P::ABgr,
+                bits: B::UInt2101010,
+            } => |width, height, source| {
+                let data =
+                    widen_packed(source, (width, height), &[2, 10, 10, 10], &[3, 2, 1, 0], 16)?;
+                let buffer = image::ImageBuffer::from_vec(width, height, data)?;
+                Some(image::DynamicImage::ImageRgba16(buffer))
+            },
             _ => return None,
         })
     }
@@ -325,6 +623,78 @@ impl Descriptor {
     }
 }
 
+/// Unpack texels into channels of `depth` bits, rounding to the nearest value.
+///
+/// The `widths` list the channels in order, starting at the least significant bits of each
+/// little-endian texel. This is the order in which the stage shader decodes them. The `order`
+/// then selects, for each channel of the result, the index of the unpacked channel, such that
+/// `Bgr` is turned into `Rgb` with `[2, 1, 0]`. Rows must be tightly packed, see [`tight_rows`].
+fn widen_packed(
+    source: &[u8],
+    (width, height): (u32, u32),
+    widths: &[u32],
+    order: &[usize],
+    depth: u32,
+) -> Option<Vec<u16>> {
+    debug_assert_eq!(widths.len(), order.len());
+    let bytes = (widths.iter().sum::<u32>() / 8) as usize;
+    let count = (width as usize).checked_mul(height as usize)?;
+    let source = source.get(..count.checked_mul(bytes)?)?;
+    let target_max = (1u32 << depth) - 1;
+
+    let mut data = Vec::with_capacity(count * widths.len());
+    let mut values = [0u16; 4];
+    for texel in source.chunks_exact(bytes) {
+        let num = texel
+            .iter()
+            .rev()
+            .fold(0u32, |num, &byte| (num << 8) | u32::from(byte));
+
+        let mut shift = 0;
+        for (value, &bits) in values.iter_mut().zip(widths) {
+            let max = (1u32 << bits) - 1;
+            let code = (num >> shift) & max;
+            *value = ((code * target_max + max / 2) / max) as u16;
+            shift += bits;
+        }
+
+        data.extend(order.iter().map(|&idx| values[idx]));
+    }
+
+    Some(data)
+}
+
+/// Remove the padding between rows, as the allocators of `image` expect contiguous rows.
+///
+/// Returns `None` if the rows overlap or the source does not contain all of them.
+fn tight_rows(
+    source: &[u8],
+    (width, height): (u32, u32),
+    texel_bytes: usize,
+    row_stride: usize,
+) -> Option<Cow<'_, [u8]>> {
+    let row = (width as usize).checked_mul(texel_bytes)?;
+    let len = row.checked_mul(height as usize)?;
+
+    if row_stride == row {
+        return source.get(..len).map(Cow::Borrowed);
+    } else if row_stride < row {
+        return None;
+    }
+
+    let mut data = Vec::with_capacity(len);
+    for y in 0..height as usize {
+        let start = y.checked_mul(row_stride)?;
+        data.extend_from_slice(source.get(start..)?.get(..row)?);
+    }
+
+    Some(Cow::Owned(data))
+}
+
+fn narrow_u8(data: Vec<u16>) -> Vec<u8> {
+    data.into_iter().map(|value| value as u8).collect()
+}
+
 impl ImageBuffer {
     /// Allocate a new image buffer given its layout.
     pub fn with_layout(layout: &BufferLayout) -> Self {
@@ -345,6 +715,57 @@ impl ImageBuffer {
     }
 }
 
+impl Planes {
+    /// The descriptor of a frame in these planes, with a luma plane of the given size.
+    ///
+    /// The texels are Y'CbCr codes, computed from the RGB `color`. Returns `None` if the width, or
+    /// for 4:2:0 layouts the height, is odd.
+    pub fn descriptor(&self, color: Color, width: u32, height: u32) -> Option<Descriptor> {
+        let texel = Self::texel();
+
+        if !self.fits(&texel, (width, height)) || !matches!(color, Color::Rgb { .. }) {
+            return None;
+        }
+
+        let descriptor = Descriptor::with_texel(texel, width, height)?.with_planes(Some(*self))?;
+        Some(Descriptor {
+            color,
+            ..descriptor
+        })
+    }
+
+    /// Whether an image of this texel and size can be stored in these planes.
+    pub(crate) fn fits(&self, texel: &Texel, (width, height): (u32, u32)) -> bool {
+        *texel == Self::texel()
+            && width % 2 == 0
+            && height % self.layout.vertical_subsampling() == 0
+    }
+
+    /// The number of rows, as wide as luma, which the chroma planes occupy.
+    pub(crate) fn chroma_rows(&self, height: u32) -> u32 {
+        // Two planes, each of half width, make up as many bytes as rows of full width.
+        height / self.layout.vertical_subsampling()
+    }
+
+    fn texel() -> Texel {
+        Texel {
+            block: Block::Pixel,
+            bits: SampleBits::UInt8x3,
+            parts: SampleParts::Yuv,
+        }
+    }
+}
+
+impl PlanarLayout {
+    /// The factor by which chroma rows are subsampled.
+    fn vertical_subsampling(self) -> u32 {
+        match self {
+            PlanarLayout::I420 | PlanarLayout::Nv12 => 2,
+            PlanarLayout::I422 | PlanarLayout::Nv16 => 1,
+        }
+    }
+}
+
 impl From<&'_ image::DynamicImage> for ByteLayout {
     fn from(image: &'_ image::DynamicImage) -> ByteLayout {
         use image::GenericImageView;
@@ -357,6 +778,7 @@ impl From<&'_ image::DynamicImage> for ByteLayout {
             height,
             row_stride: u64::from(width) * u64::from(bpp),
             texel_stride: <_>::from(bpp),
+            planes: None,
         }
     }
 }
@@ -396,6 +818,7 @@ impl From<&'_ BufferLayout> for Descriptor {
             height: buf.height(),
             row_stride: buf.as_row_layout().row_stride,
             texel_stride: buf.texel().bits.bytes(),
+            planes: None,
         };
 
         Descriptor {
@@ -404,6 +827,61 @@ impl From<&'_ BufferLayout> for Descriptor {
             // method that notes this default in its name?
             color: buf.color().unwrap_or(&Color::SRGB).clone(),
             texel: buf.texel().clone(),
+            alpha: AlphaMode::Straight,
+            cie: None,
         }
     }
 }
+
+#[test]
+fn widen_rgb565() {
+    let texel = Texel {
+        block: Block::Pixel,
+        bits: SampleBits::UInt565,
+        parts: SampleParts::Rgb,
+    };
+
+    // Red at its maximum in the low bits, half of green, and no blue.
+    let source = (31u16 | 32 << 5).to_le_bytes();
+    let allocator = Descriptor::as_image_allocator(&texel).expect("Packed texels are widened");
+    let image = allocator(1, 1, &source).expect("Enough bytes for the image");
+
+    assert_eq!(image.as_bytes(), &[255, 130, 0]);
+
+    let bgr = Texel {
+        parts: SampleParts::Bgr,
+        ..texel
+    };
+    let allocator = Descriptor::as_image_allocator(&bgr).expect("Packed texels are widened");
+    let image = allocator(1, 1, &source).expect("Enough bytes for the image");
+
+    assert_eq!(image.as_bytes(), &[0, 130, 255]);
+    assert!(allocator(2, 1, &source).is_none());
+}
+
+#[test]
+fn widen_argb2101010() {
+    let texel = Texel {
+        block: Block::Pixel,
+        bits: SampleBits::UInt2101010,
+        parts: SampleParts::ARgb,
+    };
+
+    // Opaque alpha in the low bits, then red at its maximum, and no green or blue.
+    let texel_bytes = (3u32 | 1023 << 2).to_le_bytes();
+    let mut source = texel_bytes.to_vec();
+    // Two rows of one texel, separated by padding.
+    source.extend_from_slice(&[0xff; 4]);
+    source.extend_from_slice(&texel_bytes);
+
+    let image = Descriptor::to_image(&texel, (1, 2), 8, &source).expect("Padded rows are skipped");
+    let expected: Vec<u8> = [u16::MAX, 0, 0, u16::MAX]
+        .repeat(2)
+        .iter()
+        .flat_map(|value| value.to_ne_bytes())
+        .collect();
+    assert_eq!(image.as_bytes(), &expected[..]);
+
+    // Rows may not overlap.
+    assert!(Descriptor::to_image(&texel, (2, 1), 4, &source).is_none());
+}
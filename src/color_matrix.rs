@@ -174,6 +174,69 @@ impl RowMatrix {
     pub(crate) fn mul_vec(&self, vec: [f32; 3]) -> [f32; 3] {
         self.multiply_column(vec)
     }
+
+    /// The projective transformation mapping each of four points to its correspondent.
+    ///
+    /// Returns `None` if three of the points are collinear in either quad.
+    pub(crate) fn homography(from: [[f32; 2]; 4], to: [[f32; 2]; 4]) -> Option<Self> {
+        // The eight unknowns of the matrix with its last entry fixed to one, each correspondence
+        // contributes two linear equations of the form `row · h = rhs`.
+        let mut system = [[0.0f64; 9]; 8];
+
+        for (idx, (&[x, y], &[u, v])) in from.iter().zip(&to).enumerate() {
+            let (x, y, u, v) = (f64::from(x), f64::from(y), f64::from(u), f64::from(v));
+            system[2 * idx] = [x, y, 1.0, 0.0, 0.0, 0.0, -x * u, -y * u, u];
+            system[2 * idx + 1] = [0.0, 0.0, 0.0, x, y, 1.0, -x * v, -y * v, v];
+        }
+
+        // Gaussian elimination with partial pivoting.
+        for col in 0..8 {
+            let pivot = (col..8).max_by(|&a, &b| {
+                system[a][col].abs().total_cmp(&system[b][col].abs())
+            })?;
+
+            if system[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+
+            system.swap(col, pivot);
+
+            for row in 0..8 {
+                if row == col {
+                    continue;
+                }
+
+                let factor = system[row][col] / system[col][col];
+                let pivot_row = system[col];
+                for (value, pivot) in system[row][col..].iter_mut().zip(&pivot_row[col..]) {
+                    *value -= factor * pivot;
+                }
+            }
+        }
+
+        let h = |idx: usize| (system[idx][8] / system[idx][idx]) as f32;
+        let matrix = RowMatrix([h(0), h(1), h(2), h(3), h(4), h(5), h(6), h(7), 1.0]);
+
+        // Collinear points in the target degenerate the matrix without a singular system.
+        let collinear = |quad: &[[f32; 2]; 4]| {
+            let area = |[a, b, c]: [usize; 3]| {
+                let [ax, ay] = quad[a].map(f64::from);
+                let [bx, by] = quad[b].map(f64::from);
+                let [cx, cy] = quad[c].map(f64::from);
+                ((bx - ax) * (cy - ay) - (by - ay) * (cx - ax)).abs()
+            };
+
+            [[0, 1, 2], [1, 2, 3], [2, 3, 0], [3, 0, 1]]
+                .iter()
+                .any(|&triple| area(triple) <= f64::EPSILON)
+        };
+
+        if collinear(&to) || !matrix.0.iter().all(|v| v.is_finite()) {
+            return None;
+        }
+
+        Some(matrix)
+    }
 }
 
 impl From<ColMatrix> for RowMatrix {
@@ -194,3 +257,18 @@ fn matrix_ops() {
 
     assert_eq!(mat, mat.transpose().transpose());
 }
+
+#[test]
+fn homography() {
+    let square = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+    let quad = [[10.0, 20.0], [50.0, 25.0], [45.0, 60.0], [5.0, 50.0]];
+
+    let mat = RowMatrix::homography(square, quad).unwrap();
+    for (&point, &[u, v]) in square.iter().zip(&quad) {
+        let [x, y] = mat.multiply_point(point);
+        assert!((x - u).abs() < 1e-3 && (y - v).abs() < 1e-3);
+    }
+
+    let line = [[0.0, 0.0], [1.0, 1.0], [2.0, 2.0], [0.0, 1.0]];
+    assert!(RowMatrix::homography(line, quad).is_none());
+}
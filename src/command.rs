@@ -1,13 +1,18 @@
+mod cube;
 mod dynamic;
 
+pub use self::cube::CubeError;
 pub use self::dynamic::{ShaderCommand, ShaderData, ShaderSource};
 
-use crate::buffer::{BufferLayout, ByteLayout, ChannelPosition, Descriptor, TexelExt};
+use crate::buffer::{
+    AlphaMode, Block, BufferLayout, ByteLayout, ChannelPosition, CieColor, CieModel, Descriptor,
+    Planes, SampleBits, TexelExt,
+};
 use crate::color_matrix::RowMatrix;
 use crate::pool::PoolImage;
 use crate::program::{
     CallBinding, CompileError, Frame, Function, FunctionLinked, High, ImageBufferAssignment,
-    ImageBufferPlan, ImageDescriptor, Initializer, Program, QuadTarget, Target, Texture,
+    ImageBufferPlan, ImageDescriptor, Initializer, Program, QuadTarget, Target, TexelData, Texture,
 };
 pub use crate::shaders::bilinear::Shader as Bilinear;
 pub use crate::shaders::distribution_normal2d::Shader as DistributionNormal2d;
@@ -15,7 +20,7 @@ pub use crate::shaders::fractal_noise::Shader as FractalNoise;
 
 use crate::shaders::{self, FragmentShader, PaintOnTopKind, ShaderInvocation};
 
-use image_canvas::color::{Color, ColorChannel, Whitepoint};
+use image_canvas::color::{Color, ColorChannel, Primaries, Transfer, Whitepoint};
 use image_canvas::layout::{SampleParts, Texel};
 
 use std::borrow::Cow;
@@ -74,6 +79,9 @@ pub struct CommandSignature {
 pub struct GenericDescriptor {
     size: Generic<(u32, u32)>,
     chroma: Generic<(Texel, Color)>,
+    alpha: Generic<AlphaMode>,
+    cie: Generic<Option<CieColor>>,
+    planes: Generic<Option<Planes>>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -118,6 +126,13 @@ enum Op {
         op: BinaryOp,
         desc: GenericDescriptor,
     },
+    /// i := nary(args...)
+    /// where type(i) =? Op[type(args)...]
+    Nary {
+        args: Vec<Register>,
+        op: NaryOp,
+        desc: GenericDescriptor,
+    },
     Dynamic {
         call: OperandDynKind,
         /// The planned shader invocation.
@@ -176,6 +191,10 @@ pub(crate) enum ConstructOp {
     DistributionNoise(shaders::FractalNoise),
     /// A color to repeat on pixels.
     Solid([f32; 4]),
+    /// A baked one-dimensional lookup table, one texel per entry.
+    Lut1d(shaders::lut1d::Bake),
+    /// Texel data prepared while recording, such as a three-dimensional lookup table.
+    Upload(TexelData),
 }
 
 #[derive(Clone, Debug)]
@@ -196,13 +215,64 @@ pub(crate) enum UnaryOp {
     /// And the byte width of new texel must be consistent with the current byte width.
     Transmute,
     /// Op(T) = T
-    Derivative(Derivative),
+    Derivative(Derivative, BorderMode),
+    /// Op(T) = T[.color=color, .texel=texel]
+    /// where T.color and color are RGB with the same primaries and whitepoint.
+    ToneMap(shaders::ToneMapShader),
+    /// Op(T) = T
+    /// where T.color is RGB and the adjustment applies to its encoded values.
+    HslAdjust(shaders::hsl::Shader),
+    /// Op(T) = U
+    /// where U are the initial centroids of k-means clustering the colors of T.
+    KMeans(shaders::kmeans::Shader),
+    /// Op(T) = T[.texel=texel]
+    /// where the encoded values of T are snapped to the codes of the texel, with noise.
+    Dither(shaders::dither::Shader),
+    /// Op(T) = T
+    /// where each channel is selected from the channels of T, or a constant.
+    Swizzle(shaders::channels::Shader),
+    /// Op(T) = T
+    /// where texels are moved to other integer coordinates, possibly transposing the size.
+    Orient(shaders::orient::Shader),
+    /// Op(T) = T
+    /// where the image is placed within a larger size and extended beyond its bounds.
+    Pad(shaders::pad::Shader),
+    /// Op(T) = T
+    /// where each pixel is sampled at coordinates moved by a lens model.
+    Warp(shaders::warp::Shader),
+    /// Op(T) = T
+    /// where each color channel is resampled through its own affine transformation.
+    ChannelAffine(shaders::channel_affine::Shader),
+    /// Op(T) = T
+    /// where each color channel is replaced by an extremum of its neighborhood.
+    Morphology(shaders::morphology::Shader),
+}
+
+#[derive(Clone, Debug)]
+pub(crate) enum NaryOp {
+    /// Op[T, U, V, W] = X
+    /// where each channel of X is selected from the channels of the arguments.
+    Merge(shaders::channels::Shader),
 }
 
 #[derive(Clone, Debug)]
 pub(crate) enum BinaryOp {
     /// Op = id
     Affine(Affine),
+    /// Op = id
+    Perspective(shaders::perspective::Shader),
+    /// Op[T, U] = T
+    /// where each pixel is sampled at coordinates moved by the values of U.
+    Displace(shaders::warp::Shader),
+    /// Op[T, T] = T
+    /// where the first is convolved, and the second provides the colors to keep.
+    Convolve(shaders::convolve::Shader),
+    /// Op[T, T] = T
+    /// where the colors of both are combined by a pass of morphology.
+    Morphology(shaders::morphology::Shader),
+    /// Op[T, U] = T
+    /// where each pixel takes one of the colors of U, a single row.
+    Dither(shaders::dither::Shader),
     /// Op[T, U] = T
     /// where T = U
     Inscribe { placement: Rectangle },
@@ -216,6 +286,21 @@ pub(crate) enum BinaryOp {
     /// Sample from a palette based on the color value of another image.
     /// Op[T, U] = T
     Palette(shaders::PaletteShader),
+    /// Map each channel through the same channel of a lookup table.
+    /// Op[T, U] = T
+    Lut1d,
+    /// Map colors through a three-dimensional lookup table, tiled into the image.
+    /// Op[T, U] = T
+    Lut3d(shaders::lut3d::Shader),
+    /// Between packed indices and the colors of a palette.
+    /// Op[T, U] = V
+    /// where T is the palette when decoding, the image when encoding.
+    Indexed(shaders::indexed::Shader),
+    /// A pass of k-means clustering.
+    /// Op[T, U] = V
+    /// where U are the centroids and T the image when assigning samples to them, or the previous
+    /// assignment when updating the centroids.
+    KMeans(shaders::kmeans::Shader),
 }
 
 /// A rectangle in `u32` space.
@@ -269,21 +354,40 @@ pub enum AffineSample {
     BiLinear,
 }
 
+/// The intrinsics and distortion of a camera, after Brown and Conrady.
+///
+/// A point `(x, y)` of the ideal image, relative to the principal point and divided by the focal
+/// length, is moved by the lens to `(x, y) · (1 + k1·r² + k2·r⁴ + k3·r⁶)` plus the tangential
+/// term `(2·p1·x·y + p2·(r² + 2·x²), p1·(r² + 2·y²) + 2·p2·x·y)` where `r² = x² + y²`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LensModel {
+    /// The focal length along the width and height, in pixels.
+    pub focal: [f32; 2],
+    /// The principal point, in pixels from the top-left corner.
+    pub center: [f32; 2],
+    /// The radial coefficients k1, k2 and k3.
+    pub radial: [f32; 3],
+    /// The tangential coefficients p1 and p2.
+    pub tangential: [f32; 2],
+}
+
 /// The parameters of color conversion which we will use in the draw call.
 #[derive(Clone, Debug)]
 pub(crate) enum ColorConversion {
     Xyz {
-        /// The matrix converting source to XYZ.
+        /// The matrix converting source to XYZ, adapted to the target whitepoint.
         to_xyz_matrix: RowMatrix,
         /// The matrix converting from XYZ to target.
         from_xyz_matrix: RowMatrix,
     },
     XyzToOklab {
-        /// The matrix converting source to XYZ.
+        /// The matrix converting source to XYZ, adapted to D65.
         to_xyz_matrix: RowMatrix,
     },
     OklabToXyz {
-        /// The matrix converting from XYZ to target.
+        /// The matrix converting the target to XYZ, adapted to D65.
+        ///
+        /// Its inverse converts from XYZ to target.
         from_xyz_matrix: RowMatrix,
     },
     XyzToSrLab2 {
@@ -298,6 +402,30 @@ pub(crate) enum ColorConversion {
         /// The SrLAb2 source whitepoint.
         whitepoint: Whitepoint,
     },
+    XyzToCieLab {
+        /// The matrix converting source to XYZ, adapted to the reference white.
+        to_xyz_matrix: RowMatrix,
+        /// The reference white of the target.
+        whitepoint: Whitepoint,
+        /// The model and texel representation of the target.
+        form: shaders::cielab::Form,
+    },
+    CieLabToXyz {
+        /// The matrix converting the target to XYZ, adapted to the reference white.
+        ///
+        /// Its inverse converts from XYZ to target.
+        from_xyz_matrix: RowMatrix,
+        /// The reference white of the source.
+        whitepoint: Whitepoint,
+        /// The model and texel representation of the source.
+        form: shaders::cielab::Form,
+    },
+    /// Between encoded RGB values and one of their cylindrical models.
+    Hue(shaders::hsl::Operation),
+    /// Between encoded RGB values and Y'CbCr, an affine map.
+    YCbCr { matrix: RowMatrix, offset: [f32; 3] },
+    /// The linear representation is the same, only the texel encoding differs.
+    Identity,
 }
 
 /// Reference of matrices and more: http://brucelindbloom.com/index.html?Eqn_ChromAdapt.html
@@ -333,7 +461,7 @@ pub(crate) struct ChromaticAdaptation {
     target: Whitepoint,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 #[non_exhaustive]
 pub enum ChromaticAdaptationMethod {
     /// Naive adaptation based on component-wise linear transform in XYZ.
@@ -368,11 +496,157 @@ pub enum ChromaticAdaptationMethod {
     /// 0.0389 & -0.0685 & 1.0296
     /// \end{bmatrix}
     /// ```
+    #[default]
     BradfordVonKries,
     /// Bradford's originally intended adaptation.
     BradfordNonLinear,
 }
 
+/// The cylindrical models of RGB, for hue, saturation and lightness controls.
+///
+/// All of them are relative to the primaries of the RGB color and computed from its encoded
+/// values, as common in user interfaces. Their values are stored in the first three channels of
+/// an `Rgb` or `RgbA` texel, with the hue in turns so that every value is in `[0; 1]`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum HueModel {
+    /// Hue, saturation and value.
+    Hsv,
+    /// Hue, saturation and lightness.
+    Hsl,
+    /// Hue, whiteness and blackness.
+    Hwb,
+}
+
+/// The encoding of RGB as luma and two color differences, Y'CbCr.
+///
+/// Images in this encoding keep the RGB color from which their values were computed, and have
+/// texels with `SampleParts::Yuv`. The values are computed from the encoded, not the linear, RGB
+/// values as all video standards do. Descriptors do not record the matrix and range, the same ones
+/// must be used for decoding.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct YCbCr {
+    /// The weights of red and blue in the luma.
+    pub coefficients: YCbCrCoefficients,
+    /// The range of codes which the values occupy.
+    pub range: YCbCrRange,
+}
+
+/// The matrix coefficients of Y'CbCr, as defined by ITU-R recommendations.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum YCbCrCoefficients {
+    /// BT.601, used by standard definition video and JPEG.
+    Bt601,
+    /// BT.709, used by high definition video.
+    Bt709,
+    /// BT.2020, the non-constant luminance variant.
+    Bt2020,
+}
+
+/// The range of codes used by Y'CbCr values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum YCbCrRange {
+    /// All codes, as in JPEG.
+    Full,
+    /// The studio range of video, such as `16..=235` for luma of 8-bit values.
+    Limited,
+}
+
+/// A frame of Y'CbCr with subsampled chroma, as video decoders produce and encoders consume.
+///
+/// The planes are part of the byte layout, see [`Planes`]. Staging upsamples chroma into an image
+/// of Y'CbCr at the resolution of luma, which [`CommandBuffer::planar_decode`] converts to RGB.
+/// [`CommandBuffer::planar_encode`] converts RGB back to Y'CbCr, and chroma is downsampled again
+/// when the result is stored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PlanarFrame {
+    /// The arrangement of the planes, and the siting and filter of chroma.
+    pub planes: Planes,
+    /// The matrix and range of the Y'CbCr codes.
+    pub ycbcr: YCbCr,
+}
+
+/// The layout of an indexed color image, with indices packed into bytes as in PNG.
+///
+/// As an image the bytes are a single channel of `SampleParts::Luma`, see [`Self::descriptor`].
+/// Within each byte the first pixel occupies the most significant bits, and each row starts at a
+/// new byte. They are converted from and to colors of a palette with
+/// [`CommandBuffer::indexed_decode`] and [`CommandBuffer::indexed_encode`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct IndexedLayout {
+    /// The number of bits of each index.
+    pub depth: IndexDepth,
+    /// The width of the image in pixels.
+    pub width: u32,
+    /// The height of the image in pixels.
+    pub height: u32,
+}
+
+/// The number of bits of each index in an indexed color image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum IndexDepth {
+    /// Two colors, eight pixels per byte.
+    Bits1,
+    /// Four colors, four pixels per byte.
+    Bits2,
+    /// Sixteen colors, two pixels per byte.
+    Bits4,
+    /// Up to 256 colors, one pixel per byte.
+    Bits8,
+}
+
+/// The derivation of a palette from the colors of an image, by k-means clustering in Oklab.
+///
+/// The clustering works on a grid of at most `128` by `128` samples of the image. The initial
+/// centroids are distinct colors of samples, preferring those spread evenly over that grid and
+/// then searching up to `1024` further samples spread over all of it. Each iteration assigns
+/// all samples to their nearest centroid and then moves each centroid to the mean of its
+/// samples. Every iteration is a separate pass, so their number is bounded by
+/// [`Self::MAX_ITERATIONS`]. With fewer distinct colors among the searched samples, some colors
+/// are repeated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PaletteQuantization {
+    /// The number of colors, at most `256`.
+    pub colors: u32,
+    /// The number of iterations refining the colors.
+    pub iterations: u32,
+}
+
+/// The source of one channel in [`CommandBuffer::swizzle`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChannelSource {
+    /// Take the value of a channel of the source image.
+    Channel(ColorChannel),
+    /// Set the channel to zero.
+    Zero,
+    /// Set the channel to one.
+    One,
+}
+
+/// The alpha of [`CommandBuffer::add_alpha`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AlphaSource {
+    /// The same alpha value for all pixels.
+    Value(f32),
+    /// The only channel of another image.
+    Mask(Register),
+}
+
+/// The method of distributing quantization error when reducing the bit depth.
+///
+/// All methods are deterministic, the same image is always dithered the same. Each pixel is
+/// dithered on its own. There is no error diffusion, such as Floyd-Steinberg, as its pixels
+/// depend on each other and it would take a pass for each diagonal of the image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Dither {
+    /// Ordered dithering with the thresholds of an 8-by-8 Bayer matrix.
+    Bayer,
+    /// Ordered dithering with the thresholds of a built-in, tileable 16-by-16 blue noise texture.
+    BlueNoise,
+}
+
 /// A palette lookup operation.
 ///
 /// FIXME description and implementation
@@ -396,6 +670,92 @@ pub struct Derivative {
     pub direction: Direction,
 }
 
+/// How an image is extended beyond its bounds.
+///
+/// Constant colors compare and hash by the bits of their channels.
+#[derive(Clone, Copy, Debug)]
+pub enum BorderMode {
+    /// A constant color, the linear values of its channels in RGBA order.
+    Constant([f32; 4]),
+    /// Repeat the texels at the edge.
+    Clamp,
+    /// Repeat the whole image.
+    Repeat,
+    /// Repeat the image, mirrored on every other repetition.
+    Mirror,
+}
+
+/// A convolution kernel of arbitrary weights.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Kernel {
+    /// The number of columns, odd and at most 31.
+    pub width: u32,
+    /// The number of rows, odd and at most 31.
+    pub height: u32,
+    /// The weights, row by row starting at the top-left.
+    pub weights: Vec<f32>,
+    /// How the weights are scaled before convolving.
+    pub normalization: KernelNormalization,
+    /// Which channels are convolved.
+    pub channels: KernelChannels,
+    /// How the image is extended to convolve its edges.
+    pub border: BorderMode,
+}
+
+/// How the weights of a kernel are scaled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum KernelNormalization {
+    /// Use the weights as given.
+    None,
+    /// Divide by the sum of weights, such that a constant image is unchanged.
+    ///
+    /// Kernels whose weights sum to zero, such as edge filters, are used as given.
+    Sum,
+    /// Divide by the sum of the absolute weights, such that values stay within the input range.
+    AbsoluteSum,
+}
+
+/// The channels a kernel applies to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum KernelChannels {
+    /// All channels including alpha, on colors premultiplied by alpha.
+    Color,
+    /// Only the luminance of linear RGB, keeping the differences between channels and alpha.
+    Luma,
+}
+
+/// The neighborhood of each pixel that a morphological operator takes the extremum of.
+///
+/// All elements are centered on the pixel. Pixels outside the image are ignored.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum StructuringElement {
+    /// All pixels within a radius along both axes, a square with sides of `2r + 1`.
+    Square(u32),
+    /// All pixels within a horizontal and a vertical radius.
+    Rectangle(u32, u32),
+    /// All pixels within a Euclidean distance, at most 15.
+    Disk(u32),
+    /// All pixels within a radius on the row and column of the center, at most 15.
+    Cross(u32),
+    /// An arbitrary mask, with an odd width and height of at most 31.
+    Custom {
+        width: u32,
+        height: u32,
+        /// Whether each pixel is contained, row by row starting at the top-left.
+        mask: Vec<bool>,
+    },
+}
+
+/// The widths of the borders added to each side of an image.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Insets {
+    pub left: u32,
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Direction {
     /// Along the height of the image.
@@ -460,6 +820,148 @@ pub enum DerivativeMethod {
     Scharr3To8Bit,
 }
 
+/// Compress scene-referred, linear light into the display range.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ToneMap {
+    /// The curve which maps scene luminance to display luminance.
+    pub operator: ToneMapOperator,
+    /// Exposure adjustment in stops, applied to the linear value before the curve.
+    ///
+    /// Each stop doubles the amount of light, i.e. `1.0` multiplies all channels by two.
+    pub exposure: f32,
+}
+
+/// The curve of a tone mapping.
+///
+/// All operators work on linear RGB as it is decoded when loading an image, and produce linear
+/// values in the range `[0; 1]` that are afterwards encoded with the transfer function of the
+/// target color.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum ToneMapOperator {
+    /// Global Reinhard operator on the luminance.
+    ///
+    ///     L_d = L / (1 + L)
+    Reinhard,
+    /// Reinhard's operator extended with a white point, the smallest luminance that is mapped to
+    /// pure white.
+    ///
+    ///     L_d = L · (1 + L / white²) / (1 + L)
+    ReinhardExtended { white: f32 },
+    /// John Hable's filmic curve, as used in Uncharted 2.
+    ///
+    /// It is applied per channel and normalized so that `white` maps to `1.0`. The original
+    /// suggests a `white` of `11.2`.
+    Hable { white: f32 },
+    /// Stephen Hill's fit of the ACES reference rendering and output device transform.
+    ///
+    /// The fit assumes BT.709 primaries in the working color space.
+    Aces,
+    /// A polynomial approximation of Troy Sobotka's AgX base contrast.
+    AgX,
+}
+
+/// Levels adjustment of a single channel.
+///
+/// Values are first mapped from the input range `[input_black; input_white]` to `[0; 1]` and
+/// clamped, then raised to `1 / gamma`, and finally scaled into `[output_black; output_white]`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Levels {
+    /// The input value which becomes `output_black`.
+    pub input_black: f32,
+    /// The input value which becomes `output_white`.
+    pub input_white: f32,
+    /// The gamma of the midtones, values above `1.0` brighten the image.
+    pub gamma: f32,
+    /// The smallest output value.
+    pub output_black: f32,
+    /// The largest output value.
+    pub output_white: f32,
+}
+
+/// A curve adjustment of a single channel.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Curve {
+    /// Leave the channel unchanged.
+    Identity,
+    /// A levels adjustment.
+    Levels(Levels),
+    /// A curve through control points `[input, output]`.
+    ///
+    /// The points must be sorted by strictly increasing input. Values in between are interpolated
+    /// with a monotone cubic spline (Fritsch-Carlson) so the curve never overshoots the control
+    /// points. Outside the control points the curve is constant.
+    Points(Vec<[f32; 2]>),
+}
+
+/// Whether a curve applies to linear light or to the values encoded with the transfer function.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CurveDomain {
+    /// Apply the curve to linear values, as sampled by all other operations.
+    Linear,
+    /// Apply the curve to electrical values, as they are stored in the image buffer.
+    ///
+    /// This is how most image editors apply curves. It only differs from `Linear` for colors with
+    /// a non-linear transfer function.
+    Encoded,
+}
+
+/// Curves of all channels.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Curves {
+    /// The curve for each channel, in order of the linear representation (`rgba` for RGB colors).
+    pub channels: [Curve; 4],
+    /// The values on which the curves operate.
+    pub domain: CurveDomain,
+}
+
+/// A three-dimensional color lookup table.
+///
+/// Use [`Lut3d::from_cube`] to parse a table from the text of a `.cube` file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lut3d {
+    /// The number of samples along each axis.
+    pub size: u32,
+    /// The output colors, `size³` of them with the red index changing fastest, then green, then
+    /// blue.
+    pub table: Vec<[f32; 3]>,
+    /// The input color of the first sample on each axis.
+    pub domain_min: [f32; 3],
+    /// The input color of the last sample on each axis.
+    pub domain_max: [f32; 3],
+    /// A curve for each channel, applied to the input before the cube.
+    pub shaper: Option<Lut3dShaper>,
+    /// How to interpolate between the samples of the cube.
+    pub interpolation: Lut3dInterpolation,
+}
+
+/// A one-dimensional lookup table per channel, preceding a three-dimensional table.
+///
+/// This is often used to map logarithmic or high dynamic range input to a domain which the cube
+/// samples more evenly. The outputs are the inputs of the cube, relative to its domain.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lut3dShaper {
+    /// The output values of evenly spaced samples, at least two and at most
+    /// [`Lut3dShaper::MAX_SIZE`] of them.
+    pub table: Vec<[f32; 3]>,
+    /// The input values of the first sample.
+    pub domain_min: [f32; 3],
+    /// The input values of the last sample.
+    pub domain_max: [f32; 3],
+}
+
+/// The interpolation between samples of a three-dimensional lookup table.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Lut3dInterpolation {
+    /// Interpolate all eight corners of the cell, along each axis in turn.
+    Trilinear,
+    /// Interpolate the four corners of the tetrahedron containing the color.
+    ///
+    /// This is cheaper and preserves neutral colors, as the diagonal of gray values is an edge of
+    /// all tetrahedra.
+    Tetrahedral,
+}
+
 /// Methods for removing noise from an image.
 ///
 /// WIP: these are not yet implemented.
@@ -600,6 +1102,9 @@ impl CommandBuffer {
         self.tys.push(GenericDescriptor {
             size: Generic::Generic(tyvar),
             chroma: Generic::Generic(tyvar),
+            alpha: Generic::Generic(tyvar),
+            cie: Generic::Generic(tyvar),
+            planes: Generic::Generic(tyvar),
         });
 
         descriptor
@@ -614,9 +1119,18 @@ impl CommandBuffer {
             return Err(CommandError::BAD_REGISTER);
         };
 
+        // Planes only fit the size of the frame they were declared for.
+        let planes = match size {
+            Some(_) => Generic::Concrete(None),
+            None => from.planes,
+        };
+
         let desc = GenericDescriptor {
             size: size.map_or(from.size, Generic::Concrete),
             chroma: from.chroma,
+            alpha: from.alpha,
+            cie: from.cie,
+            planes,
         };
 
         let descriptor = DescriptorVar(self.tys.len());
@@ -749,8 +1263,61 @@ impl CommandBuffer {
     }
 
     /// Select a rectangular part of an image.
+    ///
+    /// The result has the size of the rectangle, which must not be empty and must lie within the
+    /// image. Selecting the whole image returns the source register itself and a crop of a crop
+    /// selects from the original image directly.
+    ///
+    /// The views of the device can not select a part of a texture. Instead, when the part is only
+    /// painted into other images, as by [`Self::inscribe`] and [`Self::affine`], the offset of
+    /// the rectangle is folded into these operations and nothing is copied. Any other use copies
+    /// the part into a texture of its own.
     pub fn crop(&mut self, src: Register, rect: Rectangle) -> Result<Register, CommandError> {
         let desc = self.describe_reg(src)?.clone();
+
+        if rect.width() == 0 || rect.height() == 0 {
+            return Err(CommandError::INVALID_CALL);
+        }
+
+        if let Generic::Concrete((width, height)) = desc.size {
+            let whole = Rectangle::with_width_height(width, height);
+
+            if !whole.contains(rect) {
+                return Err(CommandError {
+                    inner: CommandErrorKind::BadDescriptor(
+                        desc,
+                        "The crop rectangle must lie within the image",
+                    ),
+                });
+            }
+
+            if whole == rect {
+                return Ok(src);
+            }
+        }
+
+        if let Some(&Op::Unary {
+            src: outer,
+            op: UnaryOp::Crop(region),
+            ..
+        }) = self.ops.get(src.0)
+        {
+            let rect = Rectangle {
+                x: region.x + rect.x,
+                y: region.y + rect.y,
+                max_x: region.x + rect.max_x,
+                max_y: region.y + rect.max_y,
+            };
+
+            return self.crop(outer, rect);
+        }
+
+        let desc = GenericDescriptor {
+            size: Generic::Concrete((rect.width(), rect.height())),
+            planes: Generic::Concrete(None),
+            ..desc
+        };
+
         Ok(self.push(Op::Unary {
             src,
             op: UnaryOp::Crop(rect),
@@ -758,9 +1325,192 @@ impl CommandBuffer {
         }))
     }
 
+    /// Mirror an image along one of its axes.
+    ///
+    /// With [`Direction::Width`] the columns are reversed, with [`Direction::Height`] the rows.
+    /// Texels are moved exactly, without any sampling.
+    pub fn flip(&mut self, src: Register, axis: Direction) -> Result<Register, CommandError> {
+        let (width, height) = self.orientable_size(src)?;
+
+        let (x, y) = match axis {
+            Direction::Width => ([-1, 0, width - 1], [0, 1, 0]),
+            Direction::Height => ([1, 0, 0], [0, -1, height - 1]),
+        };
+
+        self.orient(src, shaders::orient::Shader { x, y }, false)
+    }
+
+    /// Rotate an image clockwise by a number of quarter turns.
+    ///
+    /// Odd numbers of turns exchange the width and height of the image. Texels are moved exactly,
+    /// without any sampling.
+    pub fn rotate90(&mut self, src: Register, turns: u32) -> Result<Register, CommandError> {
+        let (width, height) = self.orientable_size(src)?;
+
+        let (x, y) = match turns % 4 {
+            0 => return Ok(src),
+            1 => ([0, 1, 0], [-1, 0, height - 1]),
+            2 => ([-1, 0, width - 1], [0, -1, height - 1]),
+            _ => ([0, -1, width - 1], [1, 0, 0]),
+        };
+
+        self.orient(src, shaders::orient::Shader { x, y }, turns % 2 == 1)
+    }
+
+    /// Mirror an image along its main diagonal, exchanging rows and columns.
+    pub fn transpose(&mut self, src: Register) -> Result<Register, CommandError> {
+        let _ = self.orientable_size(src)?;
+        let (x, y) = ([0, 1, 0], [1, 0, 0]);
+        self.orient(src, shaders::orient::Shader { x, y }, true)
+    }
+
+    /// The size of an image whose texels can be moved individually.
+    fn orientable_size(&self, src: Register) -> Result<(i32, i32), CommandError> {
+        let desc_src = self.describe_reg(src)?;
+        let desc_src = desc_src.as_concrete().ok_or(CommandError {
+            inner: CommandErrorKind::ConcreteDescriptorRequired,
+        })?;
+
+        // The pixels within a block would keep their order.
+        if desc_src.texel.block != Block::Pixel {
+            return Err(CommandError {
+                inner: CommandErrorKind::BadDescriptor(
+                    desc_src.into(),
+                    "Only images of single pixel texels are reoriented",
+                ),
+            });
+        }
+
+        let (width, height) = desc_src.size();
+        Ok((width as i32, height as i32))
+    }
+
+    fn orient(
+        &mut self,
+        src: Register,
+        shader: shaders::orient::Shader,
+        transposed: bool,
+    ) -> Result<Register, CommandError> {
+        let mut desc = self.describe_reg(src)?.as_concrete().ok_or(CommandError {
+            inner: CommandErrorKind::ConcreteDescriptorRequired,
+        })?;
+
+        if transposed {
+            let layout = &mut desc.layout;
+            std::mem::swap(&mut layout.width, &mut layout.height);
+            layout.row_stride = u64::from(layout.texel_stride) * u64::from(layout.width);
+        }
+
+        Ok(self.push(Op::Unary {
+            src,
+            op: UnaryOp::Orient(shader),
+            desc: desc.into(),
+        }))
+    }
+
+    /// Extend an image by borders on each side.
+    ///
+    /// The borders are filled according to the mode, for example to avoid artifacts at the edges
+    /// of a subsequent convolution.
+    pub fn pad(
+        &mut self,
+        src: Register,
+        insets: Insets,
+        border: BorderMode,
+    ) -> Result<Register, CommandError> {
+        let size = self.padded_size(src, insets)?;
+        self.extend(src, [insets.left, insets.top], size, border)
+    }
+
+    /// Extend an image to a new size, keeping its origin.
+    ///
+    /// With [`BorderMode::Repeat`] or [`BorderMode::Mirror`] this creates a tiled pattern from the
+    /// image. Texels outside the new size are discarded.
+    pub fn tile(
+        &mut self,
+        src: Register,
+        (width, height): (u32, u32),
+        border: BorderMode,
+    ) -> Result<Register, CommandError> {
+        self.extend(src, [0, 0], (width, height), border)
+    }
+
+    /// The size of an image extended by the insets.
+    fn padded_size(&self, src: Register, insets: Insets) -> Result<(u32, u32), CommandError> {
+        let desc = self.describe_reg(src)?;
+
+        let Generic::Concrete((width, height)) = desc.size else {
+            return Err(CommandError {
+                inner: CommandErrorKind::ConcreteDescriptorRequired,
+            });
+        };
+
+        let width = width
+            .checked_add(insets.left)
+            .and_then(|width| width.checked_add(insets.right));
+        let height = height
+            .checked_add(insets.top)
+            .and_then(|height| height.checked_add(insets.bottom));
+
+        match (width, height) {
+            (Some(width), Some(height)) => Ok((width, height)),
+            _ => Err(CommandError::INVALID_CALL),
+        }
+    }
+
+    fn extend(
+        &mut self,
+        src: Register,
+        offset: [u32; 2],
+        (width, height): (u32, u32),
+        border: BorderMode,
+    ) -> Result<Register, CommandError> {
+        let desc = self.describe_reg(src)?.clone();
+
+        let Generic::Concrete(size) = desc.size else {
+            return Err(CommandError {
+                inner: CommandErrorKind::ConcreteDescriptorRequired,
+            });
+        };
+
+        if width == 0 || height == 0 {
+            return Err(CommandError::INVALID_CALL);
+        }
+
+        let constant = match border {
+            BorderMode::Constant(color) => Some(color),
+            _ => None,
+        };
+
+        let op = Op::Unary {
+            src,
+            op: UnaryOp::Pad(shaders::pad::Shader {
+                offset,
+                size: [size.0, size.1],
+                constant,
+                address_mode: border.address_mode(),
+            }),
+            desc: GenericDescriptor {
+                size: Generic::Concrete((width, height)),
+                planes: Generic::Concrete(None),
+                ..desc
+            },
+        };
+
+        Ok(self.push(op))
+    }
+
     /// Create an image with different color encoding.
     ///
-    /// This goes through linear RGB, not ICC, and requires the two models to have same whitepoint.
+    /// This goes through linear RGB, not ICC. Colors relative to different whitepoints are adapted
+    /// with Bradford's method, see [`Self::color_convert_with_adaptation`] for choosing another.
+    ///
+    /// All pairs of RGB (including XYZ as primaries), Oklab and SrLab2 are supported, in both their
+    /// rectangular and polar texel forms. Conversions between Oklab and SrLab2 are planned as two
+    /// steps, through an intermediate XYZ image of half-precision floats.
+    ///
+    /// Texels with `SampleParts::Yuv` are rejected on either side. Their descriptor does not record
+    /// the matrix and range, which are passed to [`Self::color_convert_ycbcr`] instead.
     ///
     /// Note that this is not a generic operation. It selects the conversion based on the input
     /// type which requires it to have a concrete descriptor.
@@ -770,22 +1520,267 @@ impl CommandBuffer {
         color: Color,
         texel: Texel,
     ) -> Result<Register, CommandError> {
+        let is_ycbcr = |texel: &Texel| texel.parts == SampleParts::Yuv;
         let desc_src = self.describe_reg(src)?;
-        let conversion;
 
-        let desc_src = desc_src.as_concrete().ok_or(CommandError {
-            inner: CommandErrorKind::ConcreteDescriptorRequired,
-        })?;
+        // The descriptor does not record the matrix and range, we must not guess them.
+        if is_ycbcr(&texel)
+            || desc_src
+                .as_concrete()
+                .is_some_and(|desc| is_ycbcr(&desc.texel))
+        {
+            return Err(CommandError {
+                inner: CommandErrorKind::BadDescriptor(
+                    desc_src.clone(),
+                    "Y'CbCr is converted with its matrix and range by `color_convert_ycbcr`",
+                ),
+            });
+        }
 
-        // Pretend that all colors with the same whitepoint will be mapped from encoded to
-        // linear RGB when loading, and re-encoded in target format when storing them. This is
-        // almost correct, but not all GPUs will support all texel kinds. In particular
-        // some channel orders or bit-field channels are likely to be unsupported. In these
+        let method = ChromaticAdaptationMethod::default();
+        self.color_convert_with_adaptation(src, color, texel, method)
+    }
+
+    /// Convert between RGB and Y'CbCr, with chosen matrix coefficients and range.
+    ///
+    /// Either the source or the target texel has `SampleParts::Yuv` parts, in which case the
+    /// respective color is the RGB color from which Y'CbCr is computed. When both have, the values
+    /// are decoded to RGB and encoded again. Other color conversions happen before encoding and
+    /// after decoding, with an intermediate image of half-precision floats.
+    ///
+    /// The levels of the limited range are scaled to the bit depth of the texel. Floating point
+    /// texels are not codes, they hold the 8-bit levels divided by `256` such that the color
+    /// differences are centered on exactly `0.5`, and the full range is `[0; 1]`. Packed texels
+    /// must store luma and both color differences with the same number of bits, `UInt2101010` and
+    /// `UInt565` are rejected.
+    pub fn color_convert_ycbcr(
+        &mut self,
+        src: Register,
+        color: Color,
+        texel: Texel,
+        ycbcr: YCbCr,
+    ) -> Result<Register, CommandError> {
+        let desc_src = self.describe_reg(src)?;
+        let desc_src = desc_src.as_concrete().ok_or(CommandError {
+            inner: CommandErrorKind::ConcreteDescriptorRequired,
+        })?;
+
+        let from_ycbcr = desc_src.texel.parts == SampleParts::Yuv;
+        let to_ycbcr = texel.parts == SampleParts::Yuv;
+
+        if !from_ycbcr && !to_ycbcr {
+            return self.color_convert(src, color, texel);
+        }
+
+        if !matches!(
+            (&desc_src.color, &color),
+            (Color::Rgb { .. }, Color::Rgb { .. })
+        ) {
+            return Err(CommandError {
+                inner: CommandErrorKind::BadDescriptor(
+                    desc_src.into(),
+                    "Y'CbCr can only be converted from and to RGB colors",
+                ),
+            });
+        }
+
+        if (to_ycbcr && !YCbCr::is_valid_texel(&texel))
+            || (from_ycbcr && !YCbCr::is_valid_texel(&desc_src.texel))
+        {
+            return Err(CommandError::TYPE_ERR);
+        }
+
+        let intermediate = |color: &Color| {
+            let texel = Texel {
+                block: Block::Pixel,
+                bits: SampleBits::Float16x4,
+                parts: SampleParts::RgbA,
+            };
+
+            (color.clone(), texel)
+        };
+
+        if from_ycbcr {
+            let chained = to_ycbcr || desc_src.color != color;
+            let (rgb_color, rgb_texel) = if chained {
+                intermediate(&desc_src.color)
+            } else {
+                (color.clone(), texel.clone())
+            };
+
+            let (matrix, offset) = ycbcr.decode_affine(desc_src.texel.bits);
+            let conversion = ColorConversion::YCbCr { matrix, offset };
+
+            // Write the encoded values, then reinterpret them with the transfer function.
+            let linear = Self::with_linear_transfer(&rgb_color);
+            let decoded = self.push_color_convert(
+                src,
+                &desc_src,
+                conversion,
+                linear.clone().unwrap_or_else(|| rgb_color.clone()),
+                rgb_texel,
+            );
+
+            let decoded = match linear {
+                Some(_) => {
+                    let desc = self.describe_reg(decoded)?.as_concrete();
+                    let desc = desc.ok_or(CommandError::OTHER)?;
+                    self.transmute(
+                        decoded,
+                        Descriptor {
+                            color: rgb_color,
+                            ..desc
+                        },
+                    )?
+                }
+                None => decoded,
+            };
+
+            return if chained {
+                self.color_convert_ycbcr(decoded, color, texel, ycbcr)
+            } else {
+                Ok(decoded)
+            };
+        }
+
+        let src = if desc_src.color != color {
+            let (color, texel) = intermediate(&color);
+            self.color_convert(src, color, texel)?
+        } else {
+            src
+        };
+
+        let desc_src = self
+            .describe_reg(src)?
+            .as_concrete()
+            .ok_or(CommandError::OTHER)?;
+
+        // The values are computed from encoded values, which we get by ignoring the transfer.
+        let operand = match Self::with_linear_transfer(&desc_src.color) {
+            Some(color) => self.transmute(
+                src,
+                Descriptor {
+                    color,
+                    ..desc_src.clone()
+                },
+            )?,
+            None => src,
+        };
+
+        let (matrix, offset) = ycbcr.encode_affine(texel.bits);
+        let conversion = ColorConversion::YCbCr { matrix, offset };
+        Ok(self.push_color_convert(operand, &desc_src, conversion, color, texel))
+    }
+
+    /// Decode a planar Y'CbCr frame into an RGB image.
+    ///
+    /// The source is described by [`Planes::descriptor`] with the planes of the frame, and staging
+    /// has upsampled its chroma to the resolution of luma. The codes are converted to the encoded
+    /// values of `color` as with [`Self::color_convert_ycbcr`].
+    pub fn planar_decode(
+        &mut self,
+        src: Register,
+        frame: PlanarFrame,
+        color: Color,
+        texel: Texel,
+    ) -> Result<Register, CommandError> {
+        let desc_src = self.describe_reg(src)?;
+        let desc_src = desc_src.as_concrete().ok_or(CommandError {
+            inner: CommandErrorKind::ConcreteDescriptorRequired,
+        })?;
+
+        if desc_src.layout.planes != Some(frame.planes) {
+            return Err(CommandError {
+                inner: CommandErrorKind::BadDescriptor(
+                    desc_src.into(),
+                    "Not a frame stored in these planes",
+                ),
+            });
+        }
+
+        if !matches!(color, Color::Rgb { .. })
+            || !matches!(texel.parts, SampleParts::Rgb | SampleParts::RgbA)
+        {
+            return Err(CommandError::TYPE_ERR);
+        }
+
+        self.color_convert_ycbcr(src, color, texel, frame.ycbcr)
+    }
+
+    /// Encode an RGB image into a planar Y'CbCr frame.
+    ///
+    /// The image is the luma plane of the frame, so its width must be even and for 4:2:0 layouts
+    /// also its height. The codes are computed from the encoded values of the image's color, and
+    /// the result is described by [`Planes::descriptor`]. Chroma is downsampled with a filter
+    /// matching the siting of the frame when the result is stored.
+    pub fn planar_encode(
+        &mut self,
+        src: Register,
+        frame: PlanarFrame,
+    ) -> Result<Register, CommandError> {
+        let desc_src = self.describe_reg(src)?;
+        let desc_src = desc_src.as_concrete().ok_or(CommandError {
+            inner: CommandErrorKind::ConcreteDescriptorRequired,
+        })?;
+
+        if !matches!(desc_src.color, Color::Rgb { .. }) {
+            return Err(CommandError {
+                inner: CommandErrorKind::BadDescriptor(
+                    desc_src.into(),
+                    "Y'CbCr can only be converted from RGB colors",
+                ),
+            });
+        }
+
+        let (width, height) = desc_src.size();
+        let desc = frame
+            .planes
+            .descriptor(desc_src.color.clone(), width, height)
+            .ok_or_else(|| CommandError {
+                inner: CommandErrorKind::BadDescriptor(
+                    desc_src.clone().into(),
+                    "The size can not be subsampled with these planes",
+                ),
+            })?;
+
+        let ycbcr =
+            self.color_convert_ycbcr(src, desc.color.clone(), desc.texel.clone(), frame.ycbcr)?;
+
+        // Only the layout changes, the texels are stored in planes instead of next to each other.
+        Ok(self.push(Op::Unary {
+            src: ycbcr,
+            op: UnaryOp::ColorConvert(ColorConversion::Identity),
+            desc: desc.into(),
+        }))
+    }
+
+    /// Convert the color of an image, with a chosen chromatic adaptation between whitepoints.
+    ///
+    /// When the source and target are relative to different whitepoints then colors are adapted
+    /// with the given method. The adaptation is part of the same pass as the conversion.
+    pub fn color_convert_with_adaptation(
+        &mut self,
+        src: Register,
+        color: Color,
+        texel: Texel,
+        method: ChromaticAdaptationMethod,
+    ) -> Result<Register, CommandError> {
+        let desc_src = self.describe_reg(src)?;
+        let conversion;
+
+        let desc_src = desc_src.as_concrete().ok_or(CommandError {
+            inner: CommandErrorKind::ConcreteDescriptorRequired,
+        })?;
+
+        // Pretend that all colors with the same whitepoint will be mapped from encoded to
+        // linear RGB when loading, and re-encoded in target format when storing them. This is
+        // almost correct, but not all GPUs will support all texel kinds. In particular
+        // some channel orders or bit-field channels are likely to be unsupported. In these
         // cases, we will later add some temporary conversion.
         //
         // FIXME: this is growing a bit ugly with non-rgb color spaces. We should find a more
-        // general way to handle these, and in particular also handle non-rgb-to-non-rgb because
-        // that's already happening in the command encoder anyways..
+        // general way to handle these. Non-rgb-to-non-rgb is planned as a chain of two conversions
+        // through XYZ, fusing them into one shader would save a pass and the intermediate image.
 
         match (&desc_src.color, &color) {
             (
@@ -799,34 +1794,39 @@ impl CommandBuffer {
                     whitepoint: wp_dst,
                     ..
                 },
-            ) if wp_src == wp_dst => {
+            ) => {
                 conversion = ColorConversion::Xyz {
-                    from_xyz_matrix: RowMatrix(primary_src.to_xyz_row_matrix(*wp_src)),
-                    to_xyz_matrix: RowMatrix(primary_dst.to_xyz_row_matrix(*wp_dst)),
+                    to_xyz_matrix: method.adapted_to_xyz(*primary_src, *wp_src, *wp_dst)?,
+                    from_xyz_matrix: RowMatrix(primary_dst.from_xyz_row_matrix(*wp_dst)),
                 };
             }
             (
                 Color::Rgb {
                     primary,
-                    whitepoint: Whitepoint::D65,
+                    whitepoint,
                     ..
                 },
                 Color::Oklab,
             ) => {
                 conversion = ColorConversion::XyzToOklab {
-                    to_xyz_matrix: RowMatrix(primary.to_xyz_row_matrix(Whitepoint::D65)),
+                    to_xyz_matrix: method.adapted_to_xyz(*primary, *whitepoint, Whitepoint::D65)?,
                 };
             }
             (
                 Color::Oklab,
                 Color::Rgb {
                     primary,
-                    whitepoint: Whitepoint::D65,
+                    whitepoint,
                     ..
                 },
             ) => {
+                // Adapting from the target's whitepoint is the inverse of adapting to it.
                 conversion = ColorConversion::OklabToXyz {
-                    from_xyz_matrix: RowMatrix(primary.to_xyz_row_matrix(Whitepoint::D65)),
+                    from_xyz_matrix: method.adapted_to_xyz(
+                        *primary,
+                        *whitepoint,
+                        Whitepoint::D65,
+                    )?,
                 };
             }
             (
@@ -838,7 +1838,7 @@ impl CommandBuffer {
                 Color::SrLab2 { whitepoint },
             ) => {
                 conversion = ColorConversion::XyzToSrLab2 {
-                    to_xyz_matrix: RowMatrix(primary.to_xyz_row_matrix(*rgb_wp)),
+                    to_xyz_matrix: method.adapted_to_xyz(*primary, *rgb_wp, *whitepoint)?,
                     whitepoint: *whitepoint,
                 };
             }
@@ -851,10 +1851,34 @@ impl CommandBuffer {
                 },
             ) => {
                 conversion = ColorConversion::SrLab2ToXyz {
-                    from_xyz_matrix: RowMatrix(primary.to_xyz_row_matrix(*rgb_wp)),
+                    from_xyz_matrix: method.adapted_to_xyz(*primary, *rgb_wp, *whitepoint)?,
                     whitepoint: *whitepoint,
                 };
             }
+            (Color::Oklab | Color::SrLab2 { .. }, Color::Oklab | Color::SrLab2 { .. }) => {
+                if desc_src.color == color {
+                    // Only the texel differs, such as Lab and Lch, which staging handles for us.
+                    conversion = ColorConversion::Identity;
+                } else {
+                    // Plan the conversion through XYZ. Each step is one of the direct conversions
+                    // and any whitepoint adaptation happens in the second step.
+                    let whitepoint = match desc_src.color {
+                        Color::SrLab2 { whitepoint } => whitepoint,
+                        _ => Whitepoint::D65,
+                    };
+
+                    let xyz = ColorConversion::xyz_intermediate(whitepoint);
+                    let xyz_texel = Texel {
+                        block: Block::Pixel,
+                        bits: SampleBits::Float16x4,
+                        parts: SampleParts::RgbA,
+                    };
+
+                    let intermediate =
+                        self.color_convert_with_adaptation(src, xyz, xyz_texel, method.clone())?;
+                    return self.color_convert_with_adaptation(intermediate, color, texel, method);
+                }
+            }
             _ => {
                 return Err(CommandError {
                     inner: CommandErrorKind::BadDescriptor(
@@ -865,162 +1889,468 @@ impl CommandBuffer {
             }
         }
 
-        // FIXME: validate memory condition.
-        let layout = ByteLayout {
-            width: desc_src.layout.width,
-            height: desc_src.layout.height,
-            texel_stride: texel.bits.bytes(),
-            row_stride: desc_src.layout.width as u64 * texel.bits.bytes() as u64,
-        };
-
-        let op = Op::Unary {
-            src,
-            op: UnaryOp::ColorConvert(conversion),
-            desc: Descriptor {
-                color,
-                layout,
-                texel,
-            }
-            .into(),
-        };
-
-        Ok(self.push(op))
+        Ok(self.push_color_convert(src, &desc_src, conversion, color, texel))
     }
 
-    /// Perform a whitepoint adaptation.
+    /// Convert linear RGB colors into one of the CIE 1976 color spaces.
     ///
-    /// The `function` describes the method and target whitepoint of the chromatic adaptation.
-    #[allow(unreachable_patterns)]
-    pub fn chromatic_adaptation(
+    /// The texel must have `Lab`, `LabA`, `Lch` or `LchA` parts, see [`CieModel`] for how values
+    /// are stored. Colors are adapted to the reference white with Bradford's method. The result
+    /// is described as linear scalars since a `Color` can not express the space, the descriptor
+    /// records it as [`Descriptor::cie`] instead.
+    pub fn cie_encode(
         &mut self,
         src: Register,
-        method: ChromaticAdaptationMethod,
-        target: Whitepoint,
+        cie: CieColor,
+        texel: Texel,
     ) -> Result<Register, CommandError> {
         let desc_src = self.describe_reg(src)?;
-        let texel_color;
-        let source_wp;
-        let (to_xyz_matrix, from_xyz_matrix);
-
         let desc_src = desc_src.as_concrete().ok_or(CommandError {
             inner: CommandErrorKind::ConcreteDescriptorRequired,
         })?;
 
-        match desc_src.color {
+        let (primary, whitepoint) = match desc_src.color {
             Color::Rgb {
-                whitepoint,
                 primary,
-                transfer,
-                luminance,
-            } => {
-                texel_color = Color::Rgb {
-                    whitepoint: target,
-                    primary,
-                    transfer,
-                    luminance,
-                };
-
-                to_xyz_matrix = RowMatrix(primary.to_xyz_row_matrix(whitepoint));
-                from_xyz_matrix = RowMatrix(primary.from_xyz_row_matrix(target));
-                source_wp = whitepoint;
-            }
-            // Forward compatibility.
+                whitepoint,
+                ..
+            } => (primary, whitepoint),
             _ => {
                 return Err(CommandError {
                     inner: CommandErrorKind::BadDescriptor(
                         desc_src.clone().into(),
-                        "non-rgb chromatic adaptation",
+                        "Only RGB colors can be encoded as CIE colors",
                     ),
                 })
             }
         };
 
-        let desc = Descriptor {
-            color: texel_color,
-            ..desc_src.clone()
+        let form = cie.form(&texel).ok_or_else(|| CommandError {
+            inner: CommandErrorKind::BadDescriptor(
+                desc_src.clone().into(),
+                "Texel must have Lab or Lch parts",
+            ),
+        })?;
+
+        let method = ChromaticAdaptationMethod::default();
+        let conversion = ColorConversion::XyzToCieLab {
+            to_xyz_matrix: method.adapted_to_xyz(primary, whitepoint, cie.whitepoint)?,
+            whitepoint: cie.whitepoint,
+            form,
         };
 
-        let op = Op::Unary {
-            src,
-            op: UnaryOp::ChromaticAdaptation(ChromaticAdaptation {
-                to_xyz_matrix,
-                source: source_wp,
-                target,
-                from_xyz_matrix,
-                method,
-            }),
-            desc: desc.into(),
+        let color = Color::Scalars {
+            transfer: Transfer::Linear,
         };
 
-        Ok(self.push(op))
+        Ok(self.push_color_convert(src, &desc_src, conversion, color, texel))
     }
 
-    /// Embed this image as part of a larger one.
-    pub fn inscribe(
+    /// Convert an image of one of the CIE 1976 color spaces into an RGB color.
+    ///
+    /// This is the inverse of [`Self::cie_encode`]. The texel of the source determines the
+    /// representation of its values. The source must be described as linear scalars with a CIE
+    /// color, the model and reference white of its values. For an input, set [`Descriptor::cie`].
+    pub fn cie_decode(
         &mut self,
-        below: Register,
-        rect: Rectangle,
-        above: Register,
+        src: Register,
+        color: Color,
+        texel: Texel,
     ) -> Result<Register, CommandError> {
-        let desc_below = self.describe_reg(below)?;
-        let desc_above = self.describe_reg(above)?;
+        let desc_src = self.describe_reg(src)?;
+        let desc_src = desc_src.as_concrete().ok_or(CommandError {
+            inner: CommandErrorKind::ConcreteDescriptorRequired,
+        })?;
 
-        if desc_above.descriptor_chroma() != desc_below.descriptor_chroma() {
+        let linear = Color::Scalars {
+            transfer: Transfer::Linear,
+        };
+
+        if desc_src.color != linear {
             return Err(CommandError {
-                inner: CommandErrorKind::ConflictingTypes(desc_below.clone(), desc_above.clone()),
+                inner: CommandErrorKind::BadDescriptor(
+                    desc_src.into(),
+                    "CIE colors are described as linear scalars",
+                ),
             });
         }
 
-        let desc_above = desc_above.as_concrete().ok_or(CommandError {
-            inner: CommandErrorKind::ConcreteDescriptorRequired,
+        let cie = desc_src.cie.ok_or_else(|| CommandError {
+            inner: CommandErrorKind::BadDescriptor(
+                desc_src.clone().into(),
+                "Descriptor has no CIE color",
+            ),
         })?;
 
-        if Rectangle::with_layout(&desc_above.layout) != rect {
-            return Err(CommandError::OTHER);
-        }
+        let form = cie.form(&desc_src.texel).ok_or_else(|| CommandError {
+            inner: CommandErrorKind::BadDescriptor(
+                desc_src.clone().into(),
+                "Texel must have Lab or Lch parts",
+            ),
+        })?;
 
-        // This is pretty much lint status, actually. Nothing intensely bad happens if we paint
-        // outside the image, we could just paint less of it.
-        if let Some(concrete) = desc_below.as_concrete() {
-            if !Rectangle::with_layout(&concrete.layout).contains(rect) {
-                return Err(CommandError::OTHER);
-            }
-        }
+        let (primary, whitepoint) = match color {
+            Color::Rgb {
+                primary,
+                whitepoint,
+                ..
+            } => (primary, whitepoint),
+            _ => return Err(CommandError::TYPE_ERR),
+        };
 
-        let op = Op::Binary {
-            lhs: below,
-            rhs: above,
-            op: BinaryOp::Inscribe {
-                placement: rect.normalize(),
-            },
-            desc: desc_below.clone(),
+        // Adapting from the reference white is the inverse of adapting to it.
+        let method = ChromaticAdaptationMethod::default();
+        let conversion = ColorConversion::CieLabToXyz {
+            from_xyz_matrix: method.adapted_to_xyz(primary, whitepoint, cie.whitepoint)?,
+            whitepoint: cie.whitepoint,
+            form,
         };
 
-        Ok(self.push(op))
+        Ok(self.push_color_convert(src, &desc_src, conversion, color, texel))
     }
 
-    /// Extract some channels from an image data into a new view.
-    pub fn extract(
+    /// Convert RGB colors into one of their cylindrical models.
+    ///
+    /// The texel must have `Rgb` or `RgbA` parts, see [`HueModel`] for how values are stored. The
+    /// result is described as linear scalars since a `Color` can not express the model.
+    pub fn hue_encode(
         &mut self,
         src: Register,
-        channel: ColorChannel,
+        model: HueModel,
+        texel: Texel,
     ) -> Result<Register, CommandError> {
         let desc_src = self.describe_reg(src)?;
+        let desc_src = desc_src
+            .as_concrete()
+            .ok_or(CommandError {
+                inner: CommandErrorKind::ConcreteDescriptorRequired,
+            })?
+            .clone();
 
-        let desc_src = desc_src.as_concrete().ok_or(CommandError {
-            inner: CommandErrorKind::ConcreteDescriptorRequired,
-        })?;
+        if !matches!(desc_src.color, Color::Rgb { .. }) {
+            return Err(CommandError {
+                inner: CommandErrorKind::BadDescriptor(
+                    desc_src.into(),
+                    "Only RGB colors have a hue model",
+                ),
+            });
+        }
 
-        let texel = desc_src
-            .texel
-            .channel_texel(channel)
-            .ok_or(CommandError::OTHER)?;
+        if !HueModel::is_valid_texel(&texel) {
+            return Err(CommandError::TYPE_ERR);
+        }
 
-        let layout = ByteLayout {
-            texel_stride: texel.bits.bytes(),
-            width: desc_src.layout.width,
+        // The model applies to the encoded values, which we get by ignoring the transfer.
+        let operand = match Self::with_linear_transfer(&desc_src.color) {
+            Some(color) => self.transmute(
+                src,
+                Descriptor {
+                    color,
+                    ..desc_src.clone()
+                },
+            )?,
+            None => src,
+        };
+
+        let conversion = ColorConversion::Hue(shaders::hsl::Operation::Encode(model.into()));
+        let color = Color::Scalars {
+            transfer: Transfer::Linear,
+        };
+
+        Ok(self.push_color_convert(operand, &desc_src, conversion, color, texel))
+    }
+
+    /// Convert an image of a cylindrical model into an RGB color.
+    ///
+    /// This is the inverse of [`Self::hue_encode`]. The texel of the source determines the
+    /// representation of its values while its color is ignored.
+    pub fn hue_decode(
+        &mut self,
+        src: Register,
+        model: HueModel,
+        color: Color,
+        texel: Texel,
+    ) -> Result<Register, CommandError> {
+        let desc_src = self.describe_reg(src)?;
+        let desc_src = desc_src
+            .as_concrete()
+            .ok_or(CommandError {
+                inner: CommandErrorKind::ConcreteDescriptorRequired,
+            })?
+            .clone();
+
+        if !HueModel::is_valid_texel(&desc_src.texel) {
+            return Err(CommandError {
+                inner: CommandErrorKind::BadDescriptor(
+                    desc_src.into(),
+                    "Texel must have Rgb or RgbA parts",
+                ),
+            });
+        }
+
+        if !matches!(color, Color::Rgb { .. }) {
+            return Err(CommandError::TYPE_ERR);
+        }
+
+        // Write the encoded values, then reinterpret them with the transfer function.
+        let linear = Self::with_linear_transfer(&color);
+        let conversion = ColorConversion::Hue(shaders::hsl::Operation::Decode(model.into()));
+        let decoded = self.push_color_convert(
+            src,
+            &desc_src,
+            conversion,
+            linear.clone().unwrap_or_else(|| color.clone()),
+            texel,
+        );
+
+        match linear {
+            Some(_) => {
+                let desc = self.describe_reg(decoded)?.as_concrete();
+                let desc = desc.ok_or(CommandError::OTHER)?;
+                self.transmute(decoded, Descriptor { color, ..desc })
+            }
+            None => Ok(decoded),
+        }
+    }
+
+    /// Shift the hue and scale saturation and lightness in HSL, like the sliders of an editor.
+    ///
+    /// The `hue_shift` is given in degrees. The conversion into HSL, the adjustment and the
+    /// conversion back happen in one shader pass, so no precision is lost to an intermediate
+    /// texture. As for [`Self::hue_encode`] the model is computed from the encoded values.
+    ///
+    /// HSL is only defined for values within `[0; 1]`, so images of floating point texels, which
+    /// can leave that range, are rejected.
+    pub fn hsl_adjust(
+        &mut self,
+        src: Register,
+        hue_shift: f32,
+        sat_scale: f32,
+        light_scale: f32,
+    ) -> Result<Register, CommandError> {
+        let desc_src = self.describe_reg(src)?;
+        let desc_src = desc_src.as_concrete().ok_or(CommandError {
+            inner: CommandErrorKind::ConcreteDescriptorRequired,
+        })?;
+
+        if !matches!(desc_src.color, Color::Rgb { .. }) {
+            return Err(CommandError {
+                inner: CommandErrorKind::BadDescriptor(
+                    desc_src.clone().into(),
+                    "Only RGB colors have a hue model",
+                ),
+            });
+        }
+
+        if matches!(
+            desc_src.texel.bits,
+            SampleBits::Float16x4 | SampleBits::Float32x4
+        ) {
+            return Err(CommandError {
+                inner: CommandErrorKind::BadDescriptor(
+                    desc_src.clone().into(),
+                    "HSL is not defined for the values of floating point texels",
+                ),
+            });
+        }
+
+        if ![hue_shift, sat_scale, light_scale]
+            .iter()
+            .all(|v| v.is_finite())
+            || sat_scale < 0.0
+            || light_scale < 0.0
+        {
+            return Err(CommandError::INVALID_CALL);
+        }
+
+        let shader = shaders::hsl::Shader {
+            operation: shaders::hsl::Operation::Adjust {
+                hue_shift: (hue_shift / 360.0).rem_euclid(1.0),
+                saturation: sat_scale,
+                lightness: light_scale,
+            },
+        };
+
+        self.unary_on_encoded(src, UnaryOp::HslAdjust(shader))
+    }
+
+    fn push_color_convert(
+        &mut self,
+        src: Register,
+        desc_src: &Descriptor,
+        conversion: ColorConversion,
+        color: Color,
+        texel: Texel,
+    ) -> Register {
+        // FIXME: validate memory condition.
+        let layout = ByteLayout {
+            width: desc_src.layout.width,
+            height: desc_src.layout.height,
+            texel_stride: texel.bits.bytes(),
+            row_stride: u64::from(desc_src.layout.width) * u64::from(texel.bits.bytes()),
+            planes: None,
+        };
+
+        // Only values of a CIE color space need the reference white they are relative to.
+        let cie = match &conversion {
+            ColorConversion::XyzToCieLab {
+                whitepoint, form, ..
+            } => Some(CieColor {
+                model: form.model.into(),
+                whitepoint: *whitepoint,
+            }),
+            _ => None,
+        };
+
+        self.push(Op::Unary {
+            src,
+            op: UnaryOp::ColorConvert(conversion),
+            desc: Descriptor {
+                color,
+                layout,
+                texel,
+                alpha: desc_src.alpha,
+                cie,
+            }
+            .into(),
+        })
+    }
+
+    /// Perform a whitepoint adaptation.
+    ///
+    /// The `function` describes the method and target whitepoint of the chromatic adaptation.
+    #[allow(unreachable_patterns)]
+    pub fn chromatic_adaptation(
+        &mut self,
+        src: Register,
+        method: ChromaticAdaptationMethod,
+        target: Whitepoint,
+    ) -> Result<Register, CommandError> {
+        let desc_src = self.describe_reg(src)?;
+        let texel_color;
+        let source_wp;
+        let (to_xyz_matrix, from_xyz_matrix);
+
+        let desc_src = desc_src.as_concrete().ok_or(CommandError {
+            inner: CommandErrorKind::ConcreteDescriptorRequired,
+        })?;
+
+        match desc_src.color {
+            Color::Rgb {
+                whitepoint,
+                primary,
+                transfer,
+                luminance,
+            } => {
+                texel_color = Color::Rgb {
+                    whitepoint: target,
+                    primary,
+                    transfer,
+                    luminance,
+                };
+
+                to_xyz_matrix = RowMatrix(primary.to_xyz_row_matrix(whitepoint));
+                from_xyz_matrix = RowMatrix(primary.from_xyz_row_matrix(target));
+                source_wp = whitepoint;
+            }
+            // Forward compatibility.
+            _ => {
+                return Err(CommandError {
+                    inner: CommandErrorKind::BadDescriptor(
+                        desc_src.clone().into(),
+                        "non-rgb chromatic adaptation",
+                    ),
+                })
+            }
+        };
+
+        let desc = Descriptor {
+            color: texel_color,
+            ..desc_src.clone()
+        };
+
+        let op = Op::Unary {
+            src,
+            op: UnaryOp::ChromaticAdaptation(ChromaticAdaptation {
+                to_xyz_matrix,
+                source: source_wp,
+                target,
+                from_xyz_matrix,
+                method,
+            }),
+            desc: desc.into(),
+        };
+
+        Ok(self.push(op))
+    }
+
+    /// Embed this image as part of a larger one.
+    pub fn inscribe(
+        &mut self,
+        below: Register,
+        rect: Rectangle,
+        above: Register,
+    ) -> Result<Register, CommandError> {
+        let desc_below = self.describe_reg(below)?;
+        let desc_above = self.describe_reg(above)?;
+
+        if desc_above.descriptor_chroma() != desc_below.descriptor_chroma() {
+            return Err(CommandError {
+                inner: CommandErrorKind::ConflictingTypes(desc_below.clone(), desc_above.clone()),
+            });
+        }
+
+        let desc_above = desc_above.as_concrete().ok_or(CommandError {
+            inner: CommandErrorKind::ConcreteDescriptorRequired,
+        })?;
+
+        if Rectangle::with_layout(&desc_above.layout) != rect {
+            return Err(CommandError::OTHER);
+        }
+
+        // This is pretty much lint status, actually. Nothing intensely bad happens if we paint
+        // outside the image, we could just paint less of it.
+        if let Some(concrete) = desc_below.as_concrete() {
+            if !Rectangle::with_layout(&concrete.layout).contains(rect) {
+                return Err(CommandError::OTHER);
+            }
+        }
+
+        let op = Op::Binary {
+            lhs: below,
+            rhs: above,
+            op: BinaryOp::Inscribe {
+                placement: rect.normalize(),
+            },
+            desc: desc_below.clone(),
+        };
+
+        Ok(self.push(op))
+    }
+
+    /// Extract some channels from an image data into a new view.
+    pub fn extract(
+        &mut self,
+        src: Register,
+        channel: ColorChannel,
+    ) -> Result<Register, CommandError> {
+        let desc_src = self.describe_reg(src)?;
+
+        let desc_src = desc_src.as_concrete().ok_or(CommandError {
+            inner: CommandErrorKind::ConcreteDescriptorRequired,
+        })?;
+
+        let texel = desc_src
+            .texel
+            .channel_texel(channel)
+            .ok_or(CommandError::OTHER)?;
+
+        let layout = ByteLayout {
+            texel_stride: texel.bits.bytes(),
+            width: desc_src.layout.width,
             height: desc_src.layout.height,
             row_stride: (texel.bits.bytes() as u64) * u64::from(desc_src.layout.width),
+            planes: None,
         };
 
         let color = desc_src.color.clone();
@@ -1039,6 +2369,8 @@ impl CommandBuffer {
                 color,
                 layout,
                 texel,
+                alpha: AlphaMode::Straight,
+                cie: None,
             }
             .into(),
         };
@@ -1089,6 +2421,16 @@ impl CommandBuffer {
             });
         }
 
+        // The bytes are copied as they are, which only keeps their meaning within the same planes.
+        if source.planes != supposed_type.planes {
+            return Err(CommandError {
+                inner: CommandErrorKind::BadDescriptor(
+                    supposed_type,
+                    "invalid transmute with mismatched planes",
+                ),
+            });
+        }
+
         // Predict if monomorphize will only do correct transmutes. A transmute re-interprets the
         // buffer containing bit data in storage layout.
         fn can_transmute(source: Generic<(Texel, Color)>, target: Generic<(Texel, Color)>) -> bool {
@@ -1220,1551 +2562,5905 @@ impl CommandBuffer {
         Ok(self.push(op))
     }
 
-    /// Grab colors from a palette based on an underlying image of indices.
-    pub fn palette(
+    /// Merge single-channel images into the channels of one image, in a single pass.
+    ///
+    /// There must be one input for each channel of the texel, in the order of the texel. The
+    /// inputs must all have the same size and exactly one channel each, such as the results of
+    /// [`Self::extract`]. Values are combined as decoded, so merging the channels extracted from
+    /// an image with its color and texel reconstructs it. A missing alpha channel is opaque.
+    ///
+    /// There is no inverse that splits an image in one pass, as each pass paints a single
+    /// texture. Call [`Self::extract`] for each channel instead.
+    pub fn merge(
         &mut self,
-        palette: Register,
-        config: Palette,
-        indices: Register,
+        srcs: &[Register],
+        color: Color,
+        texel: Texel,
     ) -> Result<Register, CommandError> {
-        let color_desc = self.describe_reg(palette)?;
-        let idx_desc = self.describe_reg(indices)?;
+        let channels = texel.parts.color_channels();
+        let channels: Vec<_> = channels.iter().filter_map(|&channel| channel).collect();
 
-        // FIXME: check that channels are actually in indices' color type.
-        let x_coord = if let Some(coord) = config.width {
-            let pos = ChannelPosition::new(coord).ok_or(CommandError::TYPE_ERR)?;
-            pos.into_vec4()
-        } else {
-            [0.0; 4]
-        };
+        if srcs.is_empty() || srcs.len() != channels.len() {
+            return Err(CommandError::INVALID_CALL);
+        }
 
-        let y_coord = if let Some(coord) = config.height {
-            let pos = ChannelPosition::new(coord).ok_or(CommandError::TYPE_ERR)?;
-            pos.into_vec4()
-        } else {
-            [0.0; 4]
-        };
+        let mut size = None;
+        let mut select = [[[0.0; 4]; 4]; 4];
+        let mut written = [false; 4];
 
-        // Compute the target layout (and that we can represent it).
-        let target_layout = GenericDescriptor {
-            chroma: color_desc.descriptor_chroma(),
-            ..idx_desc.clone()
-        };
+        for (idx, (&src, &channel)) in srcs.iter().zip(&channels).enumerate() {
+            let desc_src = self.describe_reg(src)?;
+            let desc_src = desc_src.as_concrete().ok_or(CommandError {
+                inner: CommandErrorKind::ConcreteDescriptorRequired,
+            })?;
 
-        let op = Op::Binary {
-            lhs: palette,
-            rhs: indices,
-            op: BinaryOp::Palette(shaders::PaletteShader {
-                x_coord,
-                y_coord,
-                base_x: config.width_base,
-                base_y: config.height_base,
+            if *size.get_or_insert(desc_src.size()) != desc_src.size() {
+                return Err(CommandError {
+                    inner: CommandErrorKind::BadDescriptor(
+                        desc_src.into(),
+                        "Merged images must have the same size",
+                    ),
+                });
+            }
+
+            let weights = desc_src
+                .texel
+                .channel_weight_vec4()
+                .ok_or(CommandError::TYPE_ERR)?;
+            let position = Self::channel_position(&texel, channel)?;
+
+            select[idx][position] = weights;
+            written[position] = true;
+        }
+
+        let alpha = if written[3] { 0.0 } else { 1.0 };
+        let (width, height) = size.ok_or(CommandError::INVALID_CALL)?;
+        let desc = Descriptor::with_texel(texel, width, height).ok_or(CommandError::TYPE_ERR)?;
+
+        // The shader always reads four inputs, unused ones repeat the first and are ignored.
+        let mut args = srcs.to_vec();
+        args.resize(4, srcs[0]);
+
+        let op = Op::Nary {
+            args,
+            op: NaryOp::Merge(shaders::channels::Shader {
+                inputs: shaders::channels::Inputs::Merge,
+                select,
+                constant: [0.0, 0.0, 0.0, alpha],
+                premultiply: false,
             }),
-            desc: target_layout,
+            desc: Descriptor { color, ..desc }.into(),
         };
 
         Ok(self.push(op))
     }
 
-    /// Calculate the derivative of an image.
+    /// Rearrange the channels of an image, in a single pass.
     ///
-    /// Currently, will only calculate the derivative for color channels. The alpha channel will be
-    /// copied from the source pixel. To also calculate a derivative over the alpha channel you
-    /// should extract it as a value channel, calculate the derivative there and the inject the
-    /// result back to the image.
-    pub fn derivative(
+    /// Each source determines the channel at the same position of the texel, the sources beyond
+    /// the number of channels are ignored. A channel can be taken from any channel of the same
+    /// image, or be set to a constant zero or one. The result has the type of the source.
+    pub fn swizzle(
         &mut self,
-        image: Register,
-        config: Derivative,
+        src: Register,
+        sources: [ChannelSource; 4],
     ) -> Result<Register, CommandError> {
-        let desc = self.describe_reg(image)?.clone();
+        let desc_src = self.describe_reg(src)?;
+        let desc_src = desc_src.as_concrete().ok_or(CommandError {
+            inner: CommandErrorKind::ConcreteDescriptorRequired,
+        })?;
+
+        let texel = &desc_src.texel;
+        let mut select = [[0.0; 4]; 4];
+        let mut constant = [0.0; 4];
+
+        // Positions not written to keep their value.
+        for (position, weights) in select.iter_mut().enumerate() {
+            weights[position] = 1.0;
+        }
+
+        let channels = texel.parts.color_channels();
+        let channels = channels.iter().filter_map(|&channel| channel);
+
+        for (channel, source) in channels.zip(&sources) {
+            let position = Self::channel_position(texel, channel)?;
+
+            select[position] = match *source {
+                ChannelSource::Channel(from) => {
+                    let from = Self::channel_position(texel, from)?;
+                    let mut weights = [0.0; 4];
+                    weights[from] = 1.0;
+                    weights
+                }
+                ChannelSource::Zero => [0.0; 4],
+                ChannelSource::One => {
+                    constant[position] = 1.0;
+                    [0.0; 4]
+                }
+            };
+        }
 
         let op = Op::Unary {
-            src: image,
-            op: UnaryOp::Derivative(config),
-            desc,
+            src,
+            op: UnaryOp::Swizzle(shaders::channels::Shader {
+                inputs: shaders::channels::Inputs::Swizzle,
+                select: [select, [[0.0; 4]; 4], [[0.0; 4]; 4], [[0.0; 4]; 4]],
+                constant,
+                premultiply: false,
+            }),
+            desc: desc_src.into(),
         };
 
         Ok(self.push(op))
     }
 
-    /// Overlay this image as part of a larger one, performing blending.
-    pub fn blend(
-        &mut self,
-        _below: Register,
-        _rect: Rectangle,
-        _above: Register,
-        _blend: Blend,
-    ) -> Result<Register, CommandError> {
-        // TODO: What blending should we support
-        Err(CommandError::UNIMPLEMENTED)
+    /// The position in the texture of a channel of the texel.
+    fn channel_position(texel: &Texel, channel: ColorChannel) -> Result<usize, CommandError> {
+        let weights = texel
+            .channel_texel(channel)
+            .and_then(|texel| texel.channel_weight_vec4())
+            .ok_or(CommandError::TYPE_ERR)?;
+        let position = weights.iter().position(|&weight| weight != 0.0);
+        position.ok_or(CommandError::TYPE_ERR)
     }
 
-    /// A solid color image, from a descriptor and a single color.
+    /// Add an alpha channel to an image.
     ///
-    /// Repeats the color across all pixels, then transforms into equivalent texels.
-    pub fn solid_rgba(
+    /// The texel of the result has the channels of the source followed by alpha, with the same
+    /// sample type. The alpha is either a constant or the only channel of a mask of the same size,
+    /// taken as its decoded value.
+    pub fn add_alpha(
         &mut self,
-        describe: Descriptor,
-        color: [f32; 4],
+        src: Register,
+        alpha: AlphaSource,
     ) -> Result<Register, CommandError> {
-        if !describe.is_consistent() {
-            return Err(CommandError {
-                inner: CommandErrorKind::BadDescriptor(
-                    describe.into(),
-                    "inconsistent constant color image created",
-                ),
-            });
-        }
+        let desc_src = self.describe_reg(src)?;
+        let desc_src = desc_src.as_concrete().ok_or(CommandError {
+            inner: CommandErrorKind::ConcreteDescriptorRequired,
+        })?;
 
-        if color.len() != usize::from(describe.layout.texel_stride) {
-            return Err(CommandError {
-                inner: CommandErrorKind::BadDescriptor(
-                    describe.into(),
-                    "inconsistent color description",
-                ),
-            });
+        let texel = Self::alpha_texel(&desc_src.texel).ok_or(CommandError {
+            inner: CommandErrorKind::BadDescriptor(
+                desc_src.clone().into(),
+                "No alpha channel can be added to this texel",
+            ),
+        })?;
+
+        let (width, height) = desc_src.size();
+        let desc = Descriptor::with_texel(texel, width, height).ok_or(CommandError::TYPE_ERR)?;
+        let desc = Descriptor {
+            color: desc_src.color.clone(),
+            cie: desc_src.cie,
+            ..desc
+        };
+
+        // Keep the color channels, replace the alpha.
+        let mut select = [[[0.0; 4]; 4]; 4];
+        for (position, column) in select[0].iter_mut().enumerate().take(3) {
+            column[position] = 1.0;
         }
 
-        Ok(self.push(Op::Construct {
-            desc: describe.into(),
-            op: ConstructOp::Solid(color.to_owned()),
-        }))
+        let op = match alpha {
+            AlphaSource::Value(value) => Op::Unary {
+                src,
+                op: UnaryOp::Swizzle(shaders::channels::Shader {
+                    inputs: shaders::channels::Inputs::Swizzle,
+                    select,
+                    constant: [0.0, 0.0, 0.0, value],
+                    premultiply: false,
+                }),
+                desc: desc.into(),
+            },
+            AlphaSource::Mask(mask) => {
+                let desc_mask = self.describe_reg(mask)?;
+                let desc_mask = desc_mask.as_concrete().ok_or(CommandError {
+                    inner: CommandErrorKind::ConcreteDescriptorRequired,
+                })?;
+
+                if desc_mask.size() != desc_src.size() {
+                    return Err(CommandError {
+                        inner: CommandErrorKind::BadDescriptor(
+                            desc_mask.into(),
+                            "The mask must have the size of the image",
+                        ),
+                    });
+                }
+
+                select[1][3] = desc_mask
+                    .texel
+                    .channel_weight_vec4()
+                    .ok_or(CommandError::TYPE_ERR)?;
+
+                Op::Nary {
+                    args: vec![src, mask, src, src],
+                    op: NaryOp::Merge(shaders::channels::Shader {
+                        inputs: shaders::channels::Inputs::Merge,
+                        select,
+                        constant: [0.0; 4],
+                        premultiply: false,
+                    }),
+                    desc: desc.into(),
+                }
+            }
+        };
+
+        Ok(self.push(op))
     }
 
-    /// A 2d image with a normal distribution.
+    /// Remove the alpha channel of an image by compositing it onto a background.
     ///
-    /// The parameters are controlled through the `distribution` parameter while the `texel`
-    /// parameter controls the eventual binary encoding of the image. It must be compatible with a
-    /// single gray channel (but you can have electrical transfer functions, choose arbitrary bit
-    /// widths etc.).
-    pub fn distribution_normal2d(
+    /// The background holds the linear values of the color channels, red, green and blue or
+    /// luminance in the first value. The image is composited in linear light, the result has the
+    /// texel of the source without its alpha channel. Only RGB and scalar colors are supported. An
+    /// image without alpha channel is returned unchanged.
+    pub fn flatten(
         &mut self,
-        describe: Descriptor,
-        distribution: shaders::DistributionNormal2d,
+        src: Register,
+        background: [f32; 3],
     ) -> Result<Register, CommandError> {
-        if !describe.is_consistent() {
-            return Err(CommandError {
-                inner: CommandErrorKind::BadDescriptor(describe.into(), "inconsistent normal2d"),
-            });
+        let desc_src = self.describe_reg(src)?;
+        let desc_src = desc_src.as_concrete().ok_or(CommandError {
+            inner: CommandErrorKind::ConcreteDescriptorRequired,
+        })?;
+
+        match desc_src.color {
+            Color::Rgb { .. } | Color::Scalars { .. } => {}
+            _ => {
+                return Err(CommandError {
+                    inner: CommandErrorKind::BadDescriptor(
+                        desc_src.into(),
+                        "Only RGB and scalar colors are flattened",
+                    ),
+                })
+            }
         }
 
-        if describe.texel.parts != SampleParts::Luma && describe.texel.parts != SampleParts::LumaA {
-            return Err(CommandError {
-                inner: CommandErrorKind::BadDescriptor(
-                    describe.into(),
-                    "normal2d for non-LumA texel",
-                ),
-            });
+        let channels = desc_src.texel.parts.color_channels();
+        if !channels.contains(&Some(ColorChannel::Alpha)) {
+            return Ok(src);
         }
 
-        Ok(self.push(Op::Construct {
-            desc: describe.into(),
-            op: ConstructOp::DistributionNormal(distribution),
-        }))
+        let texel = Self::opaque_texel(&desc_src.texel).ok_or(CommandError::TYPE_ERR)?;
+        let (width, height) = desc_src.size();
+        let desc = Descriptor::with_texel(texel, width, height).ok_or(CommandError::TYPE_ERR)?;
+
+        // color * alpha + background * (1 - alpha), with the first summand premultiplied.
+        let mut select = [[0.0; 4]; 4];
+        let mut constant = [0.0, 0.0, 0.0, 1.0];
+        for (position, &value) in background.iter().enumerate() {
+            select[position][position] = 1.0;
+            select[position][3] = -value;
+            constant[position] = value;
+        }
+
+        let op = Op::Unary {
+            src,
+            op: UnaryOp::Swizzle(shaders::channels::Shader {
+                inputs: shaders::channels::Inputs::Swizzle,
+                select: [select, [[0.0; 4]; 4], [[0.0; 4]; 4], [[0.0; 4]; 4]],
+                constant,
+                premultiply: true,
+            }),
+            desc: Descriptor {
+                color: desc_src.color,
+                cie: desc_src.cie,
+                ..desc
+            }
+            .into(),
+        };
+
+        Ok(self.push(op))
     }
 
-    /// A 2d image with fractal brownian noise.
-    ///
-    /// The parameters are controlled through the `distribution` parameter. Output contains
-    /// in each of the 4 color channels uncorrelated, 1 dimensional fractal perlin noise.
-    pub fn distribution_fractal_noise(
+    /// The texel with an alpha channel appended, of the same sample type.
+    fn alpha_texel(texel: &Texel) -> Option<Texel> {
+        let parts = match texel.parts {
+            SampleParts::Luma => SampleParts::LumaA,
+            SampleParts::Rgb => SampleParts::RgbA,
+            SampleParts::Bgr => SampleParts::BgrA,
+            SampleParts::Lab => SampleParts::LabA,
+            SampleParts::Lch => SampleParts::LchA,
+            _ => return None,
+        };
+
+        let bits = match texel.bits {
+            SampleBits::UInt8 => SampleBits::UInt8x2,
+            SampleBits::UInt8x3 => SampleBits::UInt8x4,
+            SampleBits::UInt16 => SampleBits::UInt16x2,
+            SampleBits::UInt16x3 => SampleBits::UInt16x4,
+            _ => return None,
+        };
+
+        Some(Texel {
+            parts,
+            bits,
+            ..*texel
+        })
+    }
+
+    /// The texel with its alpha channel removed, of the same sample type.
+    fn opaque_texel(texel: &Texel) -> Option<Texel> {
+        let parts = match texel.parts {
+            SampleParts::LumaA => SampleParts::Luma,
+            SampleParts::RgbA | SampleParts::ARgb => SampleParts::Rgb,
+            SampleParts::BgrA | SampleParts::ABgr => SampleParts::Bgr,
+            SampleParts::LabA => SampleParts::Lab,
+            SampleParts::LchA => SampleParts::Lch,
+            _ => return None,
+        };
+
+        let bits = match texel.bits {
+            SampleBits::UInt8x2 => SampleBits::UInt8,
+            SampleBits::UInt8x4 => SampleBits::UInt8x3,
+            SampleBits::UInt16x2 => SampleBits::UInt16,
+            SampleBits::UInt16x4 => SampleBits::UInt16x3,
+            _ => return None,
+        };
+
+        Some(Texel {
+            parts,
+            bits,
+            ..*texel
+        })
+    }
+
+    /// Grab colors from a palette based on an underlying image of indices.
+    pub fn palette(
         &mut self,
-        describe: Descriptor,
-        distribution: shaders::FractalNoise,
+        palette: Register,
+        config: Palette,
+        indices: Register,
     ) -> Result<Register, CommandError> {
-        if !describe.is_consistent() {
-            return Err(CommandError {
-                inner: CommandErrorKind::BadDescriptor(
-                    describe.into(),
-                    "inconsistent descriptor for fractal noise",
-                ),
-            });
-        }
+        let color_desc = self.describe_reg(palette)?;
+        let idx_desc = self.describe_reg(indices)?;
 
-        Ok(self.push(Op::Construct {
-            desc: describe.into(),
-            op: ConstructOp::DistributionNoise(distribution),
-        }))
+        // FIXME: check that channels are actually in indices' color type.
+        let x_coord = if let Some(coord) = config.width {
+            let pos = ChannelPosition::new(coord).ok_or(CommandError::TYPE_ERR)?;
+            pos.into_vec4()
+        } else {
+            [0.0; 4]
+        };
+
+        let y_coord = if let Some(coord) = config.height {
+            let pos = ChannelPosition::new(coord).ok_or(CommandError::TYPE_ERR)?;
+            pos.into_vec4()
+        } else {
+            [0.0; 4]
+        };
+
+        // Compute the target layout (and that we can represent it).
+        let target_layout = GenericDescriptor {
+            chroma: color_desc.descriptor_chroma(),
+            alpha: color_desc.alpha.clone(),
+            cie: color_desc.cie.clone(),
+            planes: Generic::Concrete(None),
+            ..idx_desc.clone()
+        };
+
+        let op = Op::Binary {
+            lhs: palette,
+            rhs: indices,
+            op: BinaryOp::Palette(shaders::PaletteShader {
+                x_coord,
+                y_coord,
+                base_x: config.width_base,
+                base_y: config.height_base,
+            }),
+            desc: target_layout,
+        };
+
+        Ok(self.push(op))
     }
 
-    /// Evaluate a bilinear function over a 2d image.
-    ///
-    /// For each color channel, the parameter contains intervals of values that define how its
-    /// value is determined along the width and height axis.
+    /// Expand packed indices into the colors of a palette.
     ///
-    /// This can be used similar to `numpy`'s `mgrid`.
-    pub fn bilinear(
+    /// The palette is an image with a single row, one pixel per color, and the result has its
+    /// color and texel. The indices are described by [`IndexedLayout::descriptor`]. Indices beyond
+    /// the end of the palette take its last color.
+    pub fn indexed_decode(
         &mut self,
-        describe: Descriptor,
-        distribution: Bilinear,
+        palette: Register,
+        indices: Register,
+        layout: IndexedLayout,
     ) -> Result<Register, CommandError> {
-        if !describe.is_consistent() {
+        let desc_palette = self.palette_descriptor(palette)?;
+        let desc_indices = self.describe_reg(indices)?;
+        let desc_indices = desc_indices.as_concrete().ok_or(CommandError {
+            inner: CommandErrorKind::ConcreteDescriptorRequired,
+        })?;
+
+        let expected = layout.descriptor().ok_or(CommandError::INVALID_CALL)?;
+        if (expected.size(), &expected.texel) != (desc_indices.size(), &desc_indices.texel)
+            || !matches!(desc_indices.color, Color::Scalars { .. })
+        {
             return Err(CommandError {
                 inner: CommandErrorKind::BadDescriptor(
-                    describe.into(),
-                    "inconsistent descriptor for bilinear",
+                    desc_indices.into(),
+                    "Not the bytes of indices with this layout",
                 ),
             });
         }
 
-        Ok(self.push(Op::Construct {
-            desc: describe.into(),
-            op: ConstructOp::Bilinear(distribution),
+        let desc = Descriptor::with_texel(desc_palette.texel.clone(), layout.width, layout.height)
+            .ok_or(CommandError::TYPE_ERR)?;
+
+        let shader = shaders::indexed::Shader {
+            bits: layout.depth.bits(),
+            width: layout.width,
+            palette_len: desc_palette.layout.width,
+            xyz_transform: RowMatrix::diag(1.0, 1.0, 1.0),
+            direction: shaders::Direction::Decode,
+        };
+
+        Ok(self.push(Op::Binary {
+            lhs: palette,
+            rhs: indices,
+            op: BinaryOp::Indexed(shader),
+            desc: Descriptor {
+                color: desc_palette.color,
+                cie: desc_palette.cie,
+                ..desc
+            }
+            .into(),
         }))
     }
 
-    /// Overlay an affine transformation of the image.
-    pub fn affine(
+    /// Map the colors of an image to the nearest colors of a palette, as packed indices.
+    ///
+    /// The palette is an image with a single row, one pixel per color, of which only as many
+    /// colors are used as indices of the depth can address. Distances are measured in Oklab, with
+    /// the difference of alpha as a fourth coordinate. Both image and palette must be RGB and the
+    /// palette is converted to the color of the image if necessary.
+    pub fn indexed_encode(
         &mut self,
-        below: Register,
-        affine: Affine,
-        above: Register,
+        src: Register,
+        palette: Register,
+        depth: IndexDepth,
     ) -> Result<Register, CommandError> {
-        // TODO: should we check affine here?
-        let lhs = self.describe_reg(below)?.clone();
-        let rhs = self.describe_reg(above)?.clone();
+        self.encode_indices(src, palette, depth, None)
+    }
 
-        if lhs.descriptor_chroma() != rhs.descriptor_chroma() {
-            return Err(CommandError::TYPE_ERR);
-        }
+    /// Map the colors of an image to colors of a palette with dithering, as packed indices.
+    ///
+    /// This is [`Self::indexed_encode`] where each pixel first takes a palette color such that the
+    /// colors of a region average to those of the image. The ordered methods choose between the
+    /// nearest color and the one nearest to the other side of the pixel, in the proportion of
+    /// their distance in linear light.
+    pub fn indexed_encode_dithered(
+        &mut self,
+        src: Register,
+        palette: Register,
+        depth: IndexDepth,
+        method: Dither,
+    ) -> Result<Register, CommandError> {
+        self.encode_indices(src, palette, depth, Some(method))
+    }
 
-        match RowMatrix::new(affine.transformation)
-            .det()
-            .abs()
-            .partial_cmp(&f32::EPSILON)
-        {
-            Some(Ordering::Greater | Ordering::Equal) => {}
-            _ => return Err(CommandError::OTHER),
-        }
+    fn encode_indices(
+        &mut self,
+        src: Register,
+        palette: Register,
+        depth: IndexDepth,
+        dither: Option<Dither>,
+    ) -> Result<Register, CommandError> {
+        let desc_src = self.describe_reg(src)?;
+        let desc_src = desc_src.as_concrete().ok_or(CommandError {
+            inner: CommandErrorKind::ConcreteDescriptorRequired,
+        })?;
 
-        match affine.sampling {
-            AffineSample::Nearest => (),
-            AffineSample::BiLinear => {
-                // "Check for a color which we can sample bi-linearly"
-                return Err(CommandError::UNIMPLEMENTED);
+        let (primary, whitepoint) = match desc_src.color {
+            Color::Rgb {
+                primary,
+                whitepoint,
+                ..
+            } => (primary, whitepoint),
+            _ => {
+                return Err(CommandError {
+                    inner: CommandErrorKind::BadDescriptor(
+                        desc_src.into(),
+                        "Nearest colors are only searched for RGB images",
+                    ),
+                })
             }
-        }
+        };
+
+        let mut desc_palette = self.palette_descriptor(palette)?;
+        let palette = if desc_palette.color != desc_src.color {
+            let converted =
+                self.color_convert(palette, desc_src.color.clone(), desc_palette.texel.clone())?;
+            desc_palette = self.palette_descriptor(converted)?;
+            converted
+        } else {
+            palette
+        };
+
+        let (width, height) = desc_src.size();
+        let layout = IndexedLayout {
+            depth,
+            width,
+            height,
+        };
+
+        let desc = layout.descriptor().ok_or(CommandError::INVALID_CALL)?;
+        let max_len = 1u32 << depth.bits();
+        let palette_len = desc_palette.layout.width.min(max_len);
+        let xyz_transform = ChromaticAdaptationMethod::default().adapted_to_xyz(
+            primary,
+            whitepoint,
+            Whitepoint::D65,
+        )?;
+
+        // The dithered colors are all in the palette, their nearest color is exactly that one.
+        let src = match dither {
+            Some(method) => self.push_dither(
+                src,
+                Some(palette),
+                method,
+                shaders::dither::Target::Palette {
+                    len: palette_len,
+                    xyz_transform,
+                },
+                desc_src,
+            )?,
+            None => src,
+        };
+
+        let shader = shaders::indexed::Shader {
+            bits: depth.bits(),
+            width,
+            palette_len,
+            xyz_transform,
+            direction: shaders::Direction::Encode,
+        };
 
         Ok(self.push(Op::Binary {
-            lhs: below,
-            rhs: above,
-            op: BinaryOp::Affine(affine),
-            desc: lhs,
+            lhs: src,
+            rhs: palette,
+            op: BinaryOp::Indexed(shader),
+            desc: desc.into(),
         }))
     }
 
-    pub fn resize(&mut self, below: Register, upper: (u32, u32)) -> Result<Register, CommandError> {
-        let (width, height) = upper;
-        let grid_layout = Descriptor::with_texel(Texel::new_u8(SampleParts::RgbA), width, height)
-            .ok_or(CommandError::OTHER)?;
+    /// Derive a palette from the colors of an image.
+    ///
+    /// The result is an image with a single row, one pixel per color, with the color and texel of
+    /// the source. It can be used with [`Self::palette`] and [`Self::indexed_encode`]. See
+    /// [`PaletteQuantization`] for the method.
+    pub fn quantize_palette(
+        &mut self,
+        src: Register,
+        config: PaletteQuantization,
+    ) -> Result<Register, CommandError> {
+        let desc_src = self.describe_reg(src)?;
+        let desc_src = desc_src.as_concrete().ok_or(CommandError {
+            inner: CommandErrorKind::ConcreteDescriptorRequired,
+        })?;
 
-        let grid = self.bilinear(
-            grid_layout,
-            Bilinear {
-                u_min: [0.0, 0.0, 0.0, 1.0],
-                v_min: [0.0, 0.0, 0.0, 1.0],
-                uv_min: [0.0, 0.0, 0.0, 1.0],
-                u_max: [1.0, 0.0, 0.0, 1.0],
-                v_max: [0.0, 1.0, 0.0, 1.0],
-                uv_max: [0.0, 0.0, 0.0, 1.0],
-            },
+        let (primary, whitepoint) = match desc_src.color {
+            Color::Rgb {
+                primary,
+                whitepoint,
+                ..
+            } => (primary, whitepoint),
+            _ => {
+                return Err(CommandError {
+                    inner: CommandErrorKind::BadDescriptor(
+                        desc_src.into(),
+                        "Palettes are only derived from RGB images",
+                    ),
+                })
+            }
+        };
+
+        if !(1..=256).contains(&config.colors)
+            || config.iterations > PaletteQuantization::MAX_ITERATIONS
+        {
+            return Err(CommandError::INVALID_CALL);
+        }
+
+        let (width, height) = desc_src.size();
+        let grid = (width.min(128), height.min(128));
+        let xyz_transform = ChromaticAdaptationMethod::default().adapted_to_xyz(
+            primary,
+            whitepoint,
+            Whitepoint::D65,
         )?;
+        let shader = |stage| shaders::kmeans::Shader {
+            stage,
+            centroids: config.colors,
+            grid,
+            xyz_transform,
+        };
 
-        self.palette(
-            below,
-            Palette {
-                width: Some(ColorChannel::R),
-                height: Some(ColorChannel::G),
-                width_base: 0,
-                height_base: 0,
+        let lab = Texel {
+            block: Block::Pixel,
+            bits: SampleBits::Float16x4,
+            parts: SampleParts::LabA,
+        };
+
+        let centroids_desc = Descriptor {
+            color: Color::Oklab,
+            ..Descriptor::with_texel(lab, config.colors, 1).ok_or(CommandError::OTHER)?
+        };
+
+        // Each sample is stored as its color followed by the index of its centroid.
+        let assignment_desc = Descriptor::with_texel(
+            Texel {
+                block: Block::Pixel,
+                bits: SampleBits::Float16x4,
+                parts: SampleParts::RgbA,
             },
-            grid,
+            grid.0 * 2,
+            grid.1,
         )
+        .ok_or(CommandError::OTHER)?;
+
+        let mut centroids = self.push(Op::Unary {
+            src,
+            op: UnaryOp::KMeans(shader(shaders::kmeans::Stage::Init)),
+            desc: centroids_desc.clone().into(),
+        });
+
+        for _ in 0..config.iterations {
+            let assignment = self.push(Op::Binary {
+                lhs: src,
+                rhs: centroids,
+                op: BinaryOp::KMeans(shader(shaders::kmeans::Stage::Assign)),
+                desc: assignment_desc.clone().into(),
+            });
+
+            centroids = self.push(Op::Binary {
+                lhs: assignment,
+                rhs: centroids,
+                op: BinaryOp::KMeans(shader(shaders::kmeans::Stage::Update)),
+                desc: centroids_desc.clone().into(),
+            });
+        }
+
+        self.color_convert(centroids, desc_src.color.clone(), desc_src.texel.clone())
     }
 
-    /// Declare an output.
-    ///
-    /// Outputs MUST later be bound from the pool during launch.
-    pub fn output(&mut self, src: Register) -> Result<(Register, GenericDescriptor), CommandError> {
-        let outformat = self.describe_reg(src)?.clone();
-        // Ignore this, it doesn't really produce a register.
-        let register = self.push(Op::Output { src });
-        Ok((register, outformat))
+    /// The descriptor of a palette, an image with a single row of at most 256 colors.
+    fn palette_descriptor(&self, palette: Register) -> Result<Descriptor, CommandError> {
+        let desc = self.describe_reg(palette)?;
+        let desc = desc.as_concrete().ok_or(CommandError {
+            inner: CommandErrorKind::ConcreteDescriptorRequired,
+        })?;
+
+        if desc.layout.height != 1 || !(1..=256).contains(&desc.layout.width) {
+            return Err(CommandError {
+                inner: CommandErrorKind::BadDescriptor(
+                    desc.into(),
+                    "A palette is a single row of at most 256 colors",
+                ),
+            });
+        }
+
+        Ok(desc)
     }
 
-    /// Declare a render target.
+    /// Store the color channels of an image multiplied by alpha.
     ///
-    /// Render targets MUST later be bound from the pool during launch, similar to outputs. However, they are not assumed to be readable afterwards and will never be a copy target.
+    /// Only the descriptor changes, to [`AlphaMode::Premultiplied`]. Commands always work with
+    /// straight alpha internally; the colors are multiplied when the image is stored, e.g. as an
+    /// output, and divided when it is loaded. Filters such as [`Self::convolve`] multiply the
+    /// colors by alpha on their own while sampling, whatever the mode of their input.
     ///
-    /// The target register must be renderable, i.e. a color with a native texture representation.
-    pub fn render(&mut self, src: Register) -> Result<(Register, Descriptor), CommandError> {
-        let outformat = self.describe_reg(src)?.clone();
+    /// If the image is already premultiplied it is returned as is.
+    pub fn premultiply(&mut self, src: Register) -> Result<Register, CommandError> {
+        self.with_alpha_mode(src, AlphaMode::Premultiplied)
+    }
 
-        let outformat = outformat.as_concrete().ok_or(CommandError {
+    /// Store the color channels of an image independent of alpha.
+    ///
+    /// The inverse of [`Self::premultiply`]. The color of fully transparent pixels of a
+    /// premultiplied image is lost, it will be black.
+    pub fn unpremultiply(&mut self, src: Register) -> Result<Register, CommandError> {
+        self.with_alpha_mode(src, AlphaMode::Straight)
+    }
+
+    fn with_alpha_mode(
+        &mut self,
+        src: Register,
+        alpha: AlphaMode,
+    ) -> Result<Register, CommandError> {
+        let desc_src = self.describe_reg(src)?;
+        let desc_src = desc_src.as_concrete().ok_or(CommandError {
             inner: CommandErrorKind::ConcreteDescriptorRequired,
         })?;
 
-        // FIXME: this is too conservative! We need to ensure that our internal assumption about
-        // the texture descriptor is compatible with available wgpu formats (and yields the same
-        // result).
-        if ImageDescriptor::new(&outformat).is_err() {
-            return Err(CommandError::TYPE_ERR);
+        if desc_src.alpha == alpha {
+            return Ok(src);
         }
 
-        // Ignore this, it doesn't really produce a register.
-        let register = self.push(Op::Render { src });
-        Ok((register, outformat))
-    }
-
-    pub fn compile(&self) -> Result<Program, CompileError> {
-        self.link(&[], &[], &[])
+        Ok(self.push(Op::Unary {
+            src,
+            op: UnaryOp::ColorConvert(ColorConversion::Identity),
+            desc: Descriptor { alpha, ..desc_src }.into(),
+        }))
     }
 
-    /// An unergonomic interface for linking a collection of different command buffers to a
-    /// program. The `functions` are all buffers besides `self` that are linked. `links` describes
-    /// the relation between them. For each buffer (`self` at 0 then incremented across the array)
-    /// a list match all function declarations in that buffer to the command supplying the
-    /// definition. The generic signature must match each declaration it is linked to.
+    /// Reduce the values of an image to the codes of a texel, with dithering.
     ///
-    /// FIXME: higher level interface here. We should be able to configured links with pairs of a
-    /// `FunctionVar` and a higher-level wrapper around a `CommandBuffer` index. Also it makes not
-    /// much sense to treat the `self` special except as a defaulted entry point and for the
-    /// `compile` helper that does not require any linkage.
-    pub fn link(
-        &self,
-        tys: &[Descriptor],
-        functions: &[CommandBuffer],
-        links: &[&[usize]],
-    ) -> Result<Program, CompileError> {
-        // We can default to 'no links', which is fine..
-        if functions.len() + 1 < links.len() {
-            eprintln!("Bad link listings count");
-            // Error: more links than functions..
-            return Err(CompileError::NotYetImplemented);
+    /// The result has the color of `src` and the given texel, which must have unsigned integer
+    /// channels of at most 16 bits each, packed formats such as `UInt565` included. The encoded
+    /// values of each channel are snapped to the codes of that channel with the noise of the
+    /// chosen method, before the transfer function is reapplied. Storing the result, e.g. with
+    /// [`Self::output`], then encodes exactly these codes instead of rounding to the nearest ones.
+    ///
+    /// Only RGB and scalar colors are supported. Dithering towards the colors of a palette is not
+    /// part of this command, see [`Self::indexed_encode_dithered`].
+    pub fn dither(
+        &mut self,
+        src: Register,
+        method: Dither,
+        texel: Texel,
+    ) -> Result<Register, CommandError> {
+        let desc_src = self.describe_reg(src)?;
+        let desc_src = desc_src.as_concrete().ok_or(CommandError {
+            inner: CommandErrorKind::ConcreteDescriptorRequired,
+        })?;
+
+        match desc_src.color {
+            Color::Rgb { .. } | Color::Scalars { .. } => {}
+            _ => {
+                return Err(CommandError {
+                    inner: CommandErrorKind::BadDescriptor(
+                        desc_src.into(),
+                        "Only RGB and scalar colors are dithered",
+                    ),
+                })
+            }
         }
 
-        let mut high_ops = vec![];
+        let levels = Self::dither_levels(&texel).ok_or(CommandError::TYPE_ERR)?;
+        let (width, height) = desc_src.size();
+        let desc = Descriptor::with_texel(texel, width, height).ok_or(CommandError::TYPE_ERR)?;
 
-        let mut monomorphic = Monomorphizing {
-            stack: vec![],
-            monomorphic: HashMap::new(),
-            commands: Some(self).into_iter().chain(functions).collect(),
+        let encoded = Self::with_linear_transfer(&desc_src.color);
+
+        let operand = match &encoded {
+            Some(color) => self.transmute(
+                src,
+                Descriptor {
+                    color: color.clone(),
+                    ..desc_src.clone()
+                },
+            )?,
+            None => src,
         };
 
-        monomorphic.push_function(LinkedMonomorphicSignature {
-            link_idx: 0,
-            tys: Cow::Borrowed(tys).into_owned(),
-        });
+        let dithered = self.push_dither(
+            operand,
+            None,
+            method,
+            shaders::dither::Target::Levels(levels),
+            Descriptor {
+                color: encoded.unwrap_or_else(|| desc_src.color.clone()),
+                cie: desc_src.cie,
+                ..desc.clone()
+            },
+        )?;
 
-        impl Monomorphizing<'_> {
-            /// Assign a program function index to a specific generic instantiation.
-            ///
-            /// Remembers to process the monomorphization later if it was not instantiated yet.
-            pub fn push_function(&mut self, sig: LinkedMonomorphicSignature) -> Function {
-                let idx = self.monomorphic.len();
+        self.transmute(
+            dithered,
+            Descriptor {
+                color: desc_src.color,
+                cie: desc_src.cie,
+                ..desc
+            },
+        )
+    }
 
-                let stack = &mut self.stack;
-                let command = &self.commands[sig.link_idx];
+    /// Push the passes dithering an image, towards the colors of a palette if one is given.
+    fn push_dither(
+        &mut self,
+        src: Register,
+        palette: Option<Register>,
+        method: Dither,
+        target: shaders::dither::Target,
+        desc: Descriptor,
+    ) -> Result<Register, CommandError> {
+        let method = match method {
+            Dither::Bayer => shaders::dither::Method::Bayer,
+            Dither::BlueNoise => shaders::dither::Method::BlueNoise,
+        };
 
-                *self.monomorphic.entry(sig).or_insert_with_key(|key| {
-                    stack.push(CommandMonomorphization {
-                        link_idx: key.link_idx,
-                        command,
-                        tys: Cow::Owned(key.tys.to_vec()),
-                    });
+        let op = shaders::dither::Shader { method, target };
+        Ok(self.push(match palette {
+            None => Op::Unary {
+                src,
+                op: UnaryOp::Dither(op),
+                desc: desc.into(),
+            },
+            Some(palette) => Op::Binary {
+                lhs: src,
+                rhs: palette,
+                op: BinaryOp::Dither(op),
+                desc: desc.into(),
+            },
+        }))
+    }
 
-                    Function(idx)
-                })
-            }
+    /// The largest code of each channel of a texel, in RGBA order, or zero for missing channels.
+    fn dither_levels(texel: &Texel) -> Option<[f32; 4]> {
+        // The codes are stored by the staging shaders, which must support the texel.
+        let parameter = shaders::stage::XyzParameter {
+            transfer: shaders::stage::Transfer::Rgb(Transfer::Linear),
+            parts: texel.parts,
+            bits: texel.bits,
+            premultiplied: false,
+            planes: None,
+        };
+
+        parameter.stage_kind()?;
+
+        let widths: &[u32] = match texel.bits {
+            SampleBits::UInt8 => &[8],
+            SampleBits::UInt8x2 => &[8, 8],
+            SampleBits::UInt8x3 => &[8, 8, 8],
+            SampleBits::UInt8x4 => &[8, 8, 8, 8],
+            SampleBits::UInt16 => &[16],
+            SampleBits::UInt16x2 => &[16, 16],
+            SampleBits::UInt16x3 => &[16, 16, 16],
+            SampleBits::UInt16x4 => &[16, 16, 16, 16],
+            SampleBits::UInt332 => &[3, 3, 2],
+            SampleBits::UInt233 => &[2, 3, 3],
+            SampleBits::UInt565 => &[5, 6, 5],
+            SampleBits::UInt4x4 => &[4, 4, 4, 4],
+            SampleBits::UInt1010102 => &[10, 10, 10, 2],
+            SampleBits::UInt2101010 => &[2, 10, 10, 10],
+            _ => return None,
+        };
+
+        // Each channel is stored in exactly one of the fields.
+        if widths.len() != usize::from(texel.parts.num_components()) {
+            return None;
         }
 
-        let mut functions = vec![];
-        while let Some(top) = monomorphic.stack.pop() {
-            let CommandMonomorphization {
-                link_idx,
-                command,
-                tys,
-            } = top;
+        // The widths of the channels in the order they are stored.
+        let w = |idx: usize| widths.get(idx).copied();
+        let [r, g, b, a] = match texel.parts {
+            SampleParts::Luma => [w(0)?, w(0)?, w(0)?, 0],
+            SampleParts::LumaA => [w(0)?, w(0)?, w(0)?, w(1)?],
+            SampleParts::Rgb => [w(0)?, w(1)?, w(2)?, 0],
+            SampleParts::Bgr => [w(2)?, w(1)?, w(0)?, 0],
+            SampleParts::RgbA => [w(0)?, w(1)?, w(2)?, w(3)?],
+            SampleParts::BgrA => [w(2)?, w(1)?, w(0)?, w(3)?],
+            SampleParts::ARgb => [w(1)?, w(2)?, w(3)?, w(0)?],
+            SampleParts::ABgr => [w(3)?, w(2)?, w(1)?, w(0)?],
+            _ => return None,
+        };
 
-            let links = links.get(link_idx).copied().unwrap_or_default();
-            let linked = Self::link_in(command, tys, &mut high_ops, &mut monomorphic, links)?;
-            // FIXME: expand further requested generic instantiations.
-            functions.push(linked);
-        }
+        let level = |bits: u32| ((1u32 << bits) - 1) as f32;
+        Some([level(r), level(g), level(b), level(a)])
+    }
 
-        Ok(Program {
-            ops: high_ops,
-            functions,
-            entry_index: 0,
-            buffer_by_op: HashMap::default(),
-            texture_by_op: HashMap::default(),
-        })
+    /// Calculate the derivative of an image.
+    ///
+    /// Currently, will only calculate the derivative for color channels. The alpha channel will be
+    /// copied from the source pixel. To also calculate a derivative over the alpha channel you
+    /// should extract it as a value channel, calculate the derivative there and the inject the
+    /// result back to the image.
+    ///
+    /// The derivative is taken of colors premultiplied by alpha, see [`Self::premultiply`]. At the
+    /// edges the texels of the image are repeated, see [`Self::derivative_with_border`].
+    pub fn derivative(
+        &mut self,
+        image: Register,
+        config: Derivative,
+    ) -> Result<Register, CommandError> {
+        self.derivative_with_border(image, config, BorderMode::Clamp)
     }
 
-    fn link_in(
-        command: &Self,
-        tys: Cow<'_, [Descriptor]>,
-        high_ops: &mut Vec<High>,
-        mono: &mut Monomorphizing,
-        functions: &[usize],
-    ) -> Result<FunctionLinked, CompileError> {
-        if functions.len() != command.symbols.len() {
-            eprintln!("Bad linked parameter count");
-            return Err(CompileError::NotYetImplemented);
+    /// Calculate the derivative of an image, extended at its edges as chosen by the border.
+    ///
+    /// See [`Self::derivative`] and [`Self::pad`].
+    pub fn derivative_with_border(
+        &mut self,
+        image: Register,
+        config: Derivative,
+        border: BorderMode,
+    ) -> Result<Register, CommandError> {
+        // The box filter can only sample the edge, a constant border is painted around instead.
+        if let BorderMode::Constant(_) = border {
+            let (width, height) = self.padded_size(image, Insets::default())?;
+            let insets = Insets {
+                left: 1,
+                top: 1,
+                right: 1,
+                bottom: 1,
+            };
+
+            let padded = self.pad(image, insets, border)?;
+            let derived = self.derivative(padded, config)?;
+            let inner = Rectangle {
+                x: 1,
+                y: 1,
+                max_x: width + 1,
+                max_y: height + 1,
+            };
+
+            return self.crop(derived, inner);
         }
 
-        if tys.len() != command.vars.len() {
-            eprintln!("Bad type generic count");
-            return Err(CompileError::NotYetImplemented);
+        let desc = self.describe_reg(image)?.clone();
+
+        let op = Op::Unary {
+            src: image,
+            op: UnaryOp::Derivative(config, border),
+            desc,
+        };
+
+        Ok(self.push(op))
+    }
+
+    /// Convolve an image with a kernel of arbitrary weights.
+    ///
+    /// Kernels that are the outer product of a row and a column, such as box and Gaussian blurs,
+    /// are detected and applied as two one-dimensional passes. All other kernels are applied in a
+    /// single pass. A constant border is painted around the image, see [`Self::pad`].
+    ///
+    /// Colors are convolved premultiplied by alpha, see [`Self::premultiply`]. When only the
+    /// luminance is convolved the image must be RGB.
+    pub fn convolve(&mut self, src: Register, kernel: Kernel) -> Result<Register, CommandError> {
+        let max = shaders::convolve::MAX_SIZE;
+        let valid_size = |size: u32| size % 2 == 1 && size <= max;
+
+        if !valid_size(kernel.width)
+            || !valid_size(kernel.height)
+            || kernel.weights.len() != (kernel.width * kernel.height) as usize
+            || !kernel.weights.iter().all(|w| w.is_finite())
+        {
+            return Err(CommandError::INVALID_CALL);
         }
 
-        let ops = &command.ops;
-        let steps = ops.len();
-        let tys = tys.as_ref();
-        let start = high_ops.len();
+        if let BorderMode::Constant(_) = kernel.border {
+            let (width, height) = self.padded_size(src, Insets::default())?;
+            let (rx, ry) = (kernel.width / 2, kernel.height / 2);
+            let border = Insets {
+                left: rx,
+                top: ry,
+                right: rx,
+                bottom: ry,
+            };
 
-        let mut last_use = vec![0; steps];
-        let mut first_use = vec![steps; steps];
+            let padded = self.pad(src, border, kernel.border)?;
+            let kernel = Kernel {
+                border: BorderMode::Clamp,
+                ..kernel
+            };
 
-        let mut image_buffers = ImageBufferPlan::default();
+            let convolved = self.convolve(padded, kernel)?;
+            let inner = Rectangle {
+                x: rx,
+                y: ry,
+                max_x: width + rx,
+                max_y: height + ry,
+            };
 
-        // Liveness analysis.
-        for (back_idx, op) in ops.iter().rev().enumerate() {
-            let idx = ops.len() - 1 - back_idx;
-            match op {
-                Op::Input { .. }
-                | Op::Construct { .. }
-                | Op::Dynamic {
-                    call: OperandDynKind::Construct,
-                    ..
-                } => {}
-                &Op::Output { src: Register(src) } => {
-                    last_use[src] = last_use[src].max(idx);
-                    first_use[src] = first_use[src].min(idx);
-                }
-                &Op::Render { src: Register(src) } => {
-                    last_use[src] = last_use[src].max(idx);
-                    first_use[src] = first_use[src].min(idx);
-                }
-                &Op::Unary {
-                    src: Register(src), ..
-                }
-                | &Op::Dynamic {
-                    call: OperandDynKind::Unary(Register(src)),
-                    ..
-                } => {
-                    last_use[src] = last_use[src].max(idx);
-                    first_use[src] = first_use[src].min(idx);
-                }
-                &Op::Binary {
-                    lhs: Register(lhs),
-                    rhs: Register(rhs),
-                    ..
-                }
-                | &Op::Dynamic {
-                    call:
-                        OperandDynKind::Binary {
-                            lhs: Register(lhs),
-                            rhs: Register(rhs),
-                        },
-                    ..
-                } => {
-                    last_use[rhs] = last_use[rhs].max(idx);
-                    first_use[rhs] = first_use[rhs].min(idx);
-                    last_use[lhs] = last_use[lhs].max(idx);
-                    first_use[lhs] = first_use[lhs].min(idx);
-                }
-                Op::Invoke {
-                    function: _,
-                    arguments: args,
-                    results: _,
-                    generics: _,
-                } => {
-                    for &Register(arg) in args {
-                        last_use[arg] = last_use[arg].max(idx);
-                        first_use[arg] = first_use[arg].min(idx);
-                    }
+            return self.crop(convolved, inner);
+        }
+
+        let desc = self.describe_reg(src)?.clone();
+
+        let luminance = match kernel.channels {
+            KernelChannels::Color => None,
+            KernelChannels::Luma => match &desc.chroma {
+                Generic::Concrete((
+                    _,
+                    Color::Rgb {
+                        primary,
+                        whitepoint,
+                        ..
+                    },
+                )) => {
+                    let to_xyz = primary.to_xyz_row_matrix(*whitepoint);
+                    Some([to_xyz[3], to_xyz[4], to_xyz[5]])
                 }
-                // Not a use of the return value itself.
-                &Op::InvokedResult {
-                    invocation: Register(invocation),
-                    ..
-                } => {
-                    last_use[invocation] = last_use[invocation].max(idx);
-                    first_use[invocation] = first_use[invocation].min(idx);
+                _ => {
+                    return Err(CommandError {
+                        inner: CommandErrorKind::BadDescriptor(
+                            desc,
+                            "Luminance is only convolved in RGB",
+                        ),
+                    })
                 }
-            }
+            },
+        };
+
+        let address_mode = kernel.border.address_mode();
+        let weights = kernel.normalized_weights();
+
+        // Colors are multiplied by alpha when sampled and divided again in the last pass, only
+        // the luminance is moved without regard for alpha.
+        let premultiplied = luminance.is_none();
+
+        // The rows are filtered into linear half floats, whose range does not clamp the factors.
+        let intermediate = GenericDescriptor {
+            chroma: Generic::Concrete((
+                Texel {
+                    block: Block::Pixel,
+                    bits: SampleBits::Float16x4,
+                    parts: SampleParts::RgbA,
+                },
+                Color::Scalars {
+                    transfer: Transfer::Linear,
+                },
+            )),
+            planes: Generic::Concrete(None),
+            ..desc.clone()
+        };
+
+        let passes = match kernel.separate(&weights) {
+            Some((row, column)) => vec![
+                ([kernel.width, 1], row, None, intermediate),
+                ([1, kernel.height], column, luminance, desc),
+            ],
+            None => vec![([kernel.width, kernel.height], weights, luminance, desc)],
+        };
+
+        // The intermediate keeps the premultiplied colors of the rows.
+        let last = passes.len() - 1;
+        let mut convolved = src;
+        for (idx, (size, weights, luminance, desc)) in passes.into_iter().enumerate() {
+            convolved = self.push(Op::Binary {
+                lhs: convolved,
+                rhs: src,
+                op: BinaryOp::Convolve(shaders::convolve::Shader {
+                    size,
+                    weights,
+                    luminance,
+                    premultiply: premultiplied && idx == 0,
+                    straighten: premultiplied && idx == last,
+                    address_mode,
+                }),
+                desc,
+            });
         }
 
-        let mut reg_to_texture: HashMap<Register, Texture> = HashMap::default();
+        Ok(convolved)
+    }
 
-        let mut signature_in: Vec<Register> = vec![];
-        let mut signature_out: Vec<Register> = vec![];
+    /// Erode an image, taking the minimum of each color channel under the structuring element.
+    ///
+    /// This is grayscale morphology, applied to each channel of a color image and directly to
+    /// luma. Alpha is kept. The erosion is repeated for the number of iterations, which must not
+    /// be zero. Large squares and rectangles are separated into lines, each filtered with the
+    /// block scans of van Herk/Gil-Werman.
+    pub fn erode(
+        &mut self,
+        src: Register,
+        element: &StructuringElement,
+        iterations: u32,
+    ) -> Result<Register, CommandError> {
+        self.morphology(
+            src,
+            element,
+            shaders::morphology::Operation::Erode,
+            iterations,
+        )
+    }
 
-        let mut realize_texture = |idx, op: &Op| {
-            let liveness = first_use[idx]..last_use[idx];
+    /// Dilate an image, taking the maximum of each color channel under the structuring element.
+    ///
+    /// See [`Self::erode`] for the details, the element is reflected through its center.
+    pub fn dilate(
+        &mut self,
+        src: Register,
+        element: &StructuringElement,
+        iterations: u32,
+    ) -> Result<Register, CommandError> {
+        self.morphology(
+            src,
+            element,
+            shaders::morphology::Operation::Dilate,
+            iterations,
+        )
+    }
 
-            // FIXME: not all our High ops actually allocate textures..
-            let descriptor = command
-                .describe_reg(if let Op::Output { src } = op {
-                    *src
-                } else if let Op::Render { src } = op {
-                    *src
-                } else {
-                    Register(idx)
-                })
-                .expect("A non-output register");
+    /// Open an image, eroding and then dilating it.
+    ///
+    /// This removes bright details smaller than the structuring element.
+    pub fn open(
+        &mut self,
+        src: Register,
+        element: &StructuringElement,
+        iterations: u32,
+    ) -> Result<Register, CommandError> {
+        let eroded = self.erode(src, element, iterations)?;
+        self.dilate(eroded, element, iterations)
+    }
 
-            let descriptor = descriptor.monomorphize(tys);
+    /// Close an image, dilating and then eroding it.
+    ///
+    /// This fills dark details smaller than the structuring element.
+    pub fn close(
+        &mut self,
+        src: Register,
+        element: &StructuringElement,
+        iterations: u32,
+    ) -> Result<Register, CommandError> {
+        let dilated = self.dilate(src, element, iterations)?;
+        self.erode(dilated, element, iterations)
+    }
 
-            let ImageBufferAssignment { buffer: _, texture } =
-                image_buffers.allocate_for(&descriptor, liveness);
+    /// The bright details removed by opening, the image minus its opening.
+    pub fn top_hat(
+        &mut self,
+        src: Register,
+        element: &StructuringElement,
+        iterations: u32,
+    ) -> Result<Register, CommandError> {
+        let opened = self.open(src, element, iterations)?;
+        self.morphology_difference(src, opened)
+    }
 
-            Ok(texture)
+    /// The dark details filled by closing, the closing of the image minus itself.
+    pub fn black_hat(
+        &mut self,
+        src: Register,
+        element: &StructuringElement,
+        iterations: u32,
+    ) -> Result<Register, CommandError> {
+        let closed = self.close(src, element, iterations)?;
+        self.morphology_difference(closed, src)
+    }
+
+    /// The outlines of an image, its dilation minus its erosion.
+    pub fn morphological_gradient(
+        &mut self,
+        src: Register,
+        element: &StructuringElement,
+        iterations: u32,
+    ) -> Result<Register, CommandError> {
+        let dilated = self.dilate(src, element, iterations)?;
+        let eroded = self.erode(src, element, iterations)?;
+        self.morphology_difference(dilated, eroded)
+    }
+
+    fn morphology(
+        &mut self,
+        src: Register,
+        element: &StructuringElement,
+        operation: shaders::morphology::Operation,
+        iterations: u32,
+    ) -> Result<Register, CommandError> {
+        use shaders::morphology::{Kind, Pass, Shader};
+
+        let passes = match element.passes() {
+            Some(passes) if iterations > 0 => passes,
+            _ => return Err(CommandError::INVALID_CALL),
         };
 
-        for (idx, op) in ops.iter().enumerate() {
-            high_ops.push(High::StackPush(Frame {
-                name: format!("Command: {:#?}", op),
-            }));
+        let desc = self.describe_reg(src)?.clone();
+        let mut result = src;
+
+        for _ in 0..iterations {
+            for pass in &passes {
+                result = match *pass {
+                    MorphologyPass::Element { size, rows } => {
+                        let pass = Pass {
+                            vertical: false,
+                            radius: 0,
+                            backward: false,
+                            offset: 0,
+                        };
+
+                        self.push(Op::Unary {
+                            src: result,
+                            op: UnaryOp::Morphology(Shader {
+                                size,
+                                rows,
+                                ..Shader::with_pass(Kind::Element, operation, pass)
+                            }),
+                            desc: desc.clone(),
+                        })
+                    }
+                    MorphologyPass::Line { vertical, radius } => {
+                        let block = 2 * radius + 1;
+                        let scan = |backward, offset| {
+                            let pass = Pass {
+                                vertical,
+                                radius,
+                                backward,
+                                offset,
+                            };
+
+                            Shader::with_pass(Kind::Scan, operation, pass)
+                        };
+
+                        // Each step doubles the range of the scans, up to the whole block.
+                        let (mut forward, mut backward) = (result, result);
+                        let mut offset = 1;
+                        while offset < block {
+                            forward = self.push(Op::Unary {
+                                src: forward,
+                                op: UnaryOp::Morphology(scan(false, offset)),
+                                desc: desc.clone(),
+                            });
 
-            match op {
-                Op::Input { desc: _ } => {
-                    // This implicitly also persists the descriptor
-                    let texture = realize_texture(idx, op)?;
-                    high_ops.push(High::Input(Register(idx)));
-                    reg_to_texture.insert(Register(idx), texture);
-                    signature_in.push(Register(idx));
-                }
-                &Op::Output { src } => {
-                    let _texture = realize_texture(idx, op)?;
-                    signature_out.push(Register(idx));
+                            backward = self.push(Op::Unary {
+                                src: backward,
+                                op: UnaryOp::Morphology(scan(true, offset)),
+                                desc: desc.clone(),
+                            });
 
-                    high_ops.push(High::Output {
-                        src,
-                        dst: Register(idx),
-                    });
-                }
-                &Op::Render { src } => {
-                    let _texture = realize_texture(idx, op)?;
+                            offset *= 2;
+                        }
 
-                    high_ops.push(High::Render {
-                        src,
-                        dst: Register(idx),
+                        // The merge takes the backward scan first, then the forward scan.
+                        self.push(Op::Binary {
+                            lhs: backward,
+                            rhs: forward,
+                            op: BinaryOp::Morphology(Shader {
+                                kind: Kind::Merge,
+                                ..scan(false, 0)
+                            }),
+                            desc: desc.clone(),
+                        })
+                    }
+                };
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn morphology_difference(
+        &mut self,
+        lhs: Register,
+        rhs: Register,
+    ) -> Result<Register, CommandError> {
+        use shaders::morphology::{Kind, Operation, Pass, Shader};
+
+        let desc = self.describe_reg(lhs)?.clone();
+        let pass = Pass {
+            vertical: false,
+            radius: 0,
+            backward: false,
+            offset: 0,
+        };
+
+        Ok(self.push(Op::Binary {
+            lhs,
+            rhs,
+            op: BinaryOp::Morphology(Shader::with_pass(Kind::Difference, Operation::Erode, pass)),
+            desc,
+        }))
+    }
+
+    /// Map a high dynamic range image into displayable range.
+    ///
+    /// The source must be an RGB image; it is decoded to linear light as usual. The curve then
+    /// maps scene-referred values into `[0; 1]` which are encoded into the given `color` and
+    /// `texel`, for example a float image into 8-bit sRGB. The target color must have the same
+    /// primaries and whitepoint as the source, use `color_convert` to change them beforehand.
+    pub fn tone_map(
+        &mut self,
+        src: Register,
+        tone_map: ToneMap,
+        color: Color,
+        texel: Texel,
+    ) -> Result<Register, CommandError> {
+        let desc_src = self.describe_reg(src)?;
+
+        let desc_src = desc_src.as_concrete().ok_or(CommandError {
+            inner: CommandErrorKind::ConcreteDescriptorRequired,
+        })?;
+
+        let luminance = match (&desc_src.color, &color) {
+            (
+                Color::Rgb {
+                    primary: primary_src,
+                    whitepoint: wp_src,
+                    ..
+                },
+                Color::Rgb {
+                    primary: primary_dst,
+                    whitepoint: wp_dst,
+                    ..
+                },
+            ) if primary_src == primary_dst && wp_src == wp_dst => {
+                let to_xyz = primary_src.to_xyz_row_matrix(*wp_src);
+                [to_xyz[3], to_xyz[4], to_xyz[5]]
+            }
+            _ => {
+                return Err(CommandError {
+                    inner: CommandErrorKind::BadDescriptor(
+                        desc_src.clone().into(),
+                        "Tone mapping requires RGB of the same primaries",
+                    ),
+                })
+            }
+        };
+
+        let (method, white) = match tone_map.operator {
+            ToneMapOperator::Reinhard => (0, 1.0),
+            ToneMapOperator::ReinhardExtended { white } => (1, white),
+            ToneMapOperator::Hable { white } => (2, white),
+            ToneMapOperator::Aces => (3, 1.0),
+            ToneMapOperator::AgX => (4, 1.0),
+        };
+
+        if !white.is_finite() || white <= 0.0 || !tone_map.exposure.is_finite() {
+            return Err(CommandError::INVALID_CALL);
+        }
+
+        let layout = ByteLayout {
+            width: desc_src.layout.width,
+            height: desc_src.layout.height,
+            texel_stride: texel.bits.bytes(),
+            row_stride: desc_src.layout.width as u64 * texel.bits.bytes() as u64,
+            planes: None,
+        };
+
+        let op = Op::Unary {
+            src,
+            op: UnaryOp::ToneMap(shaders::ToneMapShader {
+                luminance,
+                exposure: tone_map.exposure.exp2(),
+                white,
+                method,
+            }),
+            desc: Descriptor {
+                color,
+                layout,
+                texel,
+                alpha: desc_src.alpha,
+                cie: None,
+            }
+            .into(),
+        };
+
+        Ok(self.push(op))
+    }
+
+    /// Adjust channels with individual curves.
+    ///
+    /// The curves are baked into a lookup table when the command is recorded. The table is then
+    /// painted into a texture from which each pixel samples with linear interpolation. Input
+    /// values are clamped to `[0; 1]` for the lookup.
+    pub fn curves(&mut self, src: Register, curves: Curves) -> Result<Register, CommandError> {
+        let desc_src = self.describe_reg(src)?;
+
+        let desc_src = desc_src
+            .as_concrete()
+            .ok_or(CommandError {
+                inner: CommandErrorKind::ConcreteDescriptorRequired,
+            })?
+            .clone();
+
+        if !curves.channels.iter().all(Curve::is_valid) {
+            return Err(CommandError::INVALID_CALL);
+        }
+
+        let table = curves.bake(shaders::lut1d::LUT1D_SIZE);
+        // Half precision, such that dark linear values are not rounded to 8-bit steps.
+        let texel = Texel {
+            block: Block::Pixel,
+            bits: SampleBits::Float16x4,
+            parts: SampleParts::RgbA,
+        };
+
+        let lut_layout = Descriptor::with_texel(texel, shaders::lut1d::LUT1D_SIZE as u32, 1)
+            .ok_or(CommandError::OTHER)?;
+
+        let lut = self.push(Op::Construct {
+            desc: lut_layout.into(),
+            op: ConstructOp::Lut1d(shaders::lut1d::Bake::new(table.into())),
+        });
+
+        match curves.domain {
+            CurveDomain::Linear => {
+                let desc = desc_src.into();
+                Ok(self.push(Op::Binary {
+                    lhs: src,
+                    rhs: lut,
+                    op: BinaryOp::Lut1d,
+                    desc,
+                }))
+            }
+            CurveDomain::Encoded => self.binary_on_encoded(src, lut, BinaryOp::Lut1d),
+        }
+    }
+
+    /// Map colors through a three-dimensional lookup table.
+    ///
+    /// The table applies to the values as encoded in the image, as is the convention for `.cube`
+    /// files exchanged between color grading tools. The table is converted to half precision and
+    /// uploaded as a texture, where slices of constant blue are placed next to each other. The
+    /// table has at most [`Lut3d::MAX_SIZE`] samples per axis, such that the slices fit into a
+    /// texture of `2048×2048`.
+    pub fn lut3d(&mut self, src: Register, lut: &Lut3d) -> Result<Register, CommandError> {
+        let desc_src = self.describe_reg(src)?;
+
+        if desc_src.as_concrete().is_none() {
+            return Err(CommandError {
+                inner: CommandErrorKind::ConcreteDescriptorRequired,
+            });
+        }
+
+        if !lut.is_valid() {
+            return Err(CommandError::INVALID_CALL);
+        }
+
+        // The guaranteed texture dimension of the most restricted device limits.
+        const MAX_TEXTURE_DIMENSION: u32 = 2048;
+
+        let tiles_per_row = lut.tiles_per_row();
+        let width = tiles_per_row * lut.size;
+        let height = lut.size.div_ceil(tiles_per_row) * lut.size;
+
+        if width > MAX_TEXTURE_DIMENSION || height > MAX_TEXTURE_DIMENSION {
+            return Err(CommandError::INVALID_CALL);
+        }
+
+        let texel = Texel {
+            block: Block::Pixel,
+            bits: SampleBits::Float16x4,
+            parts: SampleParts::RgbA,
+        };
+
+        let lut_layout = Descriptor::with_texel(texel, width, height).ok_or(CommandError::OTHER)?;
+
+        let table = self.push(Op::Construct {
+            desc: lut_layout.into(),
+            op: ConstructOp::Upload(TexelData(lut.to_texels(width, height).into())),
+        });
+
+        let shader = shaders::lut3d::Shader {
+            domain: [lut.domain_min, lut.domain_max],
+            size: lut.size,
+            tiles_per_row,
+            interpolation: match lut.interpolation {
+                Lut3dInterpolation::Trilinear => 0,
+                Lut3dInterpolation::Tetrahedral => 1,
+            },
+            shaper: lut.shaper.as_ref().map(Lut3dShaper::to_shader),
+        };
+
+        self.binary_on_encoded(src, table, BinaryOp::Lut3d(shader))
+    }
+
+    /// Apply a levels adjustment to all color channels, leaving alpha unchanged.
+    pub fn levels(
+        &mut self,
+        src: Register,
+        levels: Levels,
+        domain: CurveDomain,
+    ) -> Result<Register, CommandError> {
+        let curve = Curve::Levels(levels);
+
+        self.curves(
+            src,
+            Curves {
+                channels: [curve.clone(), curve.clone(), curve, Curve::Identity],
+                domain,
+            },
+        )
+    }
+
+    /// Overlay this image as part of a larger one, performing blending.
+    pub fn blend(
+        &mut self,
+        _below: Register,
+        _rect: Rectangle,
+        _above: Register,
+        _blend: Blend,
+    ) -> Result<Register, CommandError> {
+        // TODO: What blending should we support
+        Err(CommandError::UNIMPLEMENTED)
+    }
+
+    /// A solid color image, from a descriptor and a single color.
+    ///
+    /// Repeats the color across all pixels, then transforms into equivalent texels.
+    pub fn solid_rgba(
+        &mut self,
+        describe: Descriptor,
+        color: [f32; 4],
+    ) -> Result<Register, CommandError> {
+        if !describe.is_consistent() {
+            return Err(CommandError {
+                inner: CommandErrorKind::BadDescriptor(
+                    describe.into(),
+                    "inconsistent constant color image created",
+                ),
+            });
+        }
+
+        if color.len() != usize::from(describe.layout.texel_stride) {
+            return Err(CommandError {
+                inner: CommandErrorKind::BadDescriptor(
+                    describe.into(),
+                    "inconsistent color description",
+                ),
+            });
+        }
+
+        Ok(self.push(Op::Construct {
+            desc: describe.into(),
+            op: ConstructOp::Solid(color.to_owned()),
+        }))
+    }
+
+    /// A 2d image with a normal distribution.
+    ///
+    /// The parameters are controlled through the `distribution` parameter while the `texel`
+    /// parameter controls the eventual binary encoding of the image. It must be compatible with a
+    /// single gray channel (but you can have electrical transfer functions, choose arbitrary bit
+    /// widths etc.).
+    pub fn distribution_normal2d(
+        &mut self,
+        describe: Descriptor,
+        distribution: shaders::DistributionNormal2d,
+    ) -> Result<Register, CommandError> {
+        if !describe.is_consistent() {
+            return Err(CommandError {
+                inner: CommandErrorKind::BadDescriptor(describe.into(), "inconsistent normal2d"),
+            });
+        }
+
+        if describe.texel.parts != SampleParts::Luma && describe.texel.parts != SampleParts::LumaA {
+            return Err(CommandError {
+                inner: CommandErrorKind::BadDescriptor(
+                    describe.into(),
+                    "normal2d for non-LumA texel",
+                ),
+            });
+        }
+
+        Ok(self.push(Op::Construct {
+            desc: describe.into(),
+            op: ConstructOp::DistributionNormal(distribution),
+        }))
+    }
+
+    /// A 2d image with fractal brownian noise.
+    ///
+    /// The parameters are controlled through the `distribution` parameter. Output contains
+    /// in each of the 4 color channels uncorrelated, 1 dimensional fractal perlin noise.
+    pub fn distribution_fractal_noise(
+        &mut self,
+        describe: Descriptor,
+        distribution: shaders::FractalNoise,
+    ) -> Result<Register, CommandError> {
+        if !describe.is_consistent() {
+            return Err(CommandError {
+                inner: CommandErrorKind::BadDescriptor(
+                    describe.into(),
+                    "inconsistent descriptor for fractal noise",
+                ),
+            });
+        }
+
+        Ok(self.push(Op::Construct {
+            desc: describe.into(),
+            op: ConstructOp::DistributionNoise(distribution),
+        }))
+    }
+
+    /// Evaluate a bilinear function over a 2d image.
+    ///
+    /// For each color channel, the parameter contains intervals of values that define how its
+    /// value is determined along the width and height axis.
+    ///
+    /// This can be used similar to `numpy`'s `mgrid`.
+    pub fn bilinear(
+        &mut self,
+        describe: Descriptor,
+        distribution: Bilinear,
+    ) -> Result<Register, CommandError> {
+        if !describe.is_consistent() {
+            return Err(CommandError {
+                inner: CommandErrorKind::BadDescriptor(
+                    describe.into(),
+                    "inconsistent descriptor for bilinear",
+                ),
+            });
+        }
+
+        Ok(self.push(Op::Construct {
+            desc: describe.into(),
+            op: ConstructOp::Bilinear(distribution),
+        }))
+    }
+
+    /// Overlay an affine transformation of the image.
+    ///
+    /// Each pixel takes the nearest texel of the image, so that colors are never mixed.
+    pub fn affine(
+        &mut self,
+        below: Register,
+        affine: Affine,
+        above: Register,
+    ) -> Result<Register, CommandError> {
+        // TODO: should we check affine here?
+        let lhs = self.describe_reg(below)?.clone();
+        let rhs = self.describe_reg(above)?.clone();
+
+        if lhs.descriptor_chroma() != rhs.descriptor_chroma() {
+            return Err(CommandError::TYPE_ERR);
+        }
+
+        match RowMatrix::new(affine.transformation)
+            .det()
+            .abs()
+            .partial_cmp(&f32::EPSILON)
+        {
+            Some(Ordering::Greater | Ordering::Equal) => {}
+            _ => return Err(CommandError::OTHER),
+        }
+
+        match affine.sampling {
+            AffineSample::Nearest => (),
+            AffineSample::BiLinear => {
+                // "Check for a color which we can sample bi-linearly"
+                return Err(CommandError::UNIMPLEMENTED);
+            }
+        }
+
+        Ok(self.push(Op::Binary {
+            lhs: below,
+            rhs: above,
+            op: BinaryOp::Affine(affine),
+            desc: lhs,
+        }))
+    }
+
+    /// Overlay a projective transformation of the image, mapping one quad onto another.
+    ///
+    /// The quad `src_quad` of the image above is painted onto the quad `dst_quad` of the image
+    /// below, both given as the corners in order in pixel coordinates where the top-left corner of
+    /// an image is at (0, 0) and the bottom-right one at its width and height. The coordinates are
+    /// transformed for each pixel, such that the perspective is correct, which rectifies a
+    /// photographed document for example. Pixels outside of `dst_quad`, or which are mapped
+    /// outside the image above, keep the color of the image below.
+    ///
+    /// When sampled bi-linearly, the colors of the image above are mixed premultiplied by alpha,
+    /// see [`Self::premultiply`]. The image below is copied.
+    pub fn perspective(
+        &mut self,
+        below: Register,
+        src_quad: [[f32; 2]; 4],
+        dst_quad: [[f32; 2]; 4],
+        above: Register,
+        sampling: AffineSample,
+    ) -> Result<Register, CommandError> {
+        let lhs = self.describe_reg(below)?.clone();
+        let rhs = self.describe_reg(above)?.clone();
+
+        if lhs.descriptor_chroma() != rhs.descriptor_chroma() {
+            return Err(CommandError::TYPE_ERR);
+        }
+
+        let Generic::Concrete((width, height)) = rhs.size else {
+            return Err(CommandError {
+                inner: CommandErrorKind::ConcreteDescriptorRequired,
+            });
+        };
+
+        // We sample backwards, from each pixel below to the image above.
+        let mut transform =
+            RowMatrix::homography(dst_quad, src_quad).ok_or(CommandError::INVALID_CALL)?;
+
+        // The homogeneous coordinate has one sign across the quad, make it positive. The matrix
+        // is only determined up to scale and fixing its last entry may have flipped the sign.
+        let centroid = dst_quad
+            .iter()
+            .fold([0.0; 2], |[x, y], [u, v]| [x + u / 4.0, y + v / 4.0]);
+        let [_, _, z] = transform.multiply_column([centroid[0], centroid[1], 1.0]);
+        if z < 0.0 {
+            transform = RowMatrix::new(transform.into_inner().map(|entry| -entry));
+        }
+
+        Ok(self.push(Op::Binary {
+            lhs: below,
+            rhs: above,
+            op: BinaryOp::Perspective(shaders::perspective::Shader {
+                transform,
+                size: [width, height],
+                sampling: sampling.as_sampling(),
+                quad: dst_quad,
+            }),
+            desc: lhs,
+        }))
+    }
+
+    /// Transform the red, green and blue channels of an image independently, in one pass.
+    ///
+    /// Each transformation maps the texture coordinates of its channel in the source, from (0, 0)
+    /// at the top-left to (1, 1) at the bottom-right corner, onto those of the result. The channel
+    /// is resampled with the sampling of its transformation in linear RGB, beyond the edges of the
+    /// image the edge repeats. The alpha channel stays in place.
+    ///
+    /// Lateral chromatic aberration is corrected by scaling red and blue slightly around the
+    /// optical center, e.g. `Affine::new(sampling).shift(-cx, -cy).scale(s, s).shift(cx, cy)`.
+    pub fn affine_channels(
+        &mut self,
+        src: Register,
+        channels: [Affine; 3],
+    ) -> Result<Register, CommandError> {
+        let desc = self.describe_reg(src)?.clone();
+
+        match &desc.chroma {
+            Generic::Concrete((_, Color::Rgb { .. })) => {}
+            _ => {
+                return Err(CommandError {
+                    inner: CommandErrorKind::BadDescriptor(
+                        desc,
+                        "Channels are transformed in linear RGB",
+                    ),
+                })
+            }
+        }
+
+        let mut transform = [RowMatrix::diag(1.0, 1.0, 1.0); 3];
+        for (inverse, affine) in transform.iter_mut().zip(&channels) {
+            let matrix = RowMatrix::new(affine.transformation);
+
+            match matrix.det().abs().partial_cmp(&f32::EPSILON) {
+                Some(Ordering::Greater | Ordering::Equal) => {}
+                _ => return Err(CommandError::OTHER),
+            }
+
+            // We sample backwards, from the result to the source.
+            *inverse = matrix.inv();
+        }
+
+        let [r, g, b] = channels;
+        let sampling = [r, g, b].map(|affine| affine.sampling.as_sampling());
+
+        Ok(self.push(Op::Unary {
+            src,
+            op: UnaryOp::ChannelAffine(shaders::channel_affine::Shader {
+                transform,
+                sampling,
+            }),
+            desc,
+        }))
+    }
+
+    /// Correct the distortion of a lens.
+    ///
+    /// Each pixel of the result is sampled bi-linearly from where the lens moved it in the source,
+    /// see [`LensModel`]. Pixels moved from outside the source are transparent.
+    ///
+    /// The image is sampled with colors premultiplied by alpha, see [`Self::premultiply`].
+    pub fn undistort(&mut self, src: Register, lens: LensModel) -> Result<Register, CommandError> {
+        let desc = self.describe_reg(src)?.clone();
+
+        let focal_valid = lens.focal.iter().all(|f| f.is_normal());
+        let mut coefficients = lens
+            .center
+            .iter()
+            .chain(&lens.radial)
+            .chain(&lens.tangential);
+        if !focal_valid || !coefficients.all(|c| c.is_finite()) {
+            return Err(CommandError::INVALID_CALL);
+        }
+
+        let [fx, fy] = lens.focal;
+        let [cx, cy] = lens.center;
+
+        Ok(self.push(Op::Unary {
+            src,
+            op: UnaryOp::Warp(shaders::warp::Shader {
+                kind: shaders::warp::Kind::Undistort,
+                camera: [fx, fy, cx, cy],
+                radial: lens.radial,
+                tangential: lens.tangential,
+                sampling: shaders::perspective::Sampling::BiLinear,
+            }),
+            desc,
+        }))
+    }
+
+    /// Warp an image by a displacement map.
+    ///
+    /// The result has the size of the map. Each of its pixels is sampled from its own texture
+    /// coordinate, within `[0; 1]` across the image, offset by the decoded values of the first two
+    /// channels of the map. Offsets can only be negative with a signed, e.g. floating point,
+    /// texel. Pixels sampled from outside the source are transparent. The map must be concrete and
+    /// have at least two channels.
+    ///
+    /// The image is sampled with colors premultiplied by alpha, see [`Self::premultiply`].
+    pub fn displace(
+        &mut self,
+        src: Register,
+        map: Register,
+        sampling: AffineSample,
+    ) -> Result<Register, CommandError> {
+        let desc_src = self.describe_reg(src)?.clone();
+        let desc_map = self.describe_reg(map)?;
+        let desc_map = desc_map.as_concrete().ok_or(CommandError {
+            inner: CommandErrorKind::ConcreteDescriptorRequired,
+        })?;
+
+        if desc_map.texel.parts.num_components() < 2 {
+            return Err(CommandError {
+                inner: CommandErrorKind::BadDescriptor(
+                    desc_map.into(),
+                    "A displacement map needs two channels of offsets",
+                ),
+            });
+        }
+
+        let desc = GenericDescriptor {
+            size: Generic::Concrete(desc_map.size()),
+            planes: Generic::Concrete(None),
+            ..desc_src
+        };
+
+        Ok(self.push(Op::Binary {
+            lhs: src,
+            rhs: map,
+            op: BinaryOp::Displace(shaders::warp::Shader {
+                kind: shaders::warp::Kind::Displace,
+                camera: [0.0; 4],
+                radial: [0.0; 3],
+                tangential: [0.0; 2],
+                sampling: sampling.as_sampling(),
+            }),
+            desc,
+        }))
+    }
+
+    /// Resample an image to a new size.
+    ///
+    /// Each pixel takes the nearest texel of the image, so that colors are never mixed.
+    pub fn resize(&mut self, below: Register, upper: (u32, u32)) -> Result<Register, CommandError> {
+        let (width, height) = upper;
+        let grid_layout = Descriptor::with_texel(Texel::new_u8(SampleParts::RgbA), width, height)
+            .ok_or(CommandError::OTHER)?;
+
+        let grid = self.bilinear(
+            grid_layout,
+            Bilinear {
+                u_min: [0.0, 0.0, 0.0, 1.0],
+                v_min: [0.0, 0.0, 0.0, 1.0],
+                uv_min: [0.0, 0.0, 0.0, 1.0],
+                u_max: [1.0, 0.0, 0.0, 1.0],
+                v_max: [0.0, 1.0, 0.0, 1.0],
+                uv_max: [0.0, 0.0, 0.0, 1.0],
+            },
+        )?;
+
+        self.palette(
+            below,
+            Palette {
+                width: Some(ColorChannel::R),
+                height: Some(ColorChannel::G),
+                width_base: 0,
+                height_base: 0,
+            },
+            grid,
+        )
+    }
+
+    /// Declare an output.
+    ///
+    /// Outputs MUST later be bound from the pool during launch.
+    pub fn output(&mut self, src: Register) -> Result<(Register, GenericDescriptor), CommandError> {
+        let outformat = self.describe_reg(src)?.clone();
+        // Ignore this, it doesn't really produce a register.
+        let register = self.push(Op::Output { src });
+        Ok((register, outformat))
+    }
+
+    /// Declare an output, dithered to the codes of a texel.
+    ///
+    /// This is [`Self::dither`] followed by [`Self::output`], avoiding the banding of gradients
+    /// when they are stored with fewer bits than they were computed with.
+    pub fn output_dithered(
+        &mut self,
+        src: Register,
+        method: Dither,
+        texel: Texel,
+    ) -> Result<(Register, GenericDescriptor), CommandError> {
+        let dithered = self.dither(src, method, texel)?;
+        self.output(dithered)
+    }
+
+    /// Declare a render target.
+    ///
+    /// Render targets MUST later be bound from the pool during launch, similar to outputs. However, they are not assumed to be readable afterwards and will never be a copy target.
+    ///
+    /// The target register must be renderable, i.e. a color with a native texture representation.
+    pub fn render(&mut self, src: Register) -> Result<(Register, Descriptor), CommandError> {
+        let outformat = self.describe_reg(src)?.clone();
+
+        let outformat = outformat.as_concrete().ok_or(CommandError {
+            inner: CommandErrorKind::ConcreteDescriptorRequired,
+        })?;
+
+        // FIXME: this is too conservative! We need to ensure that our internal assumption about
+        // the texture descriptor is compatible with available wgpu formats (and yields the same
+        // result).
+        if ImageDescriptor::new(&outformat).is_err() {
+            return Err(CommandError::TYPE_ERR);
+        }
+
+        // Ignore this, it doesn't really produce a register.
+        let register = self.push(Op::Render { src });
+        Ok((register, outformat))
+    }
+
+    pub fn compile(&self) -> Result<Program, CompileError> {
+        self.link(&[], &[], &[])
+    }
+
+    /// An unergonomic interface for linking a collection of different command buffers to a
+    /// program. The `functions` are all buffers besides `self` that are linked. `links` describes
+    /// the relation between them. For each buffer (`self` at 0 then incremented across the array)
+    /// a list match all function declarations in that buffer to the command supplying the
+    /// definition. The generic signature must match each declaration it is linked to.
+    ///
+    /// FIXME: higher level interface here. We should be able to configured links with pairs of a
+    /// `FunctionVar` and a higher-level wrapper around a `CommandBuffer` index. Also it makes not
+    /// much sense to treat the `self` special except as a defaulted entry point and for the
+    /// `compile` helper that does not require any linkage.
+    pub fn link(
+        &self,
+        tys: &[Descriptor],
+        functions: &[CommandBuffer],
+        links: &[&[usize]],
+    ) -> Result<Program, CompileError> {
+        // We can default to 'no links', which is fine..
+        if functions.len() + 1 < links.len() {
+            eprintln!("Bad link listings count");
+            // Error: more links than functions..
+            return Err(CompileError::NotYetImplemented);
+        }
+
+        let mut high_ops = vec![];
+
+        let mut monomorphic = Monomorphizing {
+            stack: vec![],
+            monomorphic: HashMap::new(),
+            commands: Some(self).into_iter().chain(functions).collect(),
+        };
+
+        monomorphic.push_function(LinkedMonomorphicSignature {
+            link_idx: 0,
+            tys: Cow::Borrowed(tys).into_owned(),
+        });
+
+        impl Monomorphizing<'_> {
+            /// Assign a program function index to a specific generic instantiation.
+            ///
+            /// Remembers to process the monomorphization later if it was not instantiated yet.
+            pub fn push_function(&mut self, sig: LinkedMonomorphicSignature) -> Function {
+                let idx = self.monomorphic.len();
+
+                let stack = &mut self.stack;
+                let command = &self.commands[sig.link_idx];
+
+                *self.monomorphic.entry(sig).or_insert_with_key(|key| {
+                    stack.push(CommandMonomorphization {
+                        link_idx: key.link_idx,
+                        command,
+                        tys: Cow::Owned(key.tys.to_vec()),
+                    });
+
+                    Function(idx)
+                })
+            }
+        }
+
+        let mut functions = vec![];
+        while let Some(top) = monomorphic.stack.pop() {
+            let CommandMonomorphization {
+                link_idx,
+                command,
+                tys,
+            } = top;
+
+            let links = links.get(link_idx).copied().unwrap_or_default();
+            let linked = Self::link_in(command, tys, &mut high_ops, &mut monomorphic, links)?;
+            // FIXME: expand further requested generic instantiations.
+            functions.push(linked);
+        }
+
+        Ok(Program {
+            ops: high_ops,
+            functions,
+            entry_index: 0,
+            buffer_by_op: HashMap::default(),
+            texture_by_op: HashMap::default(),
+        })
+    }
+
+    fn link_in(
+        command: &Self,
+        tys: Cow<'_, [Descriptor]>,
+        high_ops: &mut Vec<High>,
+        mono: &mut Monomorphizing,
+        functions: &[usize],
+    ) -> Result<FunctionLinked, CompileError> {
+        if functions.len() != command.symbols.len() {
+            eprintln!("Bad linked parameter count");
+            return Err(CompileError::NotYetImplemented);
+        }
+
+        if tys.len() != command.vars.len() {
+            eprintln!("Bad type generic count");
+            return Err(CompileError::NotYetImplemented);
+        }
+
+        let ops = &command.ops;
+        let steps = ops.len();
+        let tys = tys.as_ref();
+        let start = high_ops.len();
+
+        let mut last_use = vec![0; steps];
+        let mut first_use = vec![steps; steps];
+
+        let mut image_buffers = ImageBufferPlan::default();
+
+        // Liveness analysis.
+        for (back_idx, op) in ops.iter().rev().enumerate() {
+            let idx = ops.len() - 1 - back_idx;
+            match op {
+                Op::Input { .. }
+                | Op::Construct { .. }
+                | Op::Dynamic {
+                    call: OperandDynKind::Construct,
+                    ..
+                } => {}
+                &Op::Output { src: Register(src) } => {
+                    last_use[src] = last_use[src].max(idx);
+                    first_use[src] = first_use[src].min(idx);
+                }
+                &Op::Render { src: Register(src) } => {
+                    last_use[src] = last_use[src].max(idx);
+                    first_use[src] = first_use[src].min(idx);
+                }
+                &Op::Unary {
+                    src: Register(src), ..
+                }
+                | &Op::Dynamic {
+                    call: OperandDynKind::Unary(Register(src)),
+                    ..
+                } => {
+                    last_use[src] = last_use[src].max(idx);
+                    first_use[src] = first_use[src].min(idx);
+                }
+                &Op::Binary {
+                    lhs: Register(lhs),
+                    rhs: Register(rhs),
+                    ..
+                }
+                | &Op::Dynamic {
+                    call:
+                        OperandDynKind::Binary {
+                            lhs: Register(lhs),
+                            rhs: Register(rhs),
+                        },
+                    ..
+                } => {
+                    last_use[rhs] = last_use[rhs].max(idx);
+                    first_use[rhs] = first_use[rhs].min(idx);
+                    last_use[lhs] = last_use[lhs].max(idx);
+                    first_use[lhs] = first_use[lhs].min(idx);
+                }
+                Op::Nary { args, .. }
+                | Op::Invoke {
+                    function: _,
+                    arguments: args,
+                    results: _,
+                    generics: _,
+                } => {
+                    for &Register(arg) in args {
+                        last_use[arg] = last_use[arg].max(idx);
+                        first_use[arg] = first_use[arg].min(idx);
+                    }
+                }
+                // Not a use of the return value itself.
+                &Op::InvokedResult {
+                    invocation: Register(invocation),
+                    ..
+                } => {
+                    last_use[invocation] = last_use[invocation].max(idx);
+                    first_use[invocation] = first_use[invocation].min(idx);
+                }
+            }
+        }
+
+        // A crop which is only painted into other images is never copied, nor allocated. Its
+        // consumers select the rectangle from the texture of the cropped image instead, which must
+        // then stay alive for as long as the crop is used.
+        let mut views: HashMap<Register, (Register, Rectangle)> = HashMap::default();
+        for (idx, op) in ops.iter().enumerate() {
+            if let &Op::Unary {
+                src,
+                op: UnaryOp::Crop(region),
+                ..
+            } = op
+            {
+                views.insert(Register(idx), (src, region));
+            }
+        }
+
+        for op in ops.iter() {
+            match op {
+                Op::Binary {
+                    op: BinaryOp::Affine(_) | BinaryOp::Inscribe { .. },
+                    ..
+                } => {}
+                Op::Input { .. } | Op::Construct { .. } => {}
+                Op::Output { src } | Op::Render { src } | Op::Unary { src, .. } => {
+                    views.remove(src);
+                }
+                Op::Binary { lhs, rhs, .. } => {
+                    views.remove(lhs);
+                    views.remove(rhs);
+                }
+                Op::Dynamic { call, .. } => match call {
+                    OperandDynKind::Construct => {}
+                    OperandDynKind::Unary(src) => {
+                        views.remove(src);
+                    }
+                    OperandDynKind::Binary { lhs, rhs } => {
+                        views.remove(lhs);
+                        views.remove(rhs);
+                    }
+                },
+                Op::Nary { args, .. }
+                | Op::Invoke {
+                    arguments: args, ..
+                } => {
+                    for arg in args {
+                        views.remove(arg);
+                    }
+                }
+                Op::InvokedResult { invocation, .. } => {
+                    views.remove(invocation);
+                }
+            }
+        }
+
+        for (&Register(view), &(Register(src), _)) in &views {
+            last_use[src] = last_use[src].max(last_use[view]);
+        }
+
+        let mut reg_to_texture: HashMap<Register, Texture> = HashMap::default();
+
+        let mut signature_in: Vec<Register> = vec![];
+        let mut signature_out: Vec<Register> = vec![];
+
+        let mut realize_texture = |idx, op: &Op| {
+            let liveness = first_use[idx]..last_use[idx];
+
+            // A view has no texture of its own, it is painted from that of the cropped image.
+            if let Some(&(src, _)) = views.get(&Register(idx)) {
+                let ImageBufferAssignment { buffer: _, texture } = image_buffers.alias_for(src);
+                return Ok(texture);
+            }
+
+            // FIXME: not all our High ops actually allocate textures..
+            let descriptor = command
+                .describe_reg(if let Op::Output { src } = op {
+                    *src
+                } else if let Op::Render { src } = op {
+                    *src
+                } else {
+                    Register(idx)
+                })
+                .expect("A non-output register");
+
+            let descriptor = descriptor.monomorphize(tys);
+
+            let ImageBufferAssignment { buffer: _, texture } =
+                image_buffers.allocate_for(&descriptor, liveness);
+
+            Ok(texture)
+        };
+
+        for (idx, op) in ops.iter().enumerate() {
+            high_ops.push(High::StackPush(Frame {
+                name: format!("Command: {:#?}", op),
+            }));
+
+            match op {
+                Op::Input { desc: _ } => {
+                    // This implicitly also persists the descriptor
+                    let texture = realize_texture(idx, op)?;
+                    high_ops.push(High::Input(Register(idx)));
+                    reg_to_texture.insert(Register(idx), texture);
+                    signature_in.push(Register(idx));
+                }
+                &Op::Output { src } => {
+                    let _texture = realize_texture(idx, op)?;
+                    signature_out.push(Register(idx));
+
+                    high_ops.push(High::Output {
+                        src,
+                        dst: Register(idx),
+                    });
+                }
+                &Op::Render { src } => {
+                    let _texture = realize_texture(idx, op)?;
+
+                    high_ops.push(High::Render {
+                        src,
+                        dst: Register(idx),
+                    });
+                }
+                Op::Construct {
+                    desc: _,
+                    op: construct_op,
+                } => {
+                    let texture = realize_texture(idx, op)?;
+
+                    match construct_op {
+                        &ConstructOp::DistributionNormal(ref distribution) => {
+                            high_ops.push(High::Construct {
+                                dst: Target::Discard(texture),
+                                fn_: Initializer::PaintFullScreen {
+                                    shader: FragmentShader::Normal2d(distribution.clone()),
+                                },
+                            })
+                        }
+                        ConstructOp::DistributionNoise(ref noise_params) => {
+                            high_ops.push(High::Construct {
+                                dst: Target::Discard(texture),
+                                fn_: Initializer::PaintFullScreen {
+                                    shader: FragmentShader::FractalNoise(noise_params.clone()),
+                                },
+                            })
+                        }
+                        ConstructOp::Bilinear(bilinear) => high_ops.push(High::Construct {
+                            dst: Target::Discard(texture),
+                            fn_: Initializer::PaintFullScreen {
+                                shader: FragmentShader::Bilinear(bilinear.clone()),
+                            },
+                        }),
+                        &ConstructOp::Solid(color) => high_ops.push(High::Construct {
+                            dst: Target::Discard(texture),
+                            fn_: Initializer::PaintFullScreen {
+                                shader: FragmentShader::SolidRgb(color.into()),
+                            },
+                        }),
+                        ConstructOp::Lut1d(bake) => high_ops.push(High::Construct {
+                            dst: Target::Discard(texture),
+                            fn_: Initializer::PaintFullScreen {
+                                shader: FragmentShader::Lut1dBake(bake.clone()),
+                            },
+                        }),
+                        ConstructOp::Upload(data) => high_ops.push(High::Upload {
+                            dst: Register(idx),
+                            data: data.clone(),
+                        }),
+                    }
+
+                    reg_to_texture.insert(Register(idx), texture);
+                }
+                Op::Unary {
+                    desc: _,
+                    src,
+                    op: unary_op,
+                } => {
+                    let texture = realize_texture(idx, op)?;
+
+                    match unary_op {
+                        &UnaryOp::Crop(_) if views.contains_key(&Register(idx)) => {}
+                        &UnaryOp::Crop(region) => {
+                            let target =
+                                Rectangle::with_width_height(region.width(), region.height());
+                            high_ops.push(High::PushOperand(reg_to_texture[src]));
+                            high_ops.push(High::Construct {
+                                dst: Target::Discard(texture),
+                                fn_: Initializer::PaintToSelection {
+                                    texture: reg_to_texture[src],
+                                    selection: region,
+                                    target: target.into(),
+                                    viewport: target,
+                                    shader: FragmentShader::PaintOnTop(PaintOnTopKind::Copy),
+                                },
+                            });
+                        }
+                        UnaryOp::ChromaticAdaptation(adaptation) => {
+                            // Determine matrix for converting to xyz, then adapt, then back.
+                            let adapt = RowMatrix::new(adaptation.to_matrix()?);
+                            let output = adapt.multiply_right(adaptation.to_xyz_matrix.into());
+                            let matrix = adaptation.from_xyz_matrix.multiply_right(output);
+
+                            // If you want to debug this (for comparison to reference):
+                            // eprintln!("{:?}", adaptation.to_xyz_matrix);
+                            // eprintln!("{:?}", adaptation.from_xyz_matrix);
+                            // eprintln!("{:?}", adapt);
+                            // eprintln!("{:?}", matrix);
+
+                            high_ops.push(High::PushOperand(reg_to_texture[src]));
+                            high_ops.push(High::Construct {
+                                dst: Target::Discard(texture),
+                                fn_: Initializer::PaintFullScreen {
+                                    shader: FragmentShader::LinearColorMatrix(
+                                        shaders::LinearColorTransform {
+                                            matrix: matrix.into(),
+                                            offset: [0.0; 3],
+                                        },
+                                    ),
+                                },
+                            });
+                        }
+                        UnaryOp::ColorConvert(color) => {
+                            // The inherent OptoToLinear transformation gets us to a linear light
+                            // representation. We want to convert this into a compatible (that is,
+                            // using the same observer definition) other linear light
+                            // representation that we then transfer back to an electrical form.
+                            // Note that these two steps happen, conveniently, automatically.
+                            // Usually it is ensured that only two images with the same linear
+                            // light representation are used in a single paint call but this
+                            // violates it on purpose.
+
+                            high_ops.push(High::PushOperand(reg_to_texture[src]));
+                            // FIXME: using a copy here but this means we do this in unnecessarily
+                            // many steps. We first decode to linear color, then draw, then code
+                            // back to the non-linear electrical space.
+                            // We could do this directly from one matrix to another or try using an
+                            // ephemeral intermediate attachment?
+                            high_ops.push(High::Construct {
+                                dst: Target::Discard(texture),
+                                fn_: Initializer::PaintFullScreen {
+                                    shader: color.to_shader(),
+                                },
+                            });
+                        }
+                        UnaryOp::Extract { channel: _ } => {
+                            high_ops.push(High::PushOperand(reg_to_texture[src]));
+
+                            high_ops.push(High::Construct {
+                                dst: Target::Discard(texture),
+                                fn_: Initializer::PaintFullScreen {
+                                    // This will grab the right channel, that is all of them.
+                                    // The actual conversion is done in de-staging of the result.
+                                    // TODO: evaluate if this is the right way to do it. We could
+                                    // also perform a LinearColorMatrix shader here with close to
+                                    // the same amount of shader code but a precise result.
+                                    shader: FragmentShader::PaintOnTop(PaintOnTopKind::Copy),
+                                },
+                            })
+                        }
+                        UnaryOp::Derivative(derivative, border) => {
+                            let address_mode = border.address_mode();
+                            let shader = derivative
+                                .method
+                                .to_shader(derivative.direction, address_mode)?;
+
+                            high_ops.push(High::PushOperand(reg_to_texture[src]));
+                            high_ops.push(High::Construct {
+                                dst: Target::Discard(texture),
+                                fn_: Initializer::PaintFullScreen { shader },
+                            })
+                        }
+                        UnaryOp::ToneMap(shader) => {
+                            high_ops.push(High::PushOperand(reg_to_texture[src]));
+                            high_ops.push(High::Construct {
+                                dst: Target::Discard(texture),
+                                fn_: Initializer::PaintFullScreen {
+                                    shader: FragmentShader::ToneMap(shader.clone()),
+                                },
+                            })
+                        }
+                        UnaryOp::HslAdjust(shader) => {
+                            high_ops.push(High::PushOperand(reg_to_texture[src]));
+                            high_ops.push(High::Construct {
+                                dst: Target::Discard(texture),
+                                fn_: Initializer::PaintFullScreen {
+                                    shader: FragmentShader::Hsl(shader.clone()),
+                                },
+                            })
+                        }
+                        UnaryOp::KMeans(shader) => {
+                            high_ops.push(High::PushOperand(reg_to_texture[src]));
+                            high_ops.push(High::Construct {
+                                dst: Target::Discard(texture),
+                                fn_: Initializer::PaintFullScreen {
+                                    shader: FragmentShader::KMeans(shader.clone()),
+                                },
+                            })
+                        }
+                        UnaryOp::Dither(shader) => {
+                            high_ops.push(High::PushOperand(reg_to_texture[src]));
+                            high_ops.push(High::Construct {
+                                dst: Target::Discard(texture),
+                                fn_: Initializer::PaintFullScreen {
+                                    shader: FragmentShader::Dither(shader.clone()),
+                                },
+                            })
+                        }
+                        UnaryOp::Swizzle(shader) => {
+                            high_ops.push(High::PushOperand(reg_to_texture[src]));
+                            high_ops.push(High::Construct {
+                                dst: Target::Discard(texture),
+                                fn_: Initializer::PaintFullScreen {
+                                    shader: FragmentShader::Channels(Box::new(shader.clone())),
+                                },
+                            })
+                        }
+                        UnaryOp::Orient(shader) => {
+                            high_ops.push(High::PushOperand(reg_to_texture[src]));
+                            high_ops.push(High::Construct {
+                                dst: Target::Discard(texture),
+                                fn_: Initializer::PaintFullScreen {
+                                    shader: FragmentShader::Orient(shader.clone()),
+                                },
+                            })
+                        }
+                        UnaryOp::Pad(shader) => {
+                            high_ops.push(High::PushOperand(reg_to_texture[src]));
+                            high_ops.push(High::Construct {
+                                dst: Target::Discard(texture),
+                                fn_: Initializer::PaintFullScreen {
+                                    shader: FragmentShader::Pad(shader.clone()),
+                                },
+                            })
+                        }
+                        UnaryOp::Warp(shader) => {
+                            high_ops.push(High::PushOperand(reg_to_texture[src]));
+                            high_ops.push(High::Construct {
+                                dst: Target::Discard(texture),
+                                fn_: Initializer::PaintFullScreen {
+                                    shader: FragmentShader::Warp(shader.clone()),
+                                },
+                            })
+                        }
+                        UnaryOp::Morphology(shader) => {
+                            high_ops.push(High::PushOperand(reg_to_texture[src]));
+                            high_ops.push(High::Construct {
+                                dst: Target::Discard(texture),
+                                fn_: Initializer::PaintFullScreen {
+                                    shader: FragmentShader::Morphology(Box::new(shader.clone())),
+                                },
+                            })
+                        }
+                        UnaryOp::ChannelAffine(shader) => {
+                            high_ops.push(High::PushOperand(reg_to_texture[src]));
+                            high_ops.push(High::Construct {
+                                dst: Target::Discard(texture),
+                                fn_: Initializer::PaintFullScreen {
+                                    shader: FragmentShader::ChannelAffine(shader.clone()),
+                                },
+                            })
+                        }
+                        UnaryOp::Transmute => high_ops.push(High::Copy {
+                            src: *src,
+                            dst: Register(idx),
+                        }),
+                    }
+
+                    reg_to_texture.insert(Register(idx), texture);
+                }
+                Op::Binary {
+                    desc: _,
+                    lhs,
+                    rhs,
+                    op: binary_op,
+                } => {
+                    let texture = realize_texture(idx, op)?;
+
+                    let lhs_descriptor = command.describe_reg(*lhs).unwrap().monomorphize(tys);
+                    let rhs_descriptor = command.describe_reg(*rhs).unwrap().monomorphize(tys);
+
+                    let lower_region = Rectangle::from(&lhs_descriptor);
+                    let upper_region = Rectangle::from(&rhs_descriptor);
+
+                    // Where an operand is a view, paint its rectangle of the cropped texture.
+                    let selection_of = |reg: &Register, region: Rectangle| match views.get(reg) {
+                        Some(&(src, selection)) => (reg_to_texture[&src], selection),
+                        None => (reg_to_texture[reg], region),
+                    };
+
+                    match binary_op {
+                        BinaryOp::Affine(affine) => {
+                            let affine_matrix = RowMatrix::new(affine.transformation);
+                            let (lower_texture, lower_selection) = selection_of(lhs, lower_region);
+                            let (upper_texture, upper_selection) = selection_of(rhs, upper_region);
+
+                            high_ops.push(High::PushOperand(lower_texture));
+                            high_ops.push(High::Construct {
+                                dst: Target::Discard(texture),
+                                fn_: Initializer::PaintToSelection {
+                                    texture: lower_texture,
+                                    selection: lower_selection,
+                                    target: lower_region.into(),
+                                    viewport: lower_region,
+                                    shader: FragmentShader::PaintOnTop(PaintOnTopKind::Copy),
+                                },
+                            });
+
+                            high_ops.push(High::PushOperand(upper_texture));
+                            high_ops.push(High::Construct {
+                                dst: Target::Load(texture),
+                                fn_: Initializer::PaintToSelection {
+                                    texture: upper_texture,
+                                    selection: upper_selection,
+                                    target: QuadTarget::from(upper_region).affine(&affine_matrix),
+                                    viewport: lower_region,
+                                    shader: FragmentShader::PaintOnTop(
+                                        affine.sampling.as_paint_on_top()?,
+                                    ),
+                                },
+                            })
+                        }
+                        BinaryOp::Perspective(shader) => {
+                            high_ops.push(High::PushOperand(reg_to_texture[lhs]));
+                            high_ops.push(High::PushOperand(reg_to_texture[rhs]));
+
+                            high_ops.push(High::Construct {
+                                dst: Target::Discard(texture),
+                                fn_: Initializer::PaintFullScreen {
+                                    shader: FragmentShader::Perspective(shader.clone()),
+                                },
+                            })
+                        }
+                        BinaryOp::Morphology(shader) => {
+                            high_ops.push(High::PushOperand(reg_to_texture[lhs]));
+                            high_ops.push(High::PushOperand(reg_to_texture[rhs]));
+
+                            high_ops.push(High::Construct {
+                                dst: Target::Discard(texture),
+                                fn_: Initializer::PaintFullScreen {
+                                    shader: FragmentShader::Morphology(Box::new(shader.clone())),
+                                },
+                            })
+                        }
+                        BinaryOp::Dither(shader) => {
+                            high_ops.push(High::PushOperand(reg_to_texture[lhs]));
+                            high_ops.push(High::PushOperand(reg_to_texture[rhs]));
+
+                            high_ops.push(High::Construct {
+                                dst: Target::Discard(texture),
+                                fn_: Initializer::PaintFullScreen {
+                                    shader: FragmentShader::Dither(shader.clone()),
+                                },
+                            })
+                        }
+                        BinaryOp::Convolve(shader) => {
+                            high_ops.push(High::PushOperand(reg_to_texture[lhs]));
+                            high_ops.push(High::PushOperand(reg_to_texture[rhs]));
+
+                            high_ops.push(High::Construct {
+                                dst: Target::Discard(texture),
+                                fn_: Initializer::PaintFullScreen {
+                                    shader: FragmentShader::Convolve(shader.clone()),
+                                },
+                            })
+                        }
+                        BinaryOp::Displace(shader) => {
+                            high_ops.push(High::PushOperand(reg_to_texture[lhs]));
+                            high_ops.push(High::PushOperand(reg_to_texture[rhs]));
+
+                            high_ops.push(High::Construct {
+                                dst: Target::Discard(texture),
+                                fn_: Initializer::PaintFullScreen {
+                                    shader: FragmentShader::Warp(shader.clone()),
+                                },
+                            })
+                        }
+                        BinaryOp::Inject {
+                            channel,
+                            from_channels,
+                        } => {
+                            high_ops.push(High::PushOperand(reg_to_texture[lhs]));
+                            high_ops.push(High::PushOperand(reg_to_texture[rhs]));
+
+                            high_ops.push(High::Construct {
+                                dst: Target::Discard(texture),
+                                fn_: Initializer::PaintFullScreen {
+                                    shader: FragmentShader::Inject(shaders::inject::Shader {
+                                        mix: channel.into_vec4(),
+                                        color: from_channels.channel_weight_vec4().unwrap(),
+                                    }),
+                                },
+                            })
+                        }
+                        BinaryOp::Inscribe { placement } => {
+                            let (lower_texture, lower_selection) = selection_of(lhs, lower_region);
+                            let (upper_texture, upper_selection) = selection_of(rhs, upper_region);
+
+                            high_ops.push(High::PushOperand(lower_texture));
+                            high_ops.push(High::Construct {
+                                dst: Target::Discard(texture),
+                                fn_: Initializer::PaintToSelection {
+                                    texture: lower_texture,
+                                    selection: lower_selection,
+                                    target: lower_region.into(),
+                                    viewport: lower_region,
+                                    shader: FragmentShader::PaintOnTop(PaintOnTopKind::Copy),
+                                },
+                            });
+
+                            high_ops.push(High::PushOperand(upper_texture));
+                            high_ops.push(High::Construct {
+                                dst: Target::Load(texture),
+                                fn_: Initializer::PaintToSelection {
+                                    texture: upper_texture,
+                                    selection: upper_selection,
+                                    target: (*placement).into(),
+                                    viewport: lower_region,
+                                    shader: FragmentShader::PaintOnTop(PaintOnTopKind::Copy),
+                                },
+                            });
+                        }
+                        BinaryOp::Lut1d => {
+                            high_ops.push(High::PushOperand(reg_to_texture[lhs]));
+                            high_ops.push(High::PushOperand(reg_to_texture[rhs]));
+
+                            high_ops.push(High::Construct {
+                                dst: Target::Discard(texture),
+                                fn_: Initializer::PaintFullScreen {
+                                    shader: FragmentShader::Lut1dApply(shaders::lut1d::Apply),
+                                },
+                            });
+                        }
+                        BinaryOp::Lut3d(shader) => {
+                            high_ops.push(High::PushOperand(reg_to_texture[lhs]));
+                            high_ops.push(High::PushOperand(reg_to_texture[rhs]));
+
+                            high_ops.push(High::Construct {
+                                dst: Target::Discard(texture),
+                                fn_: Initializer::PaintFullScreen {
+                                    shader: FragmentShader::Lut3d(shader.clone()),
+                                },
+                            });
+                        }
+                        BinaryOp::Palette(shader) => {
+                            high_ops.push(High::PushOperand(reg_to_texture[lhs]));
+                            high_ops.push(High::PushOperand(reg_to_texture[rhs]));
+
+                            high_ops.push(High::Construct {
+                                dst: Target::Load(texture),
+                                fn_: Initializer::PaintFullScreen {
+                                    shader: FragmentShader::Palette(shader.clone()),
+                                },
+                            });
+                        }
+                        BinaryOp::Indexed(shader) => {
+                            high_ops.push(High::PushOperand(reg_to_texture[lhs]));
+                            high_ops.push(High::PushOperand(reg_to_texture[rhs]));
+
+                            high_ops.push(High::Construct {
+                                dst: Target::Discard(texture),
+                                fn_: Initializer::PaintFullScreen {
+                                    shader: FragmentShader::Indexed(shader.clone()),
+                                },
+                            });
+                        }
+                        BinaryOp::KMeans(shader) => {
+                            high_ops.push(High::PushOperand(reg_to_texture[lhs]));
+                            high_ops.push(High::PushOperand(reg_to_texture[rhs]));
+
+                            high_ops.push(High::Construct {
+                                dst: Target::Discard(texture),
+                                fn_: Initializer::PaintFullScreen {
+                                    shader: FragmentShader::KMeans(shader.clone()),
+                                },
+                            });
+                        }
+                    }
+
+                    reg_to_texture.insert(Register(idx), texture);
+                }
+                Op::Nary {
+                    desc: _,
+                    args,
+                    op: nary_op,
+                } => {
+                    let texture = realize_texture(idx, op)?;
+
+                    for arg in args {
+                        high_ops.push(High::PushOperand(reg_to_texture[arg]));
+                    }
+
+                    match nary_op {
+                        NaryOp::Merge(shader) => high_ops.push(High::Construct {
+                            dst: Target::Discard(texture),
+                            fn_: Initializer::PaintFullScreen {
+                                shader: FragmentShader::Channels(Box::new(shader.clone())),
+                            },
+                        }),
+                    }
+
+                    reg_to_texture.insert(Register(idx), texture);
+                }
+                Op::Dynamic { call, command, .. } => {
+                    let texture = realize_texture(idx, op)?;
+                    let (op_unary, op_binary, arguments);
+
+                    match call {
+                        OperandDynKind::Construct => {
+                            arguments = &[][..];
+                            reg_to_texture.insert(Register(idx), texture);
+                        }
+                        OperandDynKind::Unary(reg) => {
+                            op_unary = [reg_to_texture[reg]];
+                            arguments = &op_unary[..];
+                            reg_to_texture.insert(Register(idx), texture);
+                        }
+                        OperandDynKind::Binary { lhs, rhs } => {
+                            op_binary = [reg_to_texture[lhs], reg_to_texture[rhs]];
+                            arguments = &op_binary[..];
+                            reg_to_texture.insert(Register(idx), texture);
+                        }
+                    }
+
+                    for &operand in arguments {
+                        high_ops.push(High::PushOperand(operand));
+                    }
+
+                    high_ops.push(High::Construct {
+                        dst: Target::Discard(texture),
+                        fn_: Initializer::PaintFullScreen {
+                            shader: FragmentShader::Dynamic(command.clone()),
+                        },
+                    })
+                }
+                Op::InvokedResult { .. } => {
+                    let texture = realize_texture(idx, op)?;
+
+                    high_ops.push(High::Uninit {
+                        dst: Target::Discard(texture),
+                    });
+
+                    reg_to_texture.insert(Register(idx), texture);
+                }
+                Op::Invoke {
+                    function,
+                    arguments,
+                    results,
+                    generics,
+                } => {
+                    let monomorphic_tys: Vec<_> = generics
+                        .iter()
+                        .map(|gen| gen.monomorphize(tys))
+                        .collect::<_>();
+
+                    let &FunctionVar(function_idx) = function;
+                    let link_idx = *functions
+                        .get(function_idx)
+                        .ok_or(CompileError::NotYetImplemented)?;
+
+                    let function = mono.push_function(LinkedMonomorphicSignature {
+                        link_idx,
+                        tys: monomorphic_tys,
+                    });
+
+                    let mut image_io = vec![];
+
+                    for &register in arguments {
+                        // Arguments must precede the function and already be laid out.
+                        if register.0 >= idx {
+                            return Err(CompileError::NotYetImplemented);
+                        }
+
+                        let texture = realize_texture(register.0, &ops[register.0])?;
+                        image_io.push(CallBinding::InTexture { register, texture });
+                    }
+
+                    for &register in results {
+                        // Results must precede the function and already be laid out. They are not
+                        // initialized but initialized on return.
+                        if register.0 >= idx {
+                            return Err(CompileError::NotYetImplemented);
+                        }
+
+                        let texture = realize_texture(register.0, &ops[register.0])?;
+                        image_io.push(CallBinding::OutTexture { register, texture });
+                    }
+
+                    high_ops.push(High::Call {
+                        function,
+                        image_io_buffers: Arc::from(image_io),
                     });
                 }
-                Op::Construct {
-                    desc: _,
-                    op: construct_op,
-                } => {
-                    let texture = realize_texture(idx, op)?;
+                // In case we add a new case.
+                #[allow(unreachable_patterns)]
+                _ => {
+                    eprintln!("Unimplemented operation");
+                    return Err(CompileError::NotYetImplemented);
+                }
+            }
+
+            high_ops.push(High::Done(Register(idx)));
+            high_ops.push(High::StackPop);
+        }
+
+        let end = high_ops.len();
+
+        // The registers which callers must fill. This must match the order that CallBinding is
+        // passed at call sites, i.e. be consistent with the signature.
+        let signature_registers = signature_in.into_iter().chain(signature_out).collect();
+
+        Ok(FunctionLinked {
+            ops: start..end,
+            image_buffers,
+            signature_registers,
+        })
+    }
+
+    /// Apply a binary operation to the values of `lhs` as they are encoded, not in linear light.
+    ///
+    /// For this we reinterpret the buffer as linear, which skips the transfer function when loading
+    /// and storing. This is exact since transmutation preserves the bits.
+    fn binary_on_encoded(
+        &mut self,
+        lhs: Register,
+        rhs: Register,
+        op: BinaryOp,
+    ) -> Result<Register, CommandError> {
+        let desc_src = self.describe_reg(lhs)?;
+
+        let desc_src = desc_src
+            .as_concrete()
+            .ok_or(CommandError {
+                inner: CommandErrorKind::ConcreteDescriptorRequired,
+            })?
+            .clone();
+
+        let encoded = Self::with_linear_transfer(&desc_src.color);
+
+        let operand = match &encoded {
+            Some(color) => self.transmute(
+                lhs,
+                Descriptor {
+                    color: color.clone(),
+                    ..desc_src.clone()
+                },
+            )?,
+            None => lhs,
+        };
+
+        let desc = self.describe_reg(operand)?.clone();
+        let mapped = self.push(Op::Binary {
+            lhs: operand,
+            rhs,
+            op,
+            desc,
+        });
+
+        match encoded {
+            Some(_) => self.transmute(mapped, desc_src),
+            None => Ok(mapped),
+        }
+    }
+
+    /// Apply a unary operation to the values of `src` as they are encoded, not in linear light.
+    ///
+    /// See [`Self::binary_on_encoded`], the result has the descriptor of `src`.
+    fn unary_on_encoded(&mut self, src: Register, op: UnaryOp) -> Result<Register, CommandError> {
+        let desc_src = self.describe_reg(src)?;
+
+        let desc_src = desc_src
+            .as_concrete()
+            .ok_or(CommandError {
+                inner: CommandErrorKind::ConcreteDescriptorRequired,
+            })?
+            .clone();
+
+        let encoded = Self::with_linear_transfer(&desc_src.color);
+
+        let operand = match &encoded {
+            Some(color) => self.transmute(
+                src,
+                Descriptor {
+                    color: color.clone(),
+                    ..desc_src.clone()
+                },
+            )?,
+            None => src,
+        };
+
+        let desc = self.describe_reg(operand)?.clone();
+        let mapped = self.push(Op::Unary {
+            src: operand,
+            op,
+            desc,
+        });
+
+        match encoded {
+            Some(_) => self.transmute(mapped, desc_src),
+            None => Ok(mapped),
+        }
+    }
+
+    /// The same color with a linear transfer function, if it has a different one.
+    fn with_linear_transfer(color: &Color) -> Option<Color> {
+        match *color {
+            Color::Rgb {
+                primary,
+                transfer,
+                whitepoint,
+                luminance,
+            } if transfer != Transfer::Linear => Some(Color::Rgb {
+                primary,
+                transfer: Transfer::Linear,
+                whitepoint,
+                luminance,
+            }),
+            Color::Scalars { transfer } if transfer != Transfer::Linear => Some(Color::Scalars {
+                transfer: Transfer::Linear,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Get the descriptor for a register.
+    fn describe_reg(&self, Register(reg): Register) -> Result<&GenericDescriptor, CommandError> {
+        match self.ops.get(reg) {
+            None | Some(Op::Output { .. }) | Some(Op::Render { .. }) => {
+                Err(CommandError::BAD_REGISTER)
+            }
+            Some(Op::Invoke { .. }) => {
+                // This does not describe results directly.
+                Err(CommandError::BAD_REGISTER)
+            }
+            Some(Op::InvokedResult { desc, .. })
+            | Some(Op::Input { desc })
+            | Some(Op::Construct { desc, .. })
+            | Some(Op::Unary { desc, .. })
+            | Some(Op::Binary { desc, .. })
+            | Some(Op::Nary { desc, .. })
+            | Some(Op::Dynamic { desc, .. }) => Ok(desc),
+        }
+    }
+
+    fn push(&mut self, op: Op) -> Register {
+        let reg = Register(self.ops.len());
+        self.ops.push(op);
+        reg
+    }
+}
+
+/// Impls on `CommandBuffer` that allow defining custom SPIR-V extensions.
+///
+/// Generally, the steps on the dynamic shader are:
+///
+/// 1. Check the kind, get SPIR-v code.
+/// 2. Determine the dynamic typing of the result.
+/// 3. Have the shader create binary representation of its data.
+/// 3. Create a new entry on the command buffer.
+/// 4. Not yet performed: (Validate the SPIR-V module inputs against the data definition)
+impl CommandBuffer {
+    /// Record a _constructor_.
+    pub fn construct_dynamic(&mut self, dynamic: &dyn ShaderCommand) -> Register {
+        let mut data = vec![];
+        let mut content = None;
+
+        let source = dynamic.source();
+        let desc = dynamic.data(ShaderData {
+            data_buffer: &mut data,
+            content: &mut content,
+        });
+
+        self.push(Op::Dynamic {
+            call: OperandDynKind::Construct,
+            // FIXME: maybe this conversion should be delayed.
+            // In particular, converting source to SPIR-V may take some form of 'compiler' argument
+            // that's only available during `compile` phase.
+            command: ShaderInvocation {
+                spirv: match source {
+                    ShaderSource::SpirV(spirv) => spirv,
+                },
+                shader_data: match content {
+                    None => None,
+                    Some(c) => Some(c.as_slice(&data).into()),
+                },
+                num_args: 0,
+            },
+            desc: desc.into(),
+        })
+    }
+
+    /// Record a _unary operator_.
+    pub fn unary_dynamic(&mut self, _: Register, _: &dyn ShaderCommand) -> Register {
+        todo!()
+    }
+
+    /// Record a _binary operator_.
+    pub fn binary_dynamic(&mut self, _: Register, _: Register, _: &dyn ShaderCommand) -> Register {
+        todo!()
+    }
+}
+
+impl CommandSignature {
+    pub fn is_declaration_of(&self, actual: &CommandSignature) -> bool {
+        if self.vars.len() != actual.vars.len() {
+            return false;
+        }
+
+        for (decl, actual) in self.vars.iter().zip(&actual.vars) {
+            if !decl.contains_bounds(actual) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+impl GenericDescriptor {
+    /// Query if this describes a monomorphic descriptor.
+    ///
+    /// At the moment this means a fully constrained descriptor where both size and chroma are
+    /// defined. It's a bit odd that this would be an overlapping property with having been
+    /// constructed from an actually concrete defined descriptor. If we had a non-deterministic
+    /// layout algorithm (i.e. multiple permissible layouts for one combination of size/chroma)
+    /// then this might inadvertently throw away some of this information. But for now this
+    /// information is compile time only, the actual dependence of operational semantics on layout
+    /// information is evaluated at runtime. (FIXME: I will have regretted writing this).
+    pub fn as_concrete(&self) -> Option<Descriptor> {
+        let Generic::Concrete((w, h)) = self.size else {
+            return None;
+        };
+
+        let Generic::Concrete((texel, color)) = &self.chroma else {
+            return None;
+        };
+
+        let &Generic::Concrete(alpha) = &self.alpha else {
+            return None;
+        };
+
+        let &Generic::Concrete(cie) = &self.cie else {
+            return None;
+        };
+
+        let &Generic::Concrete(planes) = &self.planes else {
+            return None;
+        };
+
+        let desc = Descriptor::with_texel(texel.clone(), w, h)?.with_planes(planes)?;
+        Some(Descriptor {
+            color: color.clone(),
+            alpha,
+            cie,
+            ..desc
+        })
+    }
+
+    /// FIXME: fallible. If we change the texel from something small to something very large we can
+    /// exceed the allocation limits that are necessary to express the layout.
+    pub fn with_chroma(&self, texel: Texel, color: Color) -> Self {
+        GenericDescriptor {
+            chroma: Generic::Concrete((texel, color)),
+            cie: Generic::Concrete(None),
+            planes: Generic::Concrete(None),
+            ..self.clone()
+        }
+    }
+
+    pub fn monomorphize(&self, decl: &[Descriptor]) -> Descriptor {
+        let (w, h) = match &self.size {
+            Generic::Concrete(descriptor) => descriptor.clone(),
+            Generic::Generic(idx) => decl[idx.0].size(),
+        };
+
+        let (texel, color) = match &self.chroma {
+            Generic::Concrete(tuple) => tuple.clone(),
+            Generic::Generic(idx) => {
+                let from = &decl[idx.0];
+                (from.texel.clone(), from.color.clone())
+            }
+        };
+
+        let alpha = match &self.alpha {
+            &Generic::Concrete(alpha) => alpha,
+            Generic::Generic(idx) => decl[idx.0].alpha,
+        };
+
+        let cie = match &self.cie {
+            &Generic::Concrete(cie) => cie,
+            Generic::Generic(idx) => decl[idx.0].cie,
+        };
+
+        let planes = match &self.planes {
+            &Generic::Concrete(planes) => planes,
+            Generic::Generic(idx) => decl[idx.0].layout.planes,
+        };
+
+        Descriptor::with_texel(texel, w, h)
+            .and_then(|desc| desc.with_planes(planes))
+            .map(|mut desc| {
+                desc.color = color;
+                desc.alpha = alpha;
+                desc.cie = cie;
+                desc
+            })
+            .expect("changing texel and color to something that does not fit memory")
+    }
+
+    /// Apply an outer variable definition, replacing generics by at least as concrete terms.
+    ///
+    /// Does not verify any bounds of the rewrites! Which we'll need to do if we had associated
+    /// constants and the rewrite was looking into paths and impls. Consider a trait (similar to
+    /// the Rust type system) / type family such as `LinearizedColor` that associates the linear
+    /// optical colorspace to an arbitrary electrical color encoding. Then we might have the
+    /// signature written in pseudo-code:
+    ///
+    /// ```text
+    ///     function <C: LinearizedColor>(arg0: {C; 256×256}, arg1: {C::Linear; 256×256})
+    /// ```
+    ///
+    /// Now if we rewrite with [C = sRGB] then we want the concrete [C::Linear=CIE-RGB-Wp-D70]
+    /// correspondence. But if we tried [C = CYMK] we have nonsense. Here we allow this function to
+    /// panic, a check must happen earlier.
+    pub fn rewrite(&self, decl: &[GenericDescriptor]) -> Self {
+        GenericDescriptor {
+            size: match &self.size {
+                &Generic::Concrete(size) => Generic::Concrete(size),
+                Generic::Generic(idx) => decl[idx.0].size.clone(),
+            },
+            chroma: match &self.chroma {
+                Generic::Concrete(chroma) => Generic::Concrete(chroma.clone()),
+                Generic::Generic(idx) => decl[idx.0].chroma.clone(),
+            },
+            alpha: match &self.alpha {
+                &Generic::Concrete(alpha) => Generic::Concrete(alpha),
+                Generic::Generic(idx) => decl[idx.0].alpha.clone(),
+            },
+            cie: match &self.cie {
+                &Generic::Concrete(cie) => Generic::Concrete(cie),
+                Generic::Generic(idx) => decl[idx.0].cie.clone(),
+            },
+            planes: match &self.planes {
+                &Generic::Concrete(planes) => Generic::Concrete(planes),
+                Generic::Generic(idx) => decl[idx.0].planes.clone(),
+            },
+        }
+    }
+
+    pub fn size(&self) -> Generic<(u32, u32)> {
+        self.size.clone()
+    }
+
+    pub fn descriptor_chroma(&self) -> Generic<(Texel, Color)> {
+        self.chroma.clone()
+    }
+}
+
+impl From<Descriptor> for GenericDescriptor {
+    fn from(desc: Descriptor) -> Self {
+        let size = desc.size();
+        let chroma = (desc.texel.clone(), desc.color.clone());
+        // Descriptors derived from a frame keep its planes only while their texels fit them.
+        let planes = desc
+            .layout
+            .planes
+            .filter(|planes| planes.fits(&desc.texel, size));
+
+        GenericDescriptor {
+            size: Generic::Concrete(size),
+            chroma: Generic::Concrete(chroma),
+            alpha: Generic::Concrete(desc.alpha),
+            cie: Generic::Concrete(desc.cie),
+            planes: Generic::Concrete(planes),
+        }
+    }
+}
+
+impl TyVarBounds {
+    pub fn contains_bounds(&self, actual: &TyVarBounds) -> bool {
+        self.is_empty() && actual.is_empty()
+    }
+
+    fn is_empty(&self) -> bool {
+        // FIXME: if we collect the list.
+        true
+    }
+}
+
+impl ColorConversion {
+    /// The linear XYZ color through which we plan conversions between non-RGB color models.
+    fn xyz_intermediate(whitepoint: Whitepoint) -> Color {
+        let luminance = match Color::SRGB {
+            Color::Rgb { luminance, .. } => luminance,
+            _ => unreachable!("sRGB is an RGB color"),
+        };
+
+        Color::Rgb {
+            primary: Primaries::Xyz,
+            transfer: Transfer::Linear,
+            whitepoint,
+            luminance,
+        }
+    }
+
+    pub(crate) fn to_shader(&self) -> FragmentShader {
+        match self {
+            ColorConversion::Identity => FragmentShader::PaintOnTop(PaintOnTopKind::Copy),
+            ColorConversion::Hue(operation) => FragmentShader::Hsl(shaders::hsl::Shader {
+                operation: *operation,
+            }),
+            ColorConversion::YCbCr { matrix, offset } => {
+                FragmentShader::LinearColorMatrix(shaders::LinearColorTransform {
+                    matrix: *matrix,
+                    offset: *offset,
+                })
+            }
+            ColorConversion::Xyz {
+                to_xyz_matrix,
+                from_xyz_matrix,
+            } => {
+                let matrix = from_xyz_matrix
+                    .multiply_right((*to_xyz_matrix).into())
+                    .into();
+
+                FragmentShader::LinearColorMatrix(shaders::LinearColorTransform {
+                    matrix,
+                    offset: [0.0; 3],
+                })
+            }
+            ColorConversion::XyzToOklab { to_xyz_matrix } => {
+                FragmentShader::Oklab(shaders::oklab::Shader::with_encode(*to_xyz_matrix))
+            }
+            ColorConversion::OklabToXyz { from_xyz_matrix } => {
+                let from_xyz_matrix = from_xyz_matrix.inv();
+                FragmentShader::Oklab(shaders::oklab::Shader::with_decode(from_xyz_matrix))
+            }
+            ColorConversion::XyzToSrLab2 {
+                to_xyz_matrix,
+                whitepoint,
+            } => FragmentShader::SrLab2(shaders::srlab2::Shader::with_encode(
+                *to_xyz_matrix,
+                *whitepoint,
+            )),
+            ColorConversion::SrLab2ToXyz {
+                from_xyz_matrix,
+                whitepoint,
+            } => {
+                let from_xyz_matrix = from_xyz_matrix.inv();
+                FragmentShader::SrLab2(shaders::srlab2::Shader::with_decode(
+                    from_xyz_matrix,
+                    *whitepoint,
+                ))
+            }
+            ColorConversion::XyzToCieLab {
+                to_xyz_matrix,
+                whitepoint,
+                form,
+            } => FragmentShader::CieLab(shaders::cielab::Shader::with_encode(
+                *to_xyz_matrix,
+                *whitepoint,
+                *form,
+            )),
+            ColorConversion::CieLabToXyz {
+                from_xyz_matrix,
+                whitepoint,
+                form,
+            } => {
+                let from_xyz_matrix = from_xyz_matrix.inv();
+                FragmentShader::CieLab(shaders::cielab::Shader::with_decode(
+                    from_xyz_matrix,
+                    *whitepoint,
+                    *form,
+                ))
+            }
+        }
+    }
+}
+
+impl YCbCr {
+    fn is_valid_texel(texel: &Texel) -> bool {
+        let parameter = shaders::stage::XyzParameter {
+            transfer: shaders::stage::Transfer::Rgb(Transfer::Linear),
+            parts: texel.parts,
+            bits: texel.bits,
+            premultiplied: false,
+            planes: None,
+        };
+
+        // Staging maps the channels to the fields in order, these must have the same width.
+        let uniform = !matches!(
+            texel.bits,
+            SampleBits::UInt332
+                | SampleBits::UInt233
+                | SampleBits::UInt565
+                | SampleBits::UInt2101010
+        );
+
+        texel.parts == SampleParts::Yuv && uniform && parameter.stage_kind().is_some()
+    }
+
+    /// The luma weights of red and blue.
+    fn weights(&self) -> (f32, f32) {
+        match self.coefficients {
+            YCbCrCoefficients::Bt601 => (0.299, 0.114),
+            YCbCrCoefficients::Bt709 => (0.2126, 0.0722),
+            YCbCrCoefficients::Bt2020 => (0.2627, 0.0593),
+        }
+    }
+
+    /// The scale and offset of luma, and of the color differences, for normalized codes.
+    fn levels(&self, bits: SampleBits) -> [[f32; 2]; 2] {
+        let depth = match bits {
+            // Not codes, the 8-bit levels are divided by 256 without an integer step.
+            SampleBits::Float16x4 | SampleBits::Float32x4 => None,
+            SampleBits::UInt1010102 | SampleBits::UInt2101010 => Some(10),
+            SampleBits::UInt16 | SampleBits::UInt16x2 => Some(16),
+            SampleBits::UInt16x3 | SampleBits::UInt16x4 => Some(16),
+            _ => Some(8),
+        };
+
+        // Codes of n bits are the 8-bit codes scaled by 2^(n-8), then normalized by 2^n-1.
+        let step = match depth {
+            Some(depth) => (1u32 << (depth - 8)) as f32 / ((1u32 << depth) - 1) as f32,
+            None => 1.0 / 256.0,
+        };
+
+        match self.range {
+            YCbCrRange::Full => [[1.0, 0.0], [1.0, 128.0 * step]],
+            YCbCrRange::Limited => [[219.0 * step, 16.0 * step], [224.0 * step, 128.0 * step]],
+        }
+    }
+
+    /// The affine map from encoded RGB to normalized Y'CbCr codes.
+    fn encode_affine(&self, bits: SampleBits) -> (RowMatrix, [f32; 3]) {
+        let (kr, kb) = self.weights();
+        let kg = 1.0 - kr - kb;
+        let [[y_scale, y_offset], [c_scale, c_offset]] = self.levels(bits);
+
+        let cb = c_scale / (2.0 * (1.0 - kb));
+        let cr = c_scale / (2.0 * (1.0 - kr));
+
+        #[rustfmt::skip]
+        let matrix = RowMatrix::new([
+            y_scale * kr, y_scale * kg, y_scale * kb,
+            -cb * kr, -cb * kg, cb * (1.0 - kb),
+            cr * (1.0 - kr), -cr * kg, -cr * kb,
+        ]);
+
+        (matrix, [y_offset, c_offset, c_offset])
+    }
+
+    /// The affine map from normalized Y'CbCr codes to encoded RGB.
+    fn decode_affine(&self, bits: SampleBits) -> (RowMatrix, [f32; 3]) {
+        let (matrix, offset) = self.encode_affine(bits);
+        let inverse = matrix.inv();
+        let [x, y, z] = inverse.multiply_column(offset);
+        (inverse, [-x, -y, -z])
+    }
+}
+
+impl PaletteQuantization {
+    /// The largest number of iterations, each of which is two passes over the samples.
+    pub const MAX_ITERATIONS: u32 = 64;
+}
+
+impl IndexedLayout {
+    /// The descriptor of the bytes of packed indices.
+    ///
+    /// Rows are padded to full bytes. Returns `None` if the size is not valid for an image.
+    pub fn descriptor(&self) -> Option<Descriptor> {
+        let bits = self.width.checked_mul(self.depth.bits())?;
+        let row_bytes = bits / 8 + u32::from(bits % 8 != 0);
+        let texel = Texel::new_u8(SampleParts::Luma);
+        Descriptor::with_texel(texel, row_bytes, self.height)
+    }
+}
+
+impl IndexDepth {
+    fn bits(self) -> u32 {
+        match self {
+            IndexDepth::Bits1 => 1,
+            IndexDepth::Bits2 => 2,
+            IndexDepth::Bits4 => 4,
+            IndexDepth::Bits8 => 8,
+        }
+    }
+}
+
+impl HueModel {
+    fn is_valid_texel(texel: &Texel) -> bool {
+        matches!(texel.parts, SampleParts::Rgb | SampleParts::RgbA)
+    }
+}
+
+impl From<HueModel> for shaders::hsl::Model {
+    fn from(model: HueModel) -> Self {
+        match model {
+            HueModel::Hsv => shaders::hsl::Model::Hsv,
+            HueModel::Hsl => shaders::hsl::Model::Hsl,
+            HueModel::Hwb => shaders::hsl::Model::Hwb,
+        }
+    }
+}
+
+impl From<shaders::cielab::Model> for CieModel {
+    fn from(model: shaders::cielab::Model) -> Self {
+        match model {
+            shaders::cielab::Model::Lab => CieModel::Lab,
+            shaders::cielab::Model::Luv => CieModel::Luv,
+        }
+    }
+}
+
+impl CieColor {
+    /// The representation of values for a texel, if it holds a CIE color.
+    fn form(&self, texel: &Texel) -> Option<shaders::cielab::Form> {
+        let polar = match texel.parts {
+            SampleParts::Lab | SampleParts::LabA => false,
+            SampleParts::Lch | SampleParts::LchA => true,
+            _ => return None,
+        };
+
+        let model = match self.model {
+            CieModel::Lab => shaders::cielab::Model::Lab,
+            CieModel::Luv => shaders::cielab::Model::Luv,
+        };
+
+        Some(shaders::cielab::Form {
+            model,
+            polar,
+            normalized: !matches!(texel.bits, SampleBits::Float16x4 | SampleBits::Float32x4),
+        })
+    }
+}
+
+impl Default for Levels {
+    fn default() -> Self {
+        Levels {
+            input_black: 0.0,
+            input_white: 1.0,
+            gamma: 1.0,
+            output_black: 0.0,
+            output_white: 1.0,
+        }
+    }
+}
+
+impl Levels {
+    fn evaluate(&self, value: f32) -> f32 {
+        let range = self.input_white - self.input_black;
+        let normalized = ((value - self.input_black) / range).clamp(0.0, 1.0);
+        let corrected = normalized.powf(self.gamma.recip());
+        self.output_black + corrected * (self.output_white - self.output_black)
+    }
+}
+
+impl Curve {
+    fn is_valid(&self) -> bool {
+        match self {
+            Curve::Identity => true,
+            Curve::Levels(levels) => {
+                let Levels {
+                    input_black,
+                    input_white,
+                    gamma,
+                    output_black,
+                    output_white,
+                } = *levels;
+
+                [input_black, input_white, gamma, output_black, output_white]
+                    .iter()
+                    .all(|v| v.is_finite())
+                    && input_black < input_white
+                    && gamma > 0.0
+            }
+            Curve::Points(points) => {
+                !points.is_empty()
+                    && points.iter().flatten().all(|v| v.is_finite())
+                    && points.windows(2).all(|w| w[0][0] < w[1][0])
+            }
+        }
+    }
+
+    /// Sample the curve at `samples` equidistant points of `[0; 1]`.
+    fn bake(&self, samples: usize) -> Vec<f32> {
+        let max = (samples - 1) as f32;
+        let positions = (0..samples).map(|idx| idx as f32 / max);
+
+        match self {
+            Curve::Identity => positions.collect(),
+            Curve::Levels(levels) => positions.map(|x| levels.evaluate(x)).collect(),
+            Curve::Points(points) => {
+                let tangents = Self::monotone_tangents(points);
+                positions
+                    .map(|x| Self::evaluate_spline(points, &tangents, x))
+                    .collect()
+            }
+        }
+    }
+
+    /// Tangents of a monotone cubic Hermite spline, by Fritsch and Carlson.
+    fn monotone_tangents(points: &[[f32; 2]]) -> Vec<f32> {
+        let n = points.len();
+        if n < 2 {
+            return vec![0.0; n];
+        }
+
+        let secants: Vec<f32> = points
+            .windows(2)
+            .map(|w| (w[1][1] - w[0][1]) / (w[1][0] - w[0][0]))
+            .collect();
+
+        let mut tangents = vec![0.0; n];
+        tangents[0] = secants[0];
+        tangents[n - 1] = secants[n - 2];
+        for (k, pair) in secants.windows(2).enumerate() {
+            let (before, after) = (pair[0], pair[1]);
+            tangents[k + 1] = if before * after <= 0.0 {
+                0.0
+            } else {
+                (before + after) / 2.0
+            };
+        }
+
+        for (k, &secant) in secants.iter().enumerate() {
+            if secant == 0.0 {
+                tangents[k] = 0.0;
+                tangents[k + 1] = 0.0;
+                continue;
+            }
+
+            let alpha = tangents[k] / secant;
+            let beta = tangents[k + 1] / secant;
+            let norm = alpha * alpha + beta * beta;
+            if norm > 9.0 {
+                let tau = 3.0 / norm.sqrt();
+                tangents[k] = tau * alpha * secant;
+                tangents[k + 1] = tau * beta * secant;
+            }
+        }
+
+        tangents
+    }
+
+    fn evaluate_spline(points: &[[f32; 2]], tangents: &[f32], x: f32) -> f32 {
+        let (first, last) = (points[0], points[points.len() - 1]);
+        if x <= first[0] {
+            return first[1];
+        } else if x >= last[0] {
+            return last[1];
+        }
+
+        let k = points.partition_point(|p| p[0] <= x) - 1;
+        let ([x0, y0], [x1, y1]) = (points[k], points[k + 1]);
+        let h = x1 - x0;
+        let t = (x - x0) / h;
+        let (t2, t3) = (t * t, t * t * t);
+
+        let h00 = 2.0 * t3 - 3.0 * t2 + 1.0;
+        let h10 = t3 - 2.0 * t2 + t;
+        let h01 = -2.0 * t3 + 3.0 * t2;
+        let h11 = t3 - t2;
+
+        h00 * y0 + h10 * h * tangents[k] + h01 * y1 + h11 * h * tangents[k + 1]
+    }
+}
+
+impl Curves {
+    /// Sample all channels into an interleaved table.
+    fn bake(&self, samples: usize) -> Vec<[f32; 4]> {
+        let channels: Vec<Vec<f32>> = self
+            .channels
+            .iter()
+            .map(|curve| curve.bake(samples))
+            .collect();
+        (0..samples)
+            .map(|idx| [0, 1, 2, 3].map(|ch| channels[ch][idx]))
+            .collect()
+    }
+}
+
+impl Lut3d {
+    /// The largest number of samples along each axis.
+    ///
+    /// The slices of such a cube fit into a texture of `2048×2048`, which all devices support.
+    pub const MAX_SIZE: u32 = 128;
+
+    /// A table mapping each color in `[0; 1]` to itself.
+    pub fn identity(size: u32) -> Self {
+        let scale = 1.0 / (size.max(2) - 1) as f32;
+        let entries = (size as usize).pow(3);
+
+        let table = (0..entries)
+            .map(|idx| {
+                let idx = idx as u32;
+                [idx % size, idx / size % size, idx / size / size].map(|v| v as f32 * scale)
+            })
+            .collect();
+
+        Lut3d {
+            size,
+            table,
+            domain_min: [0.0; 3],
+            domain_max: [1.0; 3],
+            shaper: None,
+            interpolation: Lut3dInterpolation::Tetrahedral,
+        }
+    }
+
+    fn is_valid(&self) -> bool {
+        let entries = (self.size as usize).checked_pow(3);
+
+        (2..=Self::MAX_SIZE).contains(&self.size)
+            && entries == Some(self.table.len())
+            && self.table.iter().flatten().all(|v| v.is_finite())
+            && is_valid_domain(self.domain_min, self.domain_max)
+            && self.shaper.iter().all(Lut3dShaper::is_valid)
+    }
+
+    /// The number of slices placed next to each other, making the texture roughly square.
+    fn tiles_per_row(&self) -> u32 {
+        let mut tiles = 1;
+        while tiles * tiles < self.size {
+            tiles += 1;
+        }
+        tiles
+    }
+
+    /// Arrange the table into a tiled image of `Float16x4` texels.
+    fn to_texels(&self, width: u32, height: u32) -> Vec<u8> {
+        const TEXEL_BYTES: usize = 8;
+
+        let size = self.size as usize;
+        let tiles_per_row = self.tiles_per_row() as usize;
+        let width = width as usize;
+        let mut texels = vec![0; width * height as usize * TEXEL_BYTES];
+
+        for (idx, &[r, g, b]) in self.table.iter().enumerate() {
+            let (x, y, tile) = (idx % size, idx / size % size, idx / size / size);
+            let x = (tile % tiles_per_row) * size + x;
+            let y = (tile / tiles_per_row) * size + y;
+
+            let offset = (y * width + x) * TEXEL_BYTES;
+            let texel = [r, g, b, 1.0].map(crate::util::f32_to_f16_bits);
+            texels[offset..offset + TEXEL_BYTES].copy_from_slice(bytemuck::cast_slice(&texel));
+        }
+
+        texels
+    }
+}
+
+impl Lut3dShaper {
+    /// The largest number of samples.
+    ///
+    /// The table is passed to the shader as a uniform array of this size, without resampling it.
+    pub const MAX_SIZE: u32 = shaders::lut3d::SHAPER_SIZE as u32;
+
+    fn is_valid(&self) -> bool {
+        (2..=Self::MAX_SIZE as usize).contains(&self.table.len())
+            && self.table.iter().flatten().all(|v| v.is_finite())
+            && is_valid_domain(self.domain_min, self.domain_max)
+    }
+
+    /// Pad the table to the size of the shader's array, which interpolates only its entries.
+    fn to_shader(&self) -> shaders::lut3d::Shaper {
+        let padding = shaders::lut3d::SHAPER_SIZE - self.table.len();
+
+        let table = self
+            .table
+            .iter()
+            .map(|&[r, g, b]| [r, g, b, 0.0])
+            .chain(core::iter::repeat([0.0; 4]).take(padding))
+            .collect();
+
+        shaders::lut3d::Shaper {
+            domain: [self.domain_min, self.domain_max],
+            len: self.table.len() as u32,
+            table,
+        }
+    }
+}
+
+fn is_valid_domain(min: [f32; 3], max: [f32; 3]) -> bool {
+    min.iter()
+        .zip(&max)
+        .all(|(min, max)| min.is_finite() && max.is_finite() && min < max)
+}
+
+impl ChromaticAdaptation {
+    pub(crate) fn to_matrix(&self) -> Result<[f32; 9], CompileError> {
+        self.method.to_matrix(self.source, self.target)
+    }
+}
+
+impl ChromaticAdaptationMethod {
+    /// The matrix in XYZ adapting colors relative to `source` to be relative to `target`.
+    pub(crate) fn to_matrix(
+        &self,
+        source: Whitepoint,
+        target: Whitepoint,
+    ) -> Result<[f32; 9], CompileError> {
+        use palette::{
+            chromatic_adaptation::{Method, TransformMatrix},
+            white_point as wp,
+        };
+
+        // FIXME: when you adjust the value-to-type translation, also adjust it within `method`.
+        macro_rules! translate_matrix {
+            ($source:expr, $target:expr, $($lhs:ident => $lhsty:ty)|*) => {
+                $(
+                    translate_matrix!(
+                        @$source, $target, $lhs => $lhsty :
+                        A => wp::A | B => wp::B | C => wp::C
+                        | D50 => wp::D50 | D55 => wp::D55 | D65 => wp::D65
+                        | D75 => wp::D75 | E => wp::E | F2 => wp::F2
+                        | F7 => wp::F7 | F11 => wp::F11
+                    );
+                )*
+            };
+            (@$source:expr, $target:expr, $lhs:ident => $lhsty:ty : $($rhs:ident => $ty:ty)|*) => {
+                $(
+                    if let (Whitepoint::$lhs, Whitepoint::$rhs) = ($source, $target) {
+                        return Ok((|method| {
+                            let lhswp = <$lhsty as wp::WhitePoint<f32>>::get_xyz();
+                            let rhswp = <$ty as wp::WhitePoint<f32>>::get_xyz();
+                            <Method as TransformMatrix<f32>>::generate_transform_matrix(method, lhswp, rhswp)
+                        })
+                                  as fn(&Method) -> [f32;9]);
+                    }
+                )*
+            };
+        }
+
+        // FIXME: when you adjust the value-to-type translation, also adjust it within
+        // `translate_matrix!`
+        let method = (|| {
+            translate_matrix! {
+                source, target,
+                A => wp::A | B => wp::B | C => wp::C
+                | D50 => wp::D50 | D55 => wp::D55 | D65 => wp::D65
+                | D75 => wp::D75 | E => wp::E | F2 => wp::F2
+                | F7 => wp::F7 | F11 => wp::F11
+            };
+
+            Err(CompileError::NotYetImplemented)
+        })()?;
+
+        let matrices = method(match self {
+            // Bradford's original method that does slight blue non-linearity is not yet supported.
+            // Please implement the paper if you feel compelled to.
+            ChromaticAdaptationMethod::BradfordNonLinear => {
+                return Err(CompileError::NotYetImplemented)
+            }
+            ChromaticAdaptationMethod::BradfordVonKries => &Method::Bradford,
+            ChromaticAdaptationMethod::VonKries => &Method::VonKries,
+            ChromaticAdaptationMethod::Xyz => &Method::XyzScaling,
+        });
+
+        Ok(matrices)
+    }
+
+    /// The matrix from linear RGB to XYZ, adapted to be relative to the `target` whitepoint.
+    ///
+    /// Adaptation is linear in XYZ so we fuse it with the conversion matrix.
+    fn adapted_to_xyz(
+        &self,
+        primary: Primaries,
+        source: Whitepoint,
+        target: Whitepoint,
+    ) -> Result<RowMatrix, CommandError> {
+        let to_xyz = RowMatrix(primary.to_xyz_row_matrix(source));
+
+        if source == target {
+            return Ok(to_xyz);
+        }
+
+        let adapt = self
+            .to_matrix(source, target)
+            .map_err(|_| CommandError::UNIMPLEMENTED)?;
+        Ok(RowMatrix(adapt).multiply_right(to_xyz.into()).into())
+    }
+}
+
+#[rustfmt::skip]
+impl DerivativeMethod {
+    fn to_shader(
+        &self,
+        direction: Direction,
+        address_mode: wgpu::AddressMode,
+    ) -> Result<FragmentShader, CompileError> {
+        use DerivativeMethod::*;
+        use shaders::box3;
+        match self {
+            Prewitt => {
+                let matrix = RowMatrix::with_outer_product(
+                    [1./3., 1./3., 1./3.],
+                    [0.5, 0.0, -0.5],
+                );
+
+                let shader = box3::Shader::new(
+                    direction.adjust_vertical_box(matrix),
+                    address_mode,
+                );
+                Ok(shaders::FragmentShader::Box3(shader))
+            }
+            Sobel => {
+                let matrix = RowMatrix::with_outer_product(
+                    [1./4., 1./2., 1./4.],
+                    [0.5, 0.0, -0.5],
+                );
+
+                let shader = box3::Shader::new(
+                    direction.adjust_vertical_box(matrix),
+                    address_mode,
+                );
+                Ok(shaders::FragmentShader::Box3(shader))
+            }
+            Scharr3 => {
+                let matrix = RowMatrix::with_outer_product(
+                    [46.84/256., 162.32/256., 46.84/256.],
+                    [0.5, 0.0, -0.5],
+                );
+
+                let shader = box3::Shader::new(
+                    direction.adjust_vertical_box(matrix),
+                    address_mode,
+                );
+                Ok(shaders::FragmentShader::Box3(shader))
+            }
+            Scharr3To4Bit => {
+                let matrix = RowMatrix::with_outer_product(
+                    [3./16., 10./16., 3./16.],
+                    [0.5, 0.0, -0.5],
+                );
+
+                let shader = box3::Shader::new(
+                    direction.adjust_vertical_box(matrix),
+                    address_mode,
+                );
+                Ok(shaders::FragmentShader::Box3(shader))
+            }
+            Scharr3To8Bit => {
+                let matrix = RowMatrix::with_outer_product(
+                    [47./256., 162./256., 47./256.],
+                    [0.5, 0.0, -0.5],
+                );
+
+                let shader = box3::Shader::new(
+                    direction.adjust_vertical_box(matrix),
+                    address_mode,
+                );
+                Ok(shaders::FragmentShader::Box3(shader))
+            }
+            // FIXME: implement these.
+            // When you do add them to tests/blend.rs
+            | Roberts
+            | Scharr4
+            | Scharr5
+            | Scharr5Tab => Err(CompileError::NotYetImplemented)
+        }
+    }
+}
+
+impl BorderMode {
+    /// The channels of a constant color as bits, for comparing and hashing.
+    fn bits(self) -> (u8, [u32; 4]) {
+        match self {
+            BorderMode::Constant(color) => (0, color.map(f32::to_bits)),
+            BorderMode::Clamp => (1, [0; 4]),
+            BorderMode::Repeat => (2, [0; 4]),
+            BorderMode::Mirror => (3, [0; 4]),
+        }
+    }
+
+    fn address_mode(self) -> wgpu::AddressMode {
+        match self {
+            // The constant is painted by the shader, within bounds the mode does not matter.
+            BorderMode::Constant(_) | BorderMode::Clamp => wgpu::AddressMode::ClampToEdge,
+            BorderMode::Repeat => wgpu::AddressMode::Repeat,
+            BorderMode::Mirror => wgpu::AddressMode::MirrorRepeat,
+        }
+    }
+}
+
+impl PartialEq for BorderMode {
+    fn eq(&self, other: &Self) -> bool {
+        self.bits() == other.bits()
+    }
+}
+
+impl Eq for BorderMode {}
+
+impl core::hash::Hash for BorderMode {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.bits().hash(state)
+    }
+}
+
+impl Direction {
+    fn adjust_vertical_box(self, mat: RowMatrix) -> RowMatrix {
+        match self {
+            Direction::Width => mat,
+            Direction::Height => mat.transpose(),
+        }
+    }
+}
+
+impl Kernel {
+    /// A kernel with weights used as given, applied to all channels and clamping at the edges.
+    pub fn new(width: u32, height: u32, weights: Vec<f32>) -> Self {
+        Kernel {
+            width,
+            height,
+            weights,
+            normalization: KernelNormalization::None,
+            channels: KernelChannels::Color,
+            border: BorderMode::Clamp,
+        }
+    }
+
+    /// The weights after normalization.
+    fn normalized_weights(&self) -> Vec<f32> {
+        let sum = match self.normalization {
+            KernelNormalization::None => 1.0,
+            KernelNormalization::Sum => self.weights.iter().sum(),
+            KernelNormalization::AbsoluteSum => self.weights.iter().map(|w| w.abs()).sum(),
+        };
+
+        let scale = if sum.abs() > f32::EPSILON {
+            sum.recip()
+        } else {
+            1.0
+        };
+        self.weights.iter().map(|w| w * scale).collect()
+    }
+
+    /// Decompose the weights into a row and a column kernel, if they are an outer product.
+    fn separate(&self, weights: &[f32]) -> Option<(Vec<f32>, Vec<f32>)> {
+        let (width, height) = (self.width as usize, self.height as usize);
+
+        let (pivot, &max) = weights
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.abs().total_cmp(&b.abs()))?;
+
+        if max == 0.0 {
+            return None;
+        }
+
+        let (row, col) = (pivot / width, pivot % width);
+        // The kernel is the outer product of its pivot column and its scaled pivot row.
+        let column: Vec<f32> = (0..height).map(|y| weights[y * width + col]).collect();
+        let row: Vec<f32> = weights[row * width..][..width]
+            .iter()
+            .map(|w| w / max)
+            .collect();
+
+        let tolerance = max.abs() * 1e-5;
+        let separable = (0..height).all(|y| {
+            (0..width).all(|x| (weights[y * width + x] - column[y] * row[x]).abs() <= tolerance)
+        });
+
+        if separable {
+            Some((row, column))
+        } else {
+            None
+        }
+    }
+}
+
+/// One pass of a morphological operator with a structuring element.
+enum MorphologyPass {
+    /// Take the extremum under a mask directly.
+    Element { size: [u32; 2], rows: [u32; 31] },
+    /// Take the extremum along a line, with van Herk/Gil-Werman scans in logarithmic steps.
+    Line { vertical: bool, radius: u32 },
+}
+
+impl StructuringElement {
+    /// Lines longer than this are not sampled directly but scanned in blocks.
+    const SCANNED_LINE: u32 = shaders::morphology::MAX_SIZE;
+
+    /// Decompose into passes, or `None` if the element is not valid.
+    fn passes(&self) -> Option<Vec<MorphologyPass>> {
+        let max_radius = shaders::morphology::MAX_SIZE / 2;
+
+        let (width, height, mask): (u32, u32, Vec<bool>) = match *self {
+            StructuringElement::Square(radius) => return Self::rectangle_passes(radius, radius),
+            StructuringElement::Rectangle(rx, ry) => return Self::rectangle_passes(rx, ry),
+            StructuringElement::Disk(radius) | StructuringElement::Cross(radius)
+                if radius > max_radius =>
+            {
+                return None
+            }
+            StructuringElement::Disk(radius) => {
+                let r = i64::from(radius);
+                let mask = (-r..=r)
+                    .flat_map(|y| (-r..=r).map(move |x| x * x + y * y <= r * r))
+                    .collect();
+                (2 * radius + 1, 2 * radius + 1, mask)
+            }
+            StructuringElement::Cross(radius) => {
+                let r = i64::from(radius);
+                let mask = (-r..=r)
+                    .flat_map(|y| (-r..=r).map(move |x| x == 0 || y == 0))
+                    .collect();
+                (2 * radius + 1, 2 * radius + 1, mask)
+            }
+            StructuringElement::Custom {
+                width,
+                height,
+                ref mask,
+            } => {
+                let valid_size = |size: u32| size % 2 == 1 && size <= shaders::morphology::MAX_SIZE;
+
+                if !valid_size(width)
+                    || !valid_size(height)
+                    || mask.len() != (width * height) as usize
+                    || !mask.iter().any(|&contained| contained)
+                {
+                    return None;
+                }
+
+                (width, height, mask.clone())
+            }
+        };
+
+        let mut rows = [0u32; 31];
+        for (row, line) in rows.iter_mut().zip(mask.chunks(width as usize)) {
+            *row = line
+                .iter()
+                .enumerate()
+                .filter(|&(_, &contained)| contained)
+                .fold(0, |row, (x, _)| row | 1 << x);
+        }
+
+        Some(vec![MorphologyPass::Element {
+            size: [width, height],
+            rows,
+        }])
+    }
+
+    /// A rectangle is separable into a horizontal and a vertical line.
+    ///
+    /// Returns `None` if a side is too long to be represented by the shaders.
+    fn rectangle_passes(rx: u32, ry: u32) -> Option<Vec<MorphologyPass>> {
+        let line = |vertical: bool, radius: u32| {
+            let side = radius
+                .checked_mul(2)?
+                .checked_add(1)
+                .filter(|&side| side <= i32::MAX as u32)?;
+
+            if side > Self::SCANNED_LINE {
+                return Some(MorphologyPass::Line { vertical, radius });
+            }
+
+            let mut rows = [0u32; 31];
+            let size = if vertical {
+                rows[..side as usize].iter_mut().for_each(|row| *row = 1);
+                [1, side]
+            } else {
+                rows[0] = (1 << side) - 1;
+                [side, 1]
+            };
+
+            Some(MorphologyPass::Element { size, rows })
+        };
+
+        let mut passes = vec![];
+        if rx > 0 {
+            passes.push(line(false, rx)?);
+        }
+
+        if ry > 0 {
+            passes.push(line(true, ry)?);
+        }
+
+        Some(passes)
+    }
+}
+
+#[rustfmt::skip]
+impl Affine {
+    /// Create affine parameters with identity transformation.
+    pub fn new(sampling: AffineSample) -> Self {
+        Affine {
+            transformation: [
+                1.0, 0., 0.,
+                0., 1.0, 0.,
+                0., 0., 1.0,
+            ],
+            sampling,
+        }
+    }
+
+    /// After the transformation, also scale everything.
+    ///
+    /// This corresponds to a left-side multiplication of the transformation matrix.
+    pub fn scale(self, x: f32, y: f32) -> Self {
+        let post = RowMatrix::diag(x, y, 1.0)
+            .multiply_right(RowMatrix::new(self.transformation).into());
+        let transformation = RowMatrix::from(post).into_inner();
 
-                    match construct_op {
-                        &ConstructOp::DistributionNormal(ref distribution) => {
-                            high_ops.push(High::Construct {
-                                dst: Target::Discard(texture),
-                                fn_: Initializer::PaintFullScreen {
-                                    shader: FragmentShader::Normal2d(distribution.clone()),
-                                },
-                            })
-                        }
-                        ConstructOp::DistributionNoise(ref noise_params) => {
-                            high_ops.push(High::Construct {
-                                dst: Target::Discard(texture),
-                                fn_: Initializer::PaintFullScreen {
-                                    shader: FragmentShader::FractalNoise(noise_params.clone()),
-                                },
-                            })
-                        }
-                        ConstructOp::Bilinear(bilinear) => high_ops.push(High::Construct {
-                            dst: Target::Discard(texture),
-                            fn_: Initializer::PaintFullScreen {
-                                shader: FragmentShader::Bilinear(bilinear.clone()),
-                            },
-                        }),
-                        &ConstructOp::Solid(color) => high_ops.push(High::Construct {
-                            dst: Target::Discard(texture),
-                            fn_: Initializer::PaintFullScreen {
-                                shader: FragmentShader::SolidRgb(color.into()),
-                            },
-                        }),
-                    }
+        Affine {
+            transformation,
+            ..self
+        }
+    }
 
-                    reg_to_texture.insert(Register(idx), texture);
-                }
-                Op::Unary {
-                    desc: _,
-                    src,
-                    op: unary_op,
-                } => {
-                    let texture = realize_texture(idx, op)?;
+    /// After the transformation, rotate everything clockwise.
+    ///
+    /// This corresponds to a left-side multiplication of the transformation matrix.
+    pub fn rotate(self, rad: f32) -> Self {
+        let post = RowMatrix::new([
+            rad.cos(), rad.sin(), 0.,
+            -rad.sin(), rad.cos(), 0.,
+            0., 0., 1.,
+        ]);
 
-                    match unary_op {
-                        &UnaryOp::Crop(region) => {
-                            let target =
-                                Rectangle::with_width_height(region.width(), region.height());
-                            high_ops.push(High::PushOperand(reg_to_texture[src]));
-                            high_ops.push(High::Construct {
-                                dst: Target::Discard(texture),
-                                fn_: Initializer::PaintToSelection {
-                                    texture: reg_to_texture[src],
-                                    selection: region,
-                                    target: target.into(),
-                                    viewport: target,
-                                    shader: FragmentShader::PaintOnTop(PaintOnTopKind::Copy),
-                                },
-                            });
-                        }
-                        UnaryOp::ChromaticAdaptation(adaptation) => {
-                            // Determine matrix for converting to xyz, then adapt, then back.
-                            let adapt = RowMatrix::new(adaptation.to_matrix()?);
-                            let output = adapt.multiply_right(adaptation.to_xyz_matrix.into());
-                            let matrix = adaptation.from_xyz_matrix.multiply_right(output);
+        let post = post.multiply_right(RowMatrix::new(self.transformation).into());
+        let transformation = RowMatrix::from(post).into_inner();
 
-                            // If you want to debug this (for comparison to reference):
-                            // eprintln!("{:?}", adaptation.to_xyz_matrix);
-                            // eprintln!("{:?}", adaptation.from_xyz_matrix);
-                            // eprintln!("{:?}", adapt);
-                            // eprintln!("{:?}", matrix);
+        Affine {
+            transformation,
+            ..self
+        }
+    }
 
-                            high_ops.push(High::PushOperand(reg_to_texture[src]));
-                            high_ops.push(High::Construct {
-                                dst: Target::Discard(texture),
-                                fn_: Initializer::PaintFullScreen {
-                                    shader: FragmentShader::LinearColorMatrix(
-                                        shaders::LinearColorTransform {
-                                            matrix: matrix.into(),
-                                        },
-                                    ),
-                                },
-                            });
-                        }
-                        UnaryOp::ColorConvert(color) => {
-                            // The inherent OptoToLinear transformation gets us to a linear light
-                            // representation. We want to convert this into a compatible (that is,
-                            // using the same observer definition) other linear light
-                            // representation that we then transfer back to an electrical form.
-                            // Note that these two steps happen, conveniently, automatically.
-                            // Usually it is ensured that only two images with the same linear
-                            // light representation are used in a single paint call but this
-                            // violates it on purpose.
+    /// After the transformation, shift by an x and y offset.
+    ///
+    /// This corresponds to a left-side multiplication of the transformation matrix.
+    pub fn shift(self, x: f32, y: f32) -> Self {
+        let post = RowMatrix::new([
+            1., 0., x,
+            0., 1., y,
+            0., 0., 1.,
+        ]);
+
+        let post = post.multiply_right(RowMatrix::new(self.transformation).into());
+        let transformation = RowMatrix::from(post).into_inner();
+
+        Affine {
+            transformation,
+            ..self
+        }
+    }
+}
+
+impl AffineSample {
+    fn as_paint_on_top(self) -> Result<PaintOnTopKind, CompileError> {
+        match self {
+            AffineSample::Nearest => Ok(PaintOnTopKind::Copy),
+            _ => Err(CompileError::NotYetImplemented),
+        }
+    }
+
+    fn as_sampling(self) -> shaders::perspective::Sampling {
+        match self {
+            AffineSample::Nearest => shaders::perspective::Sampling::Nearest,
+            AffineSample::BiLinear => shaders::perspective::Sampling::BiLinear,
+        }
+    }
+}
+
+impl Rectangle {
+    /// A rectangle at the origin with given width (x) and height (y).
+    pub fn with_width_height(width: u32, height: u32) -> Self {
+        Rectangle {
+            x: 0,
+            y: 0,
+            max_x: width,
+            max_y: height,
+        }
+    }
+
+    /// A rectangle describing a complete buffer.
+    pub fn with_layout(buffer: &ByteLayout) -> Self {
+        Self::with_width_height(buffer.width, buffer.height)
+    }
+
+    /// The apparent width.
+    pub fn width(self) -> u32 {
+        self.max_x.saturating_sub(self.x)
+    }
+
+    /// The apparent height.
+    pub fn height(self) -> u32 {
+        self.max_y.saturating_sub(self.y)
+    }
+
+    /// Return true if this rectangle fully contains `other`.
+    pub fn contains(self, other: Self) -> bool {
+        self.x <= other.x && self.y <= other.y && {
+            // Offsets are surely non-wrapping.
+            let offset_x = other.x - self.x;
+            let offset_y = other.y - self.y;
+            let rel_width = self.width().checked_sub(offset_x);
+            let rel_height = self.height().checked_sub(offset_y);
+            rel_width >= Some(other.width()) && rel_height >= Some(other.height())
+        }
+    }
+
+    /// Bring the rectangle into normalized form where minimum and maximum form a true interval.
+    #[must_use]
+    pub fn normalize(self) -> Rectangle {
+        Rectangle {
+            x: self.x,
+            y: self.y,
+            max_x: self.x + self.width(),
+            max_y: self.y + self.height(),
+        }
+    }
+
+    /// A rectangle that the overlap of the two.
+    #[must_use]
+    pub fn meet(self, other: Self) -> Rectangle {
+        Rectangle {
+            x: self.x.max(other.x),
+            y: self.y.max(other.y),
+            max_x: self.max_x.min(other.max_x),
+            max_y: self.max_y.min(other.max_y),
+        }
+    }
+
+    /// The meet, relative to the coordinates of this rectangle.
+    #[must_use]
+    pub fn meet_in_local_coordinates(self, other: Self) -> Rectangle {
+        // Normalize to ensure that max_{x,y} is not less than {x,y}
+        let meet = self.normalize().meet(other);
+        Rectangle {
+            x: meet.x - self.x,
+            y: meet.y - self.y,
+            max_x: meet.max_x - self.x,
+            max_y: meet.max_y - self.y,
+        }
+    }
+
+    /// A rectangle that contains both.
+    #[must_use]
+    pub fn join(self, other: Self) -> Rectangle {
+        Rectangle {
+            x: self.x.min(other.x),
+            y: self.y.min(other.y),
+            max_x: self.max_x.max(other.max_x),
+            max_y: self.max_y.max(other.max_y),
+        }
+    }
+
+    /// Remove border from all sides.
+    /// When the image is smaller than `border` in some dimension then the result is empty and
+    /// contained in the original image but otherwise unspecified.
+    #[must_use]
+    pub fn inset(self, border: u32) -> Self {
+        Rectangle {
+            x: self.x.saturating_add(border),
+            y: self.y.saturating_add(border),
+            max_x: self.max_x.saturating_sub(border),
+            max_y: self.max_y.saturating_sub(border),
+        }
+    }
+}
+
+impl From<&'_ ByteLayout> for Rectangle {
+    fn from(buffer: &ByteLayout) -> Rectangle {
+        Rectangle::with_width_height(buffer.width, buffer.height)
+    }
+}
+
+impl From<&'_ BufferLayout> for Rectangle {
+    fn from(buffer: &BufferLayout) -> Rectangle {
+        Rectangle::with_width_height(buffer.width(), buffer.height())
+    }
+}
+
+impl From<&'_ Descriptor> for Rectangle {
+    fn from(buffer: &Descriptor) -> Rectangle {
+        Rectangle::from(&buffer.layout)
+    }
+}
+
+impl CommandError {
+    /// Indicates a very generic type error.
+    const TYPE_ERR: Self = CommandError {
+        inner: CommandErrorKind::GenericTypeError,
+    };
 
-                            high_ops.push(High::PushOperand(reg_to_texture[src]));
-                            // FIXME: using a copy here but this means we do this in unnecessarily
-                            // many steps. We first decode to linear color, then draw, then code
-                            // back to the non-linear electrical space.
-                            // We could do this directly from one matrix to another or try using an
-                            // ephemeral intermediate attachment?
-                            high_ops.push(High::Construct {
-                                dst: Target::Discard(texture),
-                                fn_: Initializer::PaintFullScreen {
-                                    shader: color.to_shader(),
-                                },
-                            });
-                        }
-                        UnaryOp::Extract { channel: _ } => {
-                            high_ops.push(High::PushOperand(reg_to_texture[src]));
+    /// Indicates a very generic other error.
+    /// E.g. the usage of a command requires an extension? Not quite sure yet.
+    const OTHER: Self = CommandError {
+        inner: CommandErrorKind::Other,
+    };
 
-                            high_ops.push(High::Construct {
-                                dst: Target::Discard(texture),
-                                fn_: Initializer::PaintFullScreen {
-                                    // This will grab the right channel, that is all of them.
-                                    // The actual conversion is done in de-staging of the result.
-                                    // TODO: evaluate if this is the right way to do it. We could
-                                    // also perform a LinearColorMatrix shader here with close to
-                                    // the same amount of shader code but a precise result.
-                                    shader: FragmentShader::PaintOnTop(PaintOnTopKind::Copy),
-                                },
-                            })
-                        }
-                        UnaryOp::Derivative(derivative) => {
-                            let shader = derivative.method.to_shader(derivative.direction)?;
+    /// Specifies that a register reference was invalid.
+    const BAD_REGISTER: Self = Self::OTHER;
 
-                            high_ops.push(High::PushOperand(reg_to_texture[src]));
-                            high_ops.push(High::Construct {
-                                dst: Target::Discard(texture),
-                                fn_: Initializer::PaintFullScreen { shader },
-                            })
-                        }
-                        UnaryOp::Transmute => high_ops.push(High::Copy {
-                            src: *src,
-                            dst: Register(idx),
-                        }),
-                    }
+    /// Specifies that a register reference was invalid.
+    const INVALID_CALL: Self = Self::OTHER;
 
-                    reg_to_texture.insert(Register(idx), texture);
-                }
-                Op::Binary {
-                    desc: _,
-                    lhs,
-                    rhs,
-                    op: binary_op,
-                } => {
-                    let texture = realize_texture(idx, op)?;
+    /// This has not yet been implemented, sorry.
+    ///
+    /// Errors of this kind will be removed over the course of bringing the crate to a first stable
+    /// release, this this will be removed. The method, and importantly its signature, are already
+    /// added for the purpose of exposition and documenting the intention.
+    const UNIMPLEMENTED: Self = CommandError {
+        inner: CommandErrorKind::Unimplemented,
+    };
 
-                    let lhs_descriptor = command.describe_reg(*lhs).unwrap().monomorphize(tys);
-                    let rhs_descriptor = command.describe_reg(*rhs).unwrap().monomorphize(tys);
+    pub fn is_type_err(&self) -> bool {
+        matches!(
+            self.inner,
+            CommandErrorKind::GenericTypeError
+                | CommandErrorKind::ConflictingTypes(_, _)
+                | CommandErrorKind::BadDescriptor(_, _)
+        )
+    }
+}
 
-                    let lower_region = Rectangle::from(&lhs_descriptor);
-                    let upper_region = Rectangle::from(&rhs_descriptor);
+#[test]
+fn rectangles() {
+    let small = Rectangle::with_width_height(2, 2);
+    let large = Rectangle::with_width_height(4, 4);
 
-                    match binary_op {
-                        BinaryOp::Affine(affine) => {
-                            let affine_matrix = RowMatrix::new(affine.transformation);
+    assert_eq!(large, large.join(small));
+    assert_eq!(small, large.meet(small));
+    assert!(large.contains(small));
+    assert!(!small.contains(large));
+}
 
-                            high_ops.push(High::PushOperand(reg_to_texture[lhs]));
-                            high_ops.push(High::Construct {
-                                dst: Target::Discard(texture),
-                                fn_: Initializer::PaintToSelection {
-                                    texture: reg_to_texture[lhs],
-                                    selection: lower_region,
-                                    target: lower_region.into(),
-                                    viewport: lower_region,
-                                    shader: FragmentShader::PaintOnTop(PaintOnTopKind::Copy),
-                                },
-                            });
+#[test]
+fn color_convert_direction() {
+    use image_canvas::color::Primaries;
+
+    let descriptor = Descriptor::with_srgb_image(&image::DynamicImage::new_rgba8(1, 1));
+    let (primary, whitepoint, luminance) = match descriptor.color {
+        Color::Rgb {
+            primary,
+            whitepoint,
+            luminance,
+            ..
+        } => (primary, whitepoint, luminance),
+        _ => unreachable!("sRGB is an RGB color"),
+    };
 
-                            high_ops.push(High::PushOperand(reg_to_texture[rhs]));
-                            high_ops.push(High::Construct {
-                                dst: Target::Load(texture),
-                                fn_: Initializer::PaintToSelection {
-                                    texture: reg_to_texture[rhs],
-                                    selection: upper_region,
-                                    target: QuadTarget::from(upper_region).affine(&affine_matrix),
-                                    viewport: lower_region,
-                                    shader: FragmentShader::PaintOnTop(
-                                        affine.sampling.as_paint_on_top()?,
-                                    ),
-                                },
-                            })
-                        }
-                        BinaryOp::Inject {
-                            channel,
-                            from_channels,
-                        } => {
-                            high_ops.push(High::PushOperand(reg_to_texture[lhs]));
-                            high_ops.push(High::PushOperand(reg_to_texture[rhs]));
+    let xyz = Color::Rgb {
+        primary: Primaries::Xyz,
+        transfer: Transfer::Linear,
+        whitepoint,
+        luminance,
+    };
 
-                            high_ops.push(High::Construct {
-                                dst: Target::Discard(texture),
-                                fn_: Initializer::PaintFullScreen {
-                                    shader: FragmentShader::Inject(shaders::inject::Shader {
-                                        mix: channel.into_vec4(),
-                                        color: from_channels.channel_weight_vec4().unwrap(),
-                                    }),
-                                },
-                            })
-                        }
-                        BinaryOp::Inscribe { placement } => {
-                            high_ops.push(High::PushOperand(reg_to_texture[lhs]));
-                            high_ops.push(High::Construct {
-                                dst: Target::Discard(texture),
-                                fn_: Initializer::PaintToSelection {
-                                    texture: reg_to_texture[lhs],
-                                    selection: lower_region,
-                                    target: lower_region.into(),
-                                    viewport: lower_region,
-                                    shader: FragmentShader::PaintOnTop(PaintOnTopKind::Copy),
-                                },
-                            });
+    let mut commands = CommandBuffer::default();
+    let input = commands.input(descriptor.clone()).unwrap();
+    let converted = commands
+        .color_convert(input, xyz, descriptor.texel.clone())
+        .expect("Valid to convert between primaries");
 
-                            high_ops.push(High::PushOperand(reg_to_texture[rhs]));
-                            high_ops.push(High::Construct {
-                                dst: Target::Load(texture),
-                                fn_: Initializer::PaintToSelection {
-                                    texture: reg_to_texture[rhs],
-                                    selection: upper_region,
-                                    target: (*placement).into(),
-                                    viewport: lower_region,
-                                    shader: FragmentShader::PaintOnTop(PaintOnTopKind::Copy),
-                                },
-                            });
-                        }
-                        BinaryOp::Palette(shader) => {
-                            high_ops.push(High::PushOperand(reg_to_texture[lhs]));
-                            high_ops.push(High::PushOperand(reg_to_texture[rhs]));
+    let matrix = match &commands.ops[converted.0] {
+        Op::Unary {
+            op: UnaryOp::ColorConvert(conversion),
+            ..
+        } => match conversion.to_shader() {
+            FragmentShader::LinearColorMatrix(transform) => transform.matrix,
+            _ => panic!("Expected a matrix between RGB primaries"),
+        },
+        _ => panic!("Expected a color conversion"),
+    };
 
-                            high_ops.push(High::Construct {
-                                dst: Target::Load(texture),
-                                fn_: Initializer::PaintFullScreen {
-                                    shader: FragmentShader::Palette(shader.clone()),
-                                },
-                            });
-                        }
-                    }
+    // The red primary of the source must end up as red of the source expressed in the target's
+    // primaries, not the other way around.
+    let to_xyz = RowMatrix(primary.to_xyz_row_matrix(whitepoint));
+    let from_xyz = RowMatrix(Primaries::Xyz.from_xyz_row_matrix(whitepoint));
+    let expected = from_xyz.multiply_column(to_xyz.multiply_column([1.0, 0.0, 0.0]));
+    let red = matrix.multiply_column([1.0, 0.0, 0.0]);
+
+    for (ch, expected) in red.iter().zip(&expected) {
+        assert!(
+            (ch - expected).abs() < 1e-4,
+            "{:?} differs from {:?}",
+            red,
+            expected
+        );
+    }
+}
 
-                    reg_to_texture.insert(Register(idx), texture);
-                }
-                Op::Dynamic { call, command, .. } => {
-                    let texture = realize_texture(idx, op)?;
-                    let (op_unary, op_binary, arguments);
+#[test]
+fn simple_program() {
+    use crate::pool::Pool;
 
-                    match call {
-                        OperandDynKind::Construct => {
-                            arguments = &[][..];
-                            reg_to_texture.insert(Register(idx), texture);
-                        }
-                        OperandDynKind::Unary(reg) => {
-                            op_unary = [reg_to_texture[reg]];
-                            arguments = &op_unary[..];
-                            reg_to_texture.insert(Register(idx), texture);
-                        }
-                        OperandDynKind::Binary { lhs, rhs } => {
-                            op_binary = [reg_to_texture[lhs], reg_to_texture[rhs]];
-                            arguments = &op_binary[..];
-                            reg_to_texture.insert(Register(idx), texture);
-                        }
-                    }
+    const BACKGROUND: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/input/background.png");
+    const FOREGROUND: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/input/foreground.png");
 
-                    for &operand in arguments {
-                        high_ops.push(High::PushOperand(operand));
-                    }
+    let mut pool = Pool::new();
+    let mut commands = CommandBuffer::default();
 
-                    high_ops.push(High::Construct {
-                        dst: Target::Discard(texture),
-                        fn_: Initializer::PaintFullScreen {
-                            shader: FragmentShader::Dynamic(command.clone()),
-                        },
-                    })
-                }
-                Op::InvokedResult { .. } => {
-                    let texture = realize_texture(idx, op)?;
+    let background = image::open(BACKGROUND).expect("Background image opened");
+    let foreground = image::open(FOREGROUND).expect("Background image opened");
+    let expected = ByteLayout::from(&background);
 
-                    high_ops.push(High::Uninit {
-                        dst: Target::Discard(texture),
-                    });
+    let placement = Rectangle {
+        x: 0,
+        y: 0,
+        max_x: foreground.width(),
+        max_y: foreground.height(),
+    };
 
-                    reg_to_texture.insert(Register(idx), texture);
-                }
-                Op::Invoke {
-                    function,
-                    arguments,
-                    results,
-                    generics,
-                } => {
-                    let monomorphic_tys: Vec<_> = generics
-                        .iter()
-                        .map(|gen| gen.monomorphize(tys))
-                        .collect::<_>();
+    let background = pool.insert_srgb(&background);
+    let background = commands.input_from(background.into());
 
-                    let &FunctionVar(function_idx) = function;
-                    let link_idx = *functions
-                        .get(function_idx)
-                        .ok_or(CompileError::NotYetImplemented)?;
+    let foreground = pool.insert_srgb(&foreground);
+    let foreground = commands.input_from(foreground.into());
 
-                    let function = mono.push_function(LinkedMonomorphicSignature {
-                        link_idx,
-                        tys: monomorphic_tys,
-                    });
+    let result = commands
+        .inscribe(background, placement, foreground)
+        .expect("Valid to inscribe");
+    let (_, outformat) = commands.output(result).expect("Valid for output");
+
+    let _ = commands.compile().expect("Could build command buffer");
+    assert_eq!(outformat.as_concrete().map(|x| x.layout), Some(expected));
+}
+
+#[test]
+fn tone_map_program() {
+    let mut commands = CommandBuffer::default();
 
-                    let mut image_io = vec![];
+    let hdr = Descriptor::with_texel(
+        Texel {
+            block: Block::Pixel,
+            bits: SampleBits::Float32x4,
+            parts: SampleParts::RgbA,
+        },
+        64,
+        64,
+    )
+    .expect("Valid float descriptor");
+
+    let hdr = Descriptor {
+        color: Color::SRGB,
+        ..hdr
+    };
 
-                    for &register in arguments {
-                        // Arguments must precede the function and already be laid out.
-                        if register.0 >= idx {
-                            return Err(CompileError::NotYetImplemented);
-                        }
+    let sdr = Texel::new_u8(SampleParts::RgbA);
+    let input = commands.input(hdr).unwrap();
 
-                        let texture = realize_texture(register.0, &ops[register.0])?;
-                        image_io.push(CallBinding::InTexture { register, texture });
-                    }
+    let tone_map = ToneMap {
+        operator: ToneMapOperator::Hable { white: 11.2 },
+        exposure: 1.0,
+    };
 
-                    for &register in results {
-                        // Results must precede the function and already be laid out. They are not
-                        // initialized but initialized on return.
-                        if register.0 >= idx {
-                            return Err(CompileError::NotYetImplemented);
-                        }
+    assert!(commands
+        .tone_map(input, tone_map, Color::Oklab, sdr.clone())
+        .is_err());
+
+    let mapped = commands
+        .tone_map(input, tone_map, Color::SRGB, sdr)
+        .expect("Valid to tone map");
+
+    // A stop doubles the light, and luminance is weighted by the Y row of the sRGB primaries.
+    let shader = match &commands.ops[mapped.0] {
+        Op::Unary {
+            op: UnaryOp::ToneMap(shader),
+            ..
+        } => shader,
+        other => panic!("Tone mapped by {:?}", other),
+    };
+    assert_eq!(shader.exposure, 2.0);
+    assert_eq!(shader.white, 11.2);
+    for (weight, expected) in shader.luminance.iter().zip([0.2126, 0.7152, 0.0722]) {
+        assert!((weight - expected).abs() < 1e-3, "{:?}", shader.luminance);
+    }
 
-                        let texture = realize_texture(register.0, &ops[register.0])?;
-                        image_io.push(CallBinding::OutTexture { register, texture });
-                    }
+    let (_, outformat) = commands.output(mapped).expect("Valid for output");
 
-                    high_ops.push(High::Call {
-                        function,
-                        image_io_buffers: Arc::from(image_io),
-                    });
-                }
-                // In case we add a new case.
-                #[allow(unreachable_patterns)]
-                _ => {
-                    eprintln!("Unimplemented operation");
-                    return Err(CompileError::NotYetImplemented);
-                }
-            }
+    let _ = commands.compile().expect("Could build command buffer");
+    let outformat = outformat.as_concrete().unwrap();
+    assert_eq!(outformat.layout.texel_stride, 4);
+    assert_eq!(outformat.size(), (64, 64));
+}
 
-            high_ops.push(High::Done(Register(idx)));
-            high_ops.push(High::StackPop);
-        }
+#[test]
+fn curves_bake() {
+    let levels = Curve::Levels(Levels {
+        input_black: 0.25,
+        input_white: 0.75,
+        ..Levels::default()
+    });
+
+    let baked = levels.bake(5);
+    assert_eq!(baked, [0.0, 0.0, 0.5, 1.0, 1.0]);
+
+    let points = Curve::Points(vec![[0.0, 0.0], [0.5, 0.8], [1.0, 1.0]]);
+    assert!(points.is_valid());
+
+    let baked = points.bake(65);
+    assert_eq!(baked[0], 0.0);
+    assert!((baked[32] - 0.8).abs() < 1e-6);
+    assert_eq!(baked[64], 1.0);
+    // Monotone data remains monotone, without overshoot.
+    assert!(baked.windows(2).all(|w| w[0] <= w[1]));
+    assert!(baked.iter().all(|&v| (0.0..=1.0).contains(&v)));
+
+    assert!(!Curve::Points(vec![[0.5, 0.0], [0.5, 1.0]]).is_valid());
+}
 
-        let end = high_ops.len();
+#[test]
+fn curves_program() {
+    let mut commands = CommandBuffer::default();
 
-        // The registers which callers must fill. This must match the order that CallBinding is
-        // passed at call sites, i.e. be consistent with the signature.
-        let signature_registers = signature_in.into_iter().chain(signature_out).collect();
+    let descriptor = Descriptor::with_srgb_image(&image::DynamicImage::new_rgba8(16, 16));
+    let input = commands.input(descriptor.clone()).unwrap();
 
-        Ok(FunctionLinked {
-            ops: start..end,
-            image_buffers,
-            signature_registers,
+    let brighten = Curve::Points(vec![[0.0, 0.0], [0.5, 0.7], [1.0, 1.0]]);
+    let curved = commands
+        .curves(
+            input,
+            Curves {
+                channels: [brighten.clone(), brighten, Curve::Identity, Curve::Identity],
+                domain: CurveDomain::Linear,
+            },
+        )
+        .expect("Valid to apply curves");
+
+    let desc = commands
+        .describe_reg(curved)
+        .unwrap()
+        .as_concrete()
+        .unwrap();
+    assert_eq!(desc, descriptor);
+
+    // The table is painted in half precision, not in the 8 bits of the image.
+    let lut = commands
+        .ops
+        .iter()
+        .find_map(|op| match op {
+            Op::Construct {
+                desc,
+                op: ConstructOp::Lut1d(_),
+            } => desc.as_concrete(),
+            _ => None,
         })
-    }
+        .expect("The table is constructed");
+    assert_eq!(lut.texel.bits, SampleBits::Float16x4);
+    assert!(ImageDescriptor::new(&lut).is_ok());
+
+    let unsorted = Curve::Points(vec![[0.5, 0.0], [0.2, 1.0]]);
+    assert!(commands
+        .curves(
+            input,
+            Curves {
+                channels: [unsorted, Curve::Identity, Curve::Identity, Curve::Identity],
+                domain: CurveDomain::Encoded,
+            },
+        )
+        .is_err());
+
+    let leveled = commands
+        .levels(
+            curved,
+            Levels {
+                input_black: 0.1,
+                input_white: 0.9,
+                gamma: 1.2,
+                output_black: 0.0,
+                output_white: 1.0,
+            },
+            CurveDomain::Encoded,
+        )
+        .expect("Valid to apply levels");
 
-    /// Get the descriptor for a register.
-    fn describe_reg(&self, Register(reg): Register) -> Result<&GenericDescriptor, CommandError> {
-        match self.ops.get(reg) {
-            None | Some(Op::Output { .. }) | Some(Op::Render { .. }) => {
-                Err(CommandError::BAD_REGISTER)
-            }
-            Some(Op::Invoke { .. }) => {
-                // This does not describe results directly.
-                Err(CommandError::BAD_REGISTER)
-            }
-            Some(Op::InvokedResult { desc, .. })
-            | Some(Op::Input { desc })
-            | Some(Op::Construct { desc, .. })
-            | Some(Op::Unary { desc, .. })
-            | Some(Op::Binary { desc, .. })
-            | Some(Op::Dynamic { desc, .. }) => Ok(desc),
-        }
-    }
+    let (_, _) = commands.output(leveled).expect("Valid for output");
+    let _ = commands.compile().expect("Could build command buffer");
+}
 
-    fn push(&mut self, op: Op) -> Register {
-        let reg = Register(self.ops.len());
-        self.ops.push(op);
-        reg
-    }
+#[test]
+fn cube_parse() {
+    const CUBE: &str = "\
+# Swaps red and green.
+TITLE \"swap\"
+LUT_3D_SIZE 2
+DOMAIN_MIN 0 0 0
+DOMAIN_MAX 1 1 2
+
+0 0 0
+0 1 0
+1 0 0
+1 1 0
+0 0 1
+0 1 1
+1 0 1
+1 1 1
+";
+
+    let lut = Lut3d::from_cube(CUBE).expect("Valid cube file");
+    assert_eq!(lut.size, 2);
+    assert_eq!(lut.domain_max, [1.0, 1.0, 2.0]);
+    assert_eq!(lut.table[1], [0.0, 1.0, 0.0]);
+    assert!(lut.shaper.is_none());
+
+    const SHAPED: &str = "\
+LUT_1D_SIZE 3
+LUT_3D_SIZE 2
+LUT_1D_INPUT_RANGE 0 4
+0 0 0
+0.75 0.75 0.75
+1 1 1
+0 0 0
+1 0 0
+0 1 0
+1 1 0
+0 0 1
+1 0 1
+0 1 1
+1 1 1
+";
+
+    let lut = Lut3d::from_cube(SHAPED).expect("Valid cube file with shaper");
+    assert_eq!(lut.table, Lut3d::identity(2).table);
+    assert_eq!(lut.domain_max, [1.0; 3]);
+    let shaper = lut.shaper.expect("Has a shaper");
+    assert_eq!(shaper.table.len(), 3);
+    assert_eq!(shaper.domain_max, [4.0; 3]);
+
+    assert!(Lut3d::from_cube("LUT_3D_SIZE 2\n0 0 0\n").is_err());
+    assert!(Lut3d::from_cube("LUT_3D_SIZE 2\nLUT_3D_SIZE 2\n").is_err());
+    assert!(Lut3d::from_cube("LUT_3D_SIZE 1\n0 0 0\n").is_err());
+    assert!(Lut3d::from_cube("LUT_3D_SIZE 129\n0 0 0\n").is_err());
+    assert!(Lut3d::from_cube("LUT_1D_SIZE 513\n0 0 0\n").is_err());
+    assert!(Lut3d::from_cube("0 0 0\nLUT_1D_SIZE 2\n1 1 1\n").is_err());
+    assert!(Lut3d::from_cube("LUT_1D_SIZE 2\nDOMAIN_MIN 1 1 1\n0 0 0\n1 1 1\n").is_err());
 }
 
-/// Impls on `CommandBuffer` that allow defining custom SPIR-V extensions.
-///
-/// Generally, the steps on the dynamic shader are:
-///
-/// 1. Check the kind, get SPIR-v code.
-/// 2. Determine the dynamic typing of the result.
-/// 3. Have the shader create binary representation of its data.
-/// 3. Create a new entry on the command buffer.
-/// 4. Not yet performed: (Validate the SPIR-V module inputs against the data definition)
-impl CommandBuffer {
-    /// Record a _constructor_.
-    pub fn construct_dynamic(&mut self, dynamic: &dyn ShaderCommand) -> Register {
-        let mut data = vec![];
-        let mut content = None;
+#[test]
+fn lut3d_program() {
+    let mut commands = CommandBuffer::default();
 
-        let source = dynamic.source();
-        let desc = dynamic.data(ShaderData {
-            data_buffer: &mut data,
-            content: &mut content,
-        });
+    let descriptor = Descriptor::with_srgb_image(&image::DynamicImage::new_rgba8(16, 16));
+    let input = commands.input(descriptor).unwrap();
+
+    let mut lut = Lut3d::identity(17);
+    assert_eq!(lut.tiles_per_row(), 5);
+    let texels = lut.to_texels(5 * 17, 4 * 17);
+    // Red of the second entry, then blue of the second slice which starts the second tile.
+    assert_eq!(
+        texels[8..10],
+        crate::util::f32_to_f16_bits(1.0 / 16.0).to_le_bytes()
+    );
+    assert_eq!(
+        texels[17 * 8 + 4..17 * 8 + 6],
+        crate::util::f32_to_f16_bits(1.0 / 16.0).to_le_bytes()
+    );
+
+    let graded = commands.lut3d(input, &lut).expect("Valid to apply a table");
+    let (_, outformat) = commands.output(graded).expect("Valid for output");
+    let _ = commands.compile().expect("Could build command buffer");
+    assert_eq!(outformat.as_concrete().unwrap().size(), (16, 16));
 
-        self.push(Op::Dynamic {
-            call: OperandDynKind::Construct,
-            // FIXME: maybe this conversion should be delayed.
-            // In particular, converting source to SPIR-V may take some form of 'compiler' argument
-            // that's only available during `compile` phase.
-            command: ShaderInvocation {
-                spirv: match source {
-                    ShaderSource::SpirV(spirv) => spirv,
-                },
-                shader_data: match content {
-                    None => None,
-                    Some(c) => Some(c.as_slice(&data).into()),
-                },
-                num_args: 0,
-            },
-            desc: desc.into(),
-        })
-    }
+    lut.interpolation = Lut3dInterpolation::Trilinear;
+    lut.domain_max = [0.0; 3];
+    assert!(commands.lut3d(input, &lut).is_err());
+}
 
-    /// Record a _unary operator_.
-    pub fn unary_dynamic(&mut self, _: Register, _: &dyn ShaderCommand) -> Register {
-        todo!()
-    }
+#[test]
+fn color_convert_adaptation() {
+    let primary = match Color::SRGB {
+        Color::Rgb { primary, .. } => primary,
+        _ => unreachable!("sRGB is an RGB color"),
+    };
 
-    /// Record a _binary operator_.
-    pub fn binary_dynamic(&mut self, _: Register, _: Register, _: &dyn ShaderCommand) -> Register {
-        todo!()
+    let method = ChromaticAdaptationMethod::default();
+    let to_xyz = method
+        .adapted_to_xyz(primary, Whitepoint::D65, Whitepoint::D50)
+        .expect("Bradford adaptation is supported");
+
+    // White must be adapted to the white of the target, compare chromaticity.
+    let white = to_xyz.multiply_column([1.0; 3]);
+    let d50 = Whitepoint::D50.to_xyz();
+    for (ch, expected) in white.iter().zip(&d50) {
+        let (ch, expected) = (ch / white[1], expected / d50[1]);
+        assert!(
+            (ch - expected).abs() < 1e-3,
+            "{white:?} differs from {d50:?}"
+        );
     }
+
+    let mut commands = CommandBuffer::default();
+    let descriptor = Descriptor::with_srgb_image(&image::DynamicImage::new_rgba8(16, 16));
+    let print = match descriptor.color {
+        Color::Rgb {
+            primary,
+            transfer,
+            luminance,
+            ..
+        } => Color::Rgb {
+            primary,
+            transfer,
+            luminance,
+            whitepoint: Whitepoint::D50,
+        },
+        _ => unreachable!("sRGB is an RGB color"),
+    };
+
+    let input = commands.input(descriptor.clone()).unwrap();
+    let converted = commands
+        .color_convert(input, print, descriptor.texel.clone())
+        .expect("Valid to convert across whitepoints");
+    let oklab = commands
+        .color_convert(converted, Color::Oklab, descriptor.texel.clone())
+        .expect("Valid to convert from D50 into Oklab");
+    let (_, _) = commands.output(oklab).expect("Valid for output");
+    let _ = commands.compile().expect("Could build command buffer");
 }
 
-impl CommandSignature {
-    pub fn is_declaration_of(&self, actual: &CommandSignature) -> bool {
-        if self.vars.len() != actual.vars.len() {
-            return false;
-        }
+#[test]
+fn color_convert_chain() {
+    let mut commands = CommandBuffer::default();
 
-        for (decl, actual) in self.vars.iter().zip(&actual.vars) {
-            if !decl.contains_bounds(actual) {
-                return false;
-            }
-        }
+    let descriptor = Descriptor::with_srgb_image(&image::DynamicImage::new_rgba8(16, 16));
+    let lab = |parts| Texel {
+        parts,
+        ..descriptor.texel.clone()
+    };
 
-        true
-    }
+    let input = commands.input(descriptor.clone()).unwrap();
+    let oklab = commands
+        .color_convert(input, Color::Oklab, lab(SampleParts::LabA))
+        .expect("Valid to convert into Oklab");
+    let oklch = commands
+        .color_convert(oklab, Color::Oklab, lab(SampleParts::LchA))
+        .expect("Valid to convert into the polar form");
+
+    let srlab2 = Color::SrLab2 {
+        whitepoint: Whitepoint::D50,
+    };
+
+    let before = commands.ops.len();
+    let srlch = commands
+        .color_convert(oklch, srlab2, lab(SampleParts::LchA))
+        .expect("Valid to convert between perceptual spaces");
+    // Planned through an intermediate XYZ image.
+    assert_eq!(commands.ops.len(), before + 2);
+
+    let back = commands
+        .color_convert(srlch, descriptor.color.clone(), descriptor.texel.clone())
+        .expect("Valid to convert back to RGB");
+    let (_, _) = commands.output(back).expect("Valid for output");
+    let _ = commands.compile().expect("Could build command buffer");
 }
 
-impl GenericDescriptor {
-    /// Query if this describes a monomorphic descriptor.
-    ///
-    /// At the moment this means a fully constrained descriptor where both size and chroma are
-    /// defined. It's a bit odd that this would be an overlapping property with having been
-    /// constructed from an actually concrete defined descriptor. If we had a non-deterministic
-    /// layout algorithm (i.e. multiple permissible layouts for one combination of size/chroma)
-    /// then this might inadvertently throw away some of this information. But for now this
-    /// information is compile time only, the actual dependence of operational semantics on layout
-    /// information is evaluated at runtime. (FIXME: I will have regretted writing this).
-    pub fn as_concrete(&self) -> Option<Descriptor> {
-        let Generic::Concrete((w, h)) = self.size else {
-            return None;
-        };
+#[test]
+fn cie_round_trip() {
+    let mut commands = CommandBuffer::default();
 
-        let Generic::Concrete((texel, color)) = &self.chroma else {
-            return None;
-        };
+    let descriptor = Descriptor::with_srgb_image(&image::DynamicImage::new_rgba8(16, 16));
+    let input = commands.input(descriptor.clone()).unwrap();
 
-        Descriptor::with_texel(texel.clone(), w, h).map(|mut desc| {
-            desc.color = color.clone();
-            desc
-        })
-    }
+    let d50 = CieColor {
+        model: CieModel::Lab,
+        whitepoint: Whitepoint::D50,
+    };
 
-    /// FIXME: fallible. If we change the texel from something small to something very large we can
-    /// exceed the allocation limits that are necessary to express the layout.
-    pub fn with_chroma(&self, texel: Texel, color: Color) -> Self {
-        GenericDescriptor {
-            chroma: Generic::Concrete((texel, color)),
-            ..self.clone()
-        }
-    }
+    let not_cie = commands.cie_encode(input, d50, descriptor.texel.clone());
+    assert!(not_cie.is_err(), "RgbA parts can not hold CIE colors");
 
-    pub fn monomorphize(&self, decl: &[Descriptor]) -> Descriptor {
-        let (w, h) = match &self.size {
-            Generic::Concrete(descriptor) => descriptor.clone(),
-            Generic::Generic(idx) => decl[idx.0].size(),
-        };
+    let lch = Texel {
+        parts: SampleParts::LchA,
+        ..descriptor.texel.clone()
+    };
 
-        let (texel, color) = match &self.chroma {
-            Generic::Concrete(tuple) => tuple.clone(),
-            Generic::Generic(idx) => {
-                let from = &decl[idx.0];
-                (from.texel.clone(), from.color.clone())
-            }
-        };
+    let encoded = commands
+        .cie_encode(input, d50, lch)
+        .expect("Valid to encode as LCh(ab)");
+    let encoded_desc = commands
+        .describe_reg(encoded)
+        .unwrap()
+        .as_concrete()
+        .unwrap();
+    assert!(matches!(encoded_desc.color, Color::Scalars { .. }));
+    assert_eq!(encoded_desc.cie, Some(d50));
+
+    let not_scalars =
+        commands.cie_decode(input, descriptor.color.clone(), descriptor.texel.clone());
+    assert!(not_scalars.is_err(), "Decoded from an RGB image");
+
+    // Linear scalars that were not encoded do not tell their model and reference white.
+    let unknown = Descriptor {
+        cie: None,
+        ..encoded_desc.clone()
+    };
+    let unknown = commands.input(unknown).unwrap();
+    let unknown = commands.cie_decode(unknown, descriptor.color.clone(), descriptor.texel.clone());
+    assert!(unknown.is_err(), "Decoded without a CIE color");
 
-        Descriptor::with_texel(texel, w, h)
-            .map(|mut desc| {
-                desc.color = color;
-                desc
-            })
-            .expect("changing texel and color to something that does not fit memory")
-    }
+    let luv = CieColor {
+        model: CieModel::Luv,
+        whitepoint: Whitepoint::D65,
+    };
+    let known = Descriptor {
+        cie: Some(luv),
+        ..encoded_desc
+    };
+    let known = commands.input(known).unwrap();
+    commands
+        .cie_decode(known, descriptor.color.clone(), descriptor.texel.clone())
+        .expect("Valid to decode an input with a CIE color");
+
+    let back = commands
+        .cie_decode(encoded, descriptor.color.clone(), descriptor.texel.clone())
+        .expect("Valid to decode into RGB");
+    let (_, _) = commands.output(back).expect("Valid for output");
+    let _ = commands.compile().expect("Could build command buffer");
+}
 
-    /// Apply an outer variable definition, replacing generics by at least as concrete terms.
-    ///
-    /// Does not verify any bounds of the rewrites! Which we'll need to do if we had associated
-    /// constants and the rewrite was looking into paths and impls. Consider a trait (similar to
-    /// the Rust type system) / type family such as `LinearizedColor` that associates the linear
-    /// optical colorspace to an arbitrary electrical color encoding. Then we might have the
-    /// signature written in pseudo-code:
-    ///
-    /// ```text
-    ///     function <C: LinearizedColor>(arg0: {C; 256×256}, arg1: {C::Linear; 256×256})
-    /// ```
-    ///
-    /// Now if we rewrite with [C = sRGB] then we want the concrete [C::Linear=CIE-RGB-Wp-D70]
-    /// correspondence. But if we tried [C = CYMK] we have nonsense. Here we allow this function to
-    /// panic, a check must happen earlier.
-    pub fn rewrite(&self, decl: &[GenericDescriptor]) -> Self {
-        GenericDescriptor {
-            size: match &self.size {
-                &Generic::Concrete(size) => Generic::Concrete(size),
-                Generic::Generic(idx) => decl[idx.0].size.clone(),
-            },
-            chroma: match &self.chroma {
-                Generic::Concrete(chroma) => Generic::Concrete(chroma.clone()),
-                Generic::Generic(idx) => decl[idx.0].chroma.clone(),
-            },
-        }
-    }
+#[test]
+fn hsl_program() {
+    let mut commands = CommandBuffer::default();
+
+    let descriptor = Descriptor::with_srgb_image(&image::DynamicImage::new_rgba8(16, 16));
+    let input = commands.input(descriptor.clone()).unwrap();
+
+    let adjusted = commands
+        .hsl_adjust(input, -90.0, 1.2, 0.9)
+        .expect("Valid to adjust an RGB image");
+    assert_eq!(
+        commands.describe_reg(adjusted).unwrap(),
+        &descriptor.clone().into()
+    );
+
+    // The hue is shifted in turns, wrapping negative shifts around.
+    let operation = commands.ops.iter().find_map(|op| match op {
+        Op::Unary {
+            op: UnaryOp::HslAdjust(shader),
+            ..
+        } => Some(shader.operation),
+        _ => None,
+    });
+    assert_eq!(
+        operation,
+        Some(shaders::hsl::Operation::Adjust {
+            hue_shift: 0.75,
+            saturation: 1.2,
+            lightness: 0.9,
+        })
+    );
+    assert!(commands.hsl_adjust(input, f32::NAN, 1.0, 1.0).is_err());
+
+    let float = Texel {
+        block: Block::Pixel,
+        bits: SampleBits::Float16x4,
+        parts: SampleParts::RgbA,
+    };
+    let float = Descriptor {
+        color: descriptor.color.clone(),
+        ..Descriptor::with_texel(float, 16, 16).unwrap()
+    };
+    let float = commands.input(float).unwrap();
+    assert!(commands.hsl_adjust(float, 30.0, 1.2, 0.9).is_err());
+
+    let hsv = commands
+        .hue_encode(adjusted, HueModel::Hsv, descriptor.texel.clone())
+        .expect("Valid to convert into HSV");
+    let back = commands
+        .hue_decode(
+            hsv,
+            HueModel::Hsv,
+            descriptor.color.clone(),
+            descriptor.texel.clone(),
+        )
+        .expect("Valid to convert back into RGB");
+    assert_eq!(commands.describe_reg(back).unwrap(), &descriptor.into());
+
+    let (_, _) = commands.output(back).expect("Valid for output");
+    let _ = commands.compile().expect("Could build command buffer");
+}
+
+#[test]
+fn ycbcr_levels() {
+    let limited = YCbCr {
+        coefficients: YCbCrCoefficients::Bt709,
+        range: YCbCrRange::Limited,
+    };
+
+    let (matrix, offset) = limited.encode_affine(SampleBits::UInt8x4);
+    let apply = |matrix: RowMatrix, offset: [f32; 3], value: [f32; 3]| {
+        let [x, y, z] = matrix.multiply_column(value);
+        [x + offset[0], y + offset[1], z + offset[2]]
+    };
 
-    pub fn size(&self) -> Generic<(u32, u32)> {
-        self.size.clone()
-    }
+    let close = |a: [f32; 3], b: [f32; 3]| a.iter().zip(&b).all(|(a, b)| (a - b).abs() < 1e-4);
 
-    pub fn descriptor_chroma(&self) -> Generic<(Texel, Color)> {
-        self.chroma.clone()
-    }
-}
+    let white = apply(matrix, offset, [1.0; 3]);
+    assert!(close(white, [235.0 / 255.0, 128.0 / 255.0, 128.0 / 255.0]));
+    let black = apply(matrix, offset, [0.0; 3]);
+    assert!(close(black, [16.0 / 255.0, 128.0 / 255.0, 128.0 / 255.0]));
 
-impl From<Descriptor> for GenericDescriptor {
-    fn from(desc: Descriptor) -> Self {
-        let size = desc.size();
-        let chroma = (desc.texel.clone(), desc.color.clone());
+    let red = apply(matrix, offset, [1.0, 0.0, 0.0]);
+    assert!((red[2] - 240.0 / 255.0).abs() < 1e-4, "{:?}", red);
 
-        GenericDescriptor {
-            size: Generic::Concrete(size),
-            chroma: Generic::Concrete(chroma),
-        }
-    }
-}
+    let (inverse, inverse_offset) = limited.decode_affine(SampleBits::UInt8x4);
+    let color = [0.25, 0.5, 0.75];
+    let round_trip = apply(inverse, inverse_offset, apply(matrix, offset, color));
+    assert!(close(round_trip, color), "{:?}", round_trip);
 
-impl TyVarBounds {
-    pub fn contains_bounds(&self, actual: &TyVarBounds) -> bool {
-        self.is_empty() && actual.is_empty()
-    }
+    let full = YCbCr {
+        range: YCbCrRange::Full,
+        ..limited
+    };
 
-    fn is_empty(&self) -> bool {
-        // FIXME: if we collect the list.
-        true
-    }
-}
+    let (matrix, offset) = full.encode_affine(SampleBits::UInt16x4);
+    let white = apply(matrix, offset, [1.0; 3]);
+    assert!(close(white, [1.0, 32768.0 / 65535.0, 32768.0 / 65535.0]));
 
-impl ColorConversion {
-    pub(crate) fn to_shader(&self) -> FragmentShader {
-        match self {
-            ColorConversion::Xyz {
-                to_xyz_matrix,
-                from_xyz_matrix,
-            } => {
-                let from = from_xyz_matrix.inv();
-                let matrix = to_xyz_matrix.multiply_right(from.into()).into();
+    // Floats are not codes, the color differences are centered on exactly one half.
+    let (matrix, offset) = full.encode_affine(SampleBits::Float16x4);
+    let white = apply(matrix, offset, [1.0; 3]);
+    assert!(close(white, [1.0, 0.5, 0.5]));
 
-                FragmentShader::LinearColorMatrix(shaders::LinearColorTransform { matrix })
-            }
-            ColorConversion::XyzToOklab { to_xyz_matrix } => {
-                FragmentShader::Oklab(shaders::oklab::Shader::with_encode(*to_xyz_matrix))
-            }
-            ColorConversion::OklabToXyz { from_xyz_matrix } => {
-                let from_xyz_matrix = from_xyz_matrix.inv();
-                FragmentShader::Oklab(shaders::oklab::Shader::with_decode(from_xyz_matrix))
-            }
-            ColorConversion::XyzToSrLab2 {
-                to_xyz_matrix,
-                whitepoint,
-            } => FragmentShader::SrLab2(shaders::srlab2::Shader::with_encode(
-                *to_xyz_matrix,
-                *whitepoint,
-            )),
-            ColorConversion::SrLab2ToXyz {
-                from_xyz_matrix,
-                whitepoint,
-            } => {
-                let from_xyz_matrix = from_xyz_matrix.inv();
-                FragmentShader::SrLab2(shaders::srlab2::Shader::with_decode(
-                    from_xyz_matrix,
-                    *whitepoint,
-                ))
-            }
-        }
-    }
+    let (matrix, offset) = limited.encode_affine(SampleBits::Float32x4);
+    let black = apply(matrix, offset, [0.0; 3]);
+    assert!(close(black, [16.0 / 256.0, 0.5, 0.5]));
 }
 
-impl ChromaticAdaptation {
-    pub(crate) fn to_matrix(&self) -> Result<[f32; 9], CompileError> {
-        use palette::{
-            chromatic_adaptation::{Method, TransformMatrix},
-            white_point as wp,
-        };
+#[test]
+fn ycbcr_program() {
+    let mut commands = CommandBuffer::default();
 
-        // FIXME: when you adjust the value-to-type translation, also adjust it within `method`.
-        macro_rules! translate_matrix {
-            ($source:expr, $target:expr, $($lhs:ident => $lhsty:ty)|*) => {
-                $(
-                    translate_matrix!(
-                        @$source, $target, $lhs => $lhsty :
-                        A => wp::A | B => wp::B | C => wp::C
-                        | D50 => wp::D50 | D55 => wp::D55 | D65 => wp::D65
-                        | D75 => wp::D75 | E => wp::E | F2 => wp::F2
-                        | F7 => wp::F7 | F11 => wp::F11
-                    );
-                )*
-            };
-            (@$source:expr, $target:expr, $lhs:ident => $lhsty:ty : $($rhs:ident => $ty:ty)|*) => {
-                $(
-                    if let (Whitepoint::$lhs, Whitepoint::$rhs) = ($source, $target) {
-                        return Ok((|method| {
-                            let lhswp = <$lhsty as wp::WhitePoint<f32>>::get_xyz();
-                            let rhswp = <$ty as wp::WhitePoint<f32>>::get_xyz();
-                            <Method as TransformMatrix<f32>>::generate_transform_matrix(method, lhswp, rhswp)
-                        })
-                                  as fn(&Method) -> [f32;9]);
-                    }
-                )*
-            };
-        }
+    let descriptor = Descriptor::with_srgb_image(&image::DynamicImage::new_rgba8(16, 16));
+    let input = commands.input(descriptor.clone()).unwrap();
 
-        // FIXME: when you adjust the value-to-type translation, also adjust it within
-        // `translate_matrix!`
-        let method = (|| {
-            translate_matrix! {
-                self.source, self.target,
-                A => wp::A | B => wp::B | C => wp::C
-                | D50 => wp::D50 | D55 => wp::D55 | D65 => wp::D65
-                | D75 => wp::D75 | E => wp::E | F2 => wp::F2
-                | F7 => wp::F7 | F11 => wp::F11
-            };
+    let yuv = Texel {
+        block: Block::Pixel,
+        bits: SampleBits::UInt1010102,
+        parts: SampleParts::Yuv,
+    };
 
-            Err(CompileError::NotYetImplemented)
-        })()?;
+    let full = YCbCr {
+        coefficients: YCbCrCoefficients::Bt601,
+        range: YCbCrRange::Full,
+    };
+    let limited = YCbCr {
+        coefficients: YCbCrCoefficients::Bt709,
+        range: YCbCrRange::Limited,
+    };
 
-        let matrices = method(match self.method {
-            // Bradford's original method that does slight blue non-linearity is not yet supported.
-            // Please implement the paper if you feel compelled to.
-            ChromaticAdaptationMethod::BradfordNonLinear => {
-                return Err(CompileError::NotYetImplemented)
-            }
-            ChromaticAdaptationMethod::BradfordVonKries => &Method::Bradford,
-            ChromaticAdaptationMethod::VonKries => &Method::VonKries,
-            ChromaticAdaptationMethod::Xyz => &Method::XyzScaling,
-        });
+    let encoded = commands
+        .color_convert_ycbcr(input, descriptor.color.clone(), yuv.clone(), full)
+        .expect("Valid to encode as Y'CbCr");
 
-        Ok(matrices)
-    }
+    // Luma would be stored in the two bits of the first field.
+    let mixed_width = Texel {
+        bits: SampleBits::UInt2101010,
+        ..yuv
+    };
+    assert!(commands
+        .color_convert_ycbcr(input, descriptor.color.clone(), mixed_width, full)
+        .is_err());
+
+    // Without a matrix and range, neither encoding nor decoding is guessed.
+    assert!(commands
+        .color_convert(input, descriptor.color.clone(), yuv.clone())
+        .is_err());
+    assert!(commands
+        .color_convert(encoded, descriptor.color.clone(), descriptor.texel.clone())
+        .is_err());
+
+    let back = commands
+        .color_convert_ycbcr(
+            encoded,
+            descriptor.color.clone(),
+            descriptor.texel.clone(),
+            full,
+        )
+        .expect("Valid to decode into RGB");
+    let studio = commands
+        .color_convert_ycbcr(back, descriptor.color.clone(), yuv, limited)
+        .expect("Valid to encode with the studio range");
+    let back = commands
+        .color_convert_ycbcr(
+            studio,
+            descriptor.color.clone(),
+            descriptor.texel.clone(),
+            limited,
+        )
+        .expect("Valid to decode with the studio range");
+    let (_, _) = commands.output(back).expect("Valid for output");
+    let _ = commands.compile().expect("Could build command buffer");
 }
 
-#[rustfmt::skip]
-impl DerivativeMethod {
-    fn to_shader(&self, direction: Direction) -> Result<FragmentShader, CompileError> {
-        use DerivativeMethod::*;
-        use shaders::box3;
-        match self {
-            Prewitt => {
-                let matrix = RowMatrix::with_outer_product(
-                    [1./3., 1./3., 1./3.],
-                    [0.5, 0.0, -0.5],
-                );
+#[test]
+fn planar_program() {
+    use crate::buffer::{ChromaFilter, ChromaSiting, PlanarLayout};
 
-                let shader = box3::Shader::new(direction.adjust_vertical_box(matrix));
-                Ok(shaders::FragmentShader::Box3(shader))
-            }
-            Sobel => {
-                let matrix = RowMatrix::with_outer_product(
-                    [1./4., 1./2., 1./4.],
-                    [0.5, 0.0, -0.5],
-                );
+    let mut commands = CommandBuffer::default();
 
-                let shader = box3::Shader::new(direction.adjust_vertical_box(matrix));
-                Ok(shaders::FragmentShader::Box3(shader))
-            }
-            Scharr3 => {
-                let matrix = RowMatrix::with_outer_product(
-                    [46.84/256., 162.32/256., 46.84/256.],
-                    [0.5, 0.0, -0.5],
-                );
+    let frame = PlanarFrame {
+        planes: Planes {
+            layout: PlanarLayout::Nv12,
+            siting: ChromaSiting::Center,
+            upsampling: ChromaFilter::Bilinear,
+        },
+        ycbcr: YCbCr {
+            coefficients: YCbCrCoefficients::Bt709,
+            range: YCbCrRange::Limited,
+        },
+    };
 
-                let shader = box3::Shader::new(direction.adjust_vertical_box(matrix));
-                Ok(shaders::FragmentShader::Box3(shader))
-            }
-            Scharr3To4Bit => {
-                let matrix = RowMatrix::with_outer_product(
-                    [3./16., 10./16., 3./16.],
-                    [0.5, 0.0, -0.5],
-                );
+    let bytes = frame
+        .planes
+        .descriptor(Color::SRGB, 16, 8)
+        .expect("Valid size for 4:2:0");
+    assert_eq!(bytes.size(), (16, 8));
+    assert!(bytes.is_consistent());
+    assert_eq!(bytes.to_stored().unwrap().size(), (16, 12));
+    assert!(frame.planes.descriptor(Color::SRGB, 16, 7).is_none());
+    assert!(frame.planes.descriptor(Color::SRGB, 15, 8).is_none());
+
+    let i422 = PlanarFrame {
+        planes: Planes {
+            layout: PlanarLayout::I422,
+            ..frame.planes
+        },
+        ..frame
+    };
+    assert_eq!(
+        i422.planes
+            .descriptor(Color::SRGB, 16, 7)
+            .and_then(|desc| desc.to_stored().map(|stored| stored.size())),
+        Some((16, 14))
+    );
+
+    let input = commands.input(bytes).unwrap();
+    let rgb = Descriptor::with_srgb_image(&image::DynamicImage::new_rgba8(16, 8));
+
+    assert!(commands
+        .planar_decode(input, i422, rgb.color.clone(), rgb.texel.clone())
+        .is_err());
+
+    let decoded = commands
+        .planar_decode(input, frame, rgb.color.clone(), rgb.texel.clone())
+        .expect("Valid to decode the frame");
+    assert_eq!(
+        commands.describe_reg(decoded).unwrap().as_concrete(),
+        Some(rgb)
+    );
+
+    let encoded = commands
+        .planar_encode(decoded, frame)
+        .expect("Valid to encode the frame");
+    assert_eq!(
+        commands
+            .describe_reg(encoded)
+            .unwrap()
+            .as_concrete()
+            .and_then(|desc| desc.layout.planes),
+        Some(frame.planes)
+    );
 
-                let shader = box3::Shader::new(direction.adjust_vertical_box(matrix));
-                Ok(shaders::FragmentShader::Box3(shader))
-            }
-            Scharr3To8Bit => {
-                let matrix = RowMatrix::with_outer_product(
-                    [47./256., 162./256., 47./256.],
-                    [0.5, 0.0, -0.5],
-                );
+    let (_, _) = commands.output(encoded).expect("Valid for output");
+    let _ = commands.compile().expect("Could build command buffer");
+}
 
-                let shader = box3::Shader::new(direction.adjust_vertical_box(matrix));
-                Ok(shaders::FragmentShader::Box3(shader))
-            }
-            // FIXME: implement these.
-            // When you do add them to tests/blend.rs
-            | Roberts
-            | Scharr4
-            | Scharr5
-            | Scharr5Tab => Err(CompileError::NotYetImplemented)
-        }
+#[test]
+fn packed_program() {
+    const PACKED: &[(SampleBits, SampleParts)] = &[
+        (SampleBits::UInt565, SampleParts::Rgb),
+        (SampleBits::UInt565, SampleParts::Bgr),
+        (SampleBits::UInt332, SampleParts::Rgb),
+        (SampleBits::UInt233, SampleParts::Bgr),
+        (SampleBits::UInt4x4, SampleParts::RgbA),
+        (SampleBits::UInt1010102, SampleParts::RgbA),
+    ];
+
+    let rgba = Descriptor::with_srgb_image(&image::DynamicImage::new_rgba8(16, 16));
+
+    for &(bits, parts) in PACKED {
+        let texel = Texel {
+            block: Block::Pixel,
+            bits,
+            parts,
+        };
+
+        let mut commands = CommandBuffer::default();
+        let input = commands.input(rgba.clone()).unwrap();
+        let packed = commands
+            .color_convert(input, rgba.color.clone(), texel.clone())
+            .expect("Valid to pack the texel");
+
+        let descriptor = commands.describe_reg(packed).unwrap().as_concrete();
+        let descriptor = descriptor.expect("Packing has a concrete result");
+        assert_eq!(descriptor.texel, texel);
+        assert!(ImageDescriptor::new(&descriptor).is_ok());
+
+        let (_, _) = commands.render(packed).expect("Valid for render");
+        let (_, _) = commands.output(packed).expect("Valid for output");
+        let _ = commands.compile().expect("Could build command buffer");
+
+        let mut commands = CommandBuffer::default();
+        let input = commands.input(descriptor.clone()).unwrap();
+        let wide = commands
+            .color_convert(input, descriptor.color.clone(), rgba.texel.clone())
+            .expect("Valid to unpack the texel");
+        let (_, _) = commands.output(wide).expect("Valid for output");
+        let _ = commands.compile().expect("Could build command buffer");
     }
 }
 
-impl Direction {
-    fn adjust_vertical_box(self, mat: RowMatrix) -> RowMatrix {
-        match self {
-            Direction::Width => mat,
-            Direction::Height => mat.transpose(),
-        }
-    }
+#[test]
+fn indexed_program() {
+    let layout = IndexedLayout {
+        depth: IndexDepth::Bits2,
+        width: 13,
+        height: 4,
+    };
+
+    let bytes = layout.descriptor().expect("Valid indexed layout");
+    assert_eq!(bytes.size(), (4, 4));
+
+    let mut commands = CommandBuffer::default();
+    let palette = Descriptor::with_srgb_image(&image::DynamicImage::new_rgba8(4, 1));
+    let palette = commands.input(palette).unwrap();
+    let indices = commands.input(bytes).unwrap();
+
+    assert!(commands
+        .indexed_decode(
+            palette,
+            indices,
+            IndexedLayout {
+                depth: IndexDepth::Bits4,
+                ..layout
+            },
+        )
+        .is_err());
+
+    let colors = commands
+        .indexed_decode(palette, indices, layout)
+        .expect("Valid to expand indices");
+    let desc = commands
+        .describe_reg(colors)
+        .unwrap()
+        .as_concrete()
+        .unwrap();
+    assert_eq!(desc.size(), (13, 4));
+
+    let not_a_palette = Descriptor::with_srgb_image(&image::DynamicImage::new_rgba8(4, 2));
+    let not_a_palette = commands.input(not_a_palette).unwrap();
+    assert!(commands
+        .indexed_encode(colors, not_a_palette, IndexDepth::Bits2)
+        .is_err());
+
+    let encoded = commands
+        .indexed_encode(colors, palette, IndexDepth::Bits2)
+        .expect("Valid to map to the palette");
+    let desc = commands.describe_reg(encoded).unwrap().as_concrete();
+    assert_eq!(desc, layout.descriptor());
+
+    let (_, _) = commands.output(encoded).expect("Valid for output");
+    let _ = commands.compile().expect("Could build command buffer");
 }
 
-#[rustfmt::skip]
-impl Affine {
-    /// Create affine parameters with identity transformation.
-    pub fn new(sampling: AffineSample) -> Self {
-        Affine {
-            transformation: [
-                1.0, 0., 0.,
-                0., 1.0, 0.,
-                0., 0., 1.0,
-            ],
-            sampling,
-        }
-    }
+#[test]
+fn quantize_program() {
+    let mut commands = CommandBuffer::default();
 
-    /// After the transformation, also scale everything.
-    ///
-    /// This corresponds to a left-side multiplication of the transformation matrix.
-    pub fn scale(self, x: f32, y: f32) -> Self {
-        let post = RowMatrix::diag(x, y, 1.0)
-            .multiply_right(RowMatrix::new(self.transformation).into());
-        let transformation = RowMatrix::from(post).into_inner();
+    let descriptor = Descriptor::with_srgb_image(&image::DynamicImage::new_rgba8(300, 200));
+    let input = commands.input(descriptor.clone()).unwrap();
 
-        Affine {
-            transformation,
-            ..self
-        }
+    let too_many = PaletteQuantization {
+        colors: 257,
+        iterations: 4,
+    };
+    assert!(commands.quantize_palette(input, too_many).is_err());
+
+    let config = PaletteQuantization {
+        colors: 16,
+        iterations: 4,
+    };
+
+    let palette = commands
+        .quantize_palette(input, config)
+        .expect("Valid to quantize");
+    let desc = commands
+        .describe_reg(palette)
+        .unwrap()
+        .as_concrete()
+        .unwrap();
+    assert_eq!(desc.size(), (16, 1));
+    assert_eq!(desc.color, descriptor.color);
+    assert_eq!(desc.texel, descriptor.texel);
+
+    let mapped = commands
+        .indexed_encode(input, palette, IndexDepth::Bits4)
+        .expect("Valid to map to the palette");
+    let (_, _) = commands.output(mapped).expect("Valid for output");
+    let _ = commands.compile().expect("Could build command buffer");
+}
+
+#[test]
+fn dither_program() {
+    let rgba = Descriptor::with_srgb_image(&image::DynamicImage::new_rgba8(64, 48));
+    let rgb565 = Texel {
+        block: Block::Pixel,
+        bits: SampleBits::UInt565,
+        parts: SampleParts::Rgb,
+    };
+
+    for &method in &[Dither::Bayer, Dither::BlueNoise] {
+        let mut commands = CommandBuffer::default();
+        let input = commands.input(rgba.clone()).unwrap();
+
+        let dithered = commands
+            .dither(input, method, rgb565.clone())
+            .expect("Valid to dither");
+        let desc = commands
+            .describe_reg(dithered)
+            .unwrap()
+            .as_concrete()
+            .unwrap();
+        assert_eq!(desc.size(), (64, 48));
+        assert_eq!(desc.color, rgba.color);
+        assert_eq!(desc.texel, rgb565);
+
+        let (_, _) = commands.output(dithered).expect("Valid for output");
+        let _ = commands.compile().expect("Could build command buffer");
     }
 
-    /// After the transformation, rotate everything clockwise.
-    ///
-    /// This corresponds to a left-side multiplication of the transformation matrix.
-    pub fn rotate(self, rad: f32) -> Self {
-        let post = RowMatrix::new([
-            rad.cos(), rad.sin(), 0.,
-            -rad.sin(), rad.cos(), 0.,
-            0., 0., 1.,
-        ]);
+    let mut commands = CommandBuffer::default();
+    let input = commands.input(rgba.clone()).unwrap();
 
-        let post = post.multiply_right(RowMatrix::new(self.transformation).into());
-        let transformation = RowMatrix::from(post).into_inner();
+    let float = Texel {
+        block: Block::Pixel,
+        bits: SampleBits::Float16x4,
+        parts: SampleParts::RgbA,
+    };
+    assert!(commands.dither(input, Dither::Bayer, float).is_err());
 
-        Affine {
-            transformation,
-            ..self
-        }
+    // Luma is one channel, it does not fill the fields of a packed texel.
+    let packed_luma = Texel {
+        parts: SampleParts::Luma,
+        ..rgb565.clone()
+    };
+    assert!(commands.dither(input, Dither::Bayer, packed_luma).is_err());
+
+    let (_, desc) = commands
+        .output_dithered(input, Dither::BlueNoise, Texel::new_u8(SampleParts::Rgb))
+        .expect("Valid to dither for output");
+    assert_eq!(desc.size(), Generic::Concrete((64, 48)));
+
+    // Texels of three channels are staged in packed words.
+    let desc = desc.as_concrete().unwrap();
+    assert!(ImageDescriptor::new(&desc).is_ok());
+
+    // Dithering towards the colors of a palette, before mapping to their indices.
+    let palette = Descriptor::with_srgb_image(&image::DynamicImage::new_rgba8(4, 1));
+    let palette = commands.input(palette).unwrap();
+    let layout = IndexedLayout {
+        depth: IndexDepth::Bits2,
+        width: 64,
+        height: 48,
+    };
+
+    for &method in &[Dither::Bayer, Dither::BlueNoise] {
+        let encoded = commands
+            .indexed_encode_dithered(input, palette, layout.depth, method)
+            .expect("Valid to dither to the palette");
+        let desc = commands.describe_reg(encoded).unwrap().as_concrete();
+        assert_eq!(desc, layout.descriptor());
+        let (_, _) = commands.output(encoded).expect("Valid for output");
     }
 
-    /// After the transformation, shift by an x and y offset.
-    ///
-    /// This corresponds to a left-side multiplication of the transformation matrix.
-    pub fn shift(self, x: f32, y: f32) -> Self {
-        let post = RowMatrix::new([
-            1., 0., x,
-            0., 1., y,
-            0., 0., 1.,
-        ]);
+    let _ = commands.compile().expect("Could build command buffer");
+}
 
-        let post = post.multiply_right(RowMatrix::new(self.transformation).into());
-        let transformation = RowMatrix::from(post).into_inner();
+#[test]
+fn premultiply_program() {
+    let rgba = Descriptor::with_srgb_image(&image::DynamicImage::new_rgba8(32, 32));
+    assert_eq!(rgba.alpha, AlphaMode::Straight);
 
-        Affine {
-            transformation,
-            ..self
-        }
-    }
+    let mut commands = CommandBuffer::default();
+    let input = commands.input(rgba.clone()).unwrap();
+
+    let premultiplied = commands.premultiply(input).expect("Valid to premultiply");
+    let desc = commands
+        .describe_reg(premultiplied)
+        .unwrap()
+        .as_concrete()
+        .unwrap();
+    assert_eq!(desc.alpha, AlphaMode::Premultiplied);
+    assert_eq!(desc.texel, rgba.texel);
+
+    // Native sRGB textures can not store premultiplied texels, they are staged instead.
+    let image = ImageDescriptor::new(&desc).expect("Premultiplied texels are supported");
+    assert!(image.staging.is_some());
+
+    let again = commands.premultiply(premultiplied).unwrap();
+    assert_eq!(again, premultiplied);
+
+    let resized = commands
+        .resize(premultiplied, (16, 16))
+        .expect("Valid to resize");
+    let desc = commands
+        .describe_reg(resized)
+        .unwrap()
+        .as_concrete()
+        .unwrap();
+    assert_eq!(desc.size(), (16, 16));
+    assert_eq!(desc.alpha, AlphaMode::Premultiplied);
+
+    let straight = commands
+        .unpremultiply(resized)
+        .expect("Valid to unpremultiply");
+    let (_, desc) = commands.output(straight).expect("Valid for output");
+    assert_eq!(desc.as_concrete().unwrap().alpha, AlphaMode::Straight);
+
+    let _ = commands.compile().expect("Could build command buffer");
 }
 
-impl AffineSample {
-    fn as_paint_on_top(self) -> Result<PaintOnTopKind, CompileError> {
-        match self {
-            AffineSample::Nearest => Ok(PaintOnTopKind::Copy),
-            _ => Err(CompileError::NotYetImplemented),
-        }
-    }
+#[test]
+fn channels_program() {
+    let rgba = Descriptor::with_srgb_image(&image::DynamicImage::new_rgba8(32, 16));
+
+    let mut commands = CommandBuffer::default();
+    let input = commands.input(rgba.clone()).unwrap();
+
+    let channels = [
+        ColorChannel::R,
+        ColorChannel::G,
+        ColorChannel::B,
+        ColorChannel::Alpha,
+    ]
+    .iter()
+    .map(|&channel| commands.extract(input, channel))
+    .collect::<Result<Vec<_>, _>>()
+    .expect("Valid to extract");
+
+    let merged = commands
+        .merge(&channels, rgba.color.clone(), rgba.texel.clone())
+        .expect("Valid to merge");
+    let desc = commands
+        .describe_reg(merged)
+        .unwrap()
+        .as_concrete()
+        .unwrap();
+    assert_eq!(desc.size(), (32, 16));
+    assert_eq!(desc.texel, rgba.texel);
+    assert_eq!(desc.color, rgba.color);
+
+    assert!(commands
+        .merge(&channels[..3], rgba.color.clone(), rgba.texel.clone())
+        .is_err());
+    assert!(commands
+        .merge(&[input; 4], rgba.color.clone(), rgba.texel.clone())
+        .is_err());
+
+    let rgb = commands
+        .merge(
+            &channels[..3],
+            rgba.color.clone(),
+            Texel::new_u8(SampleParts::Rgb),
+        )
+        .expect("Valid to merge without alpha");
+
+    let swizzled = commands
+        .swizzle(
+            merged,
+            [
+                ChannelSource::Channel(ColorChannel::B),
+                ChannelSource::Channel(ColorChannel::G),
+                ChannelSource::Channel(ColorChannel::R),
+                ChannelSource::One,
+            ],
+        )
+        .expect("Valid to swizzle");
+    assert_eq!(
+        commands.describe_reg(swizzled).unwrap().as_concrete(),
+        Some(desc)
+    );
+
+    assert!(commands
+        .swizzle(
+            rgb,
+            [
+                ChannelSource::Channel(ColorChannel::Alpha),
+                ChannelSource::Zero,
+                ChannelSource::Zero,
+                ChannelSource::Zero,
+            ],
+        )
+        .is_err());
+
+    let (_, _) = commands.output(swizzled).expect("Valid for output");
+    let (_, _) = commands.output(rgb).expect("Valid for output");
+    let _ = commands.compile().expect("Could build command buffer");
 }
 
-impl Rectangle {
-    /// A rectangle at the origin with given width (x) and height (y).
-    pub fn with_width_height(width: u32, height: u32) -> Self {
-        Rectangle {
-            x: 0,
-            y: 0,
-            max_x: width,
-            max_y: height,
-        }
-    }
+#[test]
+fn alpha_program() {
+    let rgb = Descriptor::with_srgb_image(&image::DynamicImage::new_rgb8(32, 16));
+    let mask = Descriptor::with_texel(Texel::new_u8(SampleParts::Luma), 32, 16).unwrap();
 
-    /// A rectangle describing a complete buffer.
-    pub fn with_layout(buffer: &ByteLayout) -> Self {
-        Self::with_width_height(buffer.width, buffer.height)
-    }
+    let mut commands = CommandBuffer::default();
+    let input = commands.input(rgb.clone()).unwrap();
+    let mask = commands.input(mask).unwrap();
+
+    let constant = commands
+        .add_alpha(input, AlphaSource::Value(0.5))
+        .expect("Valid to add alpha");
+    let masked = commands
+        .add_alpha(input, AlphaSource::Mask(mask))
+        .expect("Valid to add alpha from mask");
+    let desc = commands
+        .describe_reg(masked)
+        .unwrap()
+        .as_concrete()
+        .unwrap();
+    assert_eq!(desc.texel, Texel::new_u8(SampleParts::RgbA));
+    assert_eq!(desc.color, rgb.color);
+
+    assert!(commands
+        .add_alpha(constant, AlphaSource::Value(1.0))
+        .is_err());
+    assert!(commands.add_alpha(input, AlphaSource::Mask(input)).is_err());
+
+    let flat = commands
+        .flatten(masked, [1.0, 1.0, 1.0])
+        .expect("Valid to flatten");
+    let desc = commands.describe_reg(flat).unwrap().as_concrete().unwrap();
+    assert_eq!(desc.texel, rgb.texel);
+    assert_eq!(desc.size(), (32, 16));
+
+    // Texels of three channels are staged in packed words.
+    let image = ImageDescriptor::new(&desc).expect("Opaque texels are supported");
+    assert!(image.staging.is_some());
+    let wide = Descriptor::with_texel(Texel::new_u16(SampleParts::Rgb), 32, 16).unwrap();
+    assert!(ImageDescriptor::new(&wide).is_ok());
+
+    let opaque = commands.flatten(input, [0.0; 3]).unwrap();
+    assert_eq!(opaque, input);
+
+    let (_, _) = commands.output(constant).expect("Valid for output");
+    let (_, _) = commands.render(flat).expect("Valid for render");
+    let (_, _) = commands.output(flat).expect("Valid for output");
+    let _ = commands.compile().expect("Could build command buffer");
+}
 
-    /// The apparent width.
-    pub fn width(self) -> u32 {
-        self.max_x.saturating_sub(self.x)
-    }
+#[test]
+fn orient_program() {
+    let rgba = Descriptor::with_srgb_image(&image::DynamicImage::new_rgba8(32, 16));
 
-    /// The apparent height.
-    pub fn height(self) -> u32 {
-        self.max_y.saturating_sub(self.y)
+    let mut commands = CommandBuffer::default();
+    let input = commands.input(rgba.clone()).unwrap();
+
+    let flipped = commands
+        .flip(input, Direction::Width)
+        .expect("Valid to flip");
+    let transposed = commands.transpose(flipped).expect("Valid to transpose");
+    let rotated = commands.rotate90(input, 3).expect("Valid to rotate");
+    assert_eq!(commands.rotate90(input, 4).unwrap(), input);
+
+    for &reg in &[transposed, rotated] {
+        let desc = commands.describe_reg(reg).unwrap().as_concrete().unwrap();
+        assert_eq!(desc.size(), (16, 32));
+        assert_eq!(desc.layout.row_stride, 64);
+        assert!(desc.is_consistent());
     }
 
-    /// Return true if this rectangle fully contains `other`.
-    pub fn contains(self, other: Self) -> bool {
-        self.x <= other.x && self.y <= other.y && {
-            // Offsets are surely non-wrapping.
-            let offset_x = other.x - self.x;
-            let offset_y = other.y - self.y;
-            let rel_width = self.width().checked_sub(offset_x);
-            let rel_height = self.height().checked_sub(offset_y);
-            rel_width >= Some(other.width()) && rel_height >= Some(other.height())
-        }
-    }
+    let (_, _) = commands.output(transposed).expect("Valid for output");
+    let (_, _) = commands.output(rotated).expect("Valid for output");
+    let _ = commands.compile().expect("Could build command buffer");
+}
 
-    /// Bring the rectangle into normalized form where minimum and maximum form a true interval.
-    #[must_use]
-    pub fn normalize(self) -> Rectangle {
-        Rectangle {
-            x: self.x,
-            y: self.y,
-            max_x: self.x + self.width(),
-            max_y: self.y + self.width(),
-        }
-    }
+#[test]
+fn crop_program() {
+    let rgba = Descriptor::with_srgb_image(&image::DynamicImage::new_rgba8(32, 16));
 
-    /// A rectangle that the overlap of the two.
-    #[must_use]
-    pub fn meet(self, other: Self) -> Rectangle {
-        Rectangle {
-            x: self.x.max(other.x),
-            y: self.y.max(other.y),
-            max_x: self.max_x.min(other.max_x),
-            max_y: self.max_y.min(other.max_y),
-        }
-    }
+    let mut commands = CommandBuffer::default();
+    let input = commands.input(rgba.clone()).unwrap();
 
-    /// The meet, relative to the coordinates of this rectangle.
-    #[must_use]
-    pub fn meet_in_local_coordinates(self, other: Self) -> Rectangle {
-        // Normalize to ensure that max_{x,y} is not less than {x,y}
-        let meet = self.normalize().meet(other);
-        Rectangle {
-            x: meet.x - self.x,
-            y: meet.y - self.y,
-            max_x: meet.max_x - self.x,
-            max_y: meet.max_y - self.y,
-        }
-    }
+    let rect = Rectangle {
+        x: 4,
+        y: 2,
+        max_x: 12,
+        max_y: 16,
+    };
 
-    /// A rectangle that contains both.
-    #[must_use]
-    pub fn join(self, other: Self) -> Rectangle {
-        Rectangle {
-            x: self.x.min(other.x),
-            y: self.y.min(other.y),
-            max_x: self.max_x.max(other.max_x),
-            max_y: self.max_y.max(other.max_y),
+    let cropped = commands.crop(input, rect).expect("Valid to crop");
+    let desc = commands
+        .describe_reg(cropped)
+        .unwrap()
+        .as_concrete()
+        .unwrap();
+    assert_eq!(desc.size(), (8, 14));
+    assert_eq!(desc.texel, rgba.texel);
+
+    let whole = Rectangle::with_layout(&rgba.layout);
+    assert_eq!(commands.crop(input, whole).unwrap(), input);
+
+    let outside = Rectangle { max_x: 33, ..rect };
+    assert!(commands.crop(input, outside).is_err());
+    let empty = Rectangle { max_x: 4, ..rect };
+    assert!(commands.crop(input, empty).is_err());
+
+    let inner = Rectangle {
+        x: 1,
+        y: 3,
+        max_x: 5,
+        max_y: 4,
+    };
+    let twice = commands.crop(cropped, inner).expect("Valid to crop");
+    match commands.ops[twice.0] {
+        Op::Unary {
+            src,
+            op: UnaryOp::Crop(region),
+            ..
+        } => {
+            assert_eq!(src, input);
+            assert_eq!(
+                region,
+                Rectangle {
+                    x: 5,
+                    y: 5,
+                    max_x: 9,
+                    max_y: 6,
+                }
+            );
         }
+        _ => panic!("Expected a crop of the input"),
     }
 
-    /// Remove border from all sides.
-    /// When the image is smaller than `border` in some dimension then the result is empty and
-    /// contained in the original image but otherwise unspecified.
-    #[must_use]
-    pub fn inset(self, border: u32) -> Self {
-        Rectangle {
-            x: self.x.saturating_add(border),
-            y: self.y.saturating_add(border),
-            max_x: self.max_x.saturating_sub(border),
-            max_y: self.max_y.saturating_sub(border),
-        }
-    }
-}
+    let (_, _) = commands.output(cropped).expect("Valid for output");
+    let _ = commands.compile().expect("Could build command buffer");
 
-impl From<&'_ ByteLayout> for Rectangle {
-    fn from(buffer: &ByteLayout) -> Rectangle {
-        Rectangle::with_width_height(buffer.width, buffer.height)
-    }
+    // Painting the crop into another image selects from the input instead of copying.
+    let mut commands = CommandBuffer::default();
+    let input = commands.input(rgba.clone()).unwrap();
+    let cropped = commands.crop(input, rect).expect("Valid to crop");
+    let below = commands
+        .crop(input, Rectangle::with_width_height(10, 16))
+        .unwrap();
+    let placement = Rectangle::with_width_height(8, 14);
+    let inscribed = commands.inscribe(below, placement, cropped).unwrap();
+    let (_, _) = commands.output(inscribed).expect("Valid for output");
+
+    let program = commands.compile().expect("Could build command buffer");
+    let selections: Vec<_> = program
+        .ops
+        .iter()
+        .filter_map(|high| match high {
+            High::Construct {
+                fn_: Initializer::PaintToSelection { selection, .. },
+                ..
+            } => Some(*selection),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(selections, [Rectangle::with_width_height(10, 16), rect]);
 }
 
-impl From<&'_ BufferLayout> for Rectangle {
-    fn from(buffer: &BufferLayout) -> Rectangle {
-        Rectangle::with_width_height(buffer.width(), buffer.height())
-    }
+#[test]
+fn pad_program() {
+    let rgba = Descriptor::with_srgb_image(&image::DynamicImage::new_rgba8(32, 16));
+
+    let mut commands = CommandBuffer::default();
+    let input = commands.input(rgba.clone()).unwrap();
+
+    let insets = Insets {
+        left: 1,
+        top: 2,
+        right: 3,
+        bottom: 4,
+    };
+
+    let padded = commands
+        .pad(input, insets, BorderMode::Constant([0.0, 0.0, 0.0, 1.0]))
+        .expect("Valid to pad");
+    let tiled = commands
+        .tile(input, (100, 40), BorderMode::Mirror)
+        .expect("Valid to tile");
+    assert!(commands.tile(input, (0, 40), BorderMode::Repeat).is_err());
+
+    let size = |commands: &CommandBuffer, reg| {
+        let desc = commands.describe_reg(reg).unwrap().as_concrete().unwrap();
+        desc.size()
+    };
+
+    assert_eq!(size(&commands, padded), (36, 22));
+    assert_eq!(size(&commands, tiled), (100, 40));
+
+    let derived = commands
+        .derivative_with_border(
+            input,
+            Derivative {
+                method: DerivativeMethod::Sobel,
+                direction: Direction::Width,
+            },
+            BorderMode::Constant([0.0; 4]),
+        )
+        .expect("Valid to derive");
+    assert_eq!(size(&commands, derived), (32, 16));
+
+    let (_, _) = commands.output(padded).expect("Valid for output");
+    let (_, _) = commands.output(tiled).expect("Valid for output");
+    let (_, _) = commands.output(derived).expect("Valid for output");
+    let _ = commands.compile().expect("Could build command buffer");
 }
 
-impl From<&'_ Descriptor> for Rectangle {
-    fn from(buffer: &Descriptor) -> Rectangle {
-        Rectangle::from(&buffer.layout)
+#[test]
+fn perspective_program() {
+    let rgba = Descriptor::with_srgb_image(&image::DynamicImage::new_rgba8(64, 48));
+    let document = Descriptor::with_srgb_image(&image::DynamicImage::new_rgba8(20, 30));
+
+    let mut commands = CommandBuffer::default();
+    let below = commands.input(rgba.clone()).unwrap();
+    let above = commands.input(document).unwrap();
+
+    let src_quad = [[0.0, 0.0], [20.0, 0.0], [20.0, 30.0], [0.0, 30.0]];
+    let dst_quad = [[10.0, 5.0], [50.0, 2.0], [60.0, 40.0], [4.0, 44.0]];
+
+    let warped = commands
+        .perspective(below, src_quad, dst_quad, above, AffineSample::BiLinear)
+        .expect("Valid to warp");
+    let desc = commands
+        .describe_reg(warped)
+        .unwrap()
+        .as_concrete()
+        .unwrap();
+    assert_eq!(desc.size(), (64, 48));
+
+    let collinear = [[0.0, 0.0], [10.0, 10.0], [20.0, 20.0], [0.0, 30.0]];
+    assert!(commands
+        .perspective(below, src_quad, collinear, above, AffineSample::Nearest)
+        .is_err());
+
+    // A strong perspective, where the matrix with its last entry fixed to one is negative on the
+    // whole quad.
+    let square = [[0.0, 0.0], [100.0, 0.0], [100.0, 100.0], [0.0, 100.0]];
+    let trapezoid = [[40.0, 60.0], [60.0, 60.0], [100.0, 150.0], [0.0, 150.0]];
+    let steep = commands
+        .perspective(below, square, trapezoid, above, AffineSample::Nearest)
+        .expect("Valid to warp");
+
+    let transform = commands
+        .ops
+        .iter()
+        .rev()
+        .find_map(|op| match op {
+            Op::Binary {
+                op: BinaryOp::Perspective(shader),
+                ..
+            } => Some(shader.transform),
+            _ => None,
+        })
+        .expect("Warped in perspective");
+
+    let [_, _, z] = transform.multiply_column([50.0, 100.0, 1.0]);
+    assert!(z > 0.0, "Behind the projection at the centroid: {}", z);
+    for (&[x, y], &[u, v]) in trapezoid.iter().zip(&square) {
+        let [px, py, z] = transform.multiply_column([x, y, 1.0]);
+        assert!(z > 0.0);
+        assert!((px / z - u).abs() < 1e-2 && (py / z - v).abs() < 1e-2);
     }
+
+    let (_, _) = commands.output(warped).expect("Valid for output");
+    let (_, _) = commands.output(steep).expect("Valid for output");
+    let _ = commands.compile().expect("Could build command buffer");
 }
 
-impl CommandError {
-    /// Indicates a very generic type error.
-    const TYPE_ERR: Self = CommandError {
-        inner: CommandErrorKind::GenericTypeError,
+#[test]
+fn warp_program() {
+    let rgba = Descriptor::with_srgb_image(&image::DynamicImage::new_rgba8(64, 48));
+    let map = Descriptor::with_texel(
+        Texel {
+            block: Block::Pixel,
+            bits: SampleBits::Float16x4,
+            parts: SampleParts::RgbA,
+        },
+        32,
+        24,
+    )
+    .unwrap();
+
+    let mut commands = CommandBuffer::default();
+    let input = commands.input(rgba.clone()).unwrap();
+    let map = commands.input(map).unwrap();
+
+    let lens = LensModel {
+        focal: [60.0, 60.0],
+        center: [32.0, 24.0],
+        radial: [-0.2, 0.05, 0.0],
+        tangential: [0.001, -0.002],
     };
 
-    /// Indicates a very generic other error.
-    /// E.g. the usage of a command requires an extension? Not quite sure yet.
-    const OTHER: Self = CommandError {
-        inner: CommandErrorKind::Other,
+    let undistorted = commands.undistort(input, lens).expect("Valid to undistort");
+    let displaced = commands
+        .displace(input, map, AffineSample::BiLinear)
+        .expect("Valid to displace");
+
+    let size = |commands: &CommandBuffer, reg| {
+        let desc = commands.describe_reg(reg).unwrap().as_concrete().unwrap();
+        desc.size()
     };
 
-    /// Specifies that a register reference was invalid.
-    const BAD_REGISTER: Self = Self::OTHER;
+    assert_eq!(size(&commands, undistorted), (64, 48));
+    assert_eq!(size(&commands, displaced), (32, 24));
 
-    /// Specifies that a register reference was invalid.
-    const INVALID_CALL: Self = Self::OTHER;
+    let luma = Descriptor::with_texel(Texel::new_u8(SampleParts::Luma), 32, 24).unwrap();
+    let luma = commands.input(luma).unwrap();
+    assert!(commands
+        .displace(input, luma, AffineSample::Nearest)
+        .is_err());
 
-    /// This has not yet been implemented, sorry.
-    ///
-    /// Errors of this kind will be removed over the course of bringing the crate to a first stable
-    /// release, this this will be removed. The method, and importantly its signature, are already
-    /// added for the purpose of exposition and documenting the intention.
-    const UNIMPLEMENTED: Self = CommandError {
-        inner: CommandErrorKind::Unimplemented,
+    let degenerate = LensModel {
+        focal: [0.0, 60.0],
+        ..lens
     };
+    assert!(commands.undistort(input, degenerate).is_err());
 
-    pub fn is_type_err(&self) -> bool {
-        matches!(
-            self.inner,
-            CommandErrorKind::GenericTypeError
-                | CommandErrorKind::ConflictingTypes(_, _)
-                | CommandErrorKind::BadDescriptor(_, _)
-        )
-    }
+    let (_, _) = commands.output(undistorted).expect("Valid for output");
+    let (_, _) = commands.output(displaced).expect("Valid for output");
+    let _ = commands.compile().expect("Could build command buffer");
 }
 
 #[test]
-fn rectangles() {
-    let small = Rectangle::with_width_height(2, 2);
-    let large = Rectangle::with_width_height(4, 4);
+fn affine_channels_program() {
+    let rgba = Descriptor::with_srgb_image(&image::DynamicImage::new_rgba8(64, 48));
 
-    assert_eq!(large, large.join(small));
-    assert_eq!(small, large.meet(small));
-    assert!(large.contains(small));
-    assert!(!small.contains(large));
+    let mut commands = CommandBuffer::default();
+    let input = commands.input(rgba.clone()).unwrap();
+
+    let around_center = |scale: f32| {
+        Affine::new(AffineSample::BiLinear)
+            .shift(-0.5, -0.5)
+            .scale(scale, scale)
+            .shift(0.5, 0.5)
+    };
+
+    let corrected = commands
+        .affine_channels(
+            input,
+            [
+                around_center(1.002),
+                Affine::new(AffineSample::Nearest),
+                around_center(0.998),
+            ],
+        )
+        .expect("Valid to transform channels");
+    let desc = commands
+        .describe_reg(corrected)
+        .unwrap()
+        .as_concrete()
+        .unwrap();
+    assert_eq!(desc.size(), (64, 48));
+    assert_eq!(desc.color, rgba.color);
+
+    let degenerate = Affine::new(AffineSample::Nearest).scale(0.0, 1.0);
+    assert!(commands
+        .affine_channels(input, [degenerate, degenerate, degenerate])
+        .is_err());
+
+    let (_, _) = commands.output(corrected).expect("Valid for output");
+    let _ = commands.compile().expect("Could build command buffer");
 }
 
 #[test]
-fn simple_program() {
-    use crate::pool::Pool;
-
-    const BACKGROUND: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/input/background.png");
-    const FOREGROUND: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/input/foreground.png");
+fn convolve_program() {
+    let rgba = Descriptor::with_srgb_image(&image::DynamicImage::new_rgba8(64, 48));
 
-    let mut pool = Pool::new();
     let mut commands = CommandBuffer::default();
+    let input = commands.input(rgba.clone()).unwrap();
+
+    let binomial = [1.0, 4.0, 6.0, 4.0, 1.0];
+    let gaussian = binomial
+        .iter()
+        .flat_map(|y| binomial.iter().map(move |x| x * y))
+        .collect();
+    let blur = Kernel {
+        normalization: KernelNormalization::Sum,
+        ..Kernel::new(5, 5, gaussian)
+    };
 
-    let background = image::open(BACKGROUND).expect("Background image opened");
-    let foreground = image::open(FOREGROUND).expect("Background image opened");
-    let expected = ByteLayout::from(&background);
+    let (row, column) = blur.separate(&blur.normalized_weights()).unwrap();
+    assert_eq!(row.len(), 5);
+    assert_eq!(column.len(), 5);
+    let sum: f32 = column.iter().sum::<f32>() * row.iter().sum::<f32>();
+    assert!((sum - 1.0).abs() < 1e-5);
 
-    let placement = Rectangle {
-        x: 0,
-        y: 0,
-        max_x: foreground.width(),
-        max_y: foreground.height(),
+    let blurred = commands.convolve(input, blur).expect("Valid to convolve");
+
+    let sharpen = Kernel {
+        channels: KernelChannels::Luma,
+        border: BorderMode::Mirror,
+        ..Kernel::new(3, 3, vec![0., -1., 0., -1., 5., -1., 0., -1., 0.])
     };
+    assert!(sharpen.separate(&sharpen.weights).is_none());
+    let sharpened = commands
+        .convolve(input, sharpen)
+        .expect("Valid to convolve");
+
+    let emboss = Kernel {
+        border: BorderMode::Constant([0.5, 0.5, 0.5, 1.0]),
+        ..Kernel::new(3, 1, vec![-1.0, 0.0, 1.0])
+    };
+    let embossed = commands.convolve(input, emboss).expect("Valid to convolve");
 
-    let background = pool.insert_srgb(&background);
-    let background = commands.input_from(background.into());
+    for &reg in &[blurred, sharpened, embossed] {
+        let desc = commands.describe_reg(reg).unwrap().as_concrete().unwrap();
+        assert_eq!(desc.size(), (64, 48));
+    }
 
-    let foreground = pool.insert_srgb(&foreground);
-    let foreground = commands.input_from(foreground.into());
+    assert!(commands
+        .convolve(input, Kernel::new(4, 3, vec![0.0; 12]))
+        .is_err());
+    assert!(commands
+        .convolve(input, Kernel::new(33, 1, vec![0.0; 33]))
+        .is_err());
+    assert!(commands
+        .convolve(input, Kernel::new(3, 3, vec![0.0; 8]))
+        .is_err());
+
+    let (_, _) = commands.output(blurred).expect("Valid for output");
+    let (_, _) = commands.output(sharpened).expect("Valid for output");
+    let (_, _) = commands.output(embossed).expect("Valid for output");
+    let _ = commands.compile().expect("Could build command buffer");
+}
 
-    let result = commands
-        .inscribe(background, placement, foreground)
-        .expect("Valid to inscribe");
-    let (_, outformat) = commands.output(result).expect("Valid for output");
+#[test]
+fn morphology_program() {
+    let luma = Descriptor::with_srgb_image(&image::DynamicImage::new_luma8(64, 48));
+    let rgba = Descriptor::with_srgb_image(&image::DynamicImage::new_rgba8(64, 48));
+
+    let mut commands = CommandBuffer::default();
+    let mask = commands.input(luma).unwrap();
+    let image = commands.input(rgba).unwrap();
+
+    // A small square is sampled directly, one pass for each axis.
+    let before = commands.ops.len();
+    let eroded = commands
+        .erode(mask, &StructuringElement::Square(2), 1)
+        .expect("Valid to erode");
+    assert_eq!(commands.ops.len() - before, 2);
+
+    // A long line is scanned in blocks of 41 pixels, in six steps each way and a merge.
+    let before = commands.ops.len();
+    let dilated = commands
+        .dilate(image, &StructuringElement::Rectangle(20, 0), 2)
+        .expect("Valid to dilate");
+    assert_eq!(commands.ops.len() - before, 26);
+
+    let disk = StructuringElement::Disk(3);
+    let cross = StructuringElement::Cross(1);
+    let custom = StructuringElement::Custom {
+        width: 3,
+        height: 1,
+        mask: vec![true, false, true],
+    };
+
+    let opened = commands.open(mask, &disk, 1).expect("Valid to open");
+    let closed = commands.close(image, &cross, 3).expect("Valid to close");
+    let top_hat = commands.top_hat(mask, &custom, 1).expect("Valid top-hat");
+    let black_hat = commands
+        .black_hat(image, &disk, 1)
+        .expect("Valid black-hat");
+    let gradient = commands
+        .morphological_gradient(image, &StructuringElement::Square(9), 1)
+        .expect("Valid gradient");
+
+    let results = [
+        eroded, dilated, opened, closed, top_hat, black_hat, gradient,
+    ];
+
+    for &reg in &results {
+        let desc = commands.describe_reg(reg).unwrap().as_concrete().unwrap();
+        assert_eq!(desc.size(), (64, 48));
+    }
+
+    assert!(commands.erode(mask, &disk, 0).is_err());
+    assert!(commands
+        .erode(mask, &StructuringElement::Disk(16), 1)
+        .is_err());
+    assert!(commands
+        .erode(mask, &StructuringElement::Square(u32::MAX), 1)
+        .is_err());
+    let empty = StructuringElement::Custom {
+        width: 3,
+        height: 3,
+        mask: vec![false; 9],
+    };
+    assert!(commands.dilate(mask, &empty, 1).is_err());
+    let even = StructuringElement::Custom {
+        width: 2,
+        height: 1,
+        mask: vec![true; 2],
+    };
+    assert!(commands.dilate(mask, &even, 1).is_err());
+
+    for &reg in &results {
+        let (_, _) = commands.output(reg).expect("Valid for output");
+    }
 
     let _ = commands.compile().expect("Could build command buffer");
-    assert_eq!(outformat.as_concrete().map(|x| x.layout), Some(expected));
 }
@@ -0,0 +1,246 @@
+//! Parsing of the `.cube` lookup table format.
+//!
+//! The format was specified by Adobe, and extended by Resolve with a one-dimensional shaper that
+//! precedes the cube in the same file.
+use super::{Lut3d, Lut3dInterpolation, Lut3dShaper};
+
+/// An error parsing a `.cube` file.
+#[derive(Debug)]
+pub struct CubeError {
+    /// The line at which the error was detected, starting at `1`.
+    line: usize,
+    kind: CubeErrorKind,
+}
+
+#[derive(Debug)]
+enum CubeErrorKind {
+    InvalidNumber,
+    WrongNumberOfValues { expected: usize, found: usize },
+    DuplicateKeyword(&'static str),
+    KeywordAfterData,
+    InvalidSize,
+    InvalidDomain,
+    MissingSize,
+    WrongNumberOfEntries { expected: usize, found: usize },
+}
+
+impl core::fmt::Display for CubeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid cube file at line {}: {}", self.line, self.kind)
+    }
+}
+
+impl core::fmt::Display for CubeErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CubeErrorKind::InvalidNumber => write!(f, "invalid number"),
+            CubeErrorKind::WrongNumberOfValues { expected, found } => {
+                write!(f, "expected {} values, found {}", expected, found)
+            }
+            CubeErrorKind::DuplicateKeyword(keyword) => write!(f, "duplicate {}", keyword),
+            CubeErrorKind::KeywordAfterData => write!(f, "keyword after table data"),
+            CubeErrorKind::InvalidSize => write!(f, "invalid table size"),
+            CubeErrorKind::InvalidDomain => write!(f, "invalid domain"),
+            CubeErrorKind::MissingSize => write!(f, "missing table size"),
+            CubeErrorKind::WrongNumberOfEntries { expected, found } => {
+                write!(f, "expected {} table entries, found {}", expected, found)
+            }
+        }
+    }
+}
+
+/// Keywords that can appear at most once.
+#[derive(Default)]
+struct Header {
+    size_1d: Option<u32>,
+    size_3d: Option<u32>,
+    domain_min: Option<[f32; 3]>,
+    domain_max: Option<[f32; 3]>,
+    range_1d: Option<[f32; 2]>,
+    range_3d: Option<[f32; 2]>,
+}
+
+impl Lut3d {
+    /// Parse a lookup table from the text of a `.cube` file.
+    ///
+    /// This supports `DOMAIN_MIN` and `DOMAIN_MAX` of the Adobe specification, which apply to the
+    /// first table in the file, as well as a shaper with `LUT_1D_SIZE`, `LUT_1D_INPUT_RANGE` and
+    /// `LUT_3D_INPUT_RANGE`. A file with only a one-dimensional table is represented by its shaper
+    /// and an identity cube. Unknown keywords, such as `TITLE`, are ignored. While the format
+    /// allows cubes of up to 256 samples per axis, sizes above [`Lut3d::MAX_SIZE`] are rejected
+    /// as they could not be applied. Likewise shapers are limited to [`Lut3dShaper::MAX_SIZE`]
+    /// instead of `65536` samples, rather than resampling them and losing detail of their curve.
+    ///
+    /// The result uses tetrahedral interpolation.
+    pub fn from_cube(text: &str) -> Result<Self, CubeError> {
+        let mut header = Header::default();
+        let mut entries: Vec<[f32; 3]> = vec![];
+        let mut line = 0;
+
+        for (idx, content) in text.lines().enumerate() {
+            line = idx + 1;
+            let err = |kind| CubeError { line, kind };
+
+            let content = content.trim();
+            if content.is_empty() || content.starts_with('#') {
+                continue;
+            }
+
+            let mut words = content.split_whitespace();
+            let keyword = match words.next() {
+                Some(keyword) => keyword,
+                None => continue,
+            };
+
+            let starts_numeric = keyword
+                .chars()
+                .next()
+                .is_some_and(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.'));
+
+            if starts_numeric {
+                let [r, g, b] = parse_values(content.split_whitespace()).map_err(err)?;
+                entries.push([r, g, b]);
+                continue;
+            }
+
+            if !entries.is_empty() {
+                return Err(err(CubeErrorKind::KeywordAfterData));
+            }
+
+            match keyword {
+                "LUT_1D_SIZE" => {
+                    let [size]: [f32; 1] = parse_values(words).map_err(err)?;
+                    let size = parse_size(size, Lut3dShaper::MAX_SIZE).map_err(err)?;
+                    set_once(&mut header.size_1d, size, "LUT_1D_SIZE").map_err(err)?;
+                }
+                "LUT_3D_SIZE" => {
+                    let [size]: [f32; 1] = parse_values(words).map_err(err)?;
+                    let size = parse_size(size, Self::MAX_SIZE).map_err(err)?;
+                    set_once(&mut header.size_3d, size, "LUT_3D_SIZE").map_err(err)?;
+                }
+                "DOMAIN_MIN" => {
+                    let min = parse_values(words).map_err(err)?;
+                    set_once(&mut header.domain_min, min, "DOMAIN_MIN").map_err(err)?;
+                }
+                "DOMAIN_MAX" => {
+                    let max = parse_values(words).map_err(err)?;
+                    set_once(&mut header.domain_max, max, "DOMAIN_MAX").map_err(err)?;
+                }
+                "LUT_1D_INPUT_RANGE" => {
+                    let range = parse_values(words).map_err(err)?;
+                    set_once(&mut header.range_1d, range, "LUT_1D_INPUT_RANGE").map_err(err)?;
+                }
+                "LUT_3D_INPUT_RANGE" => {
+                    let range = parse_values(words).map_err(err)?;
+                    set_once(&mut header.range_3d, range, "LUT_3D_INPUT_RANGE").map_err(err)?;
+                }
+                _ => {}
+            }
+        }
+
+        let err = |kind| CubeError { line, kind };
+
+        let size_1d = header.size_1d.map_or(0, |size| size as usize);
+        let size_3d = header.size_3d.map_or(0, |size| size as usize);
+
+        if header.size_1d.is_none() && header.size_3d.is_none() {
+            return Err(err(CubeErrorKind::MissingSize));
+        }
+
+        let expected = size_1d + size_3d.pow(3);
+        if entries.len() != expected {
+            return Err(err(CubeErrorKind::WrongNumberOfEntries {
+                expected,
+                found: entries.len(),
+            }));
+        }
+
+        // The Adobe domain applies to whichever table comes first.
+        let domain = [
+            header.domain_min.unwrap_or([0.0; 3]),
+            header.domain_max.unwrap_or([1.0; 3]),
+        ];
+
+        let range = |range: Option<[f32; 2]>, default: [[f32; 3]; 2]| match range {
+            Some([min, max]) => [[min; 3], [max; 3]],
+            None => default,
+        };
+
+        let shaper = header.size_1d.map(|_| {
+            let [domain_min, domain_max] = range(header.range_1d, domain);
+            Lut3dShaper {
+                table: entries[..size_1d].to_vec(),
+                domain_min,
+                domain_max,
+            }
+        });
+
+        let lut = match header.size_3d {
+            Some(size) => {
+                let default = if shaper.is_some() {
+                    [[0.0; 3], [1.0; 3]]
+                } else {
+                    domain
+                };
+
+                let [domain_min, domain_max] = range(header.range_3d, default);
+                Lut3d {
+                    size,
+                    table: entries[size_1d..].to_vec(),
+                    domain_min,
+                    domain_max,
+                    shaper,
+                    interpolation: Lut3dInterpolation::Tetrahedral,
+                }
+            }
+            None => Lut3d {
+                shaper,
+                ..Lut3d::identity(2)
+            },
+        };
+
+        if !lut.is_valid() {
+            return Err(err(CubeErrorKind::InvalidDomain));
+        }
+
+        Ok(lut)
+    }
+}
+
+fn parse_values<'lt, const N: usize>(
+    words: impl Iterator<Item = &'lt str>,
+) -> Result<[f32; N], CubeErrorKind> {
+    let mut values = [0.0; N];
+    let mut found = 0;
+
+    for word in words {
+        if let Some(value) = values.get_mut(found) {
+            *value = word.parse().map_err(|_| CubeErrorKind::InvalidNumber)?;
+        }
+
+        found += 1;
+    }
+
+    if found != N {
+        return Err(CubeErrorKind::WrongNumberOfValues { expected: N, found });
+    }
+
+    Ok(values)
+}
+
+fn parse_size(value: f32, max: u32) -> Result<u32, CubeErrorKind> {
+    if value.fract() != 0.0 || !(2.0..=max as f32).contains(&value) {
+        return Err(CubeErrorKind::InvalidSize);
+    }
+
+    Ok(value as u32)
+}
+
+fn set_once<T>(slot: &mut Option<T>, value: T, keyword: &'static str) -> Result<(), CubeErrorKind> {
+    if slot.is_some() {
+        return Err(CubeErrorKind::DuplicateKeyword(keyword));
+    }
+
+    *slot = Some(value);
+    Ok(())
+}
@@ -632,9 +632,13 @@ impl PoolImage<'_> {
         let layout = self.layout();
         let descriptor = &self.image.descriptor;
 
-        let image = Descriptor::as_image_allocator(&descriptor.texel)?;
-        let image = image(layout.width(), layout.height(), data)?;
-        Some(image)
+        let row_stride = layout.as_row_layout().row_stride;
+        Descriptor::to_image(
+            &descriptor.texel,
+            (layout.width(), layout.height()),
+            row_stride,
+            data,
+        )
     }
 
     pub fn key(&self) -> PoolKey {
@@ -688,6 +692,17 @@ impl PoolImageMut<'_> {
         self.image.data.as_bytes()
     }
 
+    /// Convert a host allocated buffer into an image of the `image` crate.
+    ///
+    /// See [`PoolImage::to_image`].
+    pub fn to_image(&self) -> Option<image::DynamicImage> {
+        let data = self.as_bytes()?;
+        let layout = self.layout();
+        let texel = &self.image.descriptor.texel;
+        let row_stride = layout.as_row_layout().row_stride;
+        Descriptor::to_image(texel, (layout.width(), layout.height()), row_stride, data)
+    }
+
     /// View the buffer as bytes.
     ///
     /// This return `Some` if the image is a host allocated buffer and `None` otherwise.
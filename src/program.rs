@@ -7,7 +7,7 @@ use std::collections::HashMap;
 use std::sync::Arc;
 
 use crate::buffer::{
-    Block, ByteLayout, Color, Descriptor, SampleBits, SampleParts, Texel, Transfer,
+    AlphaMode, Block, ByteLayout, Color, Descriptor, SampleBits, SampleParts, Texel, Transfer,
 };
 use crate::color_matrix::RowMatrix;
 use crate::command::{Rectangle, Register};
@@ -105,6 +105,8 @@ pub(crate) enum High {
     Done(Register),
     /// Copy binary data from a buffer to another.
     Copy { src: Register, dst: Register },
+    /// Fill a register with texel data prepared by the command buffer.
+    Upload { dst: Register, data: TexelData },
     /// Push one high-level function marker.
     StackPush(Frame),
     /// Pop a high-level function marker.
@@ -116,6 +118,13 @@ pub(crate) enum High {
     },
 }
 
+/// Texel data of an image, tightly packed by rows.
+///
+/// This is embedded into the program, for example for lookup tables that are too large to pass
+/// them as uniform data of a shader.
+#[derive(Clone)]
+pub(crate) struct TexelData(pub(crate) Arc<[u8]>);
+
 /// The target image texture of a paint operation (pipeline).
 #[derive(Clone, Copy, Debug)]
 pub(crate) enum Target {
@@ -545,6 +554,7 @@ pub(crate) struct RenderPipelineKey {
     pub fragment_module: ShaderDescriptorKey,
     pub fragment_entry: &'static str,
     pub primitive: PrimitiveState,
+    pub format: wgpu::TextureFormat,
 }
 
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
@@ -691,6 +701,40 @@ impl ImageDescriptor {
         let size = validate_size(&descriptor.layout)
             .ok_or_else(|| LaunchError::InternalCommandError(line!()))?;
         let mut staging = None;
+        // Native formats can not divide by alpha when loading, premultiplied texels are staged.
+        let straight = descriptor.alpha == AlphaMode::Straight;
+        let premultiplied = !straight;
+
+        if let Some(planes) = descriptor.layout.planes {
+            if !descriptor.is_consistent() {
+                return Err(LaunchError::InternalCommandError(line!()));
+            }
+
+            // Chroma is upsampled to Y'CbCr at the resolution of luma, stored as it is.
+            let parameter = shaders::stage::XyzParameter {
+                transfer: shaders::stage::Transfer::Rgb(Transfer::Linear),
+                bits: descriptor.texel.bits,
+                parts: descriptor.texel.parts,
+                premultiplied: false,
+                planes: Some(shaders::stage::PlanarParameter {
+                    planes,
+                    size: descriptor.size(),
+                }),
+            };
+
+            let stage_kind = parameter
+                .stage_kind()
+                .ok_or_else(|| LaunchError::InternalCommandError(line!()))?;
+
+            return Ok(ImageDescriptor {
+                format: parameter.linear_format(),
+                staging: Some(StagingDescriptor {
+                    stage_kind,
+                    parameter,
+                }),
+                size,
+            });
+        }
 
         let format = match (&descriptor.texel, &descriptor.color) {
             (
@@ -703,7 +747,7 @@ impl ImageDescriptor {
                     transfer: Transfer::Srgb,
                     ..
                 },
-            ) => wgpu::TextureFormat::Rgba8UnormSrgb,
+            ) if straight => wgpu::TextureFormat::Rgba8UnormSrgb,
             (
                 Texel {
                     block: Block::Pixel,
@@ -714,7 +758,7 @@ impl ImageDescriptor {
                     transfer: Transfer::Srgb,
                     ..
                 },
-            ) => wgpu::TextureFormat::Bgra8UnormSrgb,
+            ) if straight => wgpu::TextureFormat::Bgra8UnormSrgb,
             (
                 Texel {
                     block: Block::Pixel,
@@ -725,7 +769,7 @@ impl ImageDescriptor {
                     transfer: Transfer::Linear,
                     ..
                 },
-            ) => wgpu::TextureFormat::Rgba8Unorm,
+            ) if straight => wgpu::TextureFormat::Rgba8Unorm,
             (
                 Texel {
                     block: Block::Pixel,
@@ -736,7 +780,21 @@ impl ImageDescriptor {
                     transfer: Transfer::Linear,
                     ..
                 },
-            ) => wgpu::TextureFormat::Bgra8Unorm,
+            ) if straight => wgpu::TextureFormat::Bgra8Unorm,
+            (
+                Texel {
+                    block: Block::Pixel,
+                    bits: SampleBits::Float16x4,
+                    parts: SampleParts::RgbA,
+                },
+                Color::Rgb {
+                    transfer: Transfer::Linear,
+                    ..
+                }
+                | Color::Scalars {
+                    transfer: Transfer::Linear,
+                },
+            ) if straight => wgpu::TextureFormat::Rgba16Float,
             (
                 Texel {
                     block: Block::Pixel,
@@ -753,10 +811,18 @@ impl ImageDescriptor {
                 },
                 Color::Scalars { transfer, .. },
             ) => {
+                // Y'CbCr is computed from the encoded values, they are stored as they are.
+                let transfer = match *parts {
+                    SampleParts::Yuv => Transfer::Linear,
+                    _ => *transfer,
+                };
+
                 let parameter = shaders::stage::XyzParameter {
-                    transfer: shaders::stage::Transfer::Rgb(*transfer),
+                    transfer: shaders::stage::Transfer::Rgb(transfer),
                     bits: *bits,
                     parts: *parts,
+                    premultiplied,
+                    planes: None,
                 };
 
                 let result = parameter.linear_format();
@@ -791,6 +857,8 @@ impl ImageDescriptor {
                     },
                     parts: SampleParts::LchA,
                     bits: *bits,
+                    premultiplied,
+                    planes: None,
                 };
 
                 // FIXME: duplicate code.
@@ -824,6 +892,8 @@ impl ImageDescriptor {
                     },
                     parts: SampleParts::LchA,
                     bits: *bits,
+                    premultiplied,
+                    planes: None,
                 };
 
                 let result = parameter.linear_format();
@@ -859,7 +929,9 @@ impl ImageDescriptor {
 
     pub(crate) fn to_staging_texture(&self) -> Option<TextureDescriptor> {
         self.staging.map(|staging| TextureDescriptor {
-            size: staging.stage_kind.stage_size(self.size),
+            size: staging
+                .stage_kind
+                .stage_size(staging.parameter.stored_size(self.size)),
             format: staging.stage_kind.texture_format(),
             usage: TextureUsage::Staging,
         })
@@ -890,7 +962,8 @@ pub(crate) struct TextureViewDescriptor {
 }
 
 // FIXME: useless at the moment of writing, for our purposes.
-// For reinterpreting parts of a texture.
+// For reinterpreting parts of a texture. Views only select mip levels and array layers, never a
+// rectangle within a layer, so crops are still painted into a new texture.
 // Ignores format (due to library restrictions), cube, aspect, mip level.
 // pub(crate) struct TextureViewDescriptor;
 
@@ -970,6 +1043,13 @@ impl ImageBufferPlan {
         assigned
     }
 
+    /// Assign the texture of an earlier register to the next one, without allocating another.
+    pub(crate) fn alias_for(&mut self, Register(src): Register) -> ImageBufferAssignment {
+        let assigned = self.by_register[src];
+        self.by_register.push(assigned);
+        assigned
+    }
+
     pub(crate) fn get(&self, idx: Register) -> Result<ImageBufferAssignment, LaunchError> {
         self.by_register
             .get(idx.0)
@@ -1387,6 +1467,10 @@ impl Program {
 
                     encoder.copy_buffer_to_staging(*dst)?;
                 }
+                High::Upload { dst, data } => {
+                    encoder.copy_data_to_buffer(*dst, &data.0)?;
+                    encoder.copy_buffer_to_staging(*dst)?;
+                }
                 High::StackPush(frame) => {
                     encoder.push(Low::StackFrame(run::Frame {
                         name: frame.name.clone(),
@@ -1679,6 +1763,13 @@ impl From<Vec<u8>> for BufferInitContent {
     }
 }
 
+impl core::fmt::Debug for TexelData {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        // The data itself is not very informative, and potentially large.
+        write!(f, "TexelData({} bytes)", self.0.len())
+    }
+}
+
 impl QuadTarget {
     pub(crate) fn affine(&self, transform: &RowMatrix) -> Self {
         let [a, b, c, d] = self.to_screenspace_coords(&Rectangle::with_width_height(1, 1));
@@ -214,6 +214,8 @@ struct SimpleRenderPipelineDescriptor<'data> {
     vertex_bind_data: BufferBind<'data>,
     /// Texture for (set 1, binding 0)
     fragment_texture: TextureBind,
+    /// How the sampler of set 1 addresses textures outside their bounds.
+    address_mode: wgpu::AddressMode,
     /// Texture for (set 2, binding 0)
     fragment_bind_data: BufferBind<'data>,
     /// The vertex shader to use.
@@ -477,10 +479,15 @@ impl<I: ExtendOne<Low>> Encoder<I> {
             .to_aligned()
             .ok_or_else(|| LaunchError::InternalCommandError(line!()))?;
 
+        // The buffer holds the stored bytes, which for planes differ from the texture.
+        let stored = descriptor
+            .to_stored()
+            .ok_or_else(|| LaunchError::InternalCommandError(line!()))?;
+
         let buffer_layout = CanvasLayout::with_row_layout(&RowLayoutDescription {
-            texel: descriptor.texel.clone(),
-            width: texture_format.size.0.get(),
-            height: texture_format.size.1.get(),
+            texel: stored.texel.clone(),
+            width: byte_layout.width,
+            height: byte_layout.height,
             row_stride: byte_layout.row_stride,
         })
         .expect("valid layout");
@@ -635,8 +642,7 @@ impl<I: ExtendOne<Low>> Encoder<I> {
         let write_event = self.make_event();
         self.input_map.insert(idx, source_image);
 
-        let descriptor = &self.buffer_plan.texture[regmap.reg_texture.0];
-        let size = descriptor.size();
+        let size = (regmap.byte_layout.width, regmap.byte_layout.height);
 
         // See below, required for direct buffer-to-buffer copy.
         let sizeu64 = regmap.buffer_layout.u64_len();
@@ -681,6 +687,53 @@ impl<I: ExtendOne<Low>> Encoder<I> {
         Ok(())
     }
 
+    /// Copy texel data embedded in the program to the internal memory visible buffer.
+    ///
+    /// The data must be tightly packed by rows, we pad each row to the alignment of the buffer.
+    pub(crate) fn copy_data_to_buffer(
+        &mut self,
+        idx: Register,
+        data: &[u8],
+    ) -> Result<(), LaunchError> {
+        let regmap = self.allocate_register(idx)?.clone();
+        let descriptor = &self.buffer_plan.texture[regmap.reg_texture.0];
+
+        let row_len = descriptor.layout.width as usize * descriptor.layout.texel_stride as usize;
+        let height = descriptor.layout.height as usize;
+        let row_stride = regmap.byte_layout.row_stride as usize;
+        let size = regmap.buffer_layout.u64_len();
+
+        if row_len.checked_mul(height) != Some(data.len()) || row_len > row_stride {
+            return Err(LaunchError::InternalCommandError(line!()));
+        }
+
+        let mut padded = vec![0u8; size as usize];
+        for (row, target) in data
+            .chunks_exact(row_len)
+            .zip(padded.chunks_exact_mut(row_stride))
+        {
+            target[..row_len].copy_from_slice(row);
+        }
+
+        let source_buffer = DeviceBuffer(self.buffers);
+        let content = self.ingest_data(&padded);
+        self.push(Low::BufferInit(BufferDescriptorInit {
+            content,
+            usage: BufferUsage::DataIn,
+        }))?;
+
+        self.push(Low::BeginCommands)?;
+        self.push(Low::CopyBufferToBuffer {
+            source_buffer,
+            size,
+            target_buffer: regmap.buffer,
+        })?;
+        self.push(Low::EndCommands)?;
+        self.plan_run_top_command();
+
+        Ok(())
+    }
+
     /// Copy from memory visible buffer to the texture.
     pub(crate) fn copy_buffer_to_staging(&mut self, idx: Register) -> Result<(), LaunchError> {
         let regmap = self.allocate_register(idx)?.clone();
@@ -847,7 +900,7 @@ impl<I: ExtendOne<Low>> Encoder<I> {
         let target_image = self.ingest_image_data(dst)?;
         self.output_map.insert(dst, target_image);
 
-        let size = self.buffer_plan.get_info(idx)?.descriptor.size();
+        let size = (regmap.byte_layout.width, regmap.byte_layout.height);
         let sizeu64 = regmap.buffer_layout.u64_len();
 
         let source_buffer = regmap.map_read.unwrap_or(regmap.buffer);
@@ -931,6 +984,7 @@ impl<I: ExtendOne<Low>> Encoder<I> {
                     data: bytemuck::cast_slice(&Self::FULL_VERTEX_BUFFER[..]),
                 },
                 fragment_texture: TextureBind::Textures(arguments as usize),
+                address_mode: shader.address_mode(),
                 fragment_bind_data,
                 vertex: ShaderBind::ShaderMain(vertex),
                 fragment: ShaderBind::ShaderMain(fragment),
@@ -1299,6 +1353,7 @@ impl<I: ExtendOne<Low>> Encoder<I> {
                     fragment_module: f.clone(),
                     fragment_entry: fragment_entry_point,
                     primitive: PrimitiveState::TriangleStrip,
+                    format,
                 };
 
                 self.pipeline_by_op.insert(self.instruction_pointer, key);
@@ -1351,14 +1406,18 @@ impl<I: ExtendOne<Low>> Encoder<I> {
             })
     }
 
-    fn make_bind_group_sampled_texture(&mut self, count: usize) -> Result<usize, LaunchError> {
+    fn make_bind_group_sampled_texture(
+        &mut self,
+        count: usize,
+        address_mode: wgpu::AddressMode,
+    ) -> Result<usize, LaunchError> {
         let start_of_operands = match self.operands.len().checked_sub(count) {
             None => return Err(LaunchError::InternalCommandError(line!())),
             Some(i) => i,
         };
 
         let sampler = self.make_sampler(SamplerDescriptor {
-            address_mode: wgpu::AddressMode::default(),
+            address_mode,
             border_color: None,
             resize_filter: wgpu::FilterMode::Nearest,
         });
@@ -1508,7 +1567,7 @@ impl<I: ExtendOne<Low>> Encoder<I> {
         let group = match &descriptor.fragment_texture {
             TextureBind::Textures(0) => None,
             &TextureBind::Textures(count) => {
-                let group = self.make_bind_group_sampled_texture(count)?;
+                let group = self.make_bind_group_sampled_texture(count, descriptor.address_mode)?;
                 // eprintln!("Using Texture {:?} as group {:?}", texture, group);
                 Some(group)
             }
@@ -1650,6 +1709,7 @@ impl<I: ExtendOne<Low>> Encoder<I> {
                         data: bytemuck::cast_slice(&buffer[..]),
                     },
                     fragment_texture: TextureBind::Textures(1),
+                    address_mode: shader.address_mode(),
                     fragment_bind_data: BufferBind::None,
                     vertex: ShaderBind::ShaderMain(vertex),
                     fragment: ShaderBind::ShaderMain(fragment),
@@ -1677,6 +1737,7 @@ impl<I: ExtendOne<Low>> Encoder<I> {
                         data: bytemuck::cast_slice(&Self::FULL_VERTEX_BUFFER[..]),
                     },
                     fragment_texture: TextureBind::Textures(arguments as usize),
+                    address_mode: shader.address_mode(),
                     fragment_bind_data,
                     vertex: ShaderBind::ShaderMain(vertex),
                     fragment: ShaderBind::ShaderMain(fragment),
@@ -1714,6 +1775,7 @@ impl<I: ExtendOne<Low>> Encoder<I> {
                         group,
                         layout,
                     },
+                    address_mode: wgpu::AddressMode::ClampToEdge,
                     fragment_bind_data: BufferBind::Set {
                         data: bytemuck::cast_slice(&buffer[..]),
                     },
@@ -1765,6 +1827,7 @@ impl<I: ExtendOne<Low>> Encoder<I> {
                         group,
                         layout,
                     },
+                    address_mode: wgpu::AddressMode::ClampToEdge,
                     fragment_bind_data: BufferBind::Set {
                         data: bytemuck::cast_slice(&buffer[..]),
                     },
@@ -13,16 +13,21 @@ float weighted_sum(mat3x3 w, mat3x3 c) {
     return dot(matrixCompMult(c, w) * vec3(1.0), vec3(1.0));
 }
 
+// The derivative is taken of colors premultiplied by alpha.
+vec4 premultiplied(vec4 color) {
+    return vec4(color.rgb * color.a, color.a);
+}
+
 void main() {
-    vec4 p00 = textureOffset(sampler2D(in_texture, texture_sampler), uv, ivec2(-1, -1));
-    vec4 p01 = textureOffset(sampler2D(in_texture, texture_sampler), uv, ivec2(-1, 0));
-    vec4 p02 = textureOffset(sampler2D(in_texture, texture_sampler), uv, ivec2(-1, 1));
-    vec4 p10 = textureOffset(sampler2D(in_texture, texture_sampler), uv, ivec2(0, -1));
-    vec4 p11 = textureOffset(sampler2D(in_texture, texture_sampler), uv, ivec2(0, 0));
-    vec4 p12 = textureOffset(sampler2D(in_texture, texture_sampler), uv, ivec2(0, 1));
-    vec4 p20 = textureOffset(sampler2D(in_texture, texture_sampler), uv, ivec2(1, -1));
-    vec4 p21 = textureOffset(sampler2D(in_texture, texture_sampler), uv, ivec2(1, 0));
-    vec4 p22 = textureOffset(sampler2D(in_texture, texture_sampler), uv, ivec2(1, 1));
+    vec4 p00 = premultiplied(textureOffset(sampler2D(in_texture, texture_sampler), uv, ivec2(-1, -1)));
+    vec4 p01 = premultiplied(textureOffset(sampler2D(in_texture, texture_sampler), uv, ivec2(-1, 0)));
+    vec4 p02 = premultiplied(textureOffset(sampler2D(in_texture, texture_sampler), uv, ivec2(-1, 1)));
+    vec4 p10 = premultiplied(textureOffset(sampler2D(in_texture, texture_sampler), uv, ivec2(0, -1)));
+    vec4 p11 = premultiplied(textureOffset(sampler2D(in_texture, texture_sampler), uv, ivec2(0, 0)));
+    vec4 p12 = premultiplied(textureOffset(sampler2D(in_texture, texture_sampler), uv, ivec2(0, 1)));
+    vec4 p20 = premultiplied(textureOffset(sampler2D(in_texture, texture_sampler), uv, ivec2(1, -1)));
+    vec4 p21 = premultiplied(textureOffset(sampler2D(in_texture, texture_sampler), uv, ivec2(1, 0)));
+    vec4 p22 = premultiplied(textureOffset(sampler2D(in_texture, texture_sampler), uv, ivec2(1, 1)));
 
     // Remember: column-major initializer.
     mat3x3 c0 = mat3x3(
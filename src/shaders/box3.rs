@@ -10,11 +10,15 @@ pub const SHADER: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/spirv/box3.f
 #[derive(Clone, Debug, PartialEq)]
 pub(crate) struct Shader {
     matrix: RowMatrix,
+    address_mode: wgpu::AddressMode,
 }
 
 impl Shader {
-    pub fn new(matrix: RowMatrix) -> Self {
-        Shader { matrix }
+    pub fn new(matrix: RowMatrix, address_mode: wgpu::AddressMode) -> Self {
+        Shader {
+            matrix,
+            address_mode,
+        }
     }
 }
 
@@ -39,4 +43,8 @@ impl FragmentShaderData for Shader {
     fn num_args(&self) -> u32 {
         1
     }
+
+    fn address_mode(&self) -> wgpu::AddressMode {
+        self.address_mode
+    }
 }
@@ -0,0 +1,47 @@
+#version 450
+layout (location = 0) in vec2 uv;
+layout (location = 0) out vec4 f_color;
+
+layout (set = 1, binding = 0) uniform sampler texture_sampler;
+layout (set = 1, binding = 1) uniform texture2D in_texture;
+
+layout (set = 2, binding = 0, std140) uniform ChannelAffine {
+  // For each of the red, green and blue channels, maps the texture coordinates of the output
+  // onto the texture coordinates to sample the channel at.
+  mat3 transform[3];
+  // xyz: the sampling of each channel, nearest or bilinear.
+  uvec4 sampling;
+} u_affine;
+
+const uint SAMPLE_NEAREST = 0u;
+const uint SAMPLE_BILINEAR = 1u;
+
+// Texels beyond the edges repeat the edge.
+vec4 fetch(ivec2 coord) {
+  ivec2 bound = textureSize(sampler2D(in_texture, texture_sampler), 0) - 1;
+  return texelFetch(sampler2D(in_texture, texture_sampler), clamp(coord, ivec2(0), bound), 0);
+}
+
+float channel_at(int channel, vec2 size) {
+  vec3 projected = u_affine.transform[channel] * vec3(gl_FragCoord.xy / size, 1.0);
+  vec2 coord = projected.xy / projected.z * size;
+
+  if (u_affine.sampling[channel] == SAMPLE_BILINEAR) {
+    vec2 texel = coord - 0.5;
+    ivec2 base = ivec2(floor(texel));
+    vec2 t = texel - vec2(base);
+
+    vec4 top = mix(fetch(base), fetch(base + ivec2(1, 0)), t.x);
+    vec4 bottom = mix(fetch(base + ivec2(0, 1)), fetch(base + ivec2(1, 1)), t.x);
+    return mix(top, bottom, t.y)[channel];
+  } else {
+    return fetch(ivec2(floor(coord)))[channel];
+  }
+}
+
+void main() {
+  vec2 size = vec2(textureSize(sampler2D(in_texture, texture_sampler), 0));
+  float alpha = texelFetch(sampler2D(in_texture, texture_sampler), ivec2(gl_FragCoord.xy), 0).a;
+
+  f_color = vec4(channel_at(0, size), channel_at(1, size), channel_at(2, size), alpha);
+}
@@ -0,0 +1,45 @@
+use std::borrow::Cow;
+
+use super::perspective::Sampling;
+use super::{BufferInitContent, FragmentShaderData, FragmentShaderKey};
+use crate::color_matrix::RowMatrix;
+
+/// Resample each color channel through its own affine transformation.
+pub const SHADER: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/spirv/channel_affine.frag.v"));
+
+/// The shader moving the red, green and blue channels independently.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Shader {
+    /// For each channel, maps texture coordinates of the output to those to sample at.
+    pub(crate) transform: [RowMatrix; 3],
+    pub(crate) sampling: [Sampling; 3],
+}
+
+impl FragmentShaderData for Shader {
+    /// The unique key identifying this shader module.
+    fn key(&self) -> Option<FragmentShaderKey> {
+        Some(FragmentShaderKey::ChannelAffine)
+    }
+
+    /// The SPIR-V shader source code.
+    fn spirv_source(&self) -> Cow<'static, [u8]> {
+        Cow::Borrowed(SHADER)
+    }
+
+    /// Encode the shader's data into the buffer, returning the descriptor to that.
+    fn binary_data(&self, buffer: &mut Vec<u8>) -> Option<BufferInitContent> {
+        let [r, g, b] = self.sampling;
+        let sampling = [r as u32, g as u32, b as u32, 0];
+
+        let mut content = BufferInitContent::builder(buffer);
+        for transform in &self.transform {
+            content.extend_from_pods(&transform.into_mat3x3_std140());
+        }
+        content.extend_from_pods(&sampling);
+        Some(content.build())
+    }
+
+    fn num_args(&self) -> u32 {
+        1
+    }
+}
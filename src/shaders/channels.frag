@@ -0,0 +1,49 @@
+#version 450
+layout (location = 0) in vec2 uv;
+layout (location = 0) out vec4 f_color;
+
+layout (set = 1, binding = 0) uniform sampler texture_sampler;
+// The image to swizzle, or the first of the images to merge.
+layout (set = 1, binding = 1) uniform texture2D in_0;
+layout (set = 1, binding = 2) uniform texture2D in_1;
+layout (set = 1, binding = 3) uniform texture2D in_2;
+layout (set = 1, binding = 4) uniform texture2D in_3;
+
+layout (set = 2, binding = 0, std140) uniform Channels {
+  // For each input, the map from its channels to the channels of the output.
+  mat4 select[4];
+  // Added to the output, such as constant channels.
+  vec4 constant;
+  // x: non-zero to multiply the colors of the swizzled image by alpha first.
+  uvec4 mode;
+} u_channels;
+
+vec4 fetch(texture2D image) {
+  return texelFetch(sampler2D(image, texture_sampler), ivec2(gl_FragCoord.xy), 0);
+}
+
+#ifndef CHANNELS_SWIZZLE_AS_MAIN
+#define CHANNELS_SWIZZLE_AS_MAIN channels_swizzle
+#endif
+#ifndef CHANNELS_MERGE_AS_MAIN
+#define CHANNELS_MERGE_AS_MAIN channels_merge
+#endif
+
+// Paints each channel from one channel of a single image, or a constant.
+void CHANNELS_SWIZZLE_AS_MAIN() {
+  vec4 color = fetch(in_0);
+  if (u_channels.mode.x != 0u) {
+    color.rgb *= color.a;
+  }
+
+  f_color = u_channels.select[0] * color + u_channels.constant;
+}
+
+// Paints each channel from one of up to four images, or a constant.
+void CHANNELS_MERGE_AS_MAIN() {
+  f_color = u_channels.select[0] * fetch(in_0)
+    + u_channels.select[1] * fetch(in_1)
+    + u_channels.select[2] * fetch(in_2)
+    + u_channels.select[3] * fetch(in_3)
+    + u_channels.constant;
+}
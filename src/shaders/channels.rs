@@ -0,0 +1,74 @@
+use std::borrow::Cow;
+
+use super::{BufferInitContent, FragmentShaderData, FragmentShaderKey};
+
+/// Paint the channels of an image from the channels of itself, or constants.
+pub const SHADER_SWIZZLE: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/spirv/channels_swizzle.frag.v"));
+/// Paint the channels of an image from the channels of up to four images, or constants.
+pub const SHADER_MERGE: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/spirv/channels_merge.frag.v"));
+
+/// The number of inputs of the shader.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum Inputs {
+    /// A single image.
+    Swizzle,
+    /// Exactly four images, which may repeat.
+    Merge,
+}
+
+/// The shader selecting the channels of the output among the channels of its inputs.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Shader {
+    pub(crate) inputs: Inputs,
+    /// For each input, the weight of each of its channels (inner) for each output channel (outer).
+    pub(crate) select: [[[f32; 4]; 4]; 4],
+    /// The value added to each output channel.
+    pub(crate) constant: [f32; 4],
+    /// Multiply the colors of a swizzled image by alpha before selecting from them.
+    pub(crate) premultiply: bool,
+}
+
+impl FragmentShaderData for Shader {
+    /// The unique key identifying this shader module.
+    fn key(&self) -> Option<FragmentShaderKey> {
+        Some(FragmentShaderKey::Channels(self.inputs))
+    }
+
+    /// The SPIR-V shader source code.
+    fn spirv_source(&self) -> Cow<'static, [u8]> {
+        Cow::Borrowed(match self.inputs {
+            Inputs::Swizzle => SHADER_SWIZZLE,
+            Inputs::Merge => SHADER_MERGE,
+        })
+    }
+
+    /// Encode the shader's data into the buffer, returning the descriptor to that.
+    fn binary_data(&self, buffer: &mut Vec<u8>) -> Option<BufferInitContent> {
+        let mut content = BufferInitContent::builder(buffer);
+
+        for select in &self.select {
+            // The matrices are stored column-major, one column for each input channel.
+            let mut columns = [[0.0f32; 4]; 4];
+            for (output, weights) in select.iter().enumerate() {
+                for (input, &weight) in weights.iter().enumerate() {
+                    columns[input][output] = weight;
+                }
+            }
+
+            content.extend_from_pods(&columns);
+        }
+
+        content.extend_from_pods(&[self.constant]);
+        content.extend_from_pods(&[u32::from(self.premultiply), 0, 0, 0]);
+        Some(content.build())
+    }
+
+    fn num_args(&self) -> u32 {
+        match self.inputs {
+            Inputs::Swizzle => 1,
+            Inputs::Merge => 4,
+        }
+    }
+}
@@ -0,0 +1,192 @@
+#version 440
+layout (location = 0) in vec2 uv;
+layout (location = 0) out vec4 f_color;
+
+layout (set = 1, binding = 0) uniform sampler texture_sampler;
+layout (set = 1, binding = 1) uniform texture2D in_texture;
+
+layout (set = 2, binding = 0) uniform FragmentColor {
+    vec4 whitepoint_xyz;
+    mat3x3 xyz_transform;
+    // x: the model, y: polar coordinates, z: normalized to the unit range.
+    uvec4 form;
+} u_fragmentColor;
+
+const uint MODEL_LAB = 0u;
+const uint MODEL_LUV = 1u;
+
+// The chroma is scaled such that all normalized a*b* pairs have a normalized chroma.
+const float CHROMA_SCALE = 181.0193;
+
+// 6**3 / 29**3
+const float EPSILON = 216.0 / 24389.0;
+const float DELTA = 6.0 / 29.0;
+
+float cie_f(float t) {
+    if (t > EPSILON) {
+        return pow(t, 1.0 / 3.0);
+    } else {
+        return t / (3.0 * DELTA * DELTA) + 4.0 / 29.0;
+    }
+}
+
+float cie_f_inv(float t) {
+    if (t > DELTA) {
+        return t * t * t;
+    } else {
+        return 3.0 * DELTA * DELTA * (t - 4.0 / 29.0);
+    }
+}
+
+// The uv chromaticity coordinates of CIE 1976 UCS.
+vec2 cie_uv(vec3 xyz) {
+    const float denominator = xyz.x + 15.0 * xyz.y + 3.0 * xyz.z;
+    return vec2(4.0 * xyz.x, 9.0 * xyz.y) / denominator;
+}
+
+vec3 xyz_to_lab(vec3 xyz) {
+    const vec3 wp = u_fragmentColor.whitepoint_xyz.xyz;
+    const vec3 f = vec3(cie_f(xyz.x / wp.x), cie_f(xyz.y / wp.y), cie_f(xyz.z / wp.z));
+
+    return vec3(
+        116.0 * f.y - 16.0,
+        500.0 * (f.x - f.y),
+        200.0 * (f.y - f.z)
+    );
+}
+
+vec3 lab_to_xyz(vec3 Lab) {
+    const vec3 wp = u_fragmentColor.whitepoint_xyz.xyz;
+    const float fy = (Lab.x + 16.0) / 116.0;
+    const float fx = fy + Lab.y / 500.0;
+    const float fz = fy - Lab.z / 200.0;
+
+    return wp * vec3(cie_f_inv(fx), cie_f_inv(fy), cie_f_inv(fz));
+}
+
+vec3 xyz_to_luv(vec3 xyz) {
+    const vec3 wp = u_fragmentColor.whitepoint_xyz.xyz;
+    const float L = 116.0 * cie_f(xyz.y / wp.y) - 16.0;
+    // Black has no chromaticity, its chroma is zero regardless.
+    if (xyz.x + 15.0 * xyz.y + 3.0 * xyz.z <= 0.0) {
+        return vec3(L, 0.0, 0.0);
+    }
+
+    const vec2 uv_diff = cie_uv(xyz) - cie_uv(wp);
+    return vec3(L, 13.0 * L * uv_diff);
+}
+
+vec3 luv_to_xyz(vec3 Luv) {
+    const vec3 wp = u_fragmentColor.whitepoint_xyz.xyz;
+    if (Luv.x <= 0.0) {
+        return vec3(0.0);
+    }
+
+    const vec2 uv_p = Luv.yz / (13.0 * Luv.x) + cie_uv(wp);
+    const float y = wp.y * cie_f_inv((Luv.x + 16.0) / 116.0);
+
+    return vec3(
+        y * 9.0 * uv_p.x / (4.0 * uv_p.y),
+        y,
+        y * (12.0 - 3.0 * uv_p.x - 20.0 * uv_p.y) / (4.0 * uv_p.y)
+    );
+}
+
+vec3 to_polar(vec3 Lab) {
+    const float hue = degrees(atan(Lab.z, Lab.y));
+    return vec3(Lab.x, length(Lab.yz), hue < 0.0 ? hue + 360.0 : hue);
+}
+
+vec3 from_polar(vec3 Lch) {
+    const float hue = radians(Lch.z);
+    return vec3(Lch.x, Lch.y * cos(hue), Lch.y * sin(hue));
+}
+
+// Integer texels store L in [0, 100], a* and b* in [-128, 127] following ICC. The u* and v* of
+// saturated colors exceed that, they are stored in [-200, 200] instead.
+const float LUV_RANGE = 200.0;
+// The chroma of all such u*v* pairs, sqrt(2) * LUV_RANGE.
+const float LUV_CHROMA_SCALE = 282.8427;
+
+vec3 value_scale(uint model, bool polar) {
+    if (model == MODEL_LUV) {
+        const float range = 2.0 * LUV_RANGE;
+        return polar ? vec3(100.0, LUV_CHROMA_SCALE, 360.0) : vec3(100.0, range, range);
+    } else {
+        return polar ? vec3(100.0, CHROMA_SCALE, 360.0) : vec3(100.0, 255.0, 255.0);
+    }
+}
+
+vec3 value_offset(uint model, bool polar) {
+    if (polar) {
+        return vec3(0.0);
+    } else if (model == MODEL_LUV) {
+        return vec3(0.0, LUV_RANGE, LUV_RANGE);
+    } else {
+        return vec3(0.0, 128.0, 128.0);
+    }
+}
+
+vec3 normalize_values(vec3 values, uint model, bool polar) {
+    return (values + value_offset(model, polar)) / value_scale(model, polar);
+}
+
+vec3 denormalize_values(vec3 values, uint model, bool polar) {
+    return values * value_scale(model, polar) - value_offset(model, polar);
+}
+
+#ifndef CIELAB_ENCODE_AS_MAIN
+#define CIELAB_ENCODE_AS_MAIN cielab_encode
+#endif
+#ifndef CIELAB_DECODE_AS_MAIN
+#define CIELAB_DECODE_AS_MAIN cielab_decode
+#endif
+
+void CIELAB_ENCODE_AS_MAIN() {
+    // Assuming the input is some linear rgb space.
+    const vec4 rgba = texture(sampler2D(in_texture, texture_sampler), uv);
+    const vec3 xyz = u_fragmentColor.xyz_transform * rgba.rgb;
+    const bool polar = u_fragmentColor.form.y != 0u;
+
+    vec3 values;
+    if (u_fragmentColor.form.x == MODEL_LUV) {
+        values = xyz_to_luv(xyz);
+    } else {
+        values = xyz_to_lab(xyz);
+    }
+
+    if (polar) {
+        values = to_polar(values);
+    }
+
+    if (u_fragmentColor.form.z != 0u) {
+        values = normalize_values(values, u_fragmentColor.form.x, polar);
+    }
+
+    f_color = vec4(values, rgba.a);
+}
+
+void CIELAB_DECODE_AS_MAIN() {
+    const vec4 lab_a = texture(sampler2D(in_texture, texture_sampler), uv);
+    const bool polar = u_fragmentColor.form.y != 0u;
+    vec3 values = lab_a.xyz;
+
+    if (u_fragmentColor.form.z != 0u) {
+        values = denormalize_values(values, u_fragmentColor.form.x, polar);
+    }
+
+    if (polar) {
+        values = from_polar(values);
+    }
+
+    vec3 xyz;
+    if (u_fragmentColor.form.x == MODEL_LUV) {
+        xyz = luv_to_xyz(values);
+    } else {
+        xyz = lab_to_xyz(values);
+    }
+
+    // Write this as our 'linear color' (preserve alpha).
+    const vec3 rgb = u_fragmentColor.xyz_transform * xyz;
+    f_color = vec4(rgb, lab_a.a);
+}
@@ -0,0 +1,97 @@
+use std::borrow::Cow;
+
+use super::{BufferInitContent, Direction, FragmentShaderData, FragmentShaderKey};
+use crate::buffer::Whitepoint;
+use crate::color_matrix::RowMatrix;
+
+/// Transform linear rgb into CIE L*a*b* or L*u*v*.
+pub const SHADER_ENCODE: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/spirv/cielab_encode.frag.v"));
+/// Transform CIE L*a*b* or L*u*v* into linear rgb.
+pub const SHADER_DECODE: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/spirv/cielab_decode.frag.v"));
+
+/// The model of the CIE 1976 color space, as enumerated in the shader.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub(crate) enum Model {
+    Lab = 0,
+    Luv = 1,
+}
+
+/// How the three values of a model are stored in the texel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct Form {
+    pub(crate) model: Model,
+    /// Use lightness, chroma and hue instead of the rectangular coordinates.
+    pub(crate) polar: bool,
+    /// Scale values into the unit range, for texels with integer channels.
+    pub(crate) normalized: bool,
+}
+
+/// The shader between linear rgb and one of the CIE 1976 color spaces.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Shader {
+    matrix: RowMatrix,
+    whitepoint: Whitepoint,
+    form: Form,
+    direction: Direction,
+}
+
+impl Shader {
+    pub fn with_encode(matrix: RowMatrix, whitepoint: Whitepoint, form: Form) -> Self {
+        Shader {
+            matrix,
+            whitepoint,
+            form,
+            direction: Direction::Encode,
+        }
+    }
+
+    pub fn with_decode(matrix: RowMatrix, whitepoint: Whitepoint, form: Form) -> Self {
+        Shader {
+            matrix,
+            whitepoint,
+            form,
+            direction: Direction::Decode,
+        }
+    }
+}
+
+impl FragmentShaderData for Shader {
+    /// The unique key identifying this shader module.
+    fn key(&self) -> Option<FragmentShaderKey> {
+        Some(FragmentShaderKey::CieLabTransform(self.direction))
+    }
+
+    /// The SPIR-V shader source code.
+    fn spirv_source(&self) -> Cow<'static, [u8]> {
+        Cow::Borrowed(match self.direction {
+            Direction::Encode => SHADER_ENCODE,
+            Direction::Decode => SHADER_DECODE,
+        })
+    }
+
+    /// Encode the shader's data into the buffer, returning the descriptor to that.
+    fn binary_data(&self, buffer: &mut Vec<u8>) -> Option<BufferInitContent> {
+        let data = self.matrix.into_mat3x3_std140();
+        let wp = self.whitepoint.to_xyz();
+        let form = [
+            self.form.model as u32,
+            u32::from(self.form.polar),
+            u32::from(self.form.normalized),
+            0,
+        ];
+
+        let mut content = BufferInitContent::builder(buffer);
+        content.extend_from_pods(&[wp]);
+        content.align_by_exponent(4);
+        content.extend_from_pods(&[data]);
+        content.extend_from_pods(&[form]);
+        Some(content.build())
+    }
+
+    fn num_args(&self) -> u32 {
+        1
+    }
+}
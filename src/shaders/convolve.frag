@@ -0,0 +1,59 @@
+#version 450
+layout (location = 0) in vec2 uv;
+layout (location = 0) out vec4 f_color;
+
+layout (set = 1, binding = 0) uniform sampler texture_sampler;
+// The image to convolve.
+layout (set = 1, binding = 1) uniform texture2D in_texture;
+// The unfiltered image, whose colors are kept when only luminance is convolved.
+layout (set = 1, binding = 2) uniform texture2D in_source;
+
+// The largest kernel is 31 by 31, with its weights packed into vectors.
+const int MAX_WEIGHTS = 961;
+
+layout (set = 2, binding = 0, std140) uniform Convolve {
+  // xy: the width and height of the kernel, both odd.
+  ivec4 size;
+  // xyz: the luminance of linear RGB, w: non-zero to only convolve the luminance.
+  vec4 luminance;
+  // x: non-zero to multiply the colors by alpha when sampled, y: to divide the sum by its alpha.
+  uvec4 mode;
+  // The weights of the kernel, row by row.
+  vec4 weights[(MAX_WEIGHTS + 3) / 4];
+} u_convolve;
+
+float weight(int idx) {
+  return u_convolve.weights[idx / 4][idx % 4];
+}
+
+void main() {
+  ivec2 size = u_convolve.size.xy;
+  ivec2 radius = size / 2;
+  vec2 texture_size = vec2(textureSize(sampler2D(in_texture, texture_sampler), 0));
+
+  vec4 sum = vec4(0.0);
+  for (int y = 0; y < size.y; y++) {
+    for (int x = 0; x < size.x; x++) {
+      // The sampler extends the image beyond its edges.
+      vec2 coord = (gl_FragCoord.xy + vec2(x - radius.x, y - radius.y)) / texture_size;
+      vec4 texel = texture(sampler2D(in_texture, texture_sampler), coord);
+      if (u_convolve.mode.x != 0u) {
+        texel.rgb *= texel.a;
+      }
+      sum += weight(y * size.x + x) * texel;
+    }
+  }
+
+  if (u_convolve.luminance.w != 0.0) {
+    // Move the color such that its luminance is that of the convolution.
+    vec4 source = texelFetch(sampler2D(in_source, texture_sampler), ivec2(gl_FragCoord.xy), 0);
+    vec3 luminance = u_convolve.luminance.xyz;
+    float difference = dot(luminance, sum.rgb) - dot(luminance, source.rgb);
+    f_color = vec4(source.rgb + difference, source.a);
+  } else if (u_convolve.mode.y != 0u) {
+    // Nothing is left of the color of fully transparent pixels.
+    f_color = sum.a > 0.0 ? vec4(sum.rgb / sum.a, sum.a) : vec4(vec3(0.0), sum.a);
+  } else {
+    f_color = sum;
+  }
+}
@@ -0,0 +1,69 @@
+use std::borrow::Cow;
+
+use super::{BufferInitContent, FragmentShaderData, FragmentShaderKey};
+
+/// Convolve an image with a kernel of up to 31 by 31 weights.
+pub const SHADER: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/spirv/convolve.frag.v"));
+
+/// The largest width and height of a kernel.
+pub(crate) const MAX_SIZE: u32 = 31;
+
+/// The number of weights in the uniform, padded to whole vectors.
+const WEIGHTS: usize = ((MAX_SIZE * MAX_SIZE).div_ceil(4) * 4) as usize;
+
+/// One pass of a convolution, with a two-dimensional or a one-dimensional kernel.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Shader {
+    /// The width and height of the kernel, both odd.
+    pub(crate) size: [u32; 2],
+    /// The weights of the kernel, row by row.
+    pub(crate) weights: Vec<f32>,
+    /// The luminance of linear RGB, if only the luminance is convolved.
+    pub(crate) luminance: Option<[f32; 3]>,
+    /// Multiply the sampled colors by alpha.
+    pub(crate) premultiply: bool,
+    /// Divide the convolved colors by their alpha.
+    pub(crate) straighten: bool,
+    /// How the image is extended beyond its edges.
+    pub(crate) address_mode: wgpu::AddressMode,
+}
+
+impl FragmentShaderData for Shader {
+    /// The unique key identifying this shader module.
+    fn key(&self) -> Option<FragmentShaderKey> {
+        Some(FragmentShaderKey::Convolve)
+    }
+
+    /// The SPIR-V shader source code.
+    fn spirv_source(&self) -> Cow<'static, [u8]> {
+        Cow::Borrowed(SHADER)
+    }
+
+    /// Encode the shader's data into the buffer, returning the descriptor to that.
+    fn binary_data(&self, buffer: &mut Vec<u8>) -> Option<BufferInitContent> {
+        let size = [self.size[0], self.size[1], 0, 0];
+        let luminance = match self.luminance {
+            Some([r, g, b]) => [r, g, b, 1.0],
+            None => [0.0; 4],
+        };
+        let mode: [u32; 4] = [self.premultiply.into(), self.straighten.into(), 0, 0];
+
+        let mut weights = self.weights.clone();
+        weights.resize(WEIGHTS, 0.0);
+
+        let mut content = BufferInitContent::builder(buffer);
+        content.extend_from_pods(&size);
+        content.extend_from_pods(&luminance);
+        content.extend_from_pods(&mode);
+        content.extend_from_pods(&weights);
+        Some(content.build())
+    }
+
+    fn num_args(&self) -> u32 {
+        2
+    }
+
+    fn address_mode(&self) -> wgpu::AddressMode {
+        self.address_mode
+    }
+}
@@ -0,0 +1,166 @@
+#version 450
+layout (location = 0) in vec2 uv;
+layout (location = 0) out vec4 f_color;
+
+#ifdef DITHER_PALETTE_AS_MAIN
+#define DITHER_PALETTE
+#endif
+
+#ifndef DITHER_LEVELS_AS_MAIN
+#define DITHER_LEVELS_AS_MAIN dither_levels
+#endif
+#ifndef DITHER_PALETTE_AS_MAIN
+#define DITHER_PALETTE_AS_MAIN dither_palette
+#endif
+
+layout (set = 1, binding = 0) uniform sampler texture_sampler;
+layout (set = 1, binding = 1) uniform texture2D in_texture;
+#ifdef DITHER_PALETTE
+// A single row, one pixel per color.
+layout (set = 1, binding = 2) uniform texture2D in_palette;
+#endif
+
+layout (set = 2, binding = 0, std140) uniform Dither {
+  // x: method, y: number of palette entries.
+  uvec4 mode;
+  // The largest code of each channel, or zero to keep the channel as is.
+  vec4 levels;
+  // From the linear RGB of image and palette to XYZ, relative to D65.
+  mat3 xyz_transform;
+} u_dither;
+
+const uint METHOD_BAYER = 0u;
+const uint METHOD_BLUE_NOISE = 1u;
+
+// The canonical Oklab matrices, given column-wise.
+const mat3x3 M1 = mat3x3(
+        +0.8189330101, +0.0329845436, +0.0482003018,
+        +0.3618667424, +0.9293118715, +0.2643662691,
+        -0.1288597137, +0.0361456387, +0.6338517070
+    );
+
+const mat3x3 M2 = mat3x3(
+        +0.2104542553, +1.9779984951, +0.0259040371,
+        +0.7936177850, -2.4285922050, +0.7827717662,
+        -0.0040720468, +0.4505937099, -0.8086757660
+    );
+
+// A tileable 16-by-16 blue noise pattern, the rank of each cell as found by void-and-cluster.
+const uint BLUE_NOISE[256] = uint[256](
+  234,  50, 188,  19,  58, 171, 121,  47, 163,   3, 247, 104,  22, 132,  14,  65,
+  209,   8, 118,  97, 240, 205,  23, 228, 138,  64, 123, 170,  72, 224,  99, 149,
+  85, 139, 229, 165,  78, 146, 111,  84, 176, 216,  30, 231, 153, 201,  42, 180,
+  25,  62, 195,  29,  43, 185,   7, 249,  41, 100, 191,  48,  87,   5, 128, 243,
+  221, 152, 101, 253, 130, 220,  59, 200, 156,  12, 136, 112, 254, 174,  69, 109,
+  46, 189,   2,  73, 172,  90, 142, 116,  80, 237, 210,  61, 147,  33, 206, 160,
+  81, 124, 217, 113, 208,  15, 241,  27, 168,  45, 178,  20, 193,  96, 225,  18,
+  242, 164,  60,  35, 157,  53, 181,  68, 223, 105, 125,  83, 236, 131,  55, 141,
+  197,  10, 227, 134, 246,  95, 126, 198, 148,   1, 244, 161,  71,   9, 182, 106,
+  40,  93, 179,  75, 192,   6, 218,  36,  91,  57, 202,  34, 215, 155, 233,  74,
+  252, 120, 150,  24, 110,  63, 166, 119, 232, 183, 133, 103,  49, 117,  31, 167,
+  16, 212,  51, 238, 207, 137, 255,  21,  76, 151,  13, 250, 190,  88, 203, 135,
+  102, 184,  82, 169,  38,  89, 187,  52, 204,  98, 173,  67, 129,   4, 222,  56,
+  230, 144,   0, 127, 226,  11, 154, 114, 239,  39, 219,  28, 235, 145, 175,  77,
+  196,  37, 248,  70, 107, 199,  66, 177,  17, 143, 115, 159,  86,  44, 108,  26,
+  122,  92, 158, 214, 140,  32, 245,  94, 213,  79, 194,  54, 211, 186, 251, 162
+);
+
+float bayer(ivec2 pixel) {
+  uint x = uint(pixel.x) & 7u;
+  uint y = uint(pixel.y) & 7u;
+  uint xy = x ^ y;
+  uint rank = ((xy & 1u) << 5) | ((y & 1u) << 4) | ((xy & 2u) << 2)
+    | ((y & 2u) << 1) | ((xy & 4u) >> 1) | ((y & 4u) >> 2);
+  return (float(rank) + 0.5) / 64.0;
+}
+
+float blue_noise(ivec2 pixel) {
+  uint rank = BLUE_NOISE[(uint(pixel.y) & 15u) * 16u + (uint(pixel.x) & 15u)];
+  return (float(rank) + 0.5) / 256.0;
+}
+
+vec4 levels() {
+  return max(u_dither.levels, vec4(1.0));
+}
+
+vec4 fetch(ivec2 pixel) {
+  return clamp(texelFetch(sampler2D(in_texture, texture_sampler), pixel, 0), 0.0, 1.0);
+}
+
+vec4 ordered(vec4 value, float threshold) {
+  return min(floor(value * levels() + threshold), levels()) / levels();
+}
+
+float threshold(ivec2 pixel) {
+  if (u_dither.mode.x == METHOD_BAYER) {
+    return bayer(pixel);
+  } else {
+    return blue_noise(pixel);
+  }
+}
+
+// Channels without codes keep their value.
+vec4 keep_channels(vec4 result, ivec2 pixel) {
+  bvec4 keep = equal(u_dither.levels, vec4(0.0));
+  return mix(result, texelFetch(sampler2D(in_texture, texture_sampler), pixel, 0), keep);
+}
+
+void DITHER_LEVELS_AS_MAIN() {
+  ivec2 pixel = ivec2(gl_FragCoord.xy);
+  f_color = keep_channels(ordered(fetch(pixel), threshold(pixel)), pixel);
+}
+
+#ifdef DITHER_PALETTE
+uint palette_len() {
+  return max(u_dither.mode.y, 1u);
+}
+
+vec4 palette_entry(uint idx) {
+  return texelFetch(sampler2D(in_palette, texture_sampler), ivec2(idx, 0), 0);
+}
+
+vec4 to_oklab(vec4 rgba) {
+  vec3 lms = M1 * (mat3(u_dither.xyz_transform) * rgba.rgb);
+  vec3 lms_star = pow(abs(lms), vec3(1.0 / 3.0)) * sign(lms);
+  return vec4(M2 * lms_star, rgba.a);
+}
+
+// The palette color nearest in Oklab, with the difference of alpha as a fourth coordinate.
+vec4 nearest_entry(vec4 color) {
+  vec4 lab = to_oklab(color);
+  vec4 best = palette_entry(0u);
+  float best_distance = 3.4e38;
+
+  for (uint i = 0u; i < palette_len(); i++) {
+    vec4 entry = palette_entry(i);
+    vec4 diff = to_oklab(entry) - lab;
+    float dist = dot(diff, diff);
+
+    if (dist < best_distance) {
+      best = entry;
+      best_distance = dist;
+    }
+  }
+
+  return best;
+}
+
+// Choose between the nearest color and the one nearest to the other side of the value, as often
+// as the value lies towards each of them in linear light.
+vec4 ordered_entry(vec4 value, float threshold) {
+  vec4 near = nearest_entry(value);
+  vec4 far = nearest_entry(clamp(2.0 * value - near, 0.0, 1.0));
+
+  vec4 span = far - near;
+  float length2 = dot(span, span);
+  float ratio = length2 > 0.0 ? clamp(dot(value - near, span) / length2, 0.0, 1.0) : 0.0;
+  return threshold < ratio ? far : near;
+}
+
+// Paints a color of the palette for each pixel, such that the colors average to the image.
+void DITHER_PALETTE_AS_MAIN() {
+  ivec2 pixel = ivec2(gl_FragCoord.xy);
+  f_color = ordered_entry(fetch(pixel), threshold(pixel));
+}
+
+#endif
@@ -0,0 +1,101 @@
+use std::borrow::Cow;
+
+use super::{BufferInitContent, FragmentShaderData, FragmentShaderKey};
+use crate::color_matrix::RowMatrix;
+
+/// Quantize linear values to fewer levels, distributing the rounding error as noise.
+pub const SHADER_LEVELS: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/spirv/dither_levels.frag.v"));
+/// Choose colors of a palette, distributing the difference to the original as noise.
+pub const SHADER_PALETTE: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/spirv/dither_palette.frag.v"));
+
+/// The method of dithering.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum Method {
+    Bayer,
+    BlueNoise,
+}
+
+/// What the values are reduced to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum Kind {
+    Levels,
+    Palette,
+}
+
+/// The codes which the dithered values take.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Target {
+    /// The largest code of each channel, in RGBA order, or zero to leave the channel unchanged.
+    Levels([f32; 4]),
+    /// The colors of a palette, the second argument, nearest in Oklab.
+    Palette {
+        /// The number of colors in the palette.
+        len: u32,
+        /// From the linear RGB of image and palette to XYZ relative to D65.
+        xyz_transform: RowMatrix,
+    },
+}
+
+/// The shader snapping each pixel to the codes of a target bit depth or to a palette.
+///
+/// The arguments are the image, then the palette if any.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Shader {
+    pub(crate) method: Method,
+    pub(crate) target: Target,
+}
+
+impl Shader {
+    fn kind(&self) -> Kind {
+        match self.target {
+            Target::Levels(_) => Kind::Levels,
+            Target::Palette { .. } => Kind::Palette,
+        }
+    }
+}
+
+impl FragmentShaderData for Shader {
+    /// The unique key identifying this shader module.
+    fn key(&self) -> Option<FragmentShaderKey> {
+        Some(FragmentShaderKey::Dither(self.kind()))
+    }
+
+    /// The SPIR-V shader source code.
+    fn spirv_source(&self) -> Cow<'static, [u8]> {
+        Cow::Borrowed(match self.kind() {
+            Kind::Levels => SHADER_LEVELS,
+            Kind::Palette => SHADER_PALETTE,
+        })
+    }
+
+    /// Encode the shader's data into the buffer, returning the descriptor to that.
+    fn binary_data(&self, buffer: &mut Vec<u8>) -> Option<BufferInitContent> {
+        let (len, levels, xyz_transform) = match self.target {
+            Target::Levels(levels) => (0, levels, RowMatrix::diag(1.0, 1.0, 1.0)),
+            Target::Palette { len, xyz_transform } => (len, [0.0; 4], xyz_transform),
+        };
+
+        let method = match self.method {
+            Method::Bayer => 0,
+            Method::BlueNoise => 1,
+        };
+
+        let mode = [method, len, 0, 0];
+        let matrix: [f32; 12] = xyz_transform.into_mat3x3_std140();
+
+        let mut content = BufferInitContent::builder(buffer);
+        content.extend_from_pods(&[mode]);
+        content.extend_from_pods(&levels);
+        content.extend_from_pods(&matrix);
+        Some(content.build())
+    }
+
+    fn num_args(&self) -> u32 {
+        match self.kind() {
+            Kind::Levels => 1,
+            Kind::Palette => 2,
+        }
+    }
+}
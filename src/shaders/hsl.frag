@@ -0,0 +1,125 @@
+#version 450
+layout (location = 0) in vec2 uv;
+layout (location = 0) out vec4 f_color;
+
+layout (set = 1, binding = 0) uniform sampler texture_sampler;
+layout (set = 1, binding = 1) uniform texture2D in_texture;
+
+layout (set = 2, binding = 0, std140) uniform Hsl {
+  // x: operation, y: model.
+  uvec4 mode;
+  // x: hue shift in turns, y: saturation scale, z: lightness scale.
+  vec4 adjust;
+} u_hsl;
+
+const uint OPERATION_ENCODE = 0u;
+const uint OPERATION_DECODE = 1u;
+const uint OPERATION_ADJUST = 2u;
+
+const uint MODEL_HSV = 0u;
+const uint MODEL_HSL = 1u;
+const uint MODEL_HWB = 2u;
+
+// The hue in turns, and the chroma.
+vec2 hue_chroma(vec3 rgb, float hi, float lo) {
+  float chroma = hi - lo;
+  if (chroma <= 0.0) {
+    return vec2(0.0, 0.0);
+  }
+
+  float hue;
+  if (hi == rgb.r) {
+    hue = (rgb.g - rgb.b) / chroma;
+  } else if (hi == rgb.g) {
+    hue = (rgb.b - rgb.r) / chroma + 2.0;
+  } else {
+    hue = (rgb.r - rgb.g) / chroma + 4.0;
+  }
+
+  return vec2(fract(hue / 6.0), chroma);
+}
+
+vec3 rgb_to_hsv(vec3 rgb) {
+  float hi = max(rgb.r, max(rgb.g, rgb.b));
+  float lo = min(rgb.r, min(rgb.g, rgb.b));
+  vec2 hc = hue_chroma(rgb, hi, lo);
+  float saturation = hi > 0.0 ? hc.y / hi : 0.0;
+  return vec3(hc.x, saturation, hi);
+}
+
+vec3 rgb_to_hsl(vec3 rgb) {
+  float hi = max(rgb.r, max(rgb.g, rgb.b));
+  float lo = min(rgb.r, min(rgb.g, rgb.b));
+  vec2 hc = hue_chroma(rgb, hi, lo);
+  float lightness = (hi + lo) / 2.0;
+  float divisor = 1.0 - abs(2.0 * lightness - 1.0);
+  float saturation = divisor > 0.0 ? hc.y / divisor : 0.0;
+  return vec3(hc.x, saturation, lightness);
+}
+
+vec3 rgb_to_hwb(vec3 rgb) {
+  float hi = max(rgb.r, max(rgb.g, rgb.b));
+  float lo = min(rgb.r, min(rgb.g, rgb.b));
+  vec2 hc = hue_chroma(rgb, hi, lo);
+  return vec3(hc.x, lo, 1.0 - hi);
+}
+
+vec3 hsv_to_rgb(vec3 hsv) {
+  vec3 k = mod(vec3(5.0, 3.0, 1.0) + hsv.x * 6.0, 6.0);
+  vec3 ramp = clamp(min(k, 4.0 - k), 0.0, 1.0);
+  return hsv.z - hsv.z * hsv.y * ramp;
+}
+
+vec3 hsl_to_rgb(vec3 hsl) {
+  vec3 k = mod(vec3(0.0, 8.0, 4.0) + hsl.x * 12.0, 12.0);
+  float a = hsl.y * min(hsl.z, 1.0 - hsl.z);
+  vec3 ramp = clamp(min(k - 3.0, 9.0 - k), -1.0, 1.0);
+  return hsl.z - a * ramp;
+}
+
+vec3 hwb_to_rgb(vec3 hwb) {
+  float gray = hwb.y + hwb.z;
+  if (gray >= 1.0) {
+    return vec3(hwb.y / gray);
+  }
+
+  return hsv_to_rgb(vec3(hwb.x, 1.0, 1.0)) * (1.0 - gray) + hwb.y;
+}
+
+vec3 encode(vec3 rgb) {
+  if (u_hsl.mode.y == MODEL_HSV) {
+    return rgb_to_hsv(rgb);
+  } else if (u_hsl.mode.y == MODEL_HSL) {
+    return rgb_to_hsl(rgb);
+  } else {
+    return rgb_to_hwb(rgb);
+  }
+}
+
+vec3 decode(vec3 values) {
+  if (u_hsl.mode.y == MODEL_HSV) {
+    return hsv_to_rgb(values);
+  } else if (u_hsl.mode.y == MODEL_HSL) {
+    return hsl_to_rgb(values);
+  } else {
+    return hwb_to_rgb(values);
+  }
+}
+
+void main() {
+  vec4 rgba = texture(sampler2D(in_texture, texture_sampler), uv).rgba;
+  vec3 result;
+
+  if (u_hsl.mode.x == OPERATION_ENCODE) {
+    result = encode(rgba.rgb);
+  } else if (u_hsl.mode.x == OPERATION_DECODE) {
+    result = decode(rgba.rgb);
+  } else {
+    vec3 hsl = rgb_to_hsl(rgba.rgb);
+    hsl.x = fract(hsl.x + u_hsl.adjust.x);
+    hsl.yz = clamp(hsl.yz * u_hsl.adjust.yz, 0.0, 1.0);
+    result = hsl_to_rgb(hsl);
+  }
+
+  f_color = vec4(result, rgba.a);
+}
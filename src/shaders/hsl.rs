@@ -0,0 +1,74 @@
+use std::borrow::Cow;
+
+use super::{BufferInitContent, FragmentShaderData, FragmentShaderKey};
+
+/// Convert between RGB and the cylindrical models of it.
+pub const SHADER: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/spirv/hsl.frag.v"));
+
+/// A cylindrical model of RGB, as enumerated in the shader.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub(crate) enum Model {
+    Hsv = 0,
+    Hsl = 1,
+    Hwb = 2,
+}
+
+/// The transformation of the shader, as enumerated in the shader.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Operation {
+    /// From RGB into the model.
+    Encode(Model),
+    /// From the model into RGB.
+    Decode(Model),
+    /// Shift the hue, and scale saturation and lightness in HSL.
+    Adjust {
+        /// The hue shift in turns.
+        hue_shift: f32,
+        saturation: f32,
+        lightness: f32,
+    },
+}
+
+/// The shader converting, or adjusting, colors in a cylindrical model.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Shader {
+    pub(crate) operation: Operation,
+}
+
+impl FragmentShaderData for Shader {
+    /// The unique key identifying this shader module.
+    fn key(&self) -> Option<FragmentShaderKey> {
+        Some(FragmentShaderKey::Hsl)
+    }
+
+    /// The SPIR-V shader source code.
+    fn spirv_source(&self) -> Cow<'static, [u8]> {
+        Cow::Borrowed(SHADER)
+    }
+
+    /// Encode the shader's data into the buffer, returning the descriptor to that.
+    fn binary_data(&self, buffer: &mut Vec<u8>) -> Option<BufferInitContent> {
+        let (mode, adjust) = match self.operation {
+            Operation::Encode(model) => ([0, model as u32, 0, 0], [0.0; 4]),
+            Operation::Decode(model) => ([1, model as u32, 0, 0], [0.0; 4]),
+            Operation::Adjust {
+                hue_shift,
+                saturation,
+                lightness,
+            } => (
+                [2, Model::Hsl as u32, 0, 0],
+                [hue_shift, saturation, lightness, 0.0],
+            ),
+        };
+
+        let mut buffer_content = BufferInitContent::builder(buffer);
+        buffer_content.extend_from_pods(&[mode]);
+        buffer_content.extend_from_pods(&[adjust]);
+        Some(buffer_content.build())
+    }
+
+    fn num_args(&self) -> u32 {
+        1
+    }
+}
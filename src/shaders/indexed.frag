@@ -0,0 +1,108 @@
+#version 450
+layout (location = 0) in vec2 uv;
+layout (location = 0) out vec4 f_color;
+
+layout (set = 1, binding = 0) uniform sampler texture_sampler;
+// When decoding the palette, when encoding the image.
+layout (set = 1, binding = 1) uniform texture2D lhs;
+// When decoding the indices, when encoding the palette.
+layout (set = 1, binding = 2) uniform texture2D rhs;
+
+layout (set = 2, binding = 0, std140) uniform Indexed {
+  // x: bits per index, y: width of the image in pixels, z: number of palette entries.
+  uvec4 form;
+  // From the linear RGB of image and palette to XYZ, relative to D65.
+  mat3 xyz_transform;
+} u_indexed;
+
+// The canonical Oklab matrices, given column-wise.
+const mat3x3 M1 = mat3x3(
+        +0.8189330101, +0.0329845436, +0.0482003018,
+        +0.3618667424, +0.9293118715, +0.2643662691,
+        -0.1288597137, +0.0361456387, +0.6338517070
+    );
+
+const mat3x3 M2 = mat3x3(
+        +0.2104542553, +1.9779984951, +0.0259040371,
+        +0.7936177850, -2.4285922050, +0.7827717662,
+        -0.0040720468, +0.4505937099, -0.8086757660
+    );
+
+uint index_bits() {
+  return u_indexed.form.x;
+}
+
+uint index_mask() {
+  return (1u << index_bits()) - 1u;
+}
+
+uint palette_len() {
+  return max(u_indexed.form.z, 1u);
+}
+
+vec4 to_oklab(vec4 rgba) {
+  vec3 lms = M1 * (mat3(u_indexed.xyz_transform) * rgba.rgb);
+  vec3 lms_star = pow(abs(lms), vec3(1.0 / 3.0)) * sign(lms);
+  return vec4(M2 * lms_star, rgba.a);
+}
+
+uint nearest_index(vec4 color) {
+  vec4 lab = to_oklab(color);
+  uint best = 0u;
+  float best_distance = 3.4e38;
+
+  for (uint i = 0u; i < palette_len(); i++) {
+    vec4 entry = texelFetch(sampler2D(rhs, texture_sampler), ivec2(i, 0), 0);
+    vec4 diff = to_oklab(entry) - lab;
+    float dist = dot(diff, diff);
+
+    if (dist < best_distance) {
+      best = i;
+      best_distance = dist;
+    }
+  }
+
+  return best;
+}
+
+#ifndef INDEXED_DECODE_AS_MAIN
+#define INDEXED_DECODE_AS_MAIN indexed_decode
+#endif
+#ifndef INDEXED_ENCODE_AS_MAIN
+#define INDEXED_ENCODE_AS_MAIN indexed_encode
+#endif
+
+// Paints the palette color of each pixel, from the bytes of packed indices.
+void INDEXED_DECODE_AS_MAIN() {
+  ivec2 pixel = ivec2(gl_FragCoord.xy);
+  uint bit = uint(pixel.x) * index_bits();
+
+  float code = texelFetch(sampler2D(rhs, texture_sampler), ivec2(bit / 8u, pixel.y), 0).r;
+  uint byte = uint(round(code * 255.0));
+
+  // The first pixel is in the most significant bits, as in PNG.
+  uint shift = 8u - index_bits() - bit % 8u;
+  uint index = min((byte >> shift) & index_mask(), palette_len() - 1u);
+
+  f_color = texelFetch(sampler2D(lhs, texture_sampler), ivec2(index, 0), 0);
+}
+
+// Paints the bytes of packed indices, choosing the nearest palette color in Oklab.
+void INDEXED_ENCODE_AS_MAIN() {
+  ivec2 texel = ivec2(gl_FragCoord.xy);
+  uint per_byte = 8u / index_bits();
+  uint byte = 0u;
+
+  for (uint i = 0u; i < per_byte; i++) {
+    uint x = uint(texel.x) * per_byte + i;
+    if (x >= u_indexed.form.y) {
+      break;
+    }
+
+    vec4 color = texelFetch(sampler2D(lhs, texture_sampler), ivec2(x, texel.y), 0);
+    uint shift = 8u - index_bits() * (i + 1u);
+    byte |= nearest_index(color) << shift;
+  }
+
+  f_color = vec4(vec3(float(byte) / 255.0), 1.0);
+}
@@ -0,0 +1,55 @@
+use std::borrow::Cow;
+
+use super::{BufferInitContent, Direction, FragmentShaderData, FragmentShaderKey};
+use crate::color_matrix::RowMatrix;
+
+/// Expand packed indices into the colors of a palette.
+pub const SHADER_DECODE: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/spirv/indexed_decode.frag.v"));
+/// Map colors to packed indices of the nearest palette color.
+pub const SHADER_ENCODE: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/spirv/indexed_encode.frag.v"));
+
+/// The shader between packed palette indices and colors.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Shader {
+    /// The number of bits of each index, dividing 8.
+    pub(crate) bits: u32,
+    /// The width of the image in pixels.
+    pub(crate) width: u32,
+    /// The number of colors in the palette.
+    pub(crate) palette_len: u32,
+    /// From the linear RGB of image and palette to XYZ relative to D65, for the nearest search.
+    pub(crate) xyz_transform: RowMatrix,
+    pub(crate) direction: Direction,
+}
+
+impl FragmentShaderData for Shader {
+    /// The unique key identifying this shader module.
+    fn key(&self) -> Option<FragmentShaderKey> {
+        Some(FragmentShaderKey::Indexed(self.direction))
+    }
+
+    /// The SPIR-V shader source code.
+    fn spirv_source(&self) -> Cow<'static, [u8]> {
+        Cow::Borrowed(match self.direction {
+            Direction::Decode => SHADER_DECODE,
+            Direction::Encode => SHADER_ENCODE,
+        })
+    }
+
+    /// Encode the shader's data into the buffer, returning the descriptor to that.
+    fn binary_data(&self, buffer: &mut Vec<u8>) -> Option<BufferInitContent> {
+        let form = [self.bits, self.width, self.palette_len, 0];
+        let matrix: [f32; 12] = self.xyz_transform.into_mat3x3_std140();
+
+        let mut content = BufferInitContent::builder(buffer);
+        content.extend_from_pods(&[form]);
+        content.extend_from_pods(&matrix);
+        Some(content.build())
+    }
+
+    fn num_args(&self) -> u32 {
+        2
+    }
+}
@@ -0,0 +1,147 @@
+#version 450
+layout (location = 0) in vec2 uv;
+layout (location = 0) out vec4 f_color;
+
+layout (set = 1, binding = 0) uniform sampler texture_sampler;
+// The image when initializing and assigning, the assignment when updating.
+layout (set = 1, binding = 1) uniform texture2D lhs;
+// The centroids when assigning and updating.
+layout (set = 1, binding = 2) uniform texture2D rhs;
+
+layout (set = 2, binding = 0, std140) uniform KMeans {
+  // x: number of centroids, yz: size of the grid of samples.
+  uvec4 form;
+  // From the linear RGB of the image to XYZ, relative to D65.
+  mat3 xyz_transform;
+} u_kmeans;
+
+// The canonical Oklab matrices, given column-wise.
+const mat3x3 M1 = mat3x3(
+        +0.8189330101, +0.0329845436, +0.0482003018,
+        +0.3618667424, +0.9293118715, +0.2643662691,
+        -0.1288597137, +0.0361456387, +0.6338517070
+    );
+
+const mat3x3 M2 = mat3x3(
+        +0.2104542553, +1.9779984951, +0.0259040371,
+        +0.7936177850, -2.4285922050, +0.7827717662,
+        -0.0040720468, +0.4505937099, -0.8086757660
+    );
+
+int centroids() {
+  return int(u_kmeans.form.x);
+}
+
+ivec2 grid() {
+  return ivec2(u_kmeans.form.yz);
+}
+
+vec4 to_oklab(vec4 rgba) {
+  vec3 lms = M1 * (mat3(u_kmeans.xyz_transform) * rgba.rgb);
+  vec3 lms_star = pow(abs(lms), vec3(1.0 / 3.0)) * sign(lms);
+  return vec4(M2 * lms_star, rgba.a);
+}
+
+// The color of a sample from the image, taken at the center of its cell of the grid.
+vec4 sample_oklab(ivec2 cell) {
+  ivec2 size = textureSize(sampler2D(lhs, texture_sampler), 0);
+  ivec2 coord = ivec2((vec2(cell) + 0.5) * vec2(size) / vec2(grid()));
+  return to_oklab(texelFetch(sampler2D(lhs, texture_sampler), coord, 0));
+}
+
+#ifndef KMEANS_INIT_AS_MAIN
+#define KMEANS_INIT_AS_MAIN kmeans_init
+#endif
+#ifndef KMEANS_ASSIGN_AS_MAIN
+#define KMEANS_ASSIGN_AS_MAIN kmeans_assign
+#endif
+#ifndef KMEANS_UPDATE_AS_MAIN
+#define KMEANS_UPDATE_AS_MAIN kmeans_update
+#endif
+
+// The further samples considered as centroids, visited in steps coprime to any size of the grid.
+const int MAX_SCAN = 1024;
+const int SCAN_STEP = 7919;
+
+// Paints the initial centroids, distinct colors of samples. Samples spread evenly over the grid
+// come first, then up to `MAX_SCAN` others spread over all of it. If fewer distinct colors are
+// found than centroids then the last of them repeats.
+void KMEANS_INIT_AS_MAIN() {
+  int k = int(gl_FragCoord.x);
+  int total = grid().x * grid().y;
+  int candidates = centroids() + min(total, MAX_SCAN);
+
+  // The colors of the distinct samples found so far.
+  vec4 seeds[256];
+  int found = 0;
+
+  for (int j = 0; j < candidates && found <= k; j++) {
+    int index = j < centroids()
+      ? (2 * j + 1) * total / (2 * centroids())
+      : (j - centroids()) * SCAN_STEP % total;
+    vec4 lab = sample_oklab(ivec2(index % grid().x, index / grid().x));
+
+    bool distinct = true;
+    for (int i = 0; i < found && distinct; i++) {
+      distinct = any(notEqual(seeds[i], lab));
+    }
+
+    if (distinct) {
+      seeds[found] = lab;
+      found++;
+    }
+  }
+
+  f_color = seeds[min(k, found - 1)];
+}
+
+// Paints two texels for each sample, its color and the index of the nearest centroid.
+void KMEANS_ASSIGN_AS_MAIN() {
+  ivec2 texel = ivec2(gl_FragCoord.xy);
+  vec4 lab = sample_oklab(ivec2(texel.x / 2, texel.y));
+
+  if (texel.x % 2 == 0) {
+    f_color = lab;
+    return;
+  }
+
+  int best = 0;
+  float best_distance = 3.4e38;
+
+  for (int i = 0; i < centroids(); i++) {
+    vec4 diff = texelFetch(sampler2D(rhs, texture_sampler), ivec2(i, 0), 0) - lab;
+    float dist = dot(diff, diff);
+
+    if (dist < best_distance) {
+      best = i;
+      best_distance = dist;
+    }
+  }
+
+  f_color = vec4(float(best), 0.0, 0.0, 1.0);
+}
+
+// Paints the mean of the samples assigned to each centroid.
+void KMEANS_UPDATE_AS_MAIN() {
+  int k = int(gl_FragCoord.x);
+  vec4 sum = vec4(0.0);
+  float count = 0.0;
+
+  for (int y = 0; y < grid().y; y++) {
+    for (int x = 0; x < grid().x; x++) {
+      float index = texelFetch(sampler2D(lhs, texture_sampler), ivec2(2 * x + 1, y), 0).r;
+
+      if (int(round(index)) == k) {
+        sum += texelFetch(sampler2D(lhs, texture_sampler), ivec2(2 * x, y), 0);
+        count += 1.0;
+      }
+    }
+  }
+
+  // A centroid without samples stays where it is.
+  if (count > 0.0) {
+    f_color = sum / count;
+  } else {
+    f_color = texelFetch(sampler2D(rhs, texture_sampler), ivec2(k, 0), 0);
+  }
+}
@@ -0,0 +1,68 @@
+use std::borrow::Cow;
+
+use super::{BufferInitContent, FragmentShaderData, FragmentShaderKey};
+use crate::color_matrix::RowMatrix;
+
+/// Pick initial centroids from samples of an image.
+pub const SHADER_INIT: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/spirv/kmeans_init.frag.v"));
+/// Assign samples of an image to their nearest centroid.
+pub const SHADER_ASSIGN: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/spirv/kmeans_assign.frag.v"));
+/// Move centroids to the mean of their assigned samples.
+pub const SHADER_UPDATE: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/spirv/kmeans_update.frag.v"));
+
+/// One of the passes of k-means clustering.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum Stage {
+    Init,
+    Assign,
+    Update,
+}
+
+/// The shader clustering the colors of an image in Oklab.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Shader {
+    pub(crate) stage: Stage,
+    /// The number of centroids.
+    pub(crate) centroids: u32,
+    /// The size of the grid of samples taken from the image.
+    pub(crate) grid: (u32, u32),
+    /// From the linear RGB of the image to XYZ relative to D65.
+    pub(crate) xyz_transform: RowMatrix,
+}
+
+impl FragmentShaderData for Shader {
+    /// The unique key identifying this shader module.
+    fn key(&self) -> Option<FragmentShaderKey> {
+        Some(FragmentShaderKey::KMeans(self.stage))
+    }
+
+    /// The SPIR-V shader source code.
+    fn spirv_source(&self) -> Cow<'static, [u8]> {
+        Cow::Borrowed(match self.stage {
+            Stage::Init => SHADER_INIT,
+            Stage::Assign => SHADER_ASSIGN,
+            Stage::Update => SHADER_UPDATE,
+        })
+    }
+
+    /// Encode the shader's data into the buffer, returning the descriptor to that.
+    fn binary_data(&self, buffer: &mut Vec<u8>) -> Option<BufferInitContent> {
+        let form = [self.centroids, self.grid.0, self.grid.1, 0];
+        let matrix: [f32; 12] = self.xyz_transform.into_mat3x3_std140();
+
+        let mut content = BufferInitContent::builder(buffer);
+        content.extend_from_pods(&[form]);
+        content.extend_from_pods(&matrix);
+        Some(content.build())
+    }
+
+    fn num_args(&self) -> u32 {
+        match self.stage {
+            Stage::Init => 1,
+            Stage::Assign | Stage::Update => 2,
+        }
+    }
+}
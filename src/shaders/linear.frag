@@ -7,11 +7,12 @@ layout (set = 1, binding = 1) uniform texture2D in_texture;
 
 layout (set = 2, binding = 0, std140) uniform Matrix {
   mat3 rgb_matrix;
+  vec4 offset;
 } color_matrix;
 
 void main() {
     mat3 color_mat = mat3(color_matrix.rgb_matrix);
 	
     vec4 rgba = texture(sampler2D(in_texture, texture_sampler), uv).rgba;
-    f_color = vec4(color_mat * rgba.rgb, rgba.a);
+    f_color = vec4(color_mat * rgba.rgb + color_matrix.offset.rgb, rgba.a);
 }
@@ -0,0 +1,79 @@
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use super::{BufferInitContent, FragmentShaderData, FragmentShaderKey};
+
+/// Paint a one-dimensional lookup table from uniform data into a texture.
+pub const SHADER_BAKE: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/spirv/lut1d_bake.frag.v"));
+/// Map each channel through the lookup table of the same channel.
+pub const SHADER_APPLY: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/spirv/lut1d_apply.frag.v"));
+
+/// The number of entries of a baked lookup table.
+///
+/// This must agree with the uniform array in `lut1d_bake.frag`.
+pub(crate) const LUT1D_SIZE: usize = 256;
+
+/// The shader constructing a `LUT1D_SIZE`×1 texture, one texel for each entry of the table.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Bake {
+    table: Arc<[[f32; 4]]>,
+}
+
+/// The shader sampling from an image and a lookup table created by `Bake`.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Apply;
+
+impl Bake {
+    /// Create the shader from the table.
+    ///
+    /// # Panics
+    /// When the table does not contain exactly `LUT1D_SIZE` entries.
+    pub fn new(table: Arc<[[f32; 4]]>) -> Self {
+        assert_eq!(table.len(), LUT1D_SIZE);
+        Bake { table }
+    }
+}
+
+impl FragmentShaderData for Bake {
+    /// The unique key identifying this shader module.
+    fn key(&self) -> Option<FragmentShaderKey> {
+        Some(FragmentShaderKey::Lut1dBake)
+    }
+
+    /// The SPIR-V shader source code.
+    fn spirv_source(&self) -> Cow<'static, [u8]> {
+        Cow::Borrowed(SHADER_BAKE)
+    }
+
+    /// Encode the shader's data into the buffer, returning the descriptor to that.
+    fn binary_data(&self, buffer: &mut Vec<u8>) -> Option<BufferInitContent> {
+        Some(BufferInitContent::new(buffer, &self.table[..]))
+    }
+
+    fn num_args(&self) -> u32 {
+        0
+    }
+}
+
+impl FragmentShaderData for Apply {
+    /// The unique key identifying this shader module.
+    fn key(&self) -> Option<FragmentShaderKey> {
+        Some(FragmentShaderKey::Lut1dApply)
+    }
+
+    /// The SPIR-V shader source code.
+    fn spirv_source(&self) -> Cow<'static, [u8]> {
+        Cow::Borrowed(SHADER_APPLY)
+    }
+
+    /// Encode the shader's data into the buffer, returning the descriptor to that.
+    fn binary_data(&self, buffer: &mut Vec<u8>) -> Option<BufferInitContent> {
+        let size = [LUT1D_SIZE as u32, 0, 0, 0];
+        Some(BufferInitContent::new(buffer, &size))
+    }
+
+    fn num_args(&self) -> u32 {
+        2
+    }
+}
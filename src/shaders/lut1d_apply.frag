@@ -0,0 +1,35 @@
+#version 450
+layout (location = 0) in vec2 uv;
+layout (location = 0) out vec4 f_color;
+
+layout (set = 1, binding = 0) uniform sampler texture_sampler;
+layout (set = 1, binding = 1) uniform texture2D in_texture;
+layout (set = 1, binding = 2) uniform texture2D lut;
+
+layout (set = 2, binding = 0, std140) uniform Lookup {
+  // x: the number of entries in the table.
+  uvec4 size;
+} u_lookup;
+
+// Interpolate linearly between neighboring entries. We fetch texels directly
+// so this does not depend on the filter mode of the sampler.
+float lookup(float value, int channel) {
+  int size = int(u_lookup.size.x);
+  float position = clamp(value, 0.0, 1.0) * float(size - 1);
+  int lower = int(floor(position));
+  int upper = min(lower + 1, size - 1);
+
+  float lo = texelFetch(sampler2D(lut, texture_sampler), ivec2(lower, 0), 0)[channel];
+  float hi = texelFetch(sampler2D(lut, texture_sampler), ivec2(upper, 0), 0)[channel];
+  return mix(lo, hi, position - float(lower));
+}
+
+void main() {
+  vec4 value = texture(sampler2D(in_texture, texture_sampler), uv);
+
+  f_color = vec4(
+    lookup(value.r, 0),
+    lookup(value.g, 1),
+    lookup(value.b, 2),
+    lookup(value.a, 3));
+}
@@ -0,0 +1,14 @@
+#version 450
+layout (location = 0) in vec2 uv;
+layout (location = 0) out vec4 f_color;
+
+// Must agree with `LUT1D_SIZE` in `lut1d.rs`.
+layout (set = 1, binding = 0, std140) uniform Table {
+  vec4 entries[256];
+} u_table;
+
+void main() {
+  // The lookup table is painted as a single row, one texel per entry.
+  int index = clamp(int(gl_FragCoord.x), 0, 255);
+  f_color = u_table.entries[index];
+}
@@ -0,0 +1,131 @@
+#version 450
+layout (location = 0) in vec2 uv;
+layout (location = 0) out vec4 f_color;
+
+layout (set = 1, binding = 0) uniform sampler texture_sampler;
+layout (set = 1, binding = 1) uniform texture2D in_texture;
+layout (set = 1, binding = 2) uniform texture2D lut_texture;
+
+// This must agree with `SHAPER_SIZE` in `lut3d.rs`.
+const int SHAPER_SIZE = 512;
+
+layout (set = 2, binding = 0, std140) uniform Lut3d {
+  vec4 domain_min;
+  vec4 domain_max;
+  vec4 shaper_min;
+  vec4 shaper_max;
+  // x: samples per axis, y: tiles per row of the texture, z: interpolation,
+  // w: entries of the shaper, or zero without one.
+  uvec4 size;
+  vec4 shaper[SHAPER_SIZE];
+} u_lut;
+
+const uint INTERPOLATION_TRILINEAR = 0u;
+const uint INTERPOLATION_TETRAHEDRAL = 1u;
+
+vec3 shape(vec3 rgb) {
+  int last = int(u_lut.size.w) - 1;
+  vec3 range = u_lut.shaper_max.rgb - u_lut.shaper_min.rgb;
+  vec3 pos = clamp((rgb - u_lut.shaper_min.rgb) / range, 0.0, 1.0) * float(last);
+  ivec3 lo = ivec3(floor(pos));
+  ivec3 hi = min(lo + 1, ivec3(last));
+  vec3 t = pos - vec3(lo);
+
+  return vec3(
+    mix(u_lut.shaper[lo.r].r, u_lut.shaper[hi.r].r, t.r),
+    mix(u_lut.shaper[lo.g].g, u_lut.shaper[hi.g].g, t.g),
+    mix(u_lut.shaper[lo.b].b, u_lut.shaper[hi.b].b, t.b)
+  );
+}
+
+// The cube is stored as slices of constant blue, tiled in rows over the texture.
+vec3 entry(ivec3 idx) {
+  int size = int(u_lut.size.x);
+  int tiles = int(u_lut.size.y);
+  ivec2 tile = ivec2(idx.b % tiles, idx.b / tiles);
+  ivec2 coord = tile * size + idx.rg;
+  return texelFetch(sampler2D(lut_texture, texture_sampler), coord, 0).rgb;
+}
+
+vec3 trilinear(ivec3 lo, ivec3 hi, vec3 f) {
+  vec3 c000 = entry(lo);
+  vec3 c100 = entry(ivec3(hi.r, lo.g, lo.b));
+  vec3 c010 = entry(ivec3(lo.r, hi.g, lo.b));
+  vec3 c110 = entry(ivec3(hi.r, hi.g, lo.b));
+  vec3 c001 = entry(ivec3(lo.r, lo.g, hi.b));
+  vec3 c101 = entry(ivec3(hi.r, lo.g, hi.b));
+  vec3 c011 = entry(ivec3(lo.r, hi.g, hi.b));
+  vec3 c111 = entry(hi);
+
+  vec3 c00 = mix(c000, c100, f.r);
+  vec3 c10 = mix(c010, c110, f.r);
+  vec3 c01 = mix(c001, c101, f.r);
+  vec3 c11 = mix(c011, c111, f.r);
+
+  vec3 c0 = mix(c00, c10, f.g);
+  vec3 c1 = mix(c01, c11, f.g);
+
+  return mix(c0, c1, f.b);
+}
+
+// Interpolate within one of the six tetrahedra sharing the main diagonal of the cell.
+vec3 tetrahedral(ivec3 lo, ivec3 hi, vec3 f) {
+  vec3 c000 = entry(lo);
+  vec3 c111 = entry(hi);
+
+  if (f.r >= f.g) {
+    if (f.g >= f.b) {
+      vec3 c100 = entry(ivec3(hi.r, lo.g, lo.b));
+      vec3 c110 = entry(ivec3(hi.r, hi.g, lo.b));
+      return (1.0 - f.r) * c000 + (f.r - f.g) * c100 + (f.g - f.b) * c110 + f.b * c111;
+    } else if (f.r >= f.b) {
+      vec3 c100 = entry(ivec3(hi.r, lo.g, lo.b));
+      vec3 c101 = entry(ivec3(hi.r, lo.g, hi.b));
+      return (1.0 - f.r) * c000 + (f.r - f.b) * c100 + (f.b - f.g) * c101 + f.g * c111;
+    } else {
+      vec3 c001 = entry(ivec3(lo.r, lo.g, hi.b));
+      vec3 c101 = entry(ivec3(hi.r, lo.g, hi.b));
+      return (1.0 - f.b) * c000 + (f.b - f.r) * c001 + (f.r - f.g) * c101 + f.g * c111;
+    }
+  } else {
+    if (f.b > f.g) {
+      vec3 c001 = entry(ivec3(lo.r, lo.g, hi.b));
+      vec3 c011 = entry(ivec3(lo.r, hi.g, hi.b));
+      return (1.0 - f.b) * c000 + (f.b - f.g) * c001 + (f.g - f.r) * c011 + f.r * c111;
+    } else if (f.b > f.r) {
+      vec3 c010 = entry(ivec3(lo.r, hi.g, lo.b));
+      vec3 c011 = entry(ivec3(lo.r, hi.g, hi.b));
+      return (1.0 - f.g) * c000 + (f.g - f.b) * c010 + (f.b - f.r) * c011 + f.r * c111;
+    } else {
+      vec3 c010 = entry(ivec3(lo.r, hi.g, lo.b));
+      vec3 c110 = entry(ivec3(hi.r, hi.g, lo.b));
+      return (1.0 - f.g) * c000 + (f.g - f.r) * c010 + (f.r - f.b) * c110 + f.b * c111;
+    }
+  }
+}
+
+void main() {
+  vec4 rgba = texture(sampler2D(in_texture, texture_sampler), uv).rgba;
+  vec3 rgb = rgba.rgb;
+
+  if (u_lut.size.w != 0u) {
+    rgb = shape(rgb);
+  }
+
+  int size = int(u_lut.size.x);
+  vec3 range = u_lut.domain_max.rgb - u_lut.domain_min.rgb;
+  vec3 pos = clamp((rgb - u_lut.domain_min.rgb) / range, 0.0, 1.0) * float(size - 1);
+
+  ivec3 lo = min(ivec3(floor(pos)), ivec3(size - 1));
+  ivec3 hi = min(lo + 1, ivec3(size - 1));
+  vec3 f = pos - vec3(lo);
+
+  vec3 mapped;
+  if (u_lut.size.z == INTERPOLATION_TETRAHEDRAL) {
+    mapped = tetrahedral(lo, hi, f);
+  } else {
+    mapped = trilinear(lo, hi, f);
+  }
+
+  f_color = vec4(mapped, rgba.a);
+}
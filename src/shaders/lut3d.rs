@@ -0,0 +1,79 @@
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use super::{BufferInitContent, FragmentShaderData, FragmentShaderKey};
+
+/// Map colors through a three-dimensional lookup table, stored as a tiled texture.
+pub const SHADER: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/spirv/lut3d.frag.v"));
+
+/// The largest number of entries of the shaper, the size of its uniform array.
+///
+/// This must agree with the uniform array in `lut3d.frag`.
+pub(crate) const SHAPER_SIZE: usize = 512;
+
+/// The shader sampling from an image and a lookup table texture.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Shader {
+    /// The input values mapped to the first and last sample of the cube on each axis.
+    pub(crate) domain: [[f32; 3]; 2],
+    /// The number of samples along each axis of the cube.
+    pub(crate) size: u32,
+    /// The number of slices of the cube that are arranged next to each other in the texture.
+    pub(crate) tiles_per_row: u32,
+    /// The interpolation, as enumerated in the shader.
+    pub(crate) interpolation: u32,
+    /// A per-channel curve applied before the cube, with its input domain.
+    pub(crate) shaper: Option<Shaper>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Shaper {
+    pub(crate) domain: [[f32; 3]; 2],
+    /// The number of entries that are interpolated, at least two.
+    pub(crate) len: u32,
+    /// Exactly `SHAPER_SIZE` entries, padded after the first `len`.
+    pub(crate) table: Arc<[[f32; 4]]>,
+}
+
+impl FragmentShaderData for Shader {
+    /// The unique key identifying this shader module.
+    fn key(&self) -> Option<FragmentShaderKey> {
+        Some(FragmentShaderKey::Lut3d)
+    }
+
+    /// The SPIR-V shader source code.
+    fn spirv_source(&self) -> Cow<'static, [u8]> {
+        Cow::Borrowed(SHADER)
+    }
+
+    /// Encode the shader's data into the buffer, returning the descriptor to that.
+    fn binary_data(&self, buffer: &mut Vec<u8>) -> Option<BufferInitContent> {
+        let vec4 = |[r, g, b]: [f32; 3]| [r, g, b, 0.0];
+        let [min, max] = self.domain;
+        let [shaper_min, shaper_max] = self
+            .shaper
+            .as_ref()
+            .map_or([[0.0; 3], [1.0; 3]], |shaper| shaper.domain);
+
+        let mut buffer_content = BufferInitContent::builder(buffer);
+        buffer_content.extend_from_pods(&[vec4(min), vec4(max)]);
+        buffer_content.extend_from_pods(&[vec4(shaper_min), vec4(shaper_max)]);
+        buffer_content.extend_from_pods(&[
+            self.size,
+            self.tiles_per_row,
+            self.interpolation,
+            self.shaper.as_ref().map_or(0, |shaper| shaper.len),
+        ]);
+
+        match &self.shaper {
+            Some(shaper) => buffer_content.extend_from_pods(&shaper.table[..]),
+            None => buffer_content.extend_from_pods(&[[0.0f32; 4]; SHAPER_SIZE]),
+        }
+
+        Some(buffer_content.build())
+    }
+
+    fn num_args(&self) -> u32 {
+        2
+    }
+}
@@ -5,14 +5,30 @@ use std::sync::Arc;
 
 pub mod bilinear;
 pub mod box3;
+pub mod channel_affine;
+pub mod channels;
+pub mod cielab;
+pub mod convolve;
 pub mod distribution_normal2d;
+pub mod dither;
 pub mod fractal_noise;
+pub mod hsl;
+pub mod indexed;
 pub mod inject;
+pub mod kmeans;
+pub mod lut1d;
+pub mod lut3d;
+pub mod morphology;
 pub mod oklab;
+pub mod orient;
+pub mod pad;
 pub mod palette;
+pub mod perspective;
 pub mod solid_rgb;
 pub mod srlab2;
 pub mod stage;
+pub mod tone_map;
+pub mod warp;
 
 /// A vertex box shader, rendering a sole quad with given vertex and uv coordinate system.
 pub const VERT_NOOP: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/spirv/box.vert.v"));
@@ -58,6 +74,10 @@ pub(crate) trait FragmentShaderData: core::fmt::Debug {
     fn num_args(&self) -> u32 {
         1
     }
+    /// How the argument images are sampled outside of their bounds.
+    fn address_mode(&self) -> wgpu::AddressMode {
+        wgpu::AddressMode::ClampToEdge
+    }
 }
 
 impl FragmentShaderData for ShaderInvocation {
@@ -107,12 +127,46 @@ pub(crate) enum FragmentShaderKey {
     Bilinear,
     /// A shader mixing two colors, logically injecting ones channel into the other.
     Inject,
+    /// A shader selecting each channel from the channels of its inputs.
+    Channels(channels::Inputs),
     /// A shader transforming between XYZ and Oklab color space.
     OklabTransform(Direction),
     /// A shader transforming between XYZ and SrLab2 color space.
     Srlab2Transform(Direction),
+    /// A shader transforming between XYZ and CIE L*a*b* or L*u*v* color space.
+    CieLabTransform(Direction),
+    /// A shader converting between RGB and its cylindrical models, HSV, HSL and HWB.
+    Hsl,
+    /// A shader between packed palette indices and colors.
+    Indexed(Direction),
+    /// A pass of k-means clustering of colors in Oklab.
+    KMeans(kmeans::Stage),
     /// A convolution with a 3-by-3 box function.
     Box3,
+    /// Quantize channels to a bit depth or colors to a palette, with ordered noise.
+    Dither(dither::Kind),
+    /// Move texels to exact integer coordinates, flipped or rotated.
+    Orient,
+    /// Extend an image beyond its bounds, by its sampler or a constant.
+    Pad,
+    /// Paint an image through a projective transformation of coordinates.
+    Perspective,
+    /// Sample an image at coordinates moved by a lens model or a displacement map.
+    Warp(warp::Kind),
+    /// Resample each color channel through its own affine transformation.
+    ChannelAffine,
+    /// A convolution with an arbitrary kernel.
+    Convolve,
+    /// A pass of grayscale morphology.
+    Morphology(morphology::Kind),
+    /// A curve mapping scene-referred light into the display range.
+    ToneMap,
+    /// Paint a one-dimensional lookup table into a texture.
+    Lut1dBake,
+    /// Map channels through a one-dimensional lookup table.
+    Lut1dApply,
+    /// Map colors through a three-dimensional lookup table.
+    Lut3d,
     /// The key is the address of some dynamic object, unique for the duration of the pipeline.
     /// One shouldn't rely on uniqueness of soundness.
     Dynamic(usize),
@@ -140,10 +194,27 @@ pub(crate) enum FragmentShader {
     Palette(self::palette::Shader),
     Bilinear(self::bilinear::Shader),
     Inject(self::inject::Shader),
+    Channels(Box<self::channels::Shader>),
     Oklab(self::oklab::Shader),
     SrLab2(self::srlab2::Shader),
+    CieLab(self::cielab::Shader),
+    Hsl(self::hsl::Shader),
+    Indexed(self::indexed::Shader),
+    KMeans(self::kmeans::Shader),
     Box3(self::box3::Shader),
+    Dither(self::dither::Shader),
+    Orient(self::orient::Shader),
+    Pad(self::pad::Shader),
+    Perspective(self::perspective::Shader),
+    Warp(self::warp::Shader),
+    ChannelAffine(self::channel_affine::Shader),
+    Convolve(self::convolve::Shader),
+    Morphology(Box<self::morphology::Shader>),
     SolidRgb(self::solid_rgb::Shader),
+    ToneMap(self::tone_map::Shader),
+    Lut1dBake(self::lut1d::Bake),
+    Lut1dApply(self::lut1d::Apply),
+    Lut3d(self::lut3d::Shader),
     Dynamic(ShaderInvocation),
 }
 
@@ -157,10 +228,27 @@ impl FragmentShader {
             FragmentShader::Palette(palette) => palette,
             FragmentShader::Bilinear(bilinear) => bilinear,
             FragmentShader::Inject(inject) => inject,
+            FragmentShader::Channels(channels) => &**channels,
             FragmentShader::Oklab(oklab) => oklab,
             FragmentShader::SrLab2(srlab2) => srlab2,
+            FragmentShader::CieLab(cielab) => cielab,
+            FragmentShader::Hsl(hsl) => hsl,
+            FragmentShader::Indexed(indexed) => indexed,
+            FragmentShader::KMeans(kmeans) => kmeans,
             FragmentShader::Box3(box3) => box3,
+            FragmentShader::Dither(dither) => dither,
+            FragmentShader::Orient(orient) => orient,
+            FragmentShader::Pad(pad) => pad,
+            FragmentShader::Perspective(perspective) => perspective,
+            FragmentShader::Warp(warp) => warp,
+            FragmentShader::ChannelAffine(affine) => affine,
+            FragmentShader::Convolve(convolve) => convolve,
+            FragmentShader::Morphology(morphology) => &**morphology,
             FragmentShader::SolidRgb(color) => color,
+            FragmentShader::ToneMap(tone_map) => tone_map,
+            FragmentShader::Lut1dBake(bake) => bake,
+            FragmentShader::Lut1dApply(apply) => apply,
+            FragmentShader::Lut3d(lut) => lut,
             FragmentShader::Dynamic(dynamic) => dynamic,
         }
     }
@@ -192,6 +280,8 @@ impl FragmentShaderData for PaintOnTopKind {
 #[derive(Clone, Debug, PartialEq)]
 pub(crate) struct LinearColorTransform {
     pub matrix: RowMatrix,
+    /// Added after the matrix, making this an affine transform.
+    pub offset: [f32; 3],
 }
 
 impl FragmentShaderData for LinearColorTransform {
@@ -205,10 +295,16 @@ impl FragmentShaderData for LinearColorTransform {
 
     fn binary_data(&self, buffer: &mut Vec<u8>) -> Option<BufferInitContent> {
         let rgb_matrix: [f32; 12] = self.matrix.into_mat3x3_std140();
-        Some(BufferInitContent::new(buffer, &rgb_matrix))
+        let [x, y, z] = self.offset;
+
+        let mut content = BufferInitContent::builder(buffer);
+        content.extend_from_pods(&rgb_matrix);
+        content.extend_from_pods(&[x, y, z, 0.0]);
+        Some(content.build())
     }
 }
 
 pub(crate) use self::distribution_normal2d::Shader as DistributionNormal2d;
 pub(crate) use self::fractal_noise::Shader as FractalNoise;
 pub(crate) use self::palette::Shader as PaletteShader;
+pub(crate) use self::tone_map::Shader as ToneMapShader;
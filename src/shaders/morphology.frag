@@ -0,0 +1,164 @@
+#version 450
+layout (location = 0) in vec2 uv;
+layout (location = 0) out vec4 f_color;
+
+layout (set = 1, binding = 0) uniform sampler texture_sampler;
+// The image to filter, or the backward scan when merging.
+layout (set = 1, binding = 1) uniform texture2D in_image;
+// The forward scan when merging, the image to subtract for a difference.
+layout (set = 1, binding = 2) uniform texture2D in_other;
+
+layout (set = 2, binding = 0, std140) uniform Morphology {
+  // x: erode or dilate, y: the axis of a pass, 0 along rows and 1 along columns,
+  // z: the radius of a pass, w: the direction of a scan, 0 forward and 1 backward.
+  ivec4 mode;
+  // xy: the width and height of the structuring element, z: the offset of a scan step.
+  ivec4 size;
+  // Bit x of row y is set if the structuring element contains that pixel.
+  uvec4 rows[8];
+} u_morphology;
+
+const int ERODE = 0;
+const int DILATE = 1;
+
+ivec2 image_size() {
+  return textureSize(sampler2D(in_image, texture_sampler), 0);
+}
+
+bool inside(ivec2 coord) {
+  return all(greaterThanEqual(coord, ivec2(0))) && all(lessThan(coord, image_size()));
+}
+
+vec4 fetch(ivec2 coord) {
+  return texelFetch(sampler2D(in_image, texture_sampler), coord, 0);
+}
+
+vec4 fetch_other(ivec2 coord) {
+  return texelFetch(sampler2D(in_other, texture_sampler), coord, 0);
+}
+
+vec3 extremum(vec3 a, vec3 b) {
+  return u_morphology.mode.x == DILATE ? max(a, b) : min(a, b);
+}
+
+// The coordinate of a pixel moved along the axis of a one-dimensional pass.
+ivec2 along(ivec2 coord, int position) {
+  if (u_morphology.mode.y == 0) {
+    return ivec2(position, coord.y);
+  } else {
+    return ivec2(coord.x, position);
+  }
+}
+
+#ifndef MORPHOLOGY_ELEMENT_AS_MAIN
+#define MORPHOLOGY_ELEMENT_AS_MAIN morphology_element
+#endif
+#ifndef MORPHOLOGY_SCAN_AS_MAIN
+#define MORPHOLOGY_SCAN_AS_MAIN morphology_scan
+#endif
+#ifndef MORPHOLOGY_MERGE_AS_MAIN
+#define MORPHOLOGY_MERGE_AS_MAIN morphology_merge
+#endif
+#ifndef MORPHOLOGY_DIFFERENCE_AS_MAIN
+#define MORPHOLOGY_DIFFERENCE_AS_MAIN morphology_difference
+#endif
+
+// The extremum of the pixels covered by the structuring element, ignoring those outside the image.
+// Dilation uses the element reflected through its center.
+void MORPHOLOGY_ELEMENT_AS_MAIN() {
+  ivec2 coord = ivec2(gl_FragCoord.xy);
+  ivec2 size = u_morphology.size.xy;
+  ivec2 radius = size / 2;
+  int mirror = u_morphology.mode.x == DILATE ? -1 : 1;
+
+  vec4 center = fetch(coord);
+  vec3 result = center.rgb;
+  bool found = false;
+
+  for (int y = 0; y < size.y; y++) {
+    uint row = u_morphology.rows[y / 4][y % 4];
+    for (int x = 0; x < size.x; x++) {
+      if ((row & (1u << uint(x))) == 0u) {
+        continue;
+      }
+
+      ivec2 texel = coord + mirror * (ivec2(x, y) - radius);
+      if (!inside(texel)) {
+        continue;
+      }
+
+      vec3 value = fetch(texel).rgb;
+      result = found ? extremum(result, value) : value;
+      found = true;
+    }
+  }
+
+  f_color = vec4(result, center.a);
+}
+
+// One step of a segmented scan, combining each pixel with the one an offset before or after it in
+// the same block. Steps with offsets 1, 2, 4, and so on up to the block length yield the extremum
+// from the start of the pixel's block up to it, or from it to the end of the block. Blocks are as
+// long as the window of the pass, van Herk/Gil-Werman.
+void MORPHOLOGY_SCAN_AS_MAIN() {
+  ivec2 coord = ivec2(gl_FragCoord.xy);
+  int axis = u_morphology.mode.y;
+  int block = 2 * u_morphology.mode.z + 1;
+  int offset = u_morphology.size.z;
+  int position = coord[axis];
+  int start = position / block * block;
+
+  bool within;
+  int other;
+  if (u_morphology.mode.w == 0) {
+    other = position - offset;
+    within = other >= start;
+  } else {
+    other = position + offset;
+    within = other - start < block && other < image_size()[axis];
+  }
+
+  vec4 center = fetch(coord);
+  vec3 result = center.rgb;
+  if (within) {
+    result = extremum(result, fetch(along(coord, other)).rgb);
+  }
+
+  f_color = vec4(result, center.a);
+}
+
+// Combine the backward scan at the start of the window with the forward scan at its end.
+void MORPHOLOGY_MERGE_AS_MAIN() {
+  ivec2 coord = ivec2(gl_FragCoord.xy);
+  int axis = u_morphology.mode.y;
+  int radius = u_morphology.mode.z;
+  int block = 2 * radius + 1;
+  int last = image_size()[axis] - 1;
+
+  int first_pos = coord[axis] - radius;
+  int last_pos = coord[axis] + radius;
+
+  vec4 center = fetch(coord);
+  vec3 result;
+  if (first_pos < 0) {
+    // The window is clipped to the first block, which its end reaches into.
+    result = fetch_other(along(coord, min(last_pos, last))).rgb;
+  } else if (last_pos > last && first_pos / block == last / block) {
+    // The window is clipped to the last block, which the backward scan covers.
+    result = fetch(along(coord, first_pos)).rgb;
+  } else {
+    vec3 backward = fetch(along(coord, first_pos)).rgb;
+    vec3 forward = fetch_other(along(coord, min(last_pos, last))).rgb;
+    result = extremum(backward, forward);
+  }
+
+  f_color = vec4(result, center.a);
+}
+
+// Subtract the colors of the second image from the first, keeping the alpha of the first.
+void MORPHOLOGY_DIFFERENCE_AS_MAIN() {
+  ivec2 coord = ivec2(gl_FragCoord.xy);
+  vec4 minuend = fetch(coord);
+  vec4 subtrahend = fetch_other(coord);
+  f_color = vec4(minuend.rgb - subtrahend.rgb, minuend.a);
+}
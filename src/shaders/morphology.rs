@@ -0,0 +1,120 @@
+use std::borrow::Cow;
+
+use super::{BufferInitContent, FragmentShaderData, FragmentShaderKey};
+
+/// The extremum under a structuring element of up to 31 by 31 pixels.
+pub const SHADER_ELEMENT: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/spirv/morphology_element.frag.v"));
+/// A step of the extremum within blocks along an axis, up to or from each pixel.
+pub const SHADER_SCAN: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/spirv/morphology_scan.frag.v"));
+/// The extremum of a window combined from a backward and a forward scan.
+pub const SHADER_MERGE: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/spirv/morphology_merge.frag.v"));
+/// The difference of the colors of two images.
+pub const SHADER_DIFFERENCE: &[u8] = include_bytes!(concat!(
+    env!("OUT_DIR"),
+    "/spirv/morphology_difference.frag.v"
+));
+
+/// The largest width and height of a structuring element.
+pub(crate) const MAX_SIZE: u32 = 31;
+
+/// The pass of a morphological operation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum Kind {
+    Element,
+    Scan,
+    Merge,
+    Difference,
+}
+
+/// Whether the minimum or the maximum is taken.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum Operation {
+    Erode = 0,
+    Dilate = 1,
+}
+
+/// The axis and window of a one-dimensional pass.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct Pass {
+    /// Along rows if `false`, along columns if `true`.
+    pub(crate) vertical: bool,
+    /// The window extends this many pixels to each side.
+    pub(crate) radius: u32,
+    /// Whether a scan runs to the end instead of from the start of each block.
+    pub(crate) backward: bool,
+    /// The distance to the pixel combined in a step of a scan.
+    pub(crate) offset: u32,
+}
+
+/// The shader of grayscale morphology, applied to each color channel and keeping alpha.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct Shader {
+    pub(crate) kind: Kind,
+    pub(crate) operation: Operation,
+    /// The one-dimensional pass, for scans and merges.
+    pub(crate) pass: Pass,
+    /// The width and height of the structuring element, both odd.
+    pub(crate) size: [u32; 2],
+    /// Bit x of each row is set if the structuring element contains that pixel.
+    pub(crate) rows: [u32; MAX_SIZE as usize],
+}
+
+impl Shader {
+    /// A pass that does not use the structuring element.
+    pub(crate) fn with_pass(kind: Kind, operation: Operation, pass: Pass) -> Self {
+        Shader {
+            kind,
+            operation,
+            pass,
+            size: [0; 2],
+            rows: [0; MAX_SIZE as usize],
+        }
+    }
+}
+
+impl FragmentShaderData for Shader {
+    /// The unique key identifying this shader module.
+    fn key(&self) -> Option<FragmentShaderKey> {
+        Some(FragmentShaderKey::Morphology(self.kind))
+    }
+
+    /// The SPIR-V shader source code.
+    fn spirv_source(&self) -> Cow<'static, [u8]> {
+        Cow::Borrowed(match self.kind {
+            Kind::Element => SHADER_ELEMENT,
+            Kind::Scan => SHADER_SCAN,
+            Kind::Merge => SHADER_MERGE,
+            Kind::Difference => SHADER_DIFFERENCE,
+        })
+    }
+
+    /// Encode the shader's data into the buffer, returning the descriptor to that.
+    fn binary_data(&self, buffer: &mut Vec<u8>) -> Option<BufferInitContent> {
+        let mode = [
+            self.operation as u32,
+            u32::from(self.pass.vertical),
+            self.pass.radius,
+            u32::from(self.pass.backward),
+        ];
+        let size = [self.size[0], self.size[1], self.pass.offset, 0];
+
+        let mut rows = [0u32; 32];
+        rows[..self.rows.len()].copy_from_slice(&self.rows);
+
+        let mut content = BufferInitContent::builder(buffer);
+        content.extend_from_pods(&mode);
+        content.extend_from_pods(&size);
+        content.extend_from_pods(&rows);
+        Some(content.build())
+    }
+
+    fn num_args(&self) -> u32 {
+        match self.kind {
+            Kind::Element | Kind::Scan => 1,
+            Kind::Merge | Kind::Difference => 2,
+        }
+    }
+}
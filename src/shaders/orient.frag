@@ -0,0 +1,22 @@
+#version 450
+layout (location = 0) in vec2 uv;
+layout (location = 0) out vec4 f_color;
+
+layout (set = 1, binding = 0) uniform sampler texture_sampler;
+layout (set = 1, binding = 1) uniform texture2D in_texture;
+
+layout (set = 2, binding = 0, std140) uniform Orient {
+  // The input coordinate of each output texel is an integer affine function of the output
+  // coordinate: x' = dot(x.xy, coord) + x.z, respectively for y.
+  ivec4 x;
+  ivec4 y;
+} u_orient;
+
+void main() {
+  ivec2 coord = ivec2(gl_FragCoord.xy);
+  ivec2 source = ivec2(
+    u_orient.x.x * coord.x + u_orient.x.y * coord.y + u_orient.x.z,
+    u_orient.y.x * coord.x + u_orient.y.y * coord.y + u_orient.y.z);
+
+  f_color = texelFetch(sampler2D(in_texture, texture_sampler), source, 0);
+}
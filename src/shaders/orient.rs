@@ -0,0 +1,39 @@
+use std::borrow::Cow;
+
+use super::{BufferInitContent, FragmentShaderData, FragmentShaderKey};
+
+/// Move texels to other integer coordinates, for flips and rotations by quarter turns.
+pub const SHADER: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/spirv/orient.frag.v"));
+
+/// The shader fetching each texel from an integer affine function of its coordinate.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Shader {
+    /// The source column, as weights of the output column and row and an offset.
+    pub(crate) x: [i32; 3],
+    /// The source row, as weights of the output column and row and an offset.
+    pub(crate) y: [i32; 3],
+}
+
+impl FragmentShaderData for Shader {
+    /// The unique key identifying this shader module.
+    fn key(&self) -> Option<FragmentShaderKey> {
+        Some(FragmentShaderKey::Orient)
+    }
+
+    /// The SPIR-V shader source code.
+    fn spirv_source(&self) -> Cow<'static, [u8]> {
+        Cow::Borrowed(SHADER)
+    }
+
+    /// Encode the shader's data into the buffer, returning the descriptor to that.
+    fn binary_data(&self, buffer: &mut Vec<u8>) -> Option<BufferInitContent> {
+        let [xx, xy, xc] = self.x;
+        let [yx, yy, yc] = self.y;
+        let rows = [[xx, xy, xc, 0], [yx, yy, yc, 0]];
+        Some(BufferInitContent::new(buffer, &rows))
+    }
+
+    fn num_args(&self) -> u32 {
+        1
+    }
+}
@@ -0,0 +1,27 @@
+#version 450
+layout (location = 0) in vec2 uv;
+layout (location = 0) out vec4 f_color;
+
+layout (set = 1, binding = 0) uniform sampler texture_sampler;
+layout (set = 1, binding = 1) uniform texture2D in_texture;
+
+layout (set = 2, binding = 0, std140) uniform Pad {
+  // xy: the position of the image in the output, zw: the size of the image.
+  vec4 placement;
+  // The color outside the image, if the constant is used.
+  vec4 constant;
+  // x: non-zero to paint the constant outside the image instead of sampling.
+  uvec4 mode;
+} u_pad;
+
+void main() {
+  // Pixel centers map to texel centers, the sampler extends the image beyond its bounds.
+  vec2 coord = (gl_FragCoord.xy - u_pad.placement.xy) / u_pad.placement.zw;
+  bool outside = any(lessThan(coord, vec2(0.0))) || any(greaterThanEqual(coord, vec2(1.0)));
+
+  if (u_pad.mode.x != 0u && outside) {
+    f_color = u_pad.constant;
+  } else {
+    f_color = texture(sampler2D(in_texture, texture_sampler), coord);
+  }
+}
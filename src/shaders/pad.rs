@@ -0,0 +1,56 @@
+use std::borrow::Cow;
+
+use super::{BufferInitContent, FragmentShaderData, FragmentShaderKey};
+
+/// Place an image within a larger one, extending it by its sampler or a constant color.
+pub const SHADER: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/spirv/pad.frag.v"));
+
+/// The shader extending an image beyond its bounds.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Shader {
+    /// The position of the image within the output, in texels.
+    pub(crate) offset: [u32; 2],
+    /// The size of the image, in texels.
+    pub(crate) size: [u32; 2],
+    /// The color outside the image, or `None` to extend it with the address mode.
+    pub(crate) constant: Option<[f32; 4]>,
+    /// How the image is sampled outside its bounds.
+    pub(crate) address_mode: wgpu::AddressMode,
+}
+
+impl FragmentShaderData for Shader {
+    /// The unique key identifying this shader module.
+    fn key(&self) -> Option<FragmentShaderKey> {
+        Some(FragmentShaderKey::Pad)
+    }
+
+    /// The SPIR-V shader source code.
+    fn spirv_source(&self) -> Cow<'static, [u8]> {
+        Cow::Borrowed(SHADER)
+    }
+
+    /// Encode the shader's data into the buffer, returning the descriptor to that.
+    fn binary_data(&self, buffer: &mut Vec<u8>) -> Option<BufferInitContent> {
+        let placement = [
+            self.offset[0] as f32,
+            self.offset[1] as f32,
+            self.size[0] as f32,
+            self.size[1] as f32,
+        ];
+        let constant = self.constant.unwrap_or([0.0; 4]);
+        let mode = [u32::from(self.constant.is_some()), 0, 0, 0];
+
+        let mut content = BufferInitContent::builder(buffer);
+        content.extend_from_pods(&[placement, constant]);
+        content.extend_from_pods(&[mode]);
+        Some(content.build())
+    }
+
+    fn num_args(&self) -> u32 {
+        1
+    }
+
+    fn address_mode(&self) -> wgpu::AddressMode {
+        self.address_mode
+    }
+}
@@ -30,6 +30,8 @@ impl ChannelPosition {
             Cc::R => ChannelPosition::First,
             Cc::G => ChannelPosition::Second,
             Cc::B => ChannelPosition::Third,
+            Cc::Luma => ChannelPosition::First,
+            Cc::Alpha => ChannelPosition::Fourth,
             _ => return None,
         })
     }
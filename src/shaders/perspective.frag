@@ -0,0 +1,90 @@
+#version 450
+layout (location = 0) in vec2 uv;
+layout (location = 0) out vec4 f_color;
+
+layout (set = 1, binding = 0) uniform sampler texture_sampler;
+layout (set = 1, binding = 1) uniform texture2D in_below;
+layout (set = 1, binding = 2) uniform texture2D in_above;
+
+layout (set = 2, binding = 0, std140) uniform Perspective {
+  // Maps homogeneous coordinates of the output onto the coordinates of the image above.
+  mat3 transform;
+  // xy: the size of the image above.
+  vec4 size;
+  // x: the sampling, nearest or bilinear.
+  uvec4 mode;
+  // The corners of the quad painted onto, in order, two to each vector.
+  vec4 quad[2];
+} u_perspective;
+
+const uint SAMPLE_NEAREST = 0u;
+const uint SAMPLE_BILINEAR = 1u;
+
+vec4 fetch_above(ivec2 coord) {
+  ivec2 bound = ivec2(u_perspective.size.xy) - 1;
+  return texelFetch(sampler2D(in_above, texture_sampler), clamp(coord, ivec2(0), bound), 0);
+}
+
+// Colors are mixed premultiplied by alpha.
+vec4 premultiplied_above(ivec2 coord) {
+  vec4 color = fetch_above(coord);
+  return vec4(color.rgb * color.a, color.a);
+}
+
+// Whether a point is within the quad, by counting the edges crossed by a ray to the right.
+bool inside_quad(vec2 point) {
+  vec2 corners[4] = vec2[4](
+    u_perspective.quad[0].xy,
+    u_perspective.quad[0].zw,
+    u_perspective.quad[1].xy,
+    u_perspective.quad[1].zw);
+
+  bool inside = false;
+  for (int i = 0; i < 4; i++) {
+    vec2 a = corners[i];
+    vec2 b = corners[(i + 1) % 4];
+    if ((a.y > point.y) != (b.y > point.y)) {
+      float crossing = a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x);
+      if (point.x < crossing) {
+        inside = !inside;
+      }
+    }
+  }
+
+  return inside;
+}
+
+void main() {
+  vec4 below = texelFetch(sampler2D(in_below, texture_sampler), ivec2(gl_FragCoord.xy), 0);
+  vec3 projected = u_perspective.transform * vec3(gl_FragCoord.xy, 1.0);
+
+  // Points behind the projection center are not part of the quad.
+  if (!inside_quad(gl_FragCoord.xy) || projected.z <= 0.0) {
+    f_color = below;
+    return;
+  }
+
+  // The division per fragment is what makes the coordinates perspective-correct.
+  vec2 coord = projected.xy / projected.z;
+  bool outside = any(lessThan(coord, vec2(0.0)))
+    || any(greaterThanEqual(coord, u_perspective.size.xy));
+
+  if (outside) {
+    f_color = below;
+  } else if (u_perspective.mode.x == SAMPLE_BILINEAR) {
+    vec2 texel = coord - 0.5;
+    ivec2 base = ivec2(floor(texel));
+    vec2 t = texel - vec2(base);
+
+    vec4 top = mix(premultiplied_above(base), premultiplied_above(base + ivec2(1, 0)), t.x);
+    vec4 bottom = mix(
+      premultiplied_above(base + ivec2(0, 1)),
+      premultiplied_above(base + ivec2(1, 1)),
+      t.x);
+    vec4 color = mix(top, bottom, t.y);
+    // Nothing is left of the color of fully transparent pixels.
+    f_color = color.a > 0.0 ? vec4(color.rgb / color.a, color.a) : vec4(0.0);
+  } else {
+    f_color = fetch_above(ivec2(floor(coord)));
+  }
+}
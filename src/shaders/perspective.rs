@@ -0,0 +1,56 @@
+use std::borrow::Cow;
+
+use super::{BufferInitContent, FragmentShaderData, FragmentShaderKey};
+use crate::color_matrix::RowMatrix;
+
+/// Paint an image onto another through a projective transformation of coordinates.
+pub const SHADER: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/spirv/perspective.frag.v"));
+
+/// The sampling of the shader, as enumerated in the shader.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub(crate) enum Sampling {
+    Nearest = 0,
+    BiLinear = 1,
+}
+
+/// The shader painting a quad of one image onto a quad of another.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Shader {
+    /// Maps the pixel coordinates of the output onto those of the image above.
+    pub(crate) transform: RowMatrix,
+    /// The size of the image above, in texels.
+    pub(crate) size: [u32; 2],
+    pub(crate) sampling: Sampling,
+    /// The corners of the quad painted onto, in pixel coordinates of the output.
+    pub(crate) quad: [[f32; 2]; 4],
+}
+
+impl FragmentShaderData for Shader {
+    /// The unique key identifying this shader module.
+    fn key(&self) -> Option<FragmentShaderKey> {
+        Some(FragmentShaderKey::Perspective)
+    }
+
+    /// The SPIR-V shader source code.
+    fn spirv_source(&self) -> Cow<'static, [u8]> {
+        Cow::Borrowed(SHADER)
+    }
+
+    /// Encode the shader's data into the buffer, returning the descriptor to that.
+    fn binary_data(&self, buffer: &mut Vec<u8>) -> Option<BufferInitContent> {
+        let size = [self.size[0] as f32, self.size[1] as f32, 0.0, 0.0];
+        let mode = [self.sampling as u32, 0, 0, 0];
+
+        let mut content = BufferInitContent::builder(buffer);
+        content.extend_from_pods(&self.transform.into_mat3x3_std140());
+        content.extend_from_pods(&size);
+        content.extend_from_pods(&mode);
+        content.extend_from_pods(&self.quad);
+        Some(content.build())
+    }
+
+    fn num_args(&self) -> u32 {
+        2
+    }
+}
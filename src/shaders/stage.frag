@@ -8,7 +8,7 @@
  * documentation which doesn't mention this detail at all apart from an internal
  * method.
  */
-#if (defined(DECODE_RGBA32UI_AS_MAIN) || defined(DECODE_RGBA16UI_AS_MAIN) || defined(DECODE_R32UI_AS_MAIN) || defined(DECODE_R16UI_AS_MAIN) || defined(DECODE_R8UI_AS_MAIN))
+#if (defined(DECODE_RGBA32UI_AS_MAIN) || defined(DECODE_RGBA16UI_AS_MAIN) || defined(DECODE_R32UI_AS_MAIN) || defined(DECODE_R16UI_AS_MAIN) || defined(DECODE_R8UI_AS_MAIN) || defined(DECODE_RGB8UI_AS_MAIN) || defined(DECODE_RGB16UI_AS_MAIN) || defined(DECODE_PLANAR8UI_AS_MAIN))
 #define STAGE_DECODE
 #else
 #define STAGE_ENCODE
@@ -44,6 +44,24 @@
 #ifndef ENCODE_RGBA32UI_AS_MAIN
 #define ENCODE_RGBA32UI_AS_MAIN encode_rgba32ui
 #endif
+#ifndef DECODE_RGB8UI_AS_MAIN
+#define DECODE_RGB8UI_AS_MAIN decode_rgb8ui
+#endif
+#ifndef ENCODE_RGB8UI_AS_MAIN
+#define ENCODE_RGB8UI_AS_MAIN encode_rgb8ui
+#endif
+#ifndef DECODE_RGB16UI_AS_MAIN
+#define DECODE_RGB16UI_AS_MAIN decode_rgb16ui
+#endif
+#ifndef ENCODE_RGB16UI_AS_MAIN
+#define ENCODE_RGB16UI_AS_MAIN encode_rgb16ui
+#endif
+#ifndef DECODE_PLANAR8UI_AS_MAIN
+#define DECODE_PLANAR8UI_AS_MAIN decode_planar8ui
+#endif
+#ifndef ENCODE_PLANAR8UI_AS_MAIN
+#define ENCODE_PLANAR8UI_AS_MAIN encode_planar8ui
+#endif
 /** This is a special shader to convert to/from color spaces and texture
  * formats that are not natively supported. This works by introducing a staging
  * texture that is in the correct byte representation of the supposed format
@@ -91,6 +109,11 @@ layout (set = 1, binding = 1) uniform utexture2D image_r16ui;
 layout (set = 1, binding = 2) uniform utexture2D image_r32ui;
 layout (set = 1, binding = 3) uniform utexture2D image_rgba16ui;
 layout (set = 1, binding = 4) uniform utexture2D image_rgba32ui;
+// Texels of 24 and 48 bit, packed into a row of 32-bit words.
+layout (set = 1, binding = 5) uniform utexture2D image_rgb8ui;
+layout (set = 1, binding = 6) uniform utexture2D image_rgb16ui;
+// The bytes of all planes of a frame, in rows as wide as the luma plane.
+layout (set = 1, binding = 7) uniform utexture2D image_planar8ui;
 
 /** For encoding, this is the input frame buffer.
  */
@@ -100,6 +123,10 @@ layout (set = 1, binding = 34) uniform sampler read_sampler;
 
 layout (set = 2, binding = 0, std140) uniform Parameter {
   uvec4 space;
+  // x: arrangement of planes, y: chroma siting, z: upsampling filter.
+  uvec4 planes;
+  // xy: the size of the luma plane, in pixels.
+  uvec4 planar_size;
 } parameter;
 
 // FIXME: this could and should be an auto-generated header with cbindgen
@@ -203,6 +230,33 @@ uint get_horizontal_workload() {
   return max(parameter.space.a, 1) & 0xff;
 }
 
+/** Whether color channels are stored multiplied by alpha.
+ *
+ * The premultiplication applies to the encoded values, after the transfer
+ * function. Textures always hold straight alpha.
+ */
+bool is_premultiplied() {
+  return (parameter.space.a & 0x100) != 0;
+}
+
+/** Divide encoded color channels by alpha, if they are premultiplied.
+ *
+ * Fully transparent pixels keep their color, which is zero in valid data.
+ */
+vec4 alpha_straighten(vec4 electrical) {
+  if (!is_premultiplied() || electrical.a <= 0.0)
+    return electrical;
+  return vec4(electrical.rgb / electrical.a, electrical.a);
+}
+
+/** Multiply encoded color channels by alpha, if they are stored premultiplied.
+ */
+vec4 alpha_premultiply(vec4 electrical) {
+  if (!is_premultiplied())
+    return electrical;
+  return vec4(electrical.rgb * electrical.a, electrical.a);
+}
+
 /** The 'position' in the input texel to retrieve one of the actual texels of the input image.
  *
  * Since, on WebGPU, we are only allowed to Load/Store at 32-bit granularity,
@@ -241,6 +295,9 @@ ivec2 encodePixelCoord() {
 vec4 demux_uint(uint, uint kind);
 uint mux_uint(vec4, uint kind);
 
+vec4 demux_uvec4(uvec4, uint kind);
+uvec4 mux_uvec4(vec4, uint kind);
+
 vec4 parts_normalize(vec4, uint);
 vec4 parts_denormalize(vec4, uint);
 
@@ -442,7 +499,7 @@ void DECODE_R8UI_AS_MAIN() {
   vec4 components = demux_uint(work, get_sample_bits());
 
   // FIXME: YUV transform and accurate YUV transform.
-  vec4 electrical = parts_normalize(components, get_sample_parts());
+  vec4 electrical = alpha_straighten(parts_normalize(components, get_sample_parts()));
   vec4 primaries = parts_untransfer(electrical, get_transfer());
 
   vec4 fake_local_noop = vec4(0);
@@ -458,7 +515,7 @@ void ENCODE_R8UI_AS_MAIN() {
 
     vec4 electrical = parts_transfer(primaries, get_transfer());
     // FIXME: YUV transform and accurate YUV transform.
-    vec4 components = parts_denormalize(electrical, get_sample_parts());
+    vec4 components = parts_denormalize(alpha_premultiply(electrical), get_sample_parts());
 
     uint texelNum = mux_uint(clamp(components, 0.0, 1.0), get_sample_bits());
     num |= (texelNum & 0xff) << (8*i);
@@ -474,7 +531,7 @@ void DECODE_R16UI_AS_MAIN() {
   vec4 components = demux_uint(work, get_sample_bits());
 
   // FIXME: YUV transform and accurate YUV transform.
-  vec4 electrical = parts_normalize(components, get_sample_parts());
+  vec4 electrical = alpha_straighten(parts_normalize(components, get_sample_parts()));
   vec4 primaries = parts_untransfer(electrical, get_transfer());
 
   vec4 fake_local_noop = vec4(0);
@@ -490,7 +547,7 @@ void ENCODE_R16UI_AS_MAIN() {
 
     vec4 electrical = parts_transfer(primaries, get_transfer());
     // FIXME: YUV transform and accurate YUV transform.
-    vec4 components = parts_denormalize(electrical, get_sample_parts());
+    vec4 components = parts_denormalize(alpha_premultiply(electrical), get_sample_parts());
 
     uint texelNum = mux_uint(clamp(components, 0.0, 1.0), get_sample_bits());
     num |= (texelNum & 0xffff) << (16*i);
@@ -505,7 +562,7 @@ void DECODE_R32UI_AS_MAIN() {
   vec4 components = demux_uint(num, get_sample_bits());
 
   // FIXME: YUV transform and accurate YUV transform.
-  vec4 electrical = parts_normalize(components, get_sample_parts());
+  vec4 electrical = alpha_straighten(parts_normalize(components, get_sample_parts()));
   vec4 primaries = parts_untransfer(electrical, get_transfer());
 
   vec4 fake_local_noop = vec4(0);
@@ -517,13 +574,380 @@ void ENCODE_R32UI_AS_MAIN() {
 
   vec4 electrical = parts_transfer(primaries, get_transfer());
   // FIXME: YUV transform and accurate YUV transform.
-  vec4 components = parts_denormalize(electrical, get_sample_parts());
+  vec4 components = parts_denormalize(alpha_premultiply(electrical), get_sample_parts());
 
   uint num = mux_uint(clamp(components, 0.0, 1.0), get_sample_bits());
   uvec4 fake_local_noop = uvec4(0);
   encode_color = uvec4(num);
 }
 
+void DECODE_RGBA16UI_AS_MAIN() {
+  uvec4 num = texelFetch(usampler2D(image_rgba16ui, read_sampler), decodeStageTexelCoord(), 0);
+  vec4 components = demux_uvec4(num, get_sample_bits());
+
+  vec4 electrical = alpha_straighten(parts_normalize(components, get_sample_parts()));
+  vec4 primaries = parts_untransfer(electrical, get_transfer());
+
+  vec4 fake_local_noop = vec4(0);
+  decode_color = primaries;
+}
+
+void ENCODE_RGBA16UI_AS_MAIN() {
+  vec4 primaries = texture(sampler2D(in_texture, texture_sampler), uv).rgba;
+
+  vec4 electrical = parts_transfer(primaries, get_transfer());
+  vec4 components = parts_denormalize(alpha_premultiply(electrical), get_sample_parts());
+
+  uvec4 num = mux_uvec4(components, get_sample_bits());
+  uvec4 fake_local_noop = uvec4(0);
+  encode_color = num;
+}
+
+void DECODE_RGBA32UI_AS_MAIN() {
+  uvec4 num = texelFetch(usampler2D(image_rgba32ui, read_sampler), decodeStageTexelCoord(), 0);
+  vec4 components = demux_uvec4(num, get_sample_bits());
+
+  vec4 electrical = alpha_straighten(parts_normalize(components, get_sample_parts()));
+  vec4 primaries = parts_untransfer(electrical, get_transfer());
+
+  vec4 fake_local_noop = vec4(0);
+  decode_color = primaries;
+}
+
+void ENCODE_RGBA32UI_AS_MAIN() {
+  vec4 primaries = texture(sampler2D(in_texture, texture_sampler), uv).rgba;
+
+  vec4 electrical = parts_transfer(primaries, get_transfer());
+  vec4 components = parts_denormalize(alpha_premultiply(electrical), get_sample_parts());
+
+  uvec4 num = mux_uvec4(components, get_sample_bits());
+  uvec4 fake_local_noop = uvec4(0);
+  encode_color = num;
+}
+
+/** Texels of 24 and 48 bit are packed into rows of 32-bit words without padding.
+ *
+ * Each decoded pixel reads the word holding its first byte and the one after,
+ * each encoded word is assembled from the bytes of the (up to) two texels it
+ * overlaps. The bytes of a texel are handled as a little-endian 64-bit number.
+ */
+uvec2 packed_texel_bytes(uint word, uint next, uint shift) {
+  if (shift == 0) {
+    return uvec2(word, next);
+  }
+
+  return uvec2((word >> (8 * shift)) | (next << (32 - 8 * shift)), next >> (8 * shift));
+}
+
+uvec2 fetch_packed_texel(utexture2D image, uint texel_bytes) {
+  ivec2 coord = ivec2(gl_FragCoord);
+  uint offset = texel_bytes * uint(coord.x);
+  int last = textureSize(usampler2D(image, read_sampler), 0).x - 1;
+
+  ivec2 word = ivec2(offset / 4, coord.y);
+  ivec2 next = ivec2(min(word.x + 1, last), coord.y);
+  return packed_texel_bytes(
+    texelFetch(usampler2D(image, read_sampler), word, 0).x,
+    texelFetch(usampler2D(image, read_sampler), next, 0).x,
+    offset % 4);
+}
+
+vec4 decode_packed_texel(uvec2 bytes, uint texel_bytes) {
+  vec4 components;
+  if (texel_bytes == 3) {
+    components = demux_uint(bytes.x & 0xffffff, get_sample_bits());
+  } else {
+    uvec4 num = uvec4(bytes.x & MASK16, bytes.x >> 16, bytes.y & MASK16, 0);
+    components = demux_uvec4(num, get_sample_bits());
+  }
+
+  vec4 electrical = alpha_straighten(parts_normalize(components, get_sample_parts()));
+  return parts_untransfer(electrical, get_transfer());
+}
+
+uvec2 encode_packed_texel(int x, uint texel_bytes) {
+  ivec2 pixelCoord = ivec2(x, gl_FragCoord.y);
+  if (x >= textureSize(sampler2D(in_texture, texture_sampler), 0).x) {
+    return uvec2(0);
+  }
+
+  vec4 primaries = texelFetch(sampler2D(in_texture, texture_sampler), pixelCoord, 0);
+  vec4 electrical = parts_transfer(primaries, get_transfer());
+  vec4 components = parts_denormalize(alpha_premultiply(electrical), get_sample_parts());
+
+  if (texel_bytes == 3) {
+    return uvec2(mux_uint(clamp(components, 0.0, 1.0), get_sample_bits()) & 0xffffff, 0);
+  } else {
+    uvec4 num = mux_uvec4(components, get_sample_bits());
+    return uvec2(num.x | (num.y << 16), num.z);
+  }
+}
+
+uint encode_packed_word(uint texel_bytes) {
+  uint start = 4 * uint(gl_FragCoord.x);
+  uint first = start / texel_bytes;
+  uvec2 texels[2] = uvec2[2](
+    encode_packed_texel(int(first), texel_bytes),
+    encode_packed_texel(int(first) + 1, texel_bytes));
+
+  uint num = 0;
+  for (uint i = 0; i < 4; i++) {
+    uint offset = start + i;
+    uvec2 texel = texels[offset / texel_bytes - first];
+    uint byte_idx = offset % texel_bytes;
+    uint bits = byte_idx < 4 ? texel.x >> (8 * byte_idx) : texel.y >> (8 * (byte_idx - 4));
+    num |= (bits & 0xff) << (8 * i);
+  }
+
+  return num;
+}
+
+void DECODE_RGB8UI_AS_MAIN() {
+  vec4 fake_local_noop = vec4(0);
+  decode_color = decode_packed_texel(fetch_packed_texel(image_rgb8ui, 3), 3);
+}
+
+void ENCODE_RGB8UI_AS_MAIN() {
+  uvec4 fake_local_noop = uvec4(0);
+  encode_color = uvec4(encode_packed_word(3));
+}
+
+void DECODE_RGB16UI_AS_MAIN() {
+  vec4 fake_local_noop = vec4(0);
+  decode_color = decode_packed_texel(fetch_packed_texel(image_rgb16ui, 6), 6);
+}
+
+void ENCODE_RGB16UI_AS_MAIN() {
+  uvec4 fake_local_noop = uvec4(0);
+  encode_color = uvec4(encode_packed_word(6));
+}
+
+/** Frames with subsampled chroma store their samples in planes of bytes.
+ *
+ * The luma plane is followed by the chroma planes, in rows as wide as the luma
+ * plane, and four bytes are packed into each 32-bit word. Decoding upsamples
+ * chroma to each pixel, with the siting and filter of the frame. Encoding
+ * computes each byte of the planes, and averages chroma with weights that
+ * match the siting.
+ */
+const uint PLANES_I420 = 0;
+const uint PLANES_NV12 = 1;
+const uint PLANES_I422 = 2;
+const uint PLANES_NV16 = 3;
+
+const uint SITING_CENTER = 0;
+const uint SITING_LEFT = 1;
+const uint SITING_TOP_LEFT = 2;
+
+const uint FILTER_NEAREST = 0;
+const uint FILTER_BILINEAR = 1;
+
+bool planes_are_420() {
+  return parameter.planes.x == PLANES_I420 || parameter.planes.x == PLANES_NV12;
+}
+
+bool planes_are_semi_planar() {
+  return parameter.planes.x == PLANES_NV12 || parameter.planes.x == PLANES_NV16;
+}
+
+ivec2 luma_size() {
+  return ivec2(parameter.planar_size.xy);
+}
+
+ivec2 chroma_size() {
+  ivec2 size = luma_size();
+  return ivec2(size.x / 2, planes_are_420() ? size.y / 2 : size.y);
+}
+
+// The position of chroma samples relative to luma samples, in luma pixels.
+vec2 siting_offset() {
+  vec2 offset = vec2(0.5, 0.5);
+  if (parameter.planes.y == SITING_LEFT) {
+    offset.x = 0.0;
+  } else if (parameter.planes.y == SITING_TOP_LEFT) {
+    offset = vec2(0.0);
+  }
+
+  // Without vertical subsampling all chroma rows are co-sited with luma rows.
+  if (!planes_are_420()) {
+    offset.y = 0.0;
+  }
+
+  return offset;
+}
+
+// The byte at a position of the planes, as a normalized value.
+float planar_byte(ivec2 coord) {
+  ivec2 word = ivec2(coord.x / 4, coord.y);
+  uint num = texelFetch(usampler2D(image_planar8ui, read_sampler), word, 0).x;
+  return float((num >> (8 * (coord.x % 4))) & MASK8) / BITS8;
+}
+
+// The byte at an index of the chroma planes, which follow the luma plane.
+float chroma_byte(int index) {
+  int width = luma_size().x;
+  return planar_byte(ivec2(index % width, luma_size().y + index / width));
+}
+
+vec2 chroma_sample(ivec2 coord) {
+  ivec2 size = chroma_size();
+  coord = clamp(coord, ivec2(0), size - 1);
+
+  if (planes_are_semi_planar()) {
+    int index = coord.y * size.x * 2 + coord.x * 2;
+    return vec2(chroma_byte(index), chroma_byte(index + 1));
+  } else {
+    int index = coord.y * size.x + coord.x;
+    int plane = size.x * size.y;
+    return vec2(chroma_byte(index), chroma_byte(plane + index));
+  }
+}
+
+vec2 chroma_at(ivec2 pixel) {
+  vec2 scale = vec2(2.0, planes_are_420() ? 2.0 : 1.0);
+  vec2 pos = (vec2(pixel) - siting_offset()) / scale;
+
+  if (parameter.planes.z == FILTER_NEAREST) {
+    return chroma_sample(ivec2(round(pos)));
+  }
+
+  ivec2 lo = ivec2(floor(pos));
+  vec2 t = pos - vec2(lo);
+
+  vec2 c00 = chroma_sample(lo);
+  vec2 c10 = chroma_sample(lo + ivec2(1, 0));
+  vec2 c01 = chroma_sample(lo + ivec2(0, 1));
+  vec2 c11 = chroma_sample(lo + ivec2(1, 1));
+
+  return mix(mix(c00, c10, t.x), mix(c01, c11, t.x), t.y);
+}
+
+// The normalized samples of a pixel, as they are stored.
+vec3 planar_components(ivec2 pixel) {
+  pixel = clamp(pixel, ivec2(0), luma_size() - 1);
+  vec4 primaries = texelFetch(sampler2D(in_texture, texture_sampler), pixel, 0);
+
+  vec4 electrical = parts_transfer(primaries, get_transfer());
+  return parts_denormalize(electrical, get_sample_parts()).xyz;
+}
+
+// Filter the samples around a chroma sample, with taps and weights matching its siting.
+vec2 downsample_chroma(ivec2 chroma) {
+  vec2 offset = siting_offset();
+  ivec2 base = chroma * ivec2(2, planes_are_420() ? 2 : 1);
+
+  // Centered samples average two pixels, co-sited ones weight three pixels as 1:2:1.
+  vec3 x_weights = offset.x > 0.0 ? vec3(0.0, 0.5, 0.5) : vec3(0.25, 0.5, 0.25);
+  vec3 y_weights;
+  if (!planes_are_420()) {
+    y_weights = vec3(0.0, 1.0, 0.0);
+  } else {
+    y_weights = offset.y > 0.0 ? vec3(0.0, 0.5, 0.5) : vec3(0.25, 0.5, 0.25);
+  }
+
+  vec2 sum = vec2(0.0);
+  for (int dy = -1; dy <= 1; dy++) {
+    for (int dx = -1; dx <= 1; dx++) {
+      float weight = x_weights[dx + 1] * y_weights[dy + 1];
+      if (weight > 0.0) {
+        sum += weight * planar_components(base + ivec2(dx, dy)).yz;
+      }
+    }
+  }
+
+  return sum;
+}
+
+// The normalized value of a byte of the planes.
+float encode_planar_value(ivec2 coord) {
+  ivec2 size = luma_size();
+  if (coord.x >= size.x) {
+    return 0.0;
+  }
+
+  if (coord.y < size.y) {
+    return planar_components(coord).x;
+  }
+
+  ivec2 chroma = chroma_size();
+  int index = (coord.y - size.y) * size.x + coord.x;
+  int component;
+
+  if (planes_are_semi_planar()) {
+    component = index % 2;
+    index = index / 2;
+  } else {
+    int plane = chroma.x * chroma.y;
+    component = index / plane;
+    index = index % plane;
+  }
+
+  return downsample_chroma(ivec2(index % chroma.x, index / chroma.x))[component];
+}
+
+void DECODE_PLANAR8UI_AS_MAIN() {
+  ivec2 pixel = ivec2(gl_FragCoord.xy);
+  vec4 components = vec4(planar_byte(pixel), chroma_at(pixel), 1.0);
+
+  vec4 electrical = parts_normalize(components, get_sample_parts());
+  vec4 primaries = parts_untransfer(electrical, get_transfer());
+
+  vec4 fake_local_noop = vec4(0);
+  decode_color = primaries;
+}
+
+void ENCODE_PLANAR8UI_AS_MAIN() {
+  ivec2 baseCoord = encodePixelCoord();
+  uint num = 0;
+  for (int i = 0; i < get_horizontal_workload(); i++) {
+    float value = encode_planar_value(baseCoord + ivec2(i, 0));
+    num |= (uint(round(clamp(value, 0.0, 1.0) * BITS8)) & MASK8) << (8*i);
+  }
+
+  uvec4 fake_local_noop = uvec4(0);
+  encode_color = uvec4(num);
+}
+
+// The bit decoding used by 64bit and 128bit staging, one channel per component.
+// Returns the parts in the same canonical order as demux_uint.
+vec4 demux_uvec4(uvec4 num, uint kind) {
+  switch (kind) {
+  case SAMPLE_BITS_Int16x3:
+    return vec4((num & MASK16).xyz, BITS16) / BITS16;
+  case SAMPLE_BITS_Int16x4:
+    return vec4(num & MASK16) / BITS16;
+  case SAMPLE_BITS_Float16x4:
+    // Each component holds the 16 bits of a half float in its low-order bits.
+    return vec4(
+      unpackHalf2x16(num.x).x,
+      unpackHalf2x16(num.y).x,
+      unpackHalf2x16(num.z).x,
+      unpackHalf2x16(num.w).x);
+  case SAMPLE_BITS_Float32x4:
+    return uintBitsToFloat(num);
+  }
+  return BIT_DECODE_FAIL;
+}
+
+// The bit encoding used by 64bit and 128bit staging.
+// Reverses demux_uvec4. Integer channels are clamped and rounded, floats are kept as is.
+uvec4 mux_uvec4(vec4 c, uint kind) {
+  switch (kind) {
+  case SAMPLE_BITS_Int16x3:
+  case SAMPLE_BITS_Int16x4:
+    return uvec4(round(clamp(c, 0.0, 1.0) * BITS16));
+  case SAMPLE_BITS_Float16x4:
+    return uvec4(
+      packHalf2x16(vec2(c.x, 0.0)),
+      packHalf2x16(vec2(c.y, 0.0)),
+      packHalf2x16(vec2(c.z, 0.0)),
+      packHalf2x16(vec2(c.w, 0.0)));
+  case SAMPLE_BITS_Float32x4:
+    return floatBitsToUint(c);
+  }
+  return uvec4(BIT_ENCODE_FAIL);
+}
+
 // The bit decoding used by 8bit, 16bit, 32bit staging.
 // Returns the parts in a canonical order:
 // - 1 part: (x, 0., 0., 1.)
@@ -545,7 +969,7 @@ vec4 demux_uint(uint num, uint kind) {
   case SAMPLE_BITS_Inti444:
     return vec4(num & MASK4, (num >> 4) & MASK4, (num >> 8) & MASK4, MASK4) / BITS4;
   case SAMPLE_BITS_Int444i:
-    return vec4((num >> 4) & MASK4, (num >> 9) & MASK4, (num >> 12) & MASK4, MASK4) / BITS4;
+    return vec4((num >> 4) & MASK4, (num >> 8) & MASK4, (num >> 12) & MASK4, MASK4) / BITS4;
   case SAMPLE_BITS_Int565:
     return vec4(num & MASK5, (num >> 5) & MASK6, num >> 11, 1.0) / vec4(BITS5, BITS6, BITS5, 1.0);
   case SAMPLE_BITS_Int8x2:
@@ -585,13 +1009,15 @@ vec4 demux_uint(uint num, uint kind) {
 // Reverses demux_uint.
 // Defines a bunch of macros to make this easier.
 // Each position takes a number of bits to skip (sn) and bits to quantize to (bn).
+// Values are rounded to the nearest code, so that decoding and encoding again is lossless.
 // The inputs are expected in the canonical positions according to demux_uint
 // and will be put in the low-order bits of the output.
 uint mux_uint(vec4 c, uint kind) {
-#define STEALTH_MUX_UINT1(s0, b0) uint(c.x * BITS##b0)
-#define STEALTH_MUX_UINT2(s0, b0, s1, b1) ((uint(c.x * BITS##b0) << s0) + (uint(c.w * BITS##b1) << (s0+b0+s1)))
-#define STEALTH_MUX_UINT3(s0, b0, s1, b1, s2, b2) ((uint(c.x * BITS##b0) << s0) + (uint(c.y * BITS##b1) << (s0+b0+s1))+ (uint(c.z * BITS##b2) << (s0+b0+s1+b1+s2)))
-#define STEALTH_MUX_UINT4(s0, b0, s1, b1, s2, b2, s3, b3) ((uint(c.x * BITS##b0) << s0) + (uint(c.y * BITS##b1) << (s0+b0+s1))+ (uint(c.z * BITS##b2) << (s0+b0+s1+b1+s2))+ (uint(c.w * BITS##b3) << (s0+b0+s1+b1+s2+b2+s3)))
+#define STEALTH_MUX_UNORM(v, b) uint(round(v * BITS##b))
+#define STEALTH_MUX_UINT1(s0, b0) STEALTH_MUX_UNORM(c.x, b0)
+#define STEALTH_MUX_UINT2(s0, b0, s1, b1) ((STEALTH_MUX_UNORM(c.x, b0) << s0) + (STEALTH_MUX_UNORM(c.w, b1) << (s0+b0+s1)))
+#define STEALTH_MUX_UINT3(s0, b0, s1, b1, s2, b2) ((STEALTH_MUX_UNORM(c.x, b0) << s0) + (STEALTH_MUX_UNORM(c.y, b1) << (s0+b0+s1))+ (STEALTH_MUX_UNORM(c.z, b2) << (s0+b0+s1+b1+s2)))
+#define STEALTH_MUX_UINT4(s0, b0, s1, b1, s2, b2, s3, b3) ((STEALTH_MUX_UNORM(c.x, b0) << s0) + (STEALTH_MUX_UNORM(c.y, b1) << (s0+b0+s1))+ (STEALTH_MUX_UNORM(c.z, b2) << (s0+b0+s1+b1+s2))+ (STEALTH_MUX_UNORM(c.w, b3) << (s0+b0+s1+b1+s2+b2+s3)))
   switch (kind) {
   case SAMPLE_BITS_Int8:
     return STEALTH_MUX_UINT1(0, 8);
@@ -638,6 +1064,7 @@ uint mux_uint(vec4 c, uint kind) {
 #undef STEALTH_MUX_UINT2
 #undef STEALTH_MUX_UINT3
 #undef STEALTH_MUX_UINT4
+#undef STEALTH_MUX_UNORM
 }
 
 // Swap the parts into the canonical location for the color representation.
@@ -674,7 +1101,7 @@ vec4 parts_normalize(vec4 components, uint parts) {
   case SAMPLE_PARTS_Rgba:
     return components.xyzw;
   case SAMPLE_PARTS_Bgra:
-    return components.xyzw;
+    return components.zyxw;
   case SAMPLE_PARTS_Argb:
     return components.yzwx;
   case SAMPLE_PARTS_Abgr:
@@ -683,6 +1110,8 @@ vec4 parts_normalize(vec4 components, uint parts) {
     return vec4(components.yzw, 1.0);
   case SAMPLE_PARTS_Xbgr:
     return vec4(components.wzy, 1.0);
+  case SAMPLE_PARTS_Yuv:
+    return vec4(components.xyz, 1.0);
   // HACK(naga-1403):
   // this could be unified with the branch below but Naga has a bug.
   case SAMPLE_PARTS_Lab:
@@ -732,6 +1161,8 @@ vec4 parts_denormalize(vec4 c, uint parts) {
     return vec4(1.0, c.rgb);
   case SAMPLE_PARTS_Xbgr:
     return vec4(1.0, c.bgr);
+  case SAMPLE_PARTS_Yuv:
+    return vec4(c.xyz, 1.0);
   // HACK(naga-1403)
   case SAMPLE_PARTS_Lab:
     return vec4(c.xyz, 1.0);
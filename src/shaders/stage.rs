@@ -1,4 +1,7 @@
-use crate::buffer::{SampleBits, SampleParts, Transfer as RgbTransfer};
+use crate::buffer::{
+    ChromaFilter, ChromaSiting, PlanarLayout, Planes, SampleBits, SampleParts,
+    Transfer as RgbTransfer,
+};
 /// Detailed structs for the stage shader.
 use core::num::NonZeroU32;
 use wgpu::TextureFormat;
@@ -17,6 +20,17 @@ pub(crate) struct XyzParameter {
     pub bits: SampleBits,
     pub parts: SampleParts,
     pub transfer: Transfer,
+    /// Whether the encoded color channels are multiplied by alpha.
+    pub premultiplied: bool,
+    /// The planes in which the texels are stored, if any.
+    pub planes: Option<PlanarParameter>,
+}
+
+/// The planes of a frame, with the size of its luma plane.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct PlanarParameter {
+    pub planes: Planes,
+    pub size: (u32, u32),
 }
 
 /// Defines the bit representation we use for our own coding of texels and pixels.
@@ -34,20 +48,49 @@ pub(crate) enum StageKind {
     /// Each texel is 128 bit and we decode it from 32-bit RGBA.
     /// That's scarily large.
     Rgba32ui = 4,
+    /// Each texel is 24 bit, packed without padding into a row of 32-bit words.
+    Rgb8ui = 5,
+    /// Each texel is 48 bit, packed without padding into a row of 32-bit words.
+    Rgb16ui = 6,
+    /// Each texel is in planes of 8-bit samples, whose bytes we operate on 4 at the same time.
+    Planar8ui = 7,
 }
 
 impl XyzParameter {
-    pub(crate) fn serialize_std140(&self) -> [u32; 4] {
+    pub(crate) fn serialize_std140(&self) -> [u32; 12] {
+        let (planes, size) = match self.planes {
+            Some(planar) => (Self::serialize_planes(planar.planes), planar.size),
+            None => ([0; 4], (0, 0)),
+        };
+
         [
             self.transfer.as_u32(),
             Self::serialize_parts(self.parts),
             Self::serialize_bits(self.bits),
             // Upper bits are still reserved for texel block size.
-            self.horizontal_subfactor() & 0xff,
+            (self.horizontal_subfactor() & 0xff) | (u32::from(self.premultiplied) << 8),
+            planes[0],
+            planes[1],
+            planes[2],
+            planes[3],
+            size.0,
+            size.1,
+            0,
+            0,
         ]
     }
 
     pub(crate) fn stage_kind(&self) -> Option<StageKind> {
+        if self.planes.is_some() {
+            return Some(StageKind::Planar8ui);
+        }
+
+        match self.bits {
+            SampleBits::UInt8x3 => return Some(StageKind::Rgb8ui),
+            SampleBits::UInt16x3 => return Some(StageKind::Rgb16ui),
+            _ => {}
+        }
+
         Some(match self.bits.bytes() {
             1 => StageKind::R8uiX4,
             2 => StageKind::R16uiX2,
@@ -82,12 +125,16 @@ impl XyzParameter {
         }
     }
 
+    /// The bit layout, as enumerated in the shader.
+    ///
+    /// Our names list the channel widths in the order of channels, starting at the least
+    /// significant bits, while the shader lists them starting at the most significant bits.
     pub(crate) fn serialize_bits(bits: SampleBits) -> u32 {
         use SampleBits as S;
         match bits {
             S::UInt8 => 0,
-            S::UInt332 => 1,
-            S::UInt233 => 2,
+            S::UInt332 => 2,
+            S::UInt233 => 1,
             S::UInt16 => 3,
             S::UInt4x4 => 4,
             S::UInt565 => 7,
@@ -105,7 +152,46 @@ impl XyzParameter {
         }
     }
 
+    /// The arrangement, siting and filter of planes, as enumerated in the shader.
+    pub(crate) fn serialize_planes(planes: Planes) -> [u32; 4] {
+        let layout = match planes.layout {
+            PlanarLayout::I420 => 0,
+            PlanarLayout::Nv12 => 1,
+            PlanarLayout::I422 => 2,
+            PlanarLayout::Nv16 => 3,
+        };
+
+        let siting = match planes.siting {
+            ChromaSiting::Center => 0,
+            ChromaSiting::Left => 1,
+            ChromaSiting::TopLeft => 2,
+        };
+
+        let filter = match planes.upsampling {
+            ChromaFilter::Nearest => 0,
+            ChromaFilter::Bilinear => 1,
+        };
+
+        [layout, siting, filter, 0]
+    }
+
+    /// The size of the image of stored texels, which for planes is that of their bytes.
+    pub(crate) fn stored_size(&self, size: (NonZeroU32, NonZeroU32)) -> (NonZeroU32, NonZeroU32) {
+        let Some(planar) = self.planes else {
+            return size;
+        };
+
+        let (w, h) = size;
+        let rows = h.saturating_add(planar.planes.chroma_rows(h.get()));
+        (w, rows)
+    }
+
     pub(crate) fn horizontal_subfactor(&self) -> u32 {
+        // Planes store each sample in its own byte.
+        if self.planes.is_some() {
+            return 4;
+        }
+
         match self.bits.bytes() {
             1 => 4,
             2 => 2,
@@ -119,12 +205,15 @@ impl XyzParameter {
 }
 
 impl StageKind {
-    pub const ALL: [Self; 5] = [
+    pub const ALL: [Self; 8] = [
         Self::R8uiX4,
         Self::R16uiX2,
         Self::R32ui,
         Self::Rgba16ui,
         Self::Rgba32ui,
+        Self::Rgb8ui,
+        Self::Rgb16ui,
+        Self::Planar8ui,
     ];
 
     pub(crate) fn encode_entry_point(self) -> &'static str {
@@ -134,6 +223,9 @@ impl StageKind {
             Self::R32ui => "encode_r32ui",
             Self::Rgba16ui => "encode_rgba16ui",
             Self::Rgba32ui => "encode_rgba32ui",
+            Self::Rgb8ui => "encode_rgb8ui",
+            Self::Rgb16ui => "encode_rgb16ui",
+            Self::Planar8ui => "encode_planar8ui",
         }
     }
 
@@ -144,6 +236,9 @@ impl StageKind {
             Self::R32ui => "decode_r32ui",
             Self::Rgba16ui => "decode_rgba16ui",
             Self::Rgba32ui => "decode_rgba32ui",
+            Self::Rgb8ui => "decode_rgb8ui",
+            Self::Rgb16ui => "decode_rgb16ui",
+            Self::Planar8ui => "decode_planar8ui",
         }
     }
 
@@ -152,7 +247,19 @@ impl StageKind {
             Self::R8uiX4 => include_bytes!(concat!(env!("OUT_DIR"), "/spirv/stage_d8ui.frag.v")),
             Self::R16uiX2 => include_bytes!(concat!(env!("OUT_DIR"), "/spirv/stage_d16ui.frag.v")),
             Self::R32ui => include_bytes!(concat!(env!("OUT_DIR"), "/spirv/stage_d32ui.frag.v")),
-            _ => todo!("{:?}", self),
+            Self::Rgba16ui => {
+                include_bytes!(concat!(env!("OUT_DIR"), "/spirv/stage_drgba16ui.frag.v"))
+            }
+            Self::Rgba32ui => {
+                include_bytes!(concat!(env!("OUT_DIR"), "/spirv/stage_drgba32ui.frag.v"))
+            }
+            Self::Rgb8ui => include_bytes!(concat!(env!("OUT_DIR"), "/spirv/stage_drgb8ui.frag.v")),
+            Self::Rgb16ui => {
+                include_bytes!(concat!(env!("OUT_DIR"), "/spirv/stage_drgb16ui.frag.v"))
+            }
+            Self::Planar8ui => {
+                include_bytes!(concat!(env!("OUT_DIR"), "/spirv/stage_dplanar8ui.frag.v"))
+            }
         }
     }
 
@@ -161,7 +268,19 @@ impl StageKind {
             Self::R8uiX4 => include_bytes!(concat!(env!("OUT_DIR"), "/spirv/stage_e8ui.frag.v")),
             Self::R16uiX2 => include_bytes!(concat!(env!("OUT_DIR"), "/spirv/stage_e16ui.frag.v")),
             Self::R32ui => include_bytes!(concat!(env!("OUT_DIR"), "/spirv/stage_e32ui.frag.v")),
-            _ => todo!("{:?}", self),
+            Self::Rgba16ui => {
+                include_bytes!(concat!(env!("OUT_DIR"), "/spirv/stage_ergba16ui.frag.v"))
+            }
+            Self::Rgba32ui => {
+                include_bytes!(concat!(env!("OUT_DIR"), "/spirv/stage_ergba32ui.frag.v"))
+            }
+            Self::Rgb8ui => include_bytes!(concat!(env!("OUT_DIR"), "/spirv/stage_ergb8ui.frag.v")),
+            Self::Rgb16ui => {
+                include_bytes!(concat!(env!("OUT_DIR"), "/spirv/stage_ergb16ui.frag.v"))
+            }
+            Self::Planar8ui => {
+                include_bytes!(concat!(env!("OUT_DIR"), "/spirv/stage_eplanar8ui.frag.v"))
+            }
         }
     }
 
@@ -176,6 +295,8 @@ impl StageKind {
             Self::R32ui => TextureFormat::R32Uint,
             Self::Rgba16ui => TextureFormat::Rgba16Uint,
             Self::Rgba32ui => TextureFormat::Rgba32Uint,
+            Self::Rgb8ui | Self::Rgb16ui => TextureFormat::R32Uint,
+            Self::Planar8ui => TextureFormat::R32Uint,
         }
     }
 
@@ -189,13 +310,25 @@ impl StageKind {
 
     pub(crate) fn horizontal_subfactor(self) -> u32 {
         match self {
-            Self::R8uiX4 => 4,
+            Self::R8uiX4 | Self::Planar8ui => 4,
             Self::R16uiX2 => 2,
             _ => 1,
         }
     }
 
     pub(crate) fn stage_size(self, (w, h): (NonZeroU32, NonZeroU32)) -> (NonZeroU32, NonZeroU32) {
+        // The words covering all bytes of a row.
+        let packed = |bytes: u32| {
+            let w = (w.get() * bytes).div_ceil(4);
+            (NonZeroU32::new(w).unwrap(), h)
+        };
+
+        match self {
+            Self::Rgb8ui => return packed(3),
+            Self::Rgb16ui => return packed(6),
+            _ => {}
+        }
+
         let sub = self.horizontal_subfactor();
         let w = w.get() / sub + u32::from(w.get() % sub > 0);
         (NonZeroU32::new(w).unwrap(), h)
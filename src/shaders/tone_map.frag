@@ -0,0 +1,134 @@
+#version 450
+layout (location = 0) in vec2 uv;
+layout (location = 0) out vec4 f_color;
+
+layout (set = 1, binding = 0) uniform sampler texture_sampler;
+layout (set = 1, binding = 1) uniform texture2D in_texture;
+
+layout (set = 2, binding = 0, std140) uniform ToneMap {
+  // xyz: luminance weights of the working primaries, w: linear exposure factor.
+  vec4 luminance_exposure;
+  // The scene-referred value mapped to display white, where applicable.
+  float white;
+  uint method;
+} u_toneMap;
+
+const uint TONE_REINHARD = 0u;
+const uint TONE_REINHARD_EXTENDED = 1u;
+const uint TONE_HABLE = 2u;
+const uint TONE_ACES = 3u;
+const uint TONE_AGX = 4u;
+
+float luminance(vec3 rgb) {
+  return dot(rgb, u_toneMap.luminance_exposure.xyz);
+}
+
+vec3 scale_luminance(vec3 rgb, float l_in, float l_out) {
+  return l_in > 0.0 ? rgb * (l_out / l_in) : vec3(0.0);
+}
+
+vec3 reinhard(vec3 rgb) {
+  float l_in = luminance(rgb);
+  return scale_luminance(rgb, l_in, l_in / (1.0 + l_in));
+}
+
+vec3 reinhard_extended(vec3 rgb, float white) {
+  float l_in = luminance(rgb);
+  float l_out = l_in * (1.0 + l_in / (white * white)) / (1.0 + l_in);
+  return scale_luminance(rgb, l_in, l_out);
+}
+
+// John Hable's filmic curve from Uncharted 2.
+vec3 hable_partial(vec3 x) {
+  const float A = 0.15;
+  const float B = 0.50;
+  const float C = 0.10;
+  const float D = 0.20;
+  const float E = 0.02;
+  const float F = 0.30;
+  return ((x*(A*x+C*B)+D*E)/(x*(A*x+B)+D*F))-E/F;
+}
+
+vec3 hable(vec3 rgb, float white) {
+  const float EXPOSURE_BIAS = 2.0;
+  vec3 curr = hable_partial(rgb * EXPOSURE_BIAS);
+  vec3 white_scale = vec3(1.0) / hable_partial(vec3(white));
+  return curr * white_scale;
+}
+
+// Stephen Hill's fit of the ACES RRT+ODT, assumes BT.709 primaries.
+vec3 aces_fitted(vec3 rgb) {
+  // Remember: column-major initializer.
+  const mat3 ACES_INPUT = mat3(
+    0.59719, 0.07600, 0.02840,
+    0.35458, 0.90834, 0.13383,
+    0.04823, 0.01566, 0.83777
+  );
+
+  const mat3 ACES_OUTPUT = mat3(
+     1.60475, -0.10208, -0.00327,
+    -0.53108,  1.10813, -0.07276,
+    -0.07367, -0.00605,  1.07602
+  );
+
+  vec3 v = ACES_INPUT * rgb;
+  vec3 a = v * (v + 0.0245786) - 0.000090537;
+  vec3 b = v * (0.983729 * v + 0.4329510) + 0.238081;
+  return ACES_OUTPUT * (a / b);
+}
+
+// A polynomial approximation of the AgX base contrast sigmoid.
+vec3 agx_contrast(vec3 x) {
+  vec3 x2 = x * x;
+  vec3 x4 = x2 * x2;
+  return + 15.5     * x4 * x2
+         - 40.14    * x4 * x
+         + 31.96    * x4
+         - 6.868    * x2 * x
+         + 0.4298   * x2
+         + 0.1191   * x
+         - 0.00232;
+}
+
+vec3 agx(vec3 rgb) {
+  const mat3 AGX_INSET = mat3(
+    0.842479062253094, 0.0423282422610123, 0.0423756549057051,
+    0.0784335999999992, 0.878468636469772, 0.0784336,
+    0.0792237451477643, 0.0791661274605434, 0.879142973793104
+  );
+
+  const mat3 AGX_OUTSET = mat3(
+    1.19687900512017, -0.0528968517574562, -0.0529716355144438,
+    -0.0980208811401368, 1.15190312990417, -0.0980434501171241,
+    -0.0990297440797205, -0.0989611768448433, 1.15107367264116
+  );
+
+  const float MIN_EV = -12.47393;
+  const float MAX_EV = 4.026069;
+
+  vec3 v = AGX_INSET * max(rgb, vec3(1e-10));
+  v = clamp(log2(v), MIN_EV, MAX_EV);
+  v = (v - MIN_EV) / (MAX_EV - MIN_EV);
+  v = agx_contrast(v);
+  v = AGX_OUTSET * v;
+  // The sigmoid is designed to produce display encoded values.
+  return pow(max(v, vec3(0.0)), vec3(2.2));
+}
+
+void main() {
+  vec4 rgba = texture(sampler2D(in_texture, texture_sampler), uv).rgba;
+  vec3 rgb = max(rgba.rgb, vec3(0.0)) * u_toneMap.luminance_exposure.w;
+  float white = u_toneMap.white;
+
+  vec3 mapped;
+  switch (u_toneMap.method) {
+    case TONE_REINHARD: mapped = reinhard(rgb); break;
+    case TONE_REINHARD_EXTENDED: mapped = reinhard_extended(rgb, white); break;
+    case TONE_HABLE: mapped = hable(rgb, white); break;
+    case TONE_ACES: mapped = aces_fitted(rgb); break;
+    case TONE_AGX: mapped = agx(rgb); break;
+    default: mapped = rgb; break;
+  }
+
+  f_color = vec4(clamp(mapped, 0.0, 1.0), rgba.a);
+}
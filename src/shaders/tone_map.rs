@@ -0,0 +1,47 @@
+use std::borrow::Cow;
+
+use super::{BufferInitContent, FragmentShaderData, FragmentShaderKey};
+
+/// Map linear, scene-referred RGB into the display range.
+pub const SHADER: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/spirv/tone_map.frag.v"));
+
+/// The tone mapping shader, compressing unbounded luminance into `[0; 1]`.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Shader {
+    /// The weights of linear RGB channels in the luminance, the Y row of the XYZ matrix.
+    pub(crate) luminance: [f32; 3],
+    /// The factor applied to all channels before the curve.
+    pub(crate) exposure: f32,
+    /// The scene-referred value that maps to white, for curves that have such a parameter.
+    pub(crate) white: f32,
+    /// The curve, as enumerated in the shader.
+    pub(crate) method: u32,
+}
+
+impl FragmentShaderData for Shader {
+    /// The unique key identifying this shader module.
+    fn key(&self) -> Option<FragmentShaderKey> {
+        Some(FragmentShaderKey::ToneMap)
+    }
+
+    /// The SPIR-V shader source code.
+    fn spirv_source(&self) -> Cow<'static, [u8]> {
+        Cow::Borrowed(SHADER)
+    }
+
+    /// Encode the shader's data into the buffer, returning the descriptor to that.
+    fn binary_data(&self, buffer: &mut Vec<u8>) -> Option<BufferInitContent> {
+        let [lr, lg, lb] = self.luminance;
+        let mut buffer_content = BufferInitContent::builder(buffer);
+        buffer_content.extend_from_pods(&[lr, lg, lb, self.exposure]);
+        buffer_content.extend_from_pods(&[self.white]);
+        buffer_content.extend_from_pods(&[self.method]);
+        buffer_content.align_by_exponent(4);
+
+        Some(buffer_content.build())
+    }
+
+    fn num_args(&self) -> u32 {
+        1
+    }
+}
@@ -0,0 +1,94 @@
+#version 450
+layout (location = 0) in vec2 uv;
+layout (location = 0) out vec4 f_color;
+
+layout (set = 1, binding = 0) uniform sampler texture_sampler;
+// The image to sample from.
+layout (set = 1, binding = 1) uniform texture2D in_image;
+// The displacement map, when displacing.
+layout (set = 1, binding = 2) uniform texture2D in_map;
+
+layout (set = 2, binding = 0, std140) uniform Warp {
+  // xy: the focal lengths, zw: the principal point, in pixels.
+  vec4 camera;
+  // xyz: the radial coefficients k1, k2, k3.
+  vec4 radial;
+  // xy: the tangential coefficients p1, p2.
+  vec4 tangential;
+  // x: the sampling, nearest or bilinear.
+  uvec4 mode;
+} u_warp;
+
+const uint SAMPLE_NEAREST = 0u;
+const uint SAMPLE_BILINEAR = 1u;
+
+ivec2 image_size() {
+  return textureSize(sampler2D(in_image, texture_sampler), 0);
+}
+
+// Texels outside the image are transparent.
+vec4 fetch(ivec2 coord) {
+  if (any(lessThan(coord, ivec2(0))) || any(greaterThanEqual(coord, image_size()))) {
+    return vec4(0.0);
+  }
+
+  return texelFetch(sampler2D(in_image, texture_sampler), coord, 0);
+}
+
+// Colors are mixed premultiplied by alpha.
+vec4 premultiplied(ivec2 coord) {
+  vec4 color = fetch(coord);
+  return vec4(color.rgb * color.a, color.a);
+}
+
+// Sample at pixel coordinates, where the top-left corner of the image is at the origin.
+vec4 sample_at(vec2 coord) {
+  if (u_warp.mode.x == SAMPLE_BILINEAR) {
+    vec2 texel = coord - 0.5;
+    ivec2 base = ivec2(floor(texel));
+    vec2 t = texel - vec2(base);
+
+    vec4 top = mix(premultiplied(base), premultiplied(base + ivec2(1, 0)), t.x);
+    vec4 bottom = mix(premultiplied(base + ivec2(0, 1)), premultiplied(base + ivec2(1, 1)), t.x);
+    vec4 color = mix(top, bottom, t.y);
+    // Nothing is left of the color of fully transparent pixels.
+    return color.a > 0.0 ? vec4(color.rgb / color.a, color.a) : vec4(0.0);
+  } else {
+    return fetch(ivec2(floor(coord)));
+  }
+}
+
+#ifndef WARP_UNDISTORT_AS_MAIN
+#define WARP_UNDISTORT_AS_MAIN warp_undistort
+#endif
+#ifndef WARP_DISPLACE_AS_MAIN
+#define WARP_DISPLACE_AS_MAIN warp_displace
+#endif
+
+// Samples each pixel of the ideal image from where the lens distorted it to, Brown-Conrady.
+void WARP_UNDISTORT_AS_MAIN() {
+  vec2 focal = u_warp.camera.xy;
+  vec2 center = u_warp.camera.zw;
+  float k1 = u_warp.radial.x, k2 = u_warp.radial.y, k3 = u_warp.radial.z;
+  float p1 = u_warp.tangential.x, p2 = u_warp.tangential.y;
+
+  vec2 p = (gl_FragCoord.xy - center) / focal;
+  float r2 = dot(p, p);
+  float radial = 1.0 + r2 * (k1 + r2 * (k2 + r2 * k3));
+
+  vec2 distorted = p * radial + vec2(
+    2.0 * p1 * p.x * p.y + p2 * (r2 + 2.0 * p.x * p.x),
+    p1 * (r2 + 2.0 * p.y * p.y) + 2.0 * p2 * p.x * p.y);
+
+  f_color = sample_at(distorted * focal + center);
+}
+
+// Samples each pixel at its own coordinate offset by the first two channels of the map.
+void WARP_DISPLACE_AS_MAIN() {
+  ivec2 coord = ivec2(gl_FragCoord.xy);
+  vec2 map_size = vec2(textureSize(sampler2D(in_map, texture_sampler), 0));
+  vec2 offset = texelFetch(sampler2D(in_map, texture_sampler), coord, 0).xy;
+
+  vec2 target_uv = gl_FragCoord.xy / map_size + offset;
+  f_color = sample_at(target_uv * vec2(image_size()));
+}
@@ -0,0 +1,65 @@
+use std::borrow::Cow;
+
+use super::perspective::Sampling;
+use super::{BufferInitContent, FragmentShaderData, FragmentShaderKey};
+
+/// Sample each pixel from where a lens with Brown-Conrady distortion moved it.
+pub const SHADER_UNDISTORT: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/spirv/warp_undistort.frag.v"));
+/// Sample each pixel at an offset given by a displacement map.
+pub const SHADER_DISPLACE: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/spirv/warp_displace.frag.v"));
+
+/// The source of the coordinates to sample at.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum Kind {
+    Undistort,
+    Displace,
+}
+
+/// The shader sampling an image at computed coordinates, transparent outside of it.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Shader {
+    pub(crate) kind: Kind,
+    /// The focal lengths and the principal point, in pixels.
+    pub(crate) camera: [f32; 4],
+    /// The radial distortion coefficients k1, k2, k3.
+    pub(crate) radial: [f32; 3],
+    /// The tangential distortion coefficients p1, p2.
+    pub(crate) tangential: [f32; 2],
+    pub(crate) sampling: Sampling,
+}
+
+impl FragmentShaderData for Shader {
+    /// The unique key identifying this shader module.
+    fn key(&self) -> Option<FragmentShaderKey> {
+        Some(FragmentShaderKey::Warp(self.kind))
+    }
+
+    /// The SPIR-V shader source code.
+    fn spirv_source(&self) -> Cow<'static, [u8]> {
+        Cow::Borrowed(match self.kind {
+            Kind::Undistort => SHADER_UNDISTORT,
+            Kind::Displace => SHADER_DISPLACE,
+        })
+    }
+
+    /// Encode the shader's data into the buffer, returning the descriptor to that.
+    fn binary_data(&self, buffer: &mut Vec<u8>) -> Option<BufferInitContent> {
+        let [k1, k2, k3] = self.radial;
+        let [p1, p2] = self.tangential;
+        let mode = [self.sampling as u32, 0, 0, 0];
+
+        let mut content = BufferInitContent::builder(buffer);
+        content.extend_from_pods(&[self.camera, [k1, k2, k3, 0.0], [p1, p2, 0.0, 0.0]]);
+        content.extend_from_pods(&[mode]);
+        Some(content.build())
+    }
+
+    fn num_args(&self) -> u32 {
+        match self.kind {
+            Kind::Undistort => 1,
+            Kind::Displace => 2,
+        }
+    }
+}
@@ -13,3 +13,41 @@ impl<Item, T: Extend<Item> + ?Sized> ExtendOne<Item> for T {
 }
 
 pub use ping_future::Ping;
+
+/// Convert to the bits of an IEEE 754 binary16 float, rounding to nearest even.
+///
+/// Values out of range become infinite, `NaN` stays `NaN`.
+pub(crate) fn f32_to_f16_bits(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x007f_ffff;
+
+    if exponent == 0xff {
+        let quiet = if mantissa != 0 { 0x0200 } else { 0 };
+        return sign | 0x7c00 | quiet;
+    }
+
+    let exponent = exponent - 127 + 15;
+
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+
+    if exponent <= 0 {
+        // Subnormal, or too small to be represented at all.
+        if exponent < -10 {
+            return sign;
+        }
+
+        let mantissa = mantissa | 0x0080_0000;
+        let shift = (14 - exponent) as u32;
+        let round = (1 << (shift - 1)) - 1 + ((mantissa >> shift) & 1);
+        return sign | ((mantissa + round) >> shift) as u16;
+    }
+
+    let combined = ((exponent as u32) << 23) | mantissa;
+    let round = 0x0fff + ((combined >> 13) & 1);
+    // A carry of the mantissa correctly increments the exponent, up to infinity.
+    sign | ((combined + round) >> 13) as u16
+}
@@ -71,6 +71,8 @@ fn integration() {
 
     run_swap(&mut pool, pool_background.clone());
 
+    run_bgra_staging(&mut pool);
+
     run_oklab(&mut pool);
 
     run_srlab2(&mut pool);
@@ -78,6 +80,37 @@ fn integration() {
     run_derivative(&mut pool, pool_background.clone());
 
     run_solid(&mut pool);
+
+    run_wide_staging(&mut pool, pool_background.clone());
+
+    run_planar(&mut pool);
+
+    run_packed_staging(&mut pool, pool_background.clone());
+
+    run_indexed(&mut pool);
+
+    run_quantize(&mut pool);
+
+    run_dither(&mut pool);
+
+    run_premultiplied(&mut pool);
+    run_channels(&mut pool);
+    run_flatten(&mut pool);
+    run_orient(&mut pool);
+    run_tile(&mut pool);
+    run_perspective(&mut pool);
+    run_morphology(&mut pool);
+    run_convolve(&mut pool);
+    run_ycbcr(&mut pool);
+    run_curves(&mut pool);
+    run_crop(&mut pool);
+    run_warp(&mut pool);
+    run_affine_channels(&mut pool);
+    run_tone_map(&mut pool);
+    run_lut3d(&mut pool);
+    run_color_chain(&mut pool);
+    run_cie(&mut pool);
+    run_hsl(&mut pool);
 }
 
 fn run_blending(
@@ -445,6 +478,84 @@ fn run_swap(pool: &mut Pool, (orig_key, orig_descriptor): (PoolKey, Descriptor))
     util::assert_reference(image_swapped.into(), "swapped.png.crc");
 }
 
+fn run_bgra_staging(pool: &mut Pool) {
+    const TEXEL: [u8; 4] = [200, 100, 50, 255];
+
+    let image = image::RgbaImage::from_pixel(4, 4, image::Rgba(TEXEL));
+    let entry = pool.insert_srgb(&image::DynamicImage::ImageRgba8(image));
+    let (key, descriptor) = (entry.key(), entry.descriptor());
+
+    // Not a native format, encoded and decoded by the staging shaders.
+    let bgra = buffer::Texel {
+        block: buffer::Block::Pixel,
+        bits: buffer::SampleBits::UInt16x4,
+        parts: buffer::SampleParts::BgrA,
+    };
+
+    let mut commands = CommandBuffer::default();
+    let input = commands.input(descriptor.clone()).unwrap();
+    let staged = commands
+        .color_convert(input, descriptor.color.clone(), bgra)
+        .expect("Valid to reorder the channels");
+    let (output, staged_descriptor) = commands.output(staged).expect("Valid for output");
+    let staged_descriptor = staged_descriptor
+        .as_concrete()
+        .expect("Reordered texels are concrete");
+
+    let staged_key = run_once_with_output(
+        commands,
+        pool,
+        vec![(input, key)],
+        retire_with_one_image(output),
+    );
+
+    let staged = pool.entry(staged_key).unwrap();
+    let bytes = staged.as_bytes().expect("Result is host allocated");
+    let expected = [TEXEL[2], TEXEL[1], TEXEL[0], TEXEL[3]].map(|ch| u16::from(ch) * 257);
+
+    for texel in bytes.chunks_exact(8) {
+        let texel: Vec<u16> = texel
+            .chunks_exact(2)
+            .map(|ch| u16::from_le_bytes([ch[0], ch[1]]))
+            .collect();
+        // Within half a step of the 8-bit codes, which is all the precision of the input.
+        let close = texel
+            .iter()
+            .zip(&expected)
+            .all(|(&a, &b)| a.abs_diff(b) <= 128);
+        assert!(
+            close,
+            "Encoded {:?}, expected blue, green, red and alpha",
+            texel
+        );
+    }
+
+    let mut commands = CommandBuffer::default();
+    let input = commands.input(staged_descriptor.clone()).unwrap();
+    let back = commands
+        .color_convert(
+            input,
+            staged_descriptor.color.clone(),
+            descriptor.texel.clone(),
+        )
+        .expect("Valid to reorder the channels");
+    let (output, _outformat) = commands.output(back).expect("Valid for output");
+
+    let result = run_once_with_output(
+        commands,
+        pool,
+        vec![(input, staged_key)],
+        retire_with_one_image(output),
+    );
+
+    let result = pool.entry(result).unwrap();
+    let bytes = result.as_bytes().expect("Result is host allocated");
+
+    for texel in bytes.chunks_exact(4) {
+        assert_eq!(texel, TEXEL, "Decoded red, green, blue and alpha");
+    }
+}
+
 fn run_oklab(pool: &mut Pool) {
     let mut commands = CommandBuffer::default();
 
@@ -521,6 +632,8 @@ fn run_srlab2(pool: &mut Pool) {
         color: buffer::Color::Scalars {
             transfer: buffer::Transfer::Linear,
         },
+        alpha: buffer::AlphaMode::Straight,
+        cie: None,
     };
 
     let srlab2_texel = buffer::Descriptor {
@@ -641,3 +754,2066 @@ fn run_solid(pool: &mut Pool) {
 
     util::assert_reference_image(layout, "solid.crc.png");
 }
+
+/// Round-trip through texels of 64 and 128 bits, which are staged as RGBA16 and RGBA32.
+fn run_wide_staging(pool: &mut Pool, (orig_key, orig_descriptor): (PoolKey, Descriptor)) {
+    const BITS: &[buffer::SampleBits] = &[
+        buffer::SampleBits::UInt16x4,
+        buffer::SampleBits::Float16x4,
+        buffer::SampleBits::Float32x4,
+    ];
+
+    let original = pool
+        .entry(orig_key)
+        .unwrap()
+        .as_bytes()
+        .expect("Background is host allocated")
+        .to_vec();
+
+    for &bits in BITS {
+        let mut commands = CommandBuffer::default();
+        let input = commands.input(orig_descriptor.clone()).unwrap();
+
+        let wide_texel = buffer::Texel {
+            block: buffer::Block::Pixel,
+            bits,
+            parts: buffer::SampleParts::RgbA,
+        };
+
+        let wide = commands
+            .color_convert(input, orig_descriptor.color.clone(), wide_texel)
+            .expect("Valid to widen the texel");
+        let back = commands
+            .color_convert(
+                wide,
+                orig_descriptor.color.clone(),
+                orig_descriptor.texel.clone(),
+            )
+            .expect("Valid to narrow the texel");
+
+        let (output, _outformat) = commands.output(back).expect("Valid for output");
+
+        let result = run_once_with_output(
+            commands,
+            pool,
+            vec![(input, orig_key)],
+            retire_with_one_image(output),
+        );
+
+        let image = pool.entry(result).unwrap();
+        let bytes = image.as_bytes().expect("Result is host allocated");
+        assert_eq!(bytes.len(), original.len());
+
+        let max_difference = bytes
+            .iter()
+            .zip(&original)
+            .map(|(&a, &b)| a.abs_diff(b))
+            .max()
+            .unwrap_or(0);
+
+        assert!(
+            max_difference <= 1,
+            "Round-trip through {:?} changed a channel by {}",
+            bits,
+            max_difference
+        );
+    }
+}
+
+fn run_planar(pool: &mut Pool) {
+    const WIDTH: u32 = 16;
+    const HEIGHT: u32 = 8;
+
+    let frame = command::PlanarFrame {
+        planes: buffer::Planes {
+            layout: buffer::PlanarLayout::Nv12,
+            siting: buffer::ChromaSiting::default(),
+            upsampling: buffer::ChromaFilter::default(),
+        },
+        ycbcr: command::YCbCr {
+            coefficients: command::YCbCrCoefficients::Bt709,
+            range: command::YCbCrRange::Limited,
+        },
+    };
+
+    let descriptor = frame
+        .planes
+        .descriptor(buffer::Color::SRGB, WIDTH, HEIGHT)
+        .expect("Valid frame size");
+    // The chroma planes of 4:2:0 follow the luma plane in half as many rows.
+    let rows = HEIGHT + HEIGHT / 2;
+
+    // A ramp of luma under a constant, mild chroma, which stays within the RGB gamut.
+    let original: Vec<u8> = (0..rows)
+        .flat_map(|y| {
+            (0..WIDTH).map(move |x| match y < HEIGHT {
+                true => (64 + 8 * x + y) as u8,
+                false if x % 2 == 0 => 112,
+                false => 144,
+            })
+        })
+        .collect();
+
+    let luma = image::GrayImage::from_raw(WIDTH, rows, original.clone()).unwrap();
+    let buffer = buffer::ImageBuffer::from(&image::DynamicImage::ImageLuma8(luma));
+    let key = pool.insert(buffer, descriptor.clone()).key();
+
+    let mut commands = CommandBuffer::default();
+    let input = commands.input(descriptor).unwrap();
+
+    let rgb = buffer::Texel::new_u8(buffer::SampleParts::RgbA);
+    let decoded = commands
+        .planar_decode(input, frame, buffer::Color::SRGB, rgb)
+        .expect("Valid to decode the frame");
+    let encoded = commands
+        .planar_encode(decoded, frame)
+        .expect("Valid to encode the frame");
+
+    let (output, _outformat) = commands.output(encoded).expect("Valid for output");
+
+    let result = run_once_with_output(
+        commands,
+        pool,
+        vec![(input, key)],
+        retire_with_one_image(output),
+    );
+
+    let image = pool.entry(result).unwrap();
+    let bytes = image.as_bytes().expect("Result is host allocated");
+    assert_eq!(bytes.len(), original.len());
+
+    let max_difference = bytes
+        .iter()
+        .zip(&original)
+        .map(|(&a, &b)| a.abs_diff(b))
+        .max()
+        .unwrap_or(0);
+
+    assert!(
+        max_difference <= 2,
+        "Round-trip through RGB changed a code by {}",
+        max_difference
+    );
+}
+
+fn run_packed_staging(pool: &mut Pool, (orig_key, orig_descriptor): (PoolKey, Descriptor)) {
+    // The bits, the parts, the number of channels and the width of the least precise one.
+    const PACKED: &[(buffer::SampleBits, buffer::SampleParts, usize, u32)] = &[
+        (buffer::SampleBits::UInt565, buffer::SampleParts::Bgr, 3, 5),
+        (buffer::SampleBits::UInt233, buffer::SampleParts::Bgr, 3, 2),
+        (buffer::SampleBits::UInt4x4, buffer::SampleParts::RgbA, 4, 4),
+        (
+            buffer::SampleBits::UInt1010102,
+            buffer::SampleParts::RgbA,
+            4,
+            2,
+        ),
+    ];
+
+    let original = pool
+        .entry(orig_key)
+        .unwrap()
+        .to_image()
+        .expect("Background is host allocated")
+        .to_rgba8();
+
+    for &(bits, parts, channels, least_bits) in PACKED {
+        let packed_texel = buffer::Texel {
+            block: buffer::Block::Pixel,
+            bits,
+            parts,
+        };
+
+        let mut commands = CommandBuffer::default();
+        let input = commands.input(orig_descriptor.clone()).unwrap();
+        let packed = commands
+            .color_convert(input, orig_descriptor.color.clone(), packed_texel)
+            .expect("Valid to pack the texel");
+        let (output, packed_descriptor) = commands.output(packed).expect("Valid for output");
+        let packed_descriptor = packed_descriptor
+            .as_concrete()
+            .expect("Packed texels are concrete");
+
+        let packed_key = run_once_with_output(
+            commands,
+            pool,
+            vec![(input, orig_key)],
+            retire_with_one_image(output),
+        );
+
+        let image = pool.entry(packed_key).unwrap();
+        let packed_bytes = image.as_bytes().expect("Result is host allocated").to_vec();
+
+        // Widened on the host, each channel is within half a step of the least precise channel.
+        let widened = image
+            .to_image()
+            .expect("Packed texels are widened")
+            .to_rgba8();
+        let tolerance = 255 / ((1 << least_bits) - 1) / 2 + 1;
+
+        let max_difference = widened
+            .pixels()
+            .zip(original.pixels())
+            .flat_map(|(a, b)| a.0.iter().zip(&b.0).take(channels))
+            .map(|(&a, &b)| a.abs_diff(b))
+            .max()
+            .unwrap_or(0);
+
+        assert!(
+            u32::from(max_difference) <= tolerance,
+            "Packing into {:?} changed a channel by {}",
+            bits,
+            max_difference
+        );
+
+        // Decoding and encoding again must not change any code.
+        let mut commands = CommandBuffer::default();
+        let input = commands.input(packed_descriptor.clone()).unwrap();
+        let wide = commands
+            .color_convert(
+                input,
+                packed_descriptor.color.clone(),
+                orig_descriptor.texel.clone(),
+            )
+            .expect("Valid to unpack the texel");
+        let repacked = commands
+            .color_convert(
+                wide,
+                packed_descriptor.color.clone(),
+                packed_descriptor.texel.clone(),
+            )
+            .expect("Valid to pack the texel");
+        let (output, _outformat) = commands.output(repacked).expect("Valid for output");
+
+        let result = run_once_with_output(
+            commands,
+            pool,
+            vec![(input, packed_key)],
+            retire_with_one_image(output),
+        );
+
+        let image = pool.entry(result).unwrap();
+        let bytes = image.as_bytes().expect("Result is host allocated");
+        assert!(
+            bytes == packed_bytes,
+            "Round-trip through {:?} changed the codes",
+            bits
+        );
+    }
+}
+
+fn run_indexed(pool: &mut Pool) {
+    const COLORS: [[u8; 4]; 4] = [
+        [0, 0, 0, 255],
+        [255, 0, 0, 255],
+        [0, 128, 255, 255],
+        [255, 255, 255, 255],
+    ];
+
+    let layout = command::IndexedLayout {
+        depth: command::IndexDepth::Bits2,
+        width: 15,
+        height: 4,
+    };
+
+    let descriptor = layout.descriptor().expect("Valid indexed layout");
+    let (row_bytes, _) = descriptor.size();
+
+    // Every palette entry in turn, and zeroed padding bits at the end of each row.
+    let indices = |x: u32, y: u32| (x + y) % 4;
+    let original: Vec<u8> = (0..layout.height)
+        .flat_map(|y| {
+            (0..row_bytes).map(move |byte| {
+                (0..4).fold(0u8, |acc, i| {
+                    let x = byte * 4 + i;
+                    let index = if x < layout.width { indices(x, y) } else { 0 };
+                    acc | (index as u8) << (6 - 2 * i)
+                })
+            })
+        })
+        .collect();
+
+    let bytes = image::GrayImage::from_raw(row_bytes, layout.height, original.clone()).unwrap();
+    let buffer = buffer::ImageBuffer::from(&image::DynamicImage::ImageLuma8(bytes));
+    let indices_key = pool.insert(buffer, descriptor.clone()).key();
+
+    let palette = image::RgbaImage::from_fn(4, 1, |x, _| image::Rgba(COLORS[x as usize]));
+    let palette = image::DynamicImage::ImageRgba8(palette);
+    let palette_entry = pool.insert_srgb(&palette);
+    let (palette_key, palette_descriptor) = (palette_entry.key(), palette_entry.descriptor());
+
+    let mut commands = CommandBuffer::default();
+    let palette_reg = commands.input(palette_descriptor).unwrap();
+    let indices_reg = commands.input(descriptor).unwrap();
+
+    let colors = commands
+        .indexed_decode(palette_reg, indices_reg, layout)
+        .expect("Valid to expand indices");
+    let encoded = commands
+        .indexed_encode(colors, palette_reg, layout.depth)
+        .expect("Valid to map to the palette");
+
+    let (output, _outformat) = commands.output(encoded).expect("Valid for output");
+
+    let result = run_once_with_output(
+        commands,
+        pool,
+        vec![(palette_reg, palette_key), (indices_reg, indices_key)],
+        retire_with_one_image(output),
+    );
+
+    let image = pool.entry(result).unwrap();
+    let bytes = image.as_bytes().expect("Result is host allocated");
+    assert!(
+        bytes == &original[..],
+        "Indices changed in round-trip through the palette"
+    );
+}
+
+fn run_quantize(pool: &mut Pool) {
+    const BANDS: [[u8; 4]; 4] = [
+        [200, 30, 30, 255],
+        [30, 200, 30, 255],
+        [30, 30, 200, 255],
+        [240, 240, 240, 255],
+    ];
+
+    // Horizontal bands of color, each band four rows high.
+    let even = image::RgbaImage::from_fn(16, 16, |_, y| image::Rgba(BANDS[y as usize / 4]));
+    // Mostly the first band, which all but one of the evenly spread samples hit.
+    let skewed = image::RgbaImage::from_fn(16, 16, |_, y| {
+        let band = match y {
+            0..=11 => 0,
+            12 | 13 => 1,
+            14 => 2,
+            _ => 3,
+        };
+
+        image::Rgba(BANDS[band])
+    });
+
+    for image in [even, skewed] {
+        let entry = pool.insert_srgb(&image::DynamicImage::ImageRgba8(image));
+        let (key, descriptor) = (entry.key(), entry.descriptor());
+
+        let mut commands = CommandBuffer::default();
+        let input = commands.input(descriptor).unwrap();
+
+        let palette = commands
+            .quantize_palette(
+                input,
+                command::PaletteQuantization {
+                    colors: 4,
+                    iterations: 2,
+                },
+            )
+            .expect("Valid to quantize");
+
+        let (output, _outformat) = commands.output(palette).expect("Valid for output");
+
+        let result = run_once_with_output(
+            commands,
+            pool,
+            vec![(input, key)],
+            retire_with_one_image(output),
+        );
+
+        let image = pool.entry(result).unwrap();
+        let colors = image.as_bytes().expect("Result is host allocated");
+        assert_eq!(colors.len(), 16);
+
+        for band in &BANDS {
+            let found = colors
+                .chunks_exact(4)
+                .any(|color| color.iter().zip(band).all(|(&a, &b)| a.abs_diff(b) <= 2));
+
+            assert!(
+                found,
+                "No palette color close to {:?} in {:?}",
+                band, colors
+            );
+        }
+    }
+}
+
+fn run_dither(pool: &mut Pool) {
+    // A gray between two codes of 5 and of 6 bits, which rounding would move by up to 3.
+    const GRAY: u8 = 104;
+
+    let image = image::RgbaImage::from_pixel(32, 32, image::Rgba([GRAY, GRAY, GRAY, 255]));
+    let entry = pool.insert_srgb(&image::DynamicImage::ImageRgba8(image));
+    let (key, descriptor) = (entry.key(), entry.descriptor());
+
+    let rgb565 = buffer::Texel {
+        block: buffer::Block::Pixel,
+        bits: buffer::SampleBits::UInt565,
+        parts: buffer::SampleParts::Rgb,
+    };
+
+    for &method in &[command::Dither::Bayer, command::Dither::BlueNoise] {
+        let mut commands = CommandBuffer::default();
+        let input = commands.input(descriptor.clone()).unwrap();
+        let (output, _outformat) = commands
+            .output_dithered(input, method, rgb565.clone())
+            .expect("Valid to dither for output");
+
+        let result = run_once_with_output(
+            commands,
+            pool,
+            vec![(input, key)],
+            retire_with_one_image(output),
+        );
+
+        let widened = pool
+            .entry(result)
+            .unwrap()
+            .to_image()
+            .expect("Packed texels are widened")
+            .to_rgba8();
+
+        // On average, the dithered codes reproduce the original value.
+        for channel in 0..3 {
+            let sum: u32 = widened.pixels().map(|px| u32::from(px.0[channel])).sum();
+            let mean = sum as f32 / (32.0 * 32.0);
+
+            assert!(
+                (mean - f32::from(GRAY)).abs() < 1.5,
+                "Dithering with {:?} moved channel {} to {}",
+                method,
+                channel,
+                mean
+            );
+        }
+    }
+
+    // Half the light between black and white, which is the encoded value 188.
+    const HALF: u8 = 188;
+
+    let image = image::RgbaImage::from_pixel(32, 32, image::Rgba([HALF, HALF, HALF, 255]));
+    let entry = pool.insert_srgb(&image::DynamicImage::ImageRgba8(image));
+    let (key, descriptor) = (entry.key(), entry.descriptor());
+
+    let palette = image::RgbaImage::from_fn(2, 1, |x, _| {
+        image::Rgba([255 * x as u8, 255 * x as u8, 255 * x as u8, 255])
+    });
+    let palette_entry = pool.insert_srgb(&image::DynamicImage::ImageRgba8(palette));
+    let (palette_key, palette_descriptor) = (palette_entry.key(), palette_entry.descriptor());
+
+    for &method in &[command::Dither::Bayer, command::Dither::BlueNoise] {
+        let mut commands = CommandBuffer::default();
+        let input = commands.input(descriptor.clone()).unwrap();
+        let palette = commands.input(palette_descriptor.clone()).unwrap();
+        let encoded = commands
+            .indexed_encode_dithered(input, palette, command::IndexDepth::Bits1, method)
+            .expect("Valid to dither to the palette");
+        let (output, _outformat) = commands.output(encoded).expect("Valid for output");
+
+        let result = run_once_with_output(
+            commands,
+            pool,
+            vec![(input, key), (palette, palette_key)],
+            retire_with_one_image(output),
+        );
+
+        let image = pool.entry(result).unwrap();
+        let bytes = image.as_bytes().expect("Result is host allocated");
+        assert_eq!(bytes.len(), 4 * 32);
+
+        // About every second pixel is white, where nearest colors would all be white.
+        let white: u32 = bytes.iter().map(|byte| byte.count_ones()).sum();
+        let share = white as f32 / (32.0 * 32.0);
+        assert!(
+            (share - 0.5).abs() < 0.05,
+            "Dithering with {:?} to the palette made {} white",
+            method,
+            share
+        );
+    }
+}
+
+fn run_premultiplied(pool: &mut Pool) {
+    const STRAIGHT: [u8; 4] = [200, 100, 50, 128];
+    // The encoded values multiplied by alpha.
+    const PREMULTIPLIED: [u8; 4] = [100, 50, 25, 128];
+
+    let image = image::RgbaImage::from_pixel(8, 8, image::Rgba(STRAIGHT));
+    let entry = pool.insert_srgb(&image::DynamicImage::ImageRgba8(image));
+    let (key, descriptor) = (entry.key(), entry.descriptor());
+
+    let mut commands = CommandBuffer::default();
+    let input = commands.input(descriptor).unwrap();
+    let premultiplied = commands.premultiply(input).expect("Valid to premultiply");
+    let (output, outformat) = commands.output(premultiplied).expect("Valid for output");
+
+    let result = run_once_with_output(
+        commands,
+        pool,
+        vec![(input, key)],
+        retire_with_one_image(output),
+    );
+
+    let image = pool.entry(result).unwrap();
+    let bytes = image.as_bytes().expect("Result is host allocated");
+
+    for texel in bytes.chunks_exact(4) {
+        let close = texel
+            .iter()
+            .zip(&PREMULTIPLIED)
+            .all(|(&a, &b)| a.abs_diff(b) <= 1);
+        assert!(close, "Premultiplied to {:?}", texel);
+    }
+
+    // Loading the premultiplied texels restores the straight colors.
+    let premultiplied_descriptor = outformat.as_concrete().unwrap();
+    assert_eq!(
+        premultiplied_descriptor.alpha,
+        buffer::AlphaMode::Premultiplied
+    );
+
+    let mut commands = CommandBuffer::default();
+    let input = commands.input(premultiplied_descriptor).unwrap();
+    let straight = commands
+        .unpremultiply(input)
+        .expect("Valid to unpremultiply");
+    let (output, _outformat) = commands.output(straight).expect("Valid for output");
+
+    let result = run_once_with_output(
+        commands,
+        pool,
+        vec![(input, result)],
+        retire_with_one_image(output),
+    );
+
+    let image = pool.entry(result).unwrap();
+    let bytes = image.as_bytes().expect("Result is host allocated");
+
+    for texel in bytes.chunks_exact(4) {
+        let close = texel
+            .iter()
+            .zip(&STRAIGHT)
+            .all(|(&a, &b)| a.abs_diff(b) <= 2);
+        assert!(close, "Restored {:?}", texel);
+    }
+}
+
+fn run_channels(pool: &mut Pool) {
+    use buffer::ColorChannel;
+    use command::ChannelSource;
+
+    const TEXEL: [u8; 4] = [200, 100, 50, 128];
+    // Red and blue exchanged, alpha made opaque.
+    const SWIZZLED: [u8; 4] = [50, 100, 200, 255];
+
+    let image = image::RgbaImage::from_pixel(8, 8, image::Rgba(TEXEL));
+    let entry = pool.insert_srgb(&image::DynamicImage::ImageRgba8(image));
+    let (key, descriptor) = (entry.key(), entry.descriptor());
+
+    let mut commands = CommandBuffer::default();
+    let input = commands.input(descriptor.clone()).unwrap();
+    let channels = [
+        ColorChannel::R,
+        ColorChannel::G,
+        ColorChannel::B,
+        ColorChannel::Alpha,
+    ]
+    .iter()
+    .map(|&channel| commands.extract(input, channel))
+    .collect::<Result<Vec<_>, _>>()
+    .expect("Valid to extract");
+    let merged = commands
+        .merge(
+            &channels,
+            descriptor.color.clone(),
+            descriptor.texel.clone(),
+        )
+        .expect("Valid to merge");
+    let swizzled = commands
+        .swizzle(
+            merged,
+            [
+                ChannelSource::Channel(ColorChannel::B),
+                ChannelSource::Channel(ColorChannel::G),
+                ChannelSource::Channel(ColorChannel::R),
+                ChannelSource::One,
+            ],
+        )
+        .expect("Valid to swizzle");
+    let (output, _outformat) = commands.output(swizzled).expect("Valid for output");
+
+    let result = run_once_with_output(
+        commands,
+        pool,
+        vec![(input, key)],
+        retire_with_one_image(output),
+    );
+
+    let image = pool.entry(result).unwrap();
+    let bytes = image.as_bytes().expect("Result is host allocated");
+
+    for texel in bytes.chunks_exact(4) {
+        let close = texel
+            .iter()
+            .zip(&SWIZZLED)
+            .all(|(&a, &b)| a.abs_diff(b) <= 1);
+        assert!(close, "Swizzled to {:?}", texel);
+    }
+}
+
+fn run_flatten(pool: &mut Pool) {
+    const TEXEL: [u8; 4] = [200, 100, 50, 128];
+    // Composited onto white in linear light.
+    const FLATTENED: [u8; 3] = [230, 198, 190];
+
+    let image = image::RgbaImage::from_pixel(8, 8, image::Rgba(TEXEL));
+    let entry = pool.insert_srgb(&image::DynamicImage::ImageRgba8(image));
+    let (key, descriptor) = (entry.key(), entry.descriptor());
+
+    let mut commands = CommandBuffer::default();
+    let input = commands.input(descriptor).unwrap();
+    let flattened = commands
+        .flatten(input, [1.0, 1.0, 1.0])
+        .expect("Valid to flatten");
+    let (output, outformat) = commands.output(flattened).expect("Valid for output");
+
+    let result = run_once_with_output(
+        commands,
+        pool,
+        vec![(input, key)],
+        retire_with_one_image(output),
+    );
+
+    let outformat = outformat.as_concrete().unwrap();
+    assert_eq!(outformat.texel.parts, buffer::SampleParts::Rgb);
+
+    let image = pool.entry(result).unwrap();
+    let bytes = image.as_bytes().expect("Result is host allocated");
+
+    for texel in bytes.chunks_exact(3) {
+        let close = texel
+            .iter()
+            .zip(&FLATTENED)
+            .all(|(&a, &b)| a.abs_diff(b) <= 1);
+        assert!(close, "Flattened to {:?}", texel);
+    }
+}
+
+fn run_orient(pool: &mut Pool) {
+    let image = image::RgbaImage::from_fn(3, 2, |x, y| {
+        image::Rgba([50 * x as u8, 100 * y as u8, 0, 255])
+    });
+
+    let entry = pool.insert_srgb(&image::DynamicImage::ImageRgba8(image.clone()));
+    let (key, descriptor) = (entry.key(), entry.descriptor());
+
+    let mut commands = CommandBuffer::default();
+    let input = commands.input(descriptor).unwrap();
+    let rotated = commands.rotate90(input, 1).expect("Valid to rotate");
+    let (output, outformat) = commands.output(rotated).expect("Valid for output");
+
+    let result = run_once_with_output(
+        commands,
+        pool,
+        vec![(input, key)],
+        retire_with_one_image(output),
+    );
+
+    assert_eq!(outformat.as_concrete().unwrap().size(), (2, 3));
+
+    let rotated = pool.entry(result).unwrap();
+    let rotated = rotated.to_image().expect("Result is host allocated");
+    let rotated = rotated.to_rgba8();
+
+    // The left column becomes the top row, from bottom to top.
+    let expected = image::imageops::rotate90(&image);
+    for (a, b) in rotated.pixels().zip(expected.pixels()) {
+        let close = a.0.iter().zip(&b.0).all(|(&a, &b)| a.abs_diff(b) <= 1);
+        assert!(close, "Rotated to {:?}, expected {:?}", a, b);
+    }
+}
+
+fn run_tile(pool: &mut Pool) {
+    let image = image::RgbaImage::from_fn(2, 2, |x, y| {
+        image::Rgba([200 * x as u8, 200 * y as u8, 0, 255])
+    });
+
+    let entry = pool.insert_srgb(&image::DynamicImage::ImageRgba8(image.clone()));
+    let (key, descriptor) = (entry.key(), entry.descriptor());
+
+    let mut commands = CommandBuffer::default();
+    let input = commands.input(descriptor).unwrap();
+    let tiled = commands
+        .tile(input, (6, 4), command::BorderMode::Repeat)
+        .expect("Valid to tile");
+    let padded = commands
+        .pad(
+            tiled,
+            command::Insets {
+                left: 1,
+                ..Default::default()
+            },
+            command::BorderMode::Constant([0.0, 0.0, 1.0, 1.0]),
+        )
+        .expect("Valid to pad");
+    let (output, _outformat) = commands.output(padded).expect("Valid for output");
+
+    let result = run_once_with_output(
+        commands,
+        pool,
+        vec![(input, key)],
+        retire_with_one_image(output),
+    );
+
+    let padded = pool.entry(result).unwrap();
+    let padded = padded.to_image().expect("Result is host allocated");
+    let padded = padded.to_rgba8();
+    assert_eq!(padded.dimensions(), (7, 4));
+
+    for (x, y, pixel) in padded.enumerate_pixels() {
+        let expected = match x.checked_sub(1) {
+            None => image::Rgba([0, 0, 255, 255]),
+            Some(x) => *image.get_pixel(x % 2, y % 2),
+        };
+
+        let close = pixel
+            .0
+            .iter()
+            .zip(&expected.0)
+            .all(|(&a, &b)| a.abs_diff(b) <= 1);
+        assert!(
+            close,
+            "At {}, {}: {:?}, expected {:?}",
+            x, y, pixel, expected
+        );
+    }
+}
+
+fn run_perspective(pool: &mut Pool) {
+    const BLACK: image::Rgba<u8> = image::Rgba([0, 0, 0, 255]);
+    const WHITE: image::Rgba<u8> = image::Rgba([255, 255, 255, 255]);
+
+    let below = image::RgbaImage::from_pixel(8, 8, BLACK);
+    let above = image::RgbaImage::from_pixel(4, 4, WHITE);
+
+    let below = pool.insert_srgb(&image::DynamicImage::ImageRgba8(below));
+    let (below_key, below_descriptor) = (below.key(), below.descriptor());
+    let above = pool.insert_srgb(&image::DynamicImage::ImageRgba8(above));
+    let (above_key, above_descriptor) = (above.key(), above.descriptor());
+
+    let mut commands = CommandBuffer::default();
+    let below = commands.input(below_descriptor).unwrap();
+    let above = commands.input(above_descriptor).unwrap();
+
+    let src_quad = [[0.0, 0.0], [4.0, 0.0], [4.0, 4.0], [0.0, 4.0]];
+    let dst_quad = [[2.0, 2.0], [6.0, 2.0], [6.0, 6.0], [2.0, 6.0]];
+    let warped = commands
+        .perspective(
+            below,
+            src_quad,
+            dst_quad,
+            above,
+            command::AffineSample::Nearest,
+        )
+        .expect("Valid to warp");
+    let (output, _outformat) = commands.output(warped).expect("Valid for output");
+
+    let result = run_once_with_output(
+        commands,
+        pool,
+        vec![(below, below_key), (above, above_key)],
+        retire_with_one_image(output),
+    );
+
+    let warped = pool.entry(result).unwrap();
+    let warped = warped.to_image().expect("Result is host allocated");
+    let warped = warped.to_rgba8();
+
+    for (x, y, pixel) in warped.enumerate_pixels() {
+        let inside = (2..6).contains(&x) && (2..6).contains(&y);
+        let expected = if inside { WHITE } else { BLACK };
+        assert_eq!(*pixel, expected, "At {}, {}", x, y);
+    }
+
+    // Bi-linear samples mix colors premultiplied by alpha, while the image below is copied.
+    const RED: image::Rgba<u8> = image::Rgba([255, 0, 0, 255]);
+    const CLEAR_GREEN: image::Rgba<u8> = image::Rgba([0, 255, 0, 0]);
+    const CLEAR_BLUE: image::Rgba<u8> = image::Rgba([0, 0, 255, 0]);
+
+    let below = image::RgbaImage::from_pixel(4, 1, CLEAR_BLUE);
+    let above = image::RgbaImage::from_fn(2, 1, |x, _| if x == 0 { RED } else { CLEAR_GREEN });
+
+    let below = pool.insert_srgb(&image::DynamicImage::ImageRgba8(below));
+    let (below_key, below_descriptor) = (below.key(), below.descriptor());
+    let above = pool.insert_srgb(&image::DynamicImage::ImageRgba8(above));
+    let (above_key, above_descriptor) = (above.key(), above.descriptor());
+
+    let mut commands = CommandBuffer::default();
+    let below = commands.input(below_descriptor).unwrap();
+    let above = commands.input(above_descriptor).unwrap();
+
+    // The center of the second pixel is sampled right between the two texels above.
+    let src_quad = [[0.0, 0.0], [2.0, 0.0], [2.0, 1.0], [0.0, 1.0]];
+    let dst_quad = [[0.0, 0.0], [3.0, 0.0], [3.0, 1.0], [0.0, 1.0]];
+    let warped = commands
+        .perspective(
+            below,
+            src_quad,
+            dst_quad,
+            above,
+            command::AffineSample::BiLinear,
+        )
+        .expect("Valid to warp");
+    let (output, _outformat) = commands.output(warped).expect("Valid for output");
+
+    let result = run_once_with_output(
+        commands,
+        pool,
+        vec![(below, below_key), (above, above_key)],
+        retire_with_one_image(output),
+    );
+
+    let warped = pool.entry(result).unwrap();
+    let warped = warped.to_image().expect("Result is host allocated");
+    let warped = warped.to_rgba8();
+
+    let expected = [
+        [255, 0, 0, 255],
+        [255, 0, 0, 128],
+        [0, 0, 0, 0],
+        CLEAR_BLUE.0,
+    ];
+    for (x, pixel) in warped.pixels().enumerate() {
+        let close = pixel
+            .0
+            .iter()
+            .zip(&expected[x])
+            .all(|(&a, &b)| a.abs_diff(b) <= 1);
+        assert!(close, "At {}: {:?}, expected {:?}", x, pixel, expected[x]);
+    }
+}
+
+fn run_morphology(pool: &mut Pool) {
+    const BLACK: image::Rgba<u8> = image::Rgba([0, 0, 0, 255]);
+    const WHITE: image::Rgba<u8> = image::Rgba([255, 255, 255, 255]);
+
+    let image = image::RgbaImage::from_fn(40, 3, |x, y| {
+        if (7 * x + 3 * y) % 11 < 5 {
+            WHITE
+        } else {
+            BLACK
+        }
+    });
+
+    let entry = pool.insert_srgb(&image::DynamicImage::ImageRgba8(image.clone()));
+    let (key, descriptor) = (entry.key(), entry.descriptor());
+
+    // Long enough to be scanned in blocks, which do not divide the width.
+    let mut commands = CommandBuffer::default();
+    let input = commands.input(descriptor).unwrap();
+    let eroded = commands
+        .erode(input, &command::StructuringElement::Rectangle(17, 0), 1)
+        .expect("Valid to erode");
+    let dilated = commands
+        .dilate(eroded, &command::StructuringElement::Square(1), 1)
+        .expect("Valid to dilate");
+    let (output, _outformat) = commands.output(dilated).expect("Valid for output");
+
+    let result = run_once_with_output(
+        commands,
+        pool,
+        vec![(input, key)],
+        retire_with_one_image(output),
+    );
+
+    let result = pool.entry(result).unwrap();
+    let result = result.to_image().expect("Result is host allocated");
+    let result = result.to_rgba8();
+    assert_eq!(result.dimensions(), (40, 3));
+
+    // The extremum within radii around a pixel, clipped to the image.
+    fn extremum(
+        (width, height): (u32, u32),
+        (x, y): (u32, u32),
+        (rx, ry): (u32, u32),
+        pick: fn(u8, u8) -> u8,
+        value: &dyn Fn(u32, u32) -> u8,
+    ) -> u8 {
+        let xs = x.saturating_sub(rx)..=(x + rx).min(width - 1);
+        let ys = y.saturating_sub(ry)..=(y + ry).min(height - 1);
+        ys.flat_map(|y| xs.clone().map(move |x| value(x, y)))
+            .reduce(pick)
+            .unwrap()
+    }
+
+    let size = image.dimensions();
+    let eroded = |x, y| {
+        extremum(size, (x, y), (17, 0), u8::min, &|x, y| {
+            image.get_pixel(x, y).0[0]
+        })
+    };
+    for (x, y, pixel) in result.enumerate_pixels() {
+        let expected = extremum(size, (x, y), (1, 1), u8::max, &eroded);
+        assert_eq!(
+            pixel.0,
+            [expected, expected, expected, 255],
+            "At {}, {}",
+            x,
+            y
+        );
+    }
+}
+
+fn run_convolve(pool: &mut Pool) {
+    use command::{BorderMode, Kernel, KernelChannels, KernelNormalization};
+
+    const BLACK: image::Rgba<u8> = image::Rgba([0, 0, 0, 255]);
+    const WHITE: image::Rgba<u8> = image::Rgba([255, 255, 255, 255]);
+
+    // Only black and white, which are the same in linear light.
+    let image = image::RgbaImage::from_fn(
+        6,
+        5,
+        |x, y| {
+            if (x + 2 * y) % 3 == 0 {
+                WHITE
+            } else {
+                BLACK
+            }
+        },
+    );
+
+    let entry = pool.insert_srgb(&image::DynamicImage::ImageRgba8(image.clone()));
+    let (key, descriptor) = (entry.key(), entry.descriptor());
+
+    let binomial = [1.0, 2.0, 1.0, 2.0, 4.0, 2.0, 1.0, 2.0, 1.0];
+    let separable = Kernel {
+        normalization: KernelNormalization::Sum,
+        ..Kernel::new(3, 3, binomial.to_vec())
+    };
+
+    // Disturbing a corner, by too little to show in 8 bits, keeps the kernel from being separated.
+    let mut disturbed = binomial;
+    disturbed[0] += 1e-3;
+    let direct = Kernel {
+        weights: disturbed.to_vec(),
+        ..separable.clone()
+    };
+
+    let luma = Kernel {
+        normalization: KernelNormalization::Sum,
+        channels: KernelChannels::Luma,
+        border: BorderMode::Constant([0.0, 0.0, 0.0, 1.0]),
+        ..Kernel::new(3, 3, vec![1.0; 9])
+    };
+
+    let mut results = vec![];
+    for kernel in [separable, direct, luma] {
+        let mut commands = CommandBuffer::default();
+        let input = commands.input(descriptor.clone()).unwrap();
+        let convolved = commands.convolve(input, kernel).expect("Valid to convolve");
+        let (output, _outformat) = commands.output(convolved).expect("Valid for output");
+
+        let result = run_once_with_output(
+            commands,
+            pool,
+            vec![(input, key)],
+            retire_with_one_image(output),
+        );
+
+        let result = pool.entry(result).unwrap();
+        let result = result.to_image().expect("Result is host allocated");
+        let result = result.to_rgba8();
+        assert_eq!(result.dimensions(), (6, 5));
+        results.push(result);
+    }
+
+    fn encode_srgb(linear: f32) -> u8 {
+        let encoded = if linear <= 0.003_130_8 {
+            12.92 * linear
+        } else {
+            1.055 * linear.powf(1.0 / 2.4) - 0.055
+        };
+
+        (encoded * 255.0).round() as u8
+    }
+
+    // The normalized weighted sum in linear light, with pixels outside from `outside`.
+    let convolved = |(x, y): (u32, u32), weights: &[f32; 9], outside: &dyn Fn(i64, i64) -> f32| {
+        let mut sum = 0.0;
+        for (idx, weight) in weights.iter().enumerate() {
+            let (dx, dy) = (idx as i64 % 3 - 1, idx as i64 / 3 - 1);
+            sum += weight * outside(i64::from(x) + dx, i64::from(y) + dy);
+        }
+
+        sum / weights.iter().sum::<f32>()
+    };
+
+    let value = |x: i64, y: i64| f32::from(image.get_pixel(x as u32, y as u32).0[0]) / 255.0;
+    let clamped = |x: i64, y: i64| value(x.clamp(0, 5), y.clamp(0, 4));
+    let constant = |x: i64, y: i64| {
+        if (0..6).contains(&x) && (0..5).contains(&y) {
+            value(x, y)
+        } else {
+            0.0
+        }
+    };
+
+    let box_weights = [1.0; 9];
+    let cases = [
+        ("separable", &binomial, &clamped as &dyn Fn(i64, i64) -> f32),
+        ("direct", &binomial, &clamped),
+        ("luma", &box_weights, &constant),
+    ];
+
+    for (result, (name, weights, outside)) in results.iter().zip(cases) {
+        for (x, y, pixel) in result.enumerate_pixels() {
+            let value = encode_srgb(convolved((x, y), weights, outside));
+            let close = pixel.0[..3].iter().all(|&ch| ch.abs_diff(value) <= 1);
+            assert!(
+                close && pixel.0[3] == 255,
+                "{} at {}, {}: {:?}, expected {}",
+                name,
+                x,
+                y,
+                pixel,
+                value
+            );
+        }
+    }
+
+    // Both ways of applying the same kernel agree.
+    for (a, b) in results[0].pixels().zip(results[1].pixels()) {
+        let close = a.0.iter().zip(&b.0).all(|(&a, &b)| a.abs_diff(b) <= 1);
+        assert!(close, "Separated to {:?}, directly {:?}", a, b);
+    }
+}
+
+fn run_ycbcr(pool: &mut Pool) {
+    use command::{YCbCr, YCbCrCoefficients, YCbCrRange};
+
+    // Grays, primaries and a mix, in rows whose packed texels do not end on a word.
+    const COLORS: [[u8; 4]; 6] = [
+        [0, 0, 0, 255],
+        [255, 255, 255, 255],
+        [128, 128, 128, 255],
+        [255, 0, 0, 255],
+        [0, 0, 255, 255],
+        [200, 120, 40, 255],
+    ];
+
+    let image = image::RgbaImage::from_fn(6, 2, |x, y| {
+        image::Rgba(COLORS[(x as usize + 3 * y as usize) % 6])
+    });
+
+    let entry = pool.insert_srgb(&image::DynamicImage::ImageRgba8(image.clone()));
+    let (key, descriptor) = (entry.key(), entry.descriptor());
+
+    let jpeg = YCbCr {
+        coefficients: YCbCrCoefficients::Bt601,
+        range: YCbCrRange::Full,
+    };
+    let studio = YCbCr {
+        coefficients: YCbCrCoefficients::Bt709,
+        range: YCbCrRange::Limited,
+    };
+
+    // The bits, the matrix with its weights of red and blue, the scale and offset of the luma and
+    // the color difference codes, and the codes of a texel.
+    type Codes = fn(&[u8]) -> [u32; 3];
+    let cases: [(_, _, _, [f32; 4], Codes); 2] = [
+        (
+            buffer::SampleBits::UInt8x3,
+            jpeg,
+            (0.299, 0.114),
+            [255.0, 0.0, 255.0, 128.0],
+            |bytes| [0, 1, 2].map(|idx| u32::from(bytes[idx])),
+        ),
+        (
+            buffer::SampleBits::UInt1010102,
+            studio,
+            (0.2126, 0.0722),
+            [876.0, 64.0, 896.0, 512.0],
+            |bytes| {
+                let word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                [0, 10, 20].map(|shift| (word >> shift) & 0x3ff)
+            },
+        ),
+    ];
+
+    for (bits, ycbcr, (kr, kb), levels, codes) in cases {
+        let yuv = buffer::Texel {
+            block: buffer::Block::Pixel,
+            bits,
+            parts: buffer::SampleParts::Yuv,
+        };
+
+        let mut commands = CommandBuffer::default();
+        let input = commands.input(descriptor.clone()).unwrap();
+        let encoded = commands
+            .color_convert_ycbcr(input, descriptor.color.clone(), yuv, ycbcr)
+            .expect("Valid to encode as Y'CbCr");
+        let (output, encoded_descriptor) = commands.output(encoded).expect("Valid for output");
+        let encoded_descriptor = encoded_descriptor
+            .as_concrete()
+            .expect("Y'CbCr texels are concrete");
+
+        let encoded_key = run_once_with_output(
+            commands,
+            pool,
+            vec![(input, key)],
+            retire_with_one_image(output),
+        );
+
+        // Compare the codes with the matrix applied to the encoded values.
+        let [y_scale, y_offset, c_scale, c_offset] = levels;
+        let max = c_offset * 2.0 - 1.0;
+        let texel_bytes = usize::from(bits.bytes());
+        let encoded = pool.entry(encoded_key).unwrap();
+        let bytes = encoded.as_bytes().expect("Result is host allocated");
+        assert_eq!(bytes.len(), 6 * 2 * texel_bytes);
+
+        for (texel, pixel) in bytes.chunks_exact(texel_bytes).zip(image.pixels()) {
+            let [r, g, b] = [0, 1, 2].map(|idx| f32::from(pixel.0[idx]) / 255.0);
+            let y = kr * r + (1.0 - kr - kb) * g + kb * b;
+            let cb = (b - y) / (2.0 * (1.0 - kb));
+            let cr = (r - y) / (2.0 * (1.0 - kr));
+
+            let expected = [
+                y_scale * y + y_offset,
+                c_scale * cb + c_offset,
+                c_scale * cr + c_offset,
+            ]
+            .map(|code| code.round().clamp(0.0, max) as u32);
+
+            let actual = codes(texel);
+            let close = actual
+                .iter()
+                .zip(&expected)
+                .all(|(&a, &b)| a.abs_diff(b) <= 1);
+            assert!(
+                close,
+                "{:?} encoded {:?} to {:?}, expected {:?}",
+                bits, pixel, actual, expected
+            );
+        }
+
+        // Decoding with the same matrix and range gets the colors back.
+        let mut commands = CommandBuffer::default();
+        let input = commands.input(encoded_descriptor.clone()).unwrap();
+        let decoded = commands
+            .color_convert_ycbcr(
+                input,
+                encoded_descriptor.color.clone(),
+                descriptor.texel.clone(),
+                ycbcr,
+            )
+            .expect("Valid to decode into RGB");
+        let (output, _outformat) = commands.output(decoded).expect("Valid for output");
+
+        let result = run_once_with_output(
+            commands,
+            pool,
+            vec![(input, encoded_key)],
+            retire_with_one_image(output),
+        );
+
+        let result = pool.entry(result).unwrap();
+        let result = result.to_image().expect("Result is host allocated");
+        let result = result.to_rgba8();
+
+        for (decoded, pixel) in result.pixels().zip(image.pixels()) {
+            // Red and blue saturate a color difference of 8-bit codes, by half a code.
+            let close = decoded
+                .0
+                .iter()
+                .zip(&pixel.0)
+                .all(|(&a, &b)| a.abs_diff(b) <= 2);
+            assert!(close, "{:?} decoded {:?} to {:?}", bits, pixel, decoded);
+        }
+    }
+}
+
+fn run_curves(pool: &mut Pool) {
+    use command::{Curve, CurveDomain, Curves};
+
+    // Every encoded value, the darkest of which are closer in linear light than 8-bit steps.
+    let image = image::RgbaImage::from_fn(256, 1, |x, _| image::Rgba([x as u8, x as u8, 0, 255]));
+    let entry = pool.insert_srgb(&image::DynamicImage::ImageRgba8(image.clone()));
+    let (key, descriptor) = (entry.key(), entry.descriptor());
+
+    fn decode_srgb(encoded: u8) -> f32 {
+        let encoded = f32::from(encoded) / 255.0;
+        if encoded <= 0.040_45 {
+            encoded / 12.92
+        } else {
+            ((encoded + 0.055) / 1.055).powf(2.4)
+        }
+    }
+
+    fn encode_srgb(linear: f32) -> u8 {
+        let encoded = if linear <= 0.003_130_8 {
+            12.92 * linear
+        } else {
+            1.055 * linear.powf(1.0 / 2.4) - 0.055
+        };
+
+        (encoded * 255.0).round() as u8
+    }
+
+    // Halving the light of red, and the encoded values of green.
+    let half_linear = Curve::Points(vec![[0.0, 0.0], [1.0, 0.5]]);
+    let half_encoded = Curve::Levels(command::Levels {
+        input_black: 0.0,
+        input_white: 1.0,
+        gamma: 1.0,
+        output_black: 0.0,
+        output_white: 0.5,
+    });
+
+    let linear = |value: u8| encode_srgb(decode_srgb(value) * 0.5);
+    let encoded = |value: u8| (f32::from(value) * 0.5).round() as u8;
+    let cases = [
+        (
+            CurveDomain::Linear,
+            [
+                half_linear,
+                Curve::Identity,
+                Curve::Identity,
+                Curve::Identity,
+            ],
+            [&linear as &dyn Fn(u8) -> u8, &|value| value],
+        ),
+        (
+            CurveDomain::Encoded,
+            [
+                Curve::Identity,
+                half_encoded,
+                Curve::Identity,
+                Curve::Identity,
+            ],
+            [&|value| value, &encoded],
+        ),
+    ];
+
+    for (domain, channels, expected) in cases {
+        let mut commands = CommandBuffer::default();
+        let input = commands.input(descriptor.clone()).unwrap();
+        let curved = commands
+            .curves(input, Curves { channels, domain })
+            .expect("Valid to apply curves");
+        let (output, _outformat) = commands.output(curved).expect("Valid for output");
+
+        let result = run_once_with_output(
+            commands,
+            pool,
+            vec![(input, key)],
+            retire_with_one_image(output),
+        );
+
+        let result = pool.entry(result).unwrap();
+        let result = result.to_image().expect("Result is host allocated");
+        let result = result.to_rgba8();
+
+        for (curved, pixel) in result.pixels().zip(image.pixels()) {
+            let close = expected
+                .iter()
+                .zip(&curved.0)
+                .zip(&pixel.0)
+                .all(|((expected, &curved), &value)| expected(value).abs_diff(curved) <= 1);
+            assert!(
+                close && curved.0[2..] == pixel.0[2..],
+                "Curves in {:?} changed {:?} to {:?}",
+                domain,
+                pixel,
+                curved
+            );
+        }
+    }
+}
+
+fn run_crop(pool: &mut Pool) {
+    // Each texel encodes its own position.
+    let image = image::RgbaImage::from_fn(32, 16, |x, y| {
+        image::Rgba([(x * 8) as u8, (y * 16) as u8, 128, 255])
+    });
+    let entry = pool.insert_srgb(&image::DynamicImage::ImageRgba8(image.clone()));
+    let (key, descriptor) = (entry.key(), entry.descriptor());
+
+    let rect = Rectangle {
+        x: 4,
+        y: 2,
+        max_x: 12,
+        max_y: 16,
+    };
+    let below = Rectangle::with_width_height(10, 16);
+    let placement = Rectangle::with_width_height(8, 14);
+
+    // The position in the input of a texel of the result.
+    let copied = |x: u32, y: u32| (x + rect.x, y + rect.y);
+    let inscribed = |x: u32, y: u32| {
+        if placement.contains(Rectangle::with_width_height(x + 1, y + 1)) {
+            (x + rect.x, y + rect.y)
+        } else {
+            (x, y)
+        }
+    };
+    let shifted = |x: u32, y: u32| {
+        let (dx, dy) = (2, 1);
+        if (dx..dx + rect.width()).contains(&x) && (dy..dy + rect.height()).contains(&y) {
+            (x - dx + rect.x, y - dy + rect.y)
+        } else {
+            (x, y)
+        }
+    };
+
+    type Paint<'a> =
+        &'a dyn Fn(&mut CommandBuffer, command::Register, command::Register) -> command::Register;
+    let inscribe: Paint = &|commands, below, cropped| {
+        commands
+            .inscribe(below, placement, cropped)
+            .expect("Valid to inscribe")
+    };
+    let affine: Paint = &|commands, below, cropped| {
+        let shift = command::Affine::new(command::AffineSample::Nearest).shift(2.0, 1.0);
+        commands
+            .affine(below, shift, cropped)
+            .expect("Valid to paint with affine transformation")
+    };
+
+    // A crop that is output is copied, one painted into another image is selected in place.
+    let cases = [
+        (None, &copied as &dyn Fn(u32, u32) -> (u32, u32)),
+        (Some(inscribe), &inscribed),
+        (Some(affine), &shifted),
+    ];
+
+    for (paint, source) in cases {
+        let mut commands = CommandBuffer::default();
+        let input = commands.input(descriptor.clone()).unwrap();
+        let cropped = commands.crop(input, rect).expect("Valid to crop");
+
+        let result = match paint {
+            Some(paint) => {
+                let below = commands.crop(input, below).expect("Valid to crop");
+                paint(&mut commands, below, cropped)
+            }
+            None => cropped,
+        };
+
+        let (output, _outformat) = commands.output(result).expect("Valid for output");
+
+        let result = run_once_with_output(
+            commands,
+            pool,
+            vec![(input, key)],
+            retire_with_one_image(output),
+        );
+
+        let result = pool.entry(result).unwrap();
+        let result = result.to_image().expect("Result is host allocated");
+        let result = result.to_rgba8();
+
+        let size = if paint.is_some() {
+            (below.width(), below.height())
+        } else {
+            (rect.width(), rect.height())
+        };
+        assert_eq!(result.dimensions(), size);
+
+        for (x, y, pixel) in result.enumerate_pixels() {
+            let (sx, sy) = source(x, y);
+            assert_eq!(
+                pixel,
+                image.get_pixel(sx, sy),
+                "Texel {:?} of the crop does not match the input at {:?}",
+                (x, y),
+                (sx, sy)
+            );
+        }
+    }
+}
+
+fn run_warp(pool: &mut Pool) {
+    // Each texel encodes its own position.
+    let image = image::RgbaImage::from_fn(8, 8, |x, y| {
+        image::Rgba([(x * 32) as u8, (y * 32) as u8, 128, 255])
+    });
+    let entry = pool.insert_srgb(&image::DynamicImage::ImageRgba8(image.clone()));
+    let (key, descriptor) = (entry.key(), entry.descriptor());
+
+    fn warp_once(
+        pool: &mut Pool,
+        key: PoolKey,
+        descriptor: &Descriptor,
+        warp: impl FnOnce(&mut CommandBuffer, command::Register) -> command::Register,
+    ) -> image::RgbaImage {
+        let mut commands = CommandBuffer::default();
+        let input = commands.input(descriptor.clone()).unwrap();
+        let warped = warp(&mut commands, input);
+        let (output, _outformat) = commands.output(warped).expect("Valid for output");
+
+        let result = run_once_with_output(
+            commands,
+            pool,
+            vec![(input, key)],
+            retire_with_one_image(output),
+        );
+
+        let result = pool.entry(result).unwrap();
+        let result = result.to_image().expect("Result is host allocated");
+        result.to_rgba8()
+    }
+
+    // Displace by a quarter of the width, that is two texels, to the right.
+    let map_descriptor = buffer::Descriptor {
+        color: buffer::Color::Scalars {
+            transfer: buffer::Transfer::Linear,
+        },
+        ..descriptor.clone()
+    };
+    let displaced = warp_once(pool, key, &descriptor, |commands, input| {
+        let map = commands
+            .solid_rgba(map_descriptor, [0.25, 0.0, 0.0, 1.0])
+            .unwrap();
+        commands
+            .displace(input, map, command::AffineSample::Nearest)
+            .expect("Valid to displace")
+    });
+
+    for (x, y, pixel) in displaced.enumerate_pixels() {
+        if x + 2 < image.width() {
+            assert_eq!(
+                pixel,
+                image.get_pixel(x + 2, y),
+                "Texel {:?} not displaced from two texels to its right",
+                (x, y)
+            );
+        } else {
+            assert_eq!(pixel.0[3], 0, "Texel {:?} sampled outside", (x, y));
+        }
+    }
+
+    // A lens without distortion samples every texel at its center.
+    let lens = command::LensModel {
+        focal: [8.0, 8.0],
+        center: [4.0, 4.0],
+        radial: [0.0; 3],
+        tangential: [0.0; 2],
+    };
+    let identity = warp_once(pool, key, &descriptor, |commands, input| {
+        commands.undistort(input, lens).expect("Valid to undistort")
+    });
+    assert_eq!(identity, image, "Undistorted without any distortion");
+
+    // Strong barrel distortion moves the corners from outside the image, the center stays.
+    let barrel = command::LensModel {
+        radial: [1.0, 0.0, 0.0],
+        ..lens
+    };
+    let undistorted = warp_once(pool, key, &descriptor, |commands, input| {
+        commands
+            .undistort(input, barrel)
+            .expect("Valid to undistort")
+    });
+
+    for &(x, y) in &[(0, 0), (7, 0), (0, 7), (7, 7)] {
+        let pixel = undistorted.get_pixel(x, y);
+        assert_eq!(pixel.0[3], 0, "Corner {:?} sampled outside", (x, y));
+    }
+
+    for &(x, y) in &[(3, 3), (4, 4)] {
+        let (pixel, expected) = (undistorted.get_pixel(x, y), image.get_pixel(x, y));
+        let close = pixel
+            .0
+            .iter()
+            .zip(&expected.0)
+            .all(|(&a, &b)| a.abs_diff(b) <= 2);
+        assert!(
+            close,
+            "Center {:?} moved to {:?}, expected {:?}",
+            (x, y),
+            pixel,
+            expected
+        );
+    }
+}
+
+fn run_affine_channels(pool: &mut Pool) {
+    use command::{Affine, AffineSample};
+
+    let image = image::RgbaImage::from_fn(8, 8, |x, y| {
+        image::Rgba([(x * 32) as u8, (y * 32) as u8, ((x + y) * 16) as u8, 255])
+    });
+    let entry = pool.insert_srgb(&image::DynamicImage::ImageRgba8(image.clone()));
+    let (key, descriptor) = (entry.key(), entry.descriptor());
+
+    // Red moves two texels to the right, with the left edge repeated where it is sampled from
+    // outside. Green stays in place. Blue is doubled in size from the top-left corner.
+    let channels = [
+        Affine::new(AffineSample::Nearest).shift(0.25, 0.0),
+        Affine::new(AffineSample::BiLinear),
+        Affine::new(AffineSample::Nearest).scale(2.0, 2.0),
+    ];
+
+    let mut commands = CommandBuffer::default();
+    let input = commands.input(descriptor).unwrap();
+    let transformed = commands
+        .affine_channels(input, channels)
+        .expect("Valid to transform channels");
+    let (output, _outformat) = commands.output(transformed).expect("Valid for output");
+
+    let result = run_once_with_output(
+        commands,
+        pool,
+        vec![(input, key)],
+        retire_with_one_image(output),
+    );
+
+    let result = pool.entry(result).unwrap();
+    let result = result.to_image().expect("Result is host allocated");
+    let result = result.to_rgba8();
+
+    for (x, y, pixel) in result.enumerate_pixels() {
+        let expected = [
+            image.get_pixel(x.saturating_sub(2), y).0[0],
+            image.get_pixel(x, y).0[1],
+            image.get_pixel(x / 2, y / 2).0[2],
+            255,
+        ];
+
+        let close = pixel
+            .0
+            .iter()
+            .zip(&expected)
+            .all(|(&a, &b)| a.abs_diff(b) <= 1);
+        assert!(
+            close,
+            "Texel {:?} transformed to {:?}, expected {:?}",
+            (x, y),
+            pixel,
+            expected
+        );
+    }
+}
+
+fn run_tone_map(pool: &mut Pool) {
+    use command::{ToneMap, ToneMapOperator};
+
+    fn encode_srgb(linear: f32) -> u8 {
+        let encoded = if linear <= 0.003_130_8 {
+            12.92 * linear
+        } else {
+            1.055 * linear.powf(1.0 / 2.4) - 0.055
+        };
+
+        (encoded * 255.0).round() as u8
+    }
+
+    fn hable(x: f32) -> f32 {
+        let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+        (x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f) - e / f
+    }
+
+    // Grays stay gray through the matrices of ACES and AgX, whose rows sum to one.
+    fn aces(x: f32) -> f32 {
+        let a = x * (x + 0.024_578_6) - 0.000_090_537;
+        let b = x * (0.983_729 * x + 0.432_951) + 0.238_081;
+        a / b
+    }
+
+    fn agx(x: f32) -> f32 {
+        const MIN_EV: f32 = -12.473_93;
+        const MAX_EV: f32 = 4.026_069;
+        let v = (x.max(1e-10).log2().clamp(MIN_EV, MAX_EV) - MIN_EV) / (MAX_EV - MIN_EV);
+        let v = 15.5 * v.powi(6) - 40.14 * v.powi(5) + 31.96 * v.powi(4) - 6.868 * v.powi(3)
+            + 0.4298 * v.powi(2)
+            + 0.1191 * v
+            - 0.002_32;
+        v.max(0.0).powf(2.2)
+    }
+
+    // Constant gray images of linear, scene-referred light beyond the display range.
+    let linear = match buffer::Color::SRGB {
+        buffer::Color::Rgb {
+            primary,
+            whitepoint,
+            luminance,
+            ..
+        } => buffer::Color::Rgb {
+            primary,
+            transfer: buffer::Transfer::Linear,
+            whitepoint,
+            luminance,
+        },
+        _ => unreachable!("sRGB is an RGB color"),
+    };
+
+    let float = buffer::Texel {
+        block: buffer::Block::Pixel,
+        bits: buffer::SampleBits::Float16x4,
+        parts: buffer::SampleParts::RgbA,
+    };
+    let hdr = Descriptor {
+        color: linear,
+        ..Descriptor::with_texel(float, 4, 4).unwrap()
+    };
+    let sdr = buffer::Texel::new_u8(buffer::SampleParts::RgbA);
+
+    type Curve = fn(f32) -> f32;
+    let cases: [(ToneMapOperator, f32, Curve); 5] = [
+        (ToneMapOperator::Reinhard, 0.0, |l| l / (1.0 + l)),
+        (ToneMapOperator::ReinhardExtended { white: 4.0 }, 1.0, |l| {
+            l * (1.0 + l / 16.0) / (1.0 + l)
+        }),
+        (ToneMapOperator::Hable { white: 11.2 }, 0.0, |x| {
+            hable(2.0 * x) / hable(11.2)
+        }),
+        (ToneMapOperator::Aces, 0.0, aces),
+        (ToneMapOperator::AgX, -1.0, agx),
+    ];
+
+    for (operator, exposure, curve) in cases {
+        for value in [0.18, 1.0, 6.0] {
+            let mut commands = CommandBuffer::default();
+            let gray = commands
+                .bilinear(
+                    hdr.clone(),
+                    command::Bilinear {
+                        u_min: [value, value, value, 1.0],
+                        u_max: [value, value, value, 1.0],
+                        v_min: [0.0; 4],
+                        v_max: [0.0; 4],
+                        uv_min: [0.0; 4],
+                        uv_max: [0.0; 4],
+                    },
+                )
+                .unwrap();
+
+            let tone_map = ToneMap { operator, exposure };
+            let mapped = commands
+                .tone_map(gray, tone_map, buffer::Color::SRGB, sdr.clone())
+                .expect("Valid to tone map");
+            let (output, _outformat) = commands.output(mapped).expect("Valid for output");
+
+            let result =
+                run_once_with_output(commands, pool, vec![], retire_with_one_image(output));
+
+            let result = pool.entry(result).unwrap();
+            let result = result.to_image().expect("Result is host allocated");
+            let result = result.to_rgba8();
+
+            let code = encode_srgb(curve(value * exposure.exp2()).clamp(0.0, 1.0));
+            for pixel in result.pixels() {
+                let close = pixel.0[..3].iter().all(|&ch| ch.abs_diff(code) <= 1);
+                assert!(
+                    close && pixel.0[3] == 255,
+                    "{:?} mapped {} to {:?}, expected {}",
+                    operator,
+                    value,
+                    pixel,
+                    code
+                );
+            }
+        }
+    }
+}
+
+fn run_lut3d(pool: &mut Pool) {
+    use command::{Lut3d, Lut3dInterpolation};
+
+    let image = image::RgbaImage::from_fn(16, 16, |x, y| {
+        image::Rgba([(x * 17) as u8, (y * 17) as u8, ((x + y) * 8) as u8, 255])
+    });
+    let entry = pool.insert_srgb(&image::DynamicImage::ImageRgba8(image.clone()));
+    let (key, descriptor) = (entry.key(), entry.descriptor());
+
+    // A linear table is exact with any interpolation. It moves blue into red, inverted, and red
+    // into blue, so samples must be in the order of the file with red changing fastest.
+    let mut cube = String::from("# Swaps and inverts\nLUT_3D_SIZE 2\n");
+    for idx in 0..8 {
+        let [r, g, b] = [idx & 1, (idx >> 1) & 1, idx >> 2];
+        cube.push_str(&format!("{} {} {}\n", 1 - b, g, r));
+    }
+
+    let mut lut = Lut3d::from_cube(&cube).expect("Valid cube file");
+
+    for interpolation in [
+        Lut3dInterpolation::Tetrahedral,
+        Lut3dInterpolation::Trilinear,
+    ] {
+        lut.interpolation = interpolation;
+
+        let mut commands = CommandBuffer::default();
+        let input = commands.input(descriptor.clone()).unwrap();
+        let graded = commands.lut3d(input, &lut).expect("Valid to apply a table");
+        let (output, _outformat) = commands.output(graded).expect("Valid for output");
+
+        let result = run_once_with_output(
+            commands,
+            pool,
+            vec![(input, key)],
+            retire_with_one_image(output),
+        );
+
+        let result = pool.entry(result).unwrap();
+        let result = result.to_image().expect("Result is host allocated");
+        let result = result.to_rgba8();
+
+        for (graded, pixel) in result.pixels().zip(image.pixels()) {
+            let [r, g, b, a] = pixel.0;
+            let expected = [255 - b, g, r, a];
+            let close = graded
+                .0
+                .iter()
+                .zip(&expected)
+                .all(|(&a, &b)| a.abs_diff(b) <= 1);
+            assert!(
+                close,
+                "{:?} mapped {:?} to {:?}, expected {:?}",
+                interpolation, pixel, graded, expected
+            );
+        }
+    }
+}
+
+fn run_color_chain(pool: &mut Pool) {
+    let image = image::RgbaImage::from_fn(16, 16, |x, y| {
+        image::Rgba([(x * 17) as u8, (y * 17) as u8, ((x + y) * 8) as u8, 255])
+    });
+    let entry = pool.insert_srgb(&image::DynamicImage::ImageRgba8(image.clone()));
+    let (key, descriptor) = (entry.key(), entry.descriptor());
+
+    fn convert_once(
+        pool: &mut Pool,
+        key: PoolKey,
+        descriptor: &Descriptor,
+        convert: impl FnOnce(&mut CommandBuffer, command::Register) -> command::Register,
+    ) -> image::RgbaImage {
+        let mut commands = CommandBuffer::default();
+        let input = commands.input(descriptor.clone()).unwrap();
+        let converted = convert(&mut commands, input);
+        let (output, _outformat) = commands.output(converted).expect("Valid for output");
+
+        let result = run_once_with_output(
+            commands,
+            pool,
+            vec![(input, key)],
+            retire_with_one_image(output),
+        );
+
+        let result = pool.entry(result).unwrap();
+        let result = result.to_image().expect("Result is host allocated");
+        result.to_rgba8()
+    }
+
+    let print = match descriptor.color.clone() {
+        buffer::Color::Rgb {
+            primary,
+            transfer,
+            luminance,
+            ..
+        } => buffer::Color::Rgb {
+            primary,
+            transfer,
+            luminance,
+            whitepoint: Whitepoint::D50,
+        },
+        _ => unreachable!("sRGB is an RGB color"),
+    };
+
+    // Adapting to the white of print keeps grays, as white is mapped to white.
+    let adapted = convert_once(pool, key, &descriptor, |commands, input| {
+        commands
+            .color_convert(input, print.clone(), descriptor.texel.clone())
+            .expect("Valid to convert across whitepoints")
+    });
+
+    for (adapted, pixel) in adapted.pixels().zip(image.pixels()) {
+        let [r, g, b, _] = pixel.0;
+        if r == g && g == b {
+            let close = adapted
+                .0
+                .iter()
+                .zip(&pixel.0)
+                .all(|(&a, &b)| a.abs_diff(b) <= 1);
+            assert!(close, "Adapted gray {:?} to {:?}", pixel, adapted);
+        }
+    }
+
+    // Back from print, through an intermediate of half floats, gets the colors back.
+    let float = buffer::Texel {
+        block: buffer::Block::Pixel,
+        bits: buffer::SampleBits::Float16x4,
+        parts: buffer::SampleParts::RgbA,
+    };
+
+    let back = convert_once(pool, key, &descriptor, |commands, input| {
+        let adapted = commands
+            .color_convert(input, print.clone(), float.clone())
+            .expect("Valid to convert across whitepoints");
+        commands
+            .color_convert(adapted, descriptor.color.clone(), descriptor.texel.clone())
+            .expect("Valid to convert back across whitepoints")
+    });
+
+    for (back, pixel) in back.pixels().zip(image.pixels()) {
+        let close = back
+            .0
+            .iter()
+            .zip(&pixel.0)
+            .all(|(&a, &b)| a.abs_diff(b) <= 1);
+        assert!(close, "Converted {:?} back to {:?}", pixel, back);
+    }
+
+    // From print into Oklab, then between perceptual spaces planned through XYZ, and back.
+    let lch = buffer::Texel {
+        parts: buffer::SampleParts::LchA,
+        ..float.clone()
+    };
+
+    let srlab2 = buffer::Color::SrLab2 {
+        whitepoint: Whitepoint::D50,
+    };
+
+    let back = convert_once(pool, key, &descriptor, |commands, input| {
+        let adapted = commands
+            .color_convert(input, print, float)
+            .expect("Valid to convert across whitepoints");
+        let oklch = commands
+            .color_convert(adapted, buffer::Color::Oklab, lch.clone())
+            .expect("Valid to convert from D50 into Oklab");
+        let srlch = commands
+            .color_convert(oklch, srlab2, lch)
+            .expect("Valid to convert between perceptual spaces");
+        commands
+            .color_convert(srlch, descriptor.color.clone(), descriptor.texel.clone())
+            .expect("Valid to convert back to RGB")
+    });
+
+    for (back, pixel) in back.pixels().zip(image.pixels()) {
+        let close = back
+            .0
+            .iter()
+            .zip(&pixel.0)
+            .all(|(&a, &b)| a.abs_diff(b) <= 2);
+        assert!(close, "Converted {:?} back to {:?}", pixel, back);
+    }
+}
+
+fn run_cie(pool: &mut Pool) {
+    use buffer::{CieColor, CieModel};
+
+    let image = image::RgbaImage::from_fn(16, 16, |x, y| {
+        image::Rgba([(x * 17) as u8, (y * 17) as u8, ((x + y) * 8) as u8, 255])
+    });
+    let entry = pool.insert_srgb(&image::DynamicImage::ImageRgba8(image.clone()));
+    let (key, descriptor) = (entry.key(), entry.descriptor());
+
+    // White, black, a mid gray and red with their L*a*b* values relative to D50.
+    let known_colors: [([u8; 4], [f32; 3]); 4] = [
+        ([255, 255, 255, 255], [100.0, 0.0, 0.0]),
+        ([0, 0, 0, 255], [0.0, 0.0, 0.0]),
+        ([128, 128, 128, 255], [53.585, 0.0, 0.0]),
+        ([255, 0, 0, 255], [54.291, 80.805, 69.891]),
+    ];
+    let known = image::RgbaImage::from_fn(4, 1, |x, _| image::Rgba(known_colors[x as usize].0));
+    let known_entry = pool.insert_srgb(&image::DynamicImage::ImageRgba8(known.clone()));
+    let (known_key, known_descriptor) = (known_entry.key(), known_entry.descriptor());
+
+    let d50 = CieColor {
+        model: CieModel::Lab,
+        whitepoint: Whitepoint::D50,
+    };
+
+    let lab = |bits| buffer::Texel {
+        block: buffer::Block::Pixel,
+        bits,
+        parts: buffer::SampleParts::LabA,
+    };
+
+    let mut commands = CommandBuffer::default();
+    let input = commands.input(known_descriptor).unwrap();
+    let encoded = commands
+        .cie_encode(input, d50, lab(buffer::SampleBits::UInt8x4))
+        .expect("Valid to encode as L*a*b*");
+    let (output, _outformat) = commands.output(encoded).expect("Valid for output");
+
+    let result = run_once_with_output(
+        commands,
+        pool,
+        vec![(input, known_key)],
+        retire_with_one_image(output),
+    );
+
+    let result = pool.entry(result).unwrap();
+    let bytes = result.as_bytes().expect("Result is host allocated");
+    assert_eq!(bytes.len(), 4 * 4);
+
+    for (texel, (color, [l, a, b])) in bytes.chunks_exact(4).zip(&known_colors) {
+        let expected = [l / 100.0, (a + 128.0) / 255.0, (b + 128.0) / 255.0]
+            .map(|value| (value * 255.0).round() as u8);
+        let close = texel[..3]
+            .iter()
+            .zip(&expected)
+            .all(|(&a, &b)| a.abs_diff(b) <= 1);
+        assert!(
+            close && texel[3] == 255,
+            "Encoded {:?} to {:?}, expected {:?}",
+            color,
+            texel,
+            expected
+        );
+    }
+
+    // Both models get the colors back through half precision texels. Through bytes, the u* of
+    // saturated reds exceeds the range of a* and must not be clipped. Its steps are coarser,
+    // which shows in dark colors.
+    let round_trips = [
+        (CieModel::Lab, buffer::SampleBits::Float16x4, 1),
+        (CieModel::Luv, buffer::SampleBits::Float16x4, 1),
+        (CieModel::Luv, buffer::SampleBits::UInt8x4, 4),
+    ];
+
+    for (model, bits, tolerance) in round_trips {
+        let cie = CieColor {
+            model,
+            whitepoint: Whitepoint::D50,
+        };
+
+        let mut commands = CommandBuffer::default();
+        let input = commands.input(descriptor.clone()).unwrap();
+        let encoded = commands
+            .cie_encode(input, cie, lab(bits))
+            .expect("Valid to encode");
+        let decoded = commands
+            .cie_decode(encoded, descriptor.color.clone(), descriptor.texel.clone())
+            .expect("Valid to decode into RGB");
+        let (output, _outformat) = commands.output(decoded).expect("Valid for output");
+
+        let result = run_once_with_output(
+            commands,
+            pool,
+            vec![(input, key)],
+            retire_with_one_image(output),
+        );
+
+        let result = pool.entry(result).unwrap();
+        let result = result.to_image().expect("Result is host allocated");
+        let result = result.to_rgba8();
+
+        for (decoded, pixel) in result.pixels().zip(image.pixels()) {
+            let close = decoded
+                .0
+                .iter()
+                .zip(&pixel.0)
+                .all(|(&a, &b)| a.abs_diff(b) <= tolerance);
+            assert!(close, "{:?} decoded {:?} to {:?}", model, pixel, decoded);
+        }
+    }
+}
+
+fn run_hsl(pool: &mut Pool) {
+    use command::HueModel;
+
+    const COLORS: [[u8; 4]; 4] = [
+        [255, 0, 0, 255],
+        [0, 255, 0, 255],
+        [200, 120, 40, 255],
+        [64, 32, 96, 128],
+    ];
+
+    let image = image::RgbaImage::from_fn(4, 1, |x, _| image::Rgba(COLORS[x as usize]));
+    let entry = pool.insert_srgb(&image::DynamicImage::ImageRgba8(image.clone()));
+    let (key, descriptor) = (entry.key(), entry.descriptor());
+
+    // A third of a turn of hue permutes the channels. Without saturation each color becomes the
+    // gray of its lightness, the mean of its largest and smallest value. Scaling the lightness of
+    // colors at most half as light as white scales all channels.
+    type Expected = fn([u8; 4]) -> [u8; 4];
+    let cases: [((f32, f32, f32), Expected); 3] = [
+        ((120.0, 1.0, 1.0), |[r, g, b, a]| [b, r, g, a]),
+        ((0.0, 0.0, 1.0), |[r, g, b, a]| {
+            let hi = u16::from(r.max(g).max(b));
+            let lo = u16::from(r.min(g).min(b));
+            let gray = ((hi + lo) as f32 / 2.0).round() as u8;
+            [gray, gray, gray, a]
+        }),
+        ((0.0, 1.0, 0.5), |[r, g, b, a]| {
+            let [r, g, b] = [r, g, b].map(|ch| (f32::from(ch) / 2.0).round() as u8);
+            [r, g, b, a]
+        }),
+    ];
+
+    for ((hue, saturation, lightness), expected) in cases {
+        let mut commands = CommandBuffer::default();
+        let input = commands.input(descriptor.clone()).unwrap();
+        let adjusted = commands
+            .hsl_adjust(input, hue, saturation, lightness)
+            .expect("Valid to adjust an RGB image");
+        let (output, _outformat) = commands.output(adjusted).expect("Valid for output");
+
+        let result = run_once_with_output(
+            commands,
+            pool,
+            vec![(input, key)],
+            retire_with_one_image(output),
+        );
+
+        let result = pool.entry(result).unwrap();
+        let result = result.to_image().expect("Result is host allocated");
+        let result = result.to_rgba8();
+
+        for (adjusted, pixel) in result.pixels().zip(image.pixels()) {
+            let expected = expected(pixel.0);
+            let close = adjusted
+                .0
+                .iter()
+                .zip(&expected)
+                .all(|(&a, &b)| a.abs_diff(b) <= 1);
+            assert!(
+                close,
+                "Adjusted {:?} by {:?} to {:?}, expected {:?}",
+                pixel,
+                (hue, saturation, lightness),
+                adjusted,
+                expected
+            );
+        }
+    }
+
+    // The HSV values of the colors, with the hue in turns.
+    let mut commands = CommandBuffer::default();
+    let input = commands.input(descriptor.clone()).unwrap();
+    let hsv = commands
+        .hue_encode(input, HueModel::Hsv, descriptor.texel.clone())
+        .expect("Valid to convert into HSV");
+    let (output, _outformat) = commands.output(hsv).expect("Valid for output");
+
+    let result = run_once_with_output(
+        commands,
+        pool,
+        vec![(input, key)],
+        retire_with_one_image(output),
+    );
+
+    let result = pool.entry(result).unwrap();
+    let bytes = result.as_bytes().expect("Result is host allocated");
+
+    let expected: [[u8; 4]; 4] = [
+        [0, 255, 255, 255],
+        [85, 255, 255, 255],
+        [21, 204, 200, 255],
+        [191, 170, 96, 128],
+    ];
+
+    for ((texel, expected), color) in bytes.chunks_exact(4).zip(&expected).zip(&COLORS) {
+        let close = texel
+            .iter()
+            .zip(expected)
+            .all(|(&a, &b)| a.abs_diff(b) <= 1);
+        assert!(
+            close,
+            "Encoded {:?} to HSV {:?}, expected {:?}",
+            color, texel, expected
+        );
+    }
+}
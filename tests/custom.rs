@@ -70,6 +70,8 @@ fn mandelbrot() {
             bits: buffer::SampleBits::UInt8x4,
             parts: buffer::SampleParts::LchA,
         },
+        alpha: buffer::AlphaMode::Straight,
+        cie: None,
     }));
 
     let srgb = Descriptor::with_srgb_image(&target);